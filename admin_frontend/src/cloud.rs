@@ -0,0 +1,41 @@
+use crate::models::CloudResponse;
+use crate::AppState;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+
+/// Calls the API of AppFlowy Cloud with the access token of the session. Returns the data of the
+/// response, or the message of the error.
+pub async fn cloud_request<T>(
+  state: &AppState,
+  access_token: &str,
+  method: Method,
+  path: &str,
+  query: &[(&str, String)],
+  body: Option<serde_json::Value>,
+) -> Result<Option<T>, String>
+where
+  T: DeserializeOwned,
+{
+  let mut builder = state
+    .cloud_client
+    .request(method, format!("{}{}", state.appflowy_cloud_url, path))
+    .bearer_auth(access_token)
+    .query(query);
+  if let Some(body) = body {
+    builder = builder
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(body.to_string());
+  }
+  let text = builder
+    .send()
+    .await
+    .map_err(|err| err.to_string())?
+    .text()
+    .await
+    .map_err(|err| err.to_string())?;
+  let resp: CloudResponse<T> = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+  if resp.code != 0 {
+    return Err(format!("code: {}, message: {}", resp.code, resp.message));
+  }
+  Ok(resp.data)
+}
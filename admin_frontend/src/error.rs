@@ -45,6 +45,7 @@ impl From<redis::RedisError> for WebApiError<'_> {
 pub enum WebAppError {
   AskamaError(askama::Error),
   GoTrueError(gotrue_entity::error::GoTrueError),
+  CloudError(String),
 }
 
 impl IntoResponse for WebAppError {
@@ -58,6 +59,10 @@ impl IntoResponse for WebAppError {
         tracing::error!("gotrue error: {:?}", e);
         Redirect::to("/login").into_response()
       },
+      WebAppError::CloudError(e) => {
+        tracing::error!("appflowy cloud error: {}", e);
+        (status::StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+      },
     }
   }
 }
@@ -1,3 +1,4 @@
+mod cloud;
 mod error;
 mod models;
 mod response;
@@ -35,10 +36,14 @@ async fn main() {
       .await
       .unwrap();
   let session_store = session::SessionStorage::new(redis_client);
+  let appflowy_cloud_url =
+    std::env::var("APPFLOWY_CLOUD_URL").unwrap_or("http://appflowy_cloud:8000".to_string());
 
   let state = AppState {
     gotrue_client,
     session_store,
+    cloud_client: reqwest::Client::new(),
+    appflowy_cloud_url,
   };
 
   let web_app_router = web_app::router(state.clone()).with_state(state.clone());
@@ -67,4 +72,7 @@ async fn main() {
 pub struct AppState {
   pub gotrue_client: gotrue::api::Client,
   pub session_store: session::SessionStorage,
+  /// Calls the admin endpoints of AppFlowy Cloud, with the access token of the admin session.
+  pub cloud_client: reqwest::Client,
+  pub appflowy_cloud_url: String,
 }
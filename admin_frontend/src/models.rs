@@ -1,3 +1,4 @@
+use gotrue_entity::dto::User;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -15,6 +16,13 @@ pub struct WebApiPutUserRequest {
 pub struct WebApiChangePasswordRequest {
   pub new_password: String,
   pub confirm_password: String,
+  /// The code sent by `/web-api/reauthenticate`, required if GoTrue is configured to require it.
+  pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct WebApiRecoverRequest {
+  pub email: String,
 }
 
 #[derive(Deserialize)]
@@ -24,7 +32,48 @@ pub struct WebApiAdminCreateUserRequest {
   pub require_email_verification: bool,
 }
 
+#[derive(Deserialize)]
+pub struct WebApiBanUserRequest {
+  /// The user is banned indefinitely if it's empty.
+  pub duration_hours: String,
+  pub reason: String,
+}
+
 #[derive(Deserialize)]
 pub struct WebApiInviteUserRequest {
   pub email: String,
 }
+
+#[derive(Deserialize)]
+pub struct AdminUsersQuery {
+  pub page: Option<u32>,
+  pub filter: Option<String>,
+}
+
+/// The response of the AppFlowy Cloud API.
+#[derive(Deserialize)]
+pub struct CloudResponse<T> {
+  pub data: Option<T>,
+  #[serde(default)]
+  pub code: i32,
+  #[serde(default)]
+  pub message: String,
+}
+
+/// A user listed by `/api/admin/user` of AppFlowy Cloud.
+#[derive(Deserialize)]
+pub struct CloudAdminUser {
+  pub user: User,
+  pub uid: Option<i64>,
+  pub name: Option<String>,
+  pub workspace_count: i64,
+  pub storage_usage: i64,
+}
+
+#[derive(Deserialize)]
+pub struct CloudAdminUserPage {
+  pub items: Vec<CloudAdminUser>,
+  pub page: u32,
+  pub per_page: u32,
+  pub total: u64,
+}
@@ -45,7 +45,12 @@ pub struct AdminHome<'a> {
 #[derive(Template)]
 #[template(path = "components/admin_users.html")]
 pub struct AdminUsers<'a> {
-  pub users: &'a [gotrue_entity::dto::User],
+  pub users: &'a [crate::models::CloudAdminUser],
+  pub filter: &'a str,
+  /// Starts from 1.
+  pub page: u32,
+  pub total_pages: u32,
+  pub total: u64,
 }
 
 #[derive(Template)]
@@ -73,4 +78,20 @@ mod filters {
         .unwrap_or_else(|| default_val.to_string()),
     )
   }
+
+  /// Formats a size in bytes, e.g. `1.5 MB`.
+  pub fn bytes(input: &i64) -> ::askama::Result<String> {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = *input as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+      size /= 1024.0;
+      unit += 1;
+    }
+    if unit == 0 {
+      Ok(format!("{} {}", input, UNITS[unit]))
+    } else {
+      Ok(format!("{:.1} {}", size, UNITS[unit]))
+    }
+  }
 }
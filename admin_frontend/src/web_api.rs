@@ -1,7 +1,8 @@
+use crate::cloud::cloud_request;
 use crate::error::WebApiError;
 use crate::models::{
-  WebApiAdminCreateUserRequest, WebApiChangePasswordRequest, WebApiInviteUserRequest,
-  WebApiPutUserRequest,
+  WebApiAdminCreateUserRequest, WebApiBanUserRequest, WebApiChangePasswordRequest,
+  WebApiInviteUserRequest, WebApiPutUserRequest, WebApiRecoverRequest,
 };
 use crate::response::WebApiResponse;
 use crate::session::{self, UserSession};
@@ -15,17 +16,23 @@ use axum::{extract::State, routing::post, Router};
 use axum_extra::extract::cookie::Cookie;
 use axum_extra::extract::CookieJar;
 use gotrue::grant::{Grant, RefreshTokenGrant};
-use gotrue::params::{AdminDeleteUserParams, AdminUserParams, GenerateLinkParams, MagicLinkParams};
+use gotrue::params::{
+  AdminDeleteUserParams, AdminUserParams, GenerateLinkParams, MagicLinkParams, RecoverParams,
+};
 use gotrue_entity::dto::{UpdateGotrueUserParams, User};
+use reqwest::Method;
+use serde_json::json;
 
 pub fn router() -> Router<AppState> {
   Router::new()
     .route("/login", post(login_handler))
     .route("/login_refresh/:refresh_token", post(login_refresh_handler))
     .route("/logout", post(logout_handler))
+    .route("/recover", post(recover_handler))
 
     // user
     .route("/change_password", post(change_password_handler))
+    .route("/reauthenticate", post(reauthenticate_handler))
     .route("/oauth_login/:provider", post(post_oauth_login_handler))
     .route("/invite", post(invite_handler))
     .route("/open_app", post(open_app_handler))
@@ -36,6 +43,10 @@ pub fn router() -> Router<AppState> {
       "/admin/user/:user_uuid",
       delete(admin_delete_user_handler).put(admin_update_user_handler),
     )
+    .route(
+      "/admin/user/:user_uuid/ban",
+      post(admin_ban_user_handler).delete(admin_unban_user_handler),
+    )
     .route(
       "/admin/user/:email/generate-link",
       post(post_user_generate_link_handler),
@@ -103,15 +114,39 @@ pub async fn change_password_handler(
     .gotrue_client
     .update_user(
       &session.access_token,
-      &UpdateGotrueUserParams {
-        password: Some(param.new_password),
-        ..Default::default()
-      },
+      &UpdateGotrueUserParams::new()
+        .with_opt_password(Some(param.new_password))
+        .with_opt_nonce(param.nonce.filter(|nonce| !nonce.is_empty())),
     )
     .await?;
   Ok(res.into())
 }
 
+// Sends the code to enter when changing the password to the email of the user
+pub async fn reauthenticate_handler(
+  State(state): State<AppState>,
+  session: UserSession,
+) -> Result<WebApiResponse<()>, WebApiError<'static>> {
+  state
+    .gotrue_client
+    .reauthenticate(&session.access_token)
+    .await?;
+  Ok(().into())
+}
+
+// Sends the password recovery email, its link signs the user in
+// so that the password can be changed
+pub async fn recover_handler(
+  State(state): State<AppState>,
+  Form(param): Form<WebApiRecoverRequest>,
+) -> Result<WebApiResponse<()>, WebApiError<'static>> {
+  state
+    .gotrue_client
+    .recover(&RecoverParams { email: param.email })
+    .await?;
+  Ok(().into())
+}
+
 static DEFAULT_HOST: HeaderValue = HeaderValue::from_static("localhost");
 static DEFAULT_SCHEME: HeaderValue = HeaderValue::from_static("http");
 pub async fn post_oauth_login_handler(
@@ -195,6 +230,55 @@ pub async fn admin_delete_user_handler(
   Ok(().into())
 }
 
+/// Bans the user through AppFlowy Cloud, which also bans the user in GoTrue and closes the
+/// websocket connections of the user.
+pub async fn admin_ban_user_handler(
+  State(state): State<AppState>,
+  session: UserSession,
+  Path(user_uuid): Path<String>,
+  Form(param): Form<WebApiBanUserRequest>,
+) -> Result<WebApiResponse<()>, WebApiError<'static>> {
+  let duration_secs = match param.duration_hours.trim() {
+    "" => None,
+    hours => Some(
+      hours
+        .parse::<i64>()
+        .map_err(|_| WebApiError::new(status::StatusCode::BAD_REQUEST, "invalid duration"))?
+        * 3600,
+    ),
+  };
+  let reason = Some(param.reason.trim().to_string()).filter(|reason| !reason.is_empty());
+  cloud_request::<serde_json::Value>(
+    &state,
+    &session.access_token,
+    Method::POST,
+    &format!("/api/admin/user/{}/ban", user_uuid),
+    &[],
+    Some(json!({ "duration_secs": duration_secs, "reason": reason })),
+  )
+  .await
+  .map_err(|err| WebApiError::new(status::StatusCode::BAD_REQUEST, err))?;
+  Ok(().into())
+}
+
+pub async fn admin_unban_user_handler(
+  State(state): State<AppState>,
+  session: UserSession,
+  Path(user_uuid): Path<String>,
+) -> Result<WebApiResponse<()>, WebApiError<'static>> {
+  cloud_request::<()>(
+    &state,
+    &session.access_token,
+    Method::DELETE,
+    &format!("/api/admin/user/{}/ban", user_uuid),
+    &[],
+    None,
+  )
+  .await
+  .map_err(|err| WebApiError::new(status::StatusCode::BAD_REQUEST, err))?;
+  Ok(().into())
+}
+
 pub async fn admin_add_user_handler(
   State(state): State<AppState>,
   session: UserSession,
@@ -1,11 +1,14 @@
 use crate::error::WebAppError;
 use crate::session::UserSession;
 use askama::Template;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::response::Result;
 use axum::{response::Html, routing::get, Router};
 use gotrue_entity::dto::User;
+use reqwest::Method;
 
+use crate::cloud::cloud_request;
+use crate::models::{AdminUsersQuery, CloudAdminUserPage};
 use crate::{templates, AppState};
 
 pub fn router(state: AppState) -> Router<AppState> {
@@ -91,23 +94,34 @@ pub async fn admin_home_handler(
 pub async fn admin_users_handler(
   State(state): State<AppState>,
   session: UserSession,
+  Query(query): Query<AdminUsersQuery>,
 ) -> Result<Html<String>, WebAppError> {
-  let users = state
-    .gotrue_client
-    .admin_list_user(&session.access_token)
-    .await
-    .map_or_else(
-      |err| {
-        tracing::error!("Error getting user list: {:?}", err);
-        vec![]
-      },
-      |r| r.users,
-    )
-    .into_iter()
-    .filter(|user| user.deleted_at.is_none())
-    .collect::<Vec<_>>();
-
-  render_template(templates::AdminUsers { users: &users })
+  let filter = query.filter.unwrap_or_default();
+  let mut params = vec![("page", query.page.unwrap_or(1).to_string())];
+  if !filter.trim().is_empty() {
+    params.push(("filter", filter.clone()));
+  }
+  let user_page: CloudAdminUserPage = cloud_request(
+    &state,
+    &session.access_token,
+    Method::GET,
+    "/api/admin/user",
+    &params,
+    None,
+  )
+  .await
+  .map_err(WebAppError::CloudError)?
+  .ok_or_else(|| WebAppError::CloudError("the user list is missing".to_string()))?;
+
+  let per_page = user_page.per_page.max(1) as u64;
+  let total_pages = ((user_page.total + per_page - 1) / per_page).max(1) as u32;
+  render_template(templates::AdminUsers {
+    users: &user_page.items,
+    filter: &filter,
+    page: user_page.page,
+    total_pages,
+    total: user_page.total,
+  })
 }
 
 pub async fn admin_user_details_handler(
@@ -1,31 +1,48 @@
 use crate::notify::{ClientToken, TokenStateReceiver};
 use anyhow::{anyhow, Context};
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
 use bytes::Bytes;
 use database_entity::dto::{
-  AFBlobMetadata, AFBlobRecord, AFCollabMember, AFCollabMembers, AFUserProfile,
-  AFUserWorkspaceInfo, AFWorkspace, AFWorkspaceMember, AFWorkspaces, BatchQueryCollabParams,
-  BatchQueryCollabResult, CollabMemberIdentify, DeleteCollabParams, InsertCollabMemberParams,
-  InsertCollabParams, QueryCollabMembers, QueryCollabParams, RawData, UpdateCollabMemberParams,
+  AFApiTokenCreated, AFApiTokens, AFAuditLogPage, AFBlobDownloadLink, AFBlobMetadata, AFBlobRecord,
+  AFCollabMember, AFCollabMembers, AFFileTypePolicy, AFPermissions, AFPresignedDownload,
+  AFPresignedUpload, AFShareLinkCreated, AFShareLinks, AFSharedCollab, AFSsoProvider,
+  AFSsoProviders, AFUserBan, AFUserDataExport, AFUserDeletion, AFUserProfile, AFUserSessions,
+  AFUserWorkspaceInfo, AFWorkspace, AFWorkspaceInvite, AFWorkspaceMember, AFWorkspaceRole,
+  AFWorkspaceRoles, AFWorkspaces, BanUserParams, BatchQueryCollabParams, BatchQueryCollabResult,
+  CollabMemberIdentify, CompletePresignedUploadParams, CreateApiTokenParams, CreateShareLinkParams,
+  CreateSsoProviderParams, CreateWorkspaceRoleParams, DeleteCollabParams, InsertCollabMemberParams,
+  InsertCollabParams, PresignedUploadParams, QueryAuditLogParams, QueryBlobMetadataParams,
+  QueryCollabMembers, QueryCollabParams, RawData, SetCollabParentParams, UpdateCollabMemberParams,
+  UpdateWorkspaceRoleParams,
 };
 use futures_util::StreamExt;
 use gotrue::grant::Grant;
 use gotrue::grant::PasswordGrant;
 use gotrue::grant::RefreshTokenGrant;
 use gotrue::params::MagicLinkParams;
-use gotrue::params::{AdminUserParams, GenerateLinkParams};
+use gotrue::params::{
+  AdminUserParams, EnrollFactorParams, GenerateLinkParams, SsoParams, VerifyFactorParams,
+};
 use mime::Mime;
 use parking_lot::RwLock;
 use reqwest::header;
 use reqwest::Method;
 use reqwest::RequestBuilder;
 use scraper::{Html, Selector};
+use sha2::{Digest, Sha256};
 use shared_entity::app_error::AppError;
 use shared_entity::data::AppResponse;
 use shared_entity::dto::auth_dto::SignInTokenResponse;
 use shared_entity::dto::auth_dto::UpdateUserParams;
+use shared_entity::dto::auth_dto::{
+  AdminListUsersQuery, AdminUserPage, ChangeEmailParams, ChangePasswordParams,
+  PasswordRecoveryParams, SsoSignInProvider, VerifyOtpParams, VerifyOtpResponse,
+};
 use shared_entity::dto::workspace_dto::{
-  CreateWorkspaceMembers, WorkspaceBlobMetadata, WorkspaceMemberChangeset, WorkspaceMembers,
-  WorkspaceSpaceUsage,
+  AcceptWorkspaceInvite, BlobGCReports, CreateWorkspaceMember, CreateWorkspaceMembers,
+  ExportAuditLogParams, RevokeWorkspaceInvite, WorkspaceBlobMetadata, WorkspaceBlobMetadataPage,
+  WorkspaceMemberChangeset, WorkspaceMembers, WorkspaceSpaceUsage, WorkspaceUsageByFileType,
 };
 use shared_entity::error_code::url_missing_param;
 use shared_entity::error_code::ErrorCode;
@@ -35,9 +52,16 @@ use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tracing::{event, instrument};
 use url::Url;
+use uuid::Uuid;
 
 use gotrue_entity::dto::SignUpResponse::{Authenticated, NotAuthenticated};
-use gotrue_entity::dto::{AccessTokenResponse, OAuthProvider, UpdateGotrueUserParams, User};
+use gotrue_entity::dto::{
+  AccessTokenResponse, Factor, FactorChallenge, FactorType, OAuthProvider, OtpType, TotpEnrollment,
+  UpdateGotrueUserParams, User,
+};
+
+/// The header that carries the password of a password protected share link.
+const SHARE_PASSWORD_HEADER: &str = "X-Share-Password";
 
 /// `Client` is responsible for managing communication with the GoTrue API and cloud storage.
 ///
@@ -50,6 +74,7 @@ use gotrue_entity::dto::{AccessTokenResponse, OAuthProvider, UpdateGotrueUserPar
 /// - `base_url`: The base URL for API requests.
 /// - `ws_addr`: The WebSocket address for real-time communication.
 /// - `token`: An `Arc<RwLock<ClientToken>>` managing the client's authentication token.
+/// - `client_version`: The version of the app, which is shown in the user's device list.
 ///
 pub struct Client {
   pub(crate) cloud_client: reqwest::Client,
@@ -57,6 +82,7 @@ pub struct Client {
   base_url: String,
   ws_addr: String,
   token: Arc<RwLock<ClientToken>>,
+  client_version: Option<String>,
 }
 
 /// Hardcoded schema in the frontend application. Do not change this value.
@@ -77,9 +103,16 @@ impl Client {
       cloud_client: reqwest_client.clone(),
       gotrue_client: gotrue::api::Client::new(reqwest_client, gotrue_url),
       token: Arc::new(RwLock::new(ClientToken::new())),
+      client_version: None,
     }
   }
 
+  /// Sets the version of the app, which is sent when the websocket connection is opened.
+  pub fn with_client_version(mut self, client_version: &str) -> Self {
+    self.client_version = Some(client_version.to_string());
+    self
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub fn restore_token(&self, token: &str) -> Result<(), AppError> {
     if token.is_empty() {
@@ -178,12 +211,28 @@ impl Client {
   /// - `Ok(String)`: A `String` containing the constructed authorization URL if the specified provider is available.
   /// - `Err(AppError)`: An `AppError` indicating either the OAuth provider is invalid or other issues occurred while fetching settings.
   ///
+  /// For [OAuthProvider::Saml], the URL is generated by GoTrue from the SAML provider registered
+  /// for the domain. Use [Client::discover_sso_provider] to find the provider of an email.
+  ///
   #[instrument(level = "debug", skip_all, err)]
   pub async fn generate_oauth_url_with_provider(
     &self,
     provider: &OAuthProvider,
   ) -> Result<String, AppError> {
     let settings = self.gotrue_client.settings().await?;
+    if let OAuthProvider::Saml { domain } = provider {
+      if !settings.saml_enabled {
+        return Err(ErrorCode::InvalidOAuthProvider.into());
+      }
+      let params = SsoParams {
+        domain: Some(domain.clone()),
+        redirect_to: Some(DESKTOP_CALLBACK_URL.to_string()),
+        skip_http_redirect: true,
+        ..Default::default()
+      };
+      let oauth_url = self.gotrue_client.sso_url(&params).await?;
+      return Ok(oauth_url.url);
+    }
     if !settings.external.has_provider(provider) {
       return Err(ErrorCode::InvalidOAuthProvider.into());
     }
@@ -388,6 +437,52 @@ impl Client {
       .into_data()
   }
 
+  /// Sets the avatar of the user. The image is cropped to a square and resized by the server.
+  /// Returns the profile with the new [AFUserProfile::avatar_url].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn upload_user_avatar<T: Into<Bytes>, M: ToString>(
+    &self,
+    data: T,
+    mime: M,
+  ) -> Result<AFUserProfile, AppError> {
+    let url = format!("{}/api/user/avatar", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .header(header::CONTENT_TYPE, mime.to_string())
+      .body(data.into())
+      .send()
+      .await?;
+    AppResponse::<AFUserProfile>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn delete_user_avatar(&self) -> Result<(), AppError> {
+    let url = format!("{}/api/user/avatar", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Downloads the avatar with the `avatar_url` of the [AFUserProfile] or the [AFWorkspaceMember],
+  /// which is relative to the base url of the server.
+  pub async fn get_user_avatar(&self, avatar_url: &str) -> Result<Bytes, AppError> {
+    let url = format!("{}{}", self.base_url, avatar_url);
+    let resp = self.cloud_client.get(&url).send().await?;
+    if !resp.status().is_success() {
+      return Err(AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("failed to download avatar, status code: {}", resp.status()),
+      ));
+    }
+    Ok(resp.bytes().await?)
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub async fn get_user_workspace_info(&self) -> Result<AFUserWorkspaceInfo, AppError> {
     let url = format!("{}/api/user/workspace", self.base_url);
@@ -401,6 +496,164 @@ impl Client {
       .into_data()
   }
 
+  /// Creates a personal access token. The returned plaintext token can't be retrieved again.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn create_api_token(
+    &self,
+    params: CreateApiTokenParams,
+  ) -> Result<AFApiTokenCreated, AppError> {
+    let url = format!("{}/api/user/token", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<AFApiTokenCreated>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn list_api_tokens(&self) -> Result<AFApiTokens, AppError> {
+    let url = format!("{}/api/user/token", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFApiTokens>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn revoke_api_token(&self, token_id: &Uuid) -> Result<(), AppError> {
+    let url = format!("{}/api/user/token/{}", self.base_url, token_id);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Lists the devices that the user is signed in on.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn list_user_sessions(&self) -> Result<AFUserSessions, AppError> {
+    let url = format!("{}/api/user/session", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFUserSessions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Signs the device out. It can't refresh its access token anymore and its websocket
+  /// connection is closed.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn sign_out_device(&self, device_id: &str) -> Result<(), AppError> {
+    let url = format!("{}/api/user/session/{}", self.base_url, device_id);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Schedules the deletion of the account. The account, the workspaces that can't be handed
+  /// over to another member and their files are deleted once the grace period is over.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn request_account_deletion(&self) -> Result<AFUserDeletion, AppError> {
+    let url = format!("{}/api/user/deletion", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFUserDeletion>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_account_deletion(&self) -> Result<AFUserDeletion, AppError> {
+    let url = format!("{}/api/user/deletion", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFUserDeletion>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn cancel_account_deletion(&self) -> Result<(), AppError> {
+    let url = format!("{}/api/user/deletion", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Starts exporting the data of the user. Poll [Client::get_data_export] until the export is
+  /// completed, then download it with [Client::download_data_export].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn request_data_export(&self) -> Result<AFUserDataExport, AppError> {
+    let url = format!("{}/api/user/export", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFUserDataExport>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_data_export(&self, export_id: &Uuid) -> Result<AFUserDataExport, AppError> {
+    let url = format!("{}/api/user/export/{}", self.base_url, export_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFUserDataExport>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Downloads the zip archive of a completed export.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn download_data_export(&self, export_id: &Uuid) -> Result<Bytes, AppError> {
+    let url = format!("{}/api/user/export/{}/download", self.base_url, export_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+
+    // The archive is sent as an attachment, errors are sent as a regular response
+    if resp.headers().contains_key(header::CONTENT_DISPOSITION) {
+      Ok(resp.bytes().await?)
+    } else {
+      AppResponse::<()>::from_response(resp).await?.into_error()?;
+      Err(AppError::new(
+        ErrorCode::Unhandled,
+        "the data export archive is missing",
+      ))
+    }
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub async fn get_workspaces(&self) -> Result<AFWorkspaces, AppError> {
     let url = format!("{}/api/workspace/list", self.base_url);
@@ -463,6 +716,118 @@ impl Client {
       .into_data()
   }
 
+  /// Returns all the permissions that can be granted to a custom workspace role.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_permissions(&self) -> Result<AFPermissions, AppError> {
+    let url = format!("{}/api/workspace/permission", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFPermissions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the built-in roles and the custom roles of the workspace.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_workspace_roles(
+    &self,
+    workspace_id: &str,
+  ) -> Result<AFWorkspaceRoles, AppError> {
+    let url = format!("{}/api/workspace/{}/role", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFWorkspaceRoles>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Creates a custom role in the workspace. Only the owner of the workspace can create roles.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn create_workspace_role(
+    &self,
+    workspace_id: &str,
+    params: CreateWorkspaceRoleParams,
+  ) -> Result<AFWorkspaceRole, AppError> {
+    let url = format!("{}/api/workspace/{}/role", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<AFWorkspaceRole>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn update_workspace_role(
+    &self,
+    workspace_id: &str,
+    role_id: i32,
+    params: UpdateWorkspaceRoleParams,
+  ) -> Result<AFWorkspaceRole, AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/role/{}",
+      self.base_url, workspace_id, role_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<AFWorkspaceRole>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Deletes a custom role. The role must not be assigned to any member.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn delete_workspace_role(
+    &self,
+    workspace_id: &str,
+    role_id: i32,
+  ) -> Result<(), AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/role/{}",
+      self.base_url, workspace_id, role_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Returns a page of the audit log of the workspace, newest first. Only the owner of the
+  /// workspace can read it. Pass the `next_cursor` of the returned page as the cursor of the
+  /// params to get the next page.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_workspace_audit_log(
+    &self,
+    workspace_id: &str,
+    params: &QueryAuditLogParams,
+  ) -> Result<AFAuditLogPage, AppError> {
+    let url = format!("{}/api/workspace/{}/audit_log", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(params)
+      .send()
+      .await?;
+    AppResponse::<AFAuditLogPage>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub async fn add_workspace_members<T: Into<CreateWorkspaceMembers>, W: AsRef<str>>(
     &self,
@@ -528,26 +893,88 @@ impl Client {
     Ok(())
   }
 
-  // pub async fn update_workspace_member(&self, workspace_uuid: Uuid, member)
-
-  #[instrument(skip_all, err)]
-  pub async fn sign_in_password(&self, email: &str, password: &str) -> Result<bool, AppError> {
-    let access_token_resp = self
-      .gotrue_client
-      .token(&Grant::Password(PasswordGrant {
-        email: email.to_owned(),
-        password: password.to_owned(),
-      }))
-      .await?;
-    let is_new = self
-      .verify_token_cloud(&access_token_resp.access_token)
+  /// Invites the email to the workspace. Send the returned invitation to the email, the invitee
+  /// joins the workspace with [Client::accept_workspace_invite].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn create_workspace_invite<T: AsRef<str>>(
+    &self,
+    workspace_id: T,
+    member: CreateWorkspaceMember,
+  ) -> Result<AFWorkspaceInvite, AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/invite",
+      self.base_url,
+      workspace_id.as_ref()
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&member)
+      .send()
       .await?;
-    self.token.write().set(access_token_resp);
-    Ok(is_new)
+    AppResponse::<AFWorkspaceInvite>::from_response(resp)
+      .await?
+      .into_data()
   }
 
-  /// Refreshes the access token using the stored refresh token.
-  ///
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn revoke_workspace_invite<T: AsRef<str>>(
+    &self,
+    workspace_id: T,
+    token: &str,
+  ) -> Result<(), AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/invite",
+      self.base_url,
+      workspace_id.as_ref()
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .json(&RevokeWorkspaceInvite {
+        token: token.to_string(),
+      })
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Joins the workspace with the invitation sent to the email of the signed in user.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn accept_workspace_invite(&self, invite: &AFWorkspaceInvite) -> Result<(), AppError> {
+    let url = format!("{}/api/workspace/invite/accept", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&AcceptWorkspaceInvite {
+        workspace_id: invite.workspace_id,
+        token: invite.token.clone(),
+      })
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  // pub async fn update_workspace_member(&self, workspace_uuid: Uuid, member)
+
+  #[instrument(skip_all, err)]
+  pub async fn sign_in_password(&self, email: &str, password: &str) -> Result<bool, AppError> {
+    let access_token_resp = self
+      .gotrue_client
+      .token(&Grant::Password(PasswordGrant {
+        email: email.to_owned(),
+        password: password.to_owned(),
+      }))
+      .await?;
+    let is_new = self
+      .verify_token_cloud(&access_token_resp.access_token)
+      .await?;
+    self.token.write().set(access_token_resp);
+    Ok(is_new)
+  }
+
+  /// Refreshes the access token using the stored refresh token.
+  ///
   /// This function attempts to refresh the access token by sending a request to the authentication server
   /// using the stored refresh token. If successful, it updates the stored access token with the new one
   /// received from the server.
@@ -627,6 +1054,164 @@ impl Client {
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  /// Sends the password recovery email to the given address. The code in the email signs the
+  /// user in with [Client::verify_otp], and the password can then be set with
+  /// [Client::change_password].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn send_password_recovery(&self, email: &str) -> Result<(), AppError> {
+    let url = format!("{}/api/user/password/recover", self.base_url);
+    let params = PasswordRecoveryParams {
+      email: email.to_string(),
+    };
+    let resp = self.cloud_client.post(&url).json(&params).send().await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Verifies the code sent by email. Returns true if the user is a new user. The client is signed
+  /// in if the code signs the user in, which isn't the case for the first of the two codes sent
+  /// by an email change.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn verify_otp(
+    &self,
+    otp_type: OtpType,
+    email: &str,
+    token: &str,
+  ) -> Result<bool, AppError> {
+    let url = format!("{}/api/user/otp/verify", self.base_url);
+    let params = VerifyOtpParams {
+      otp_type,
+      email: email.to_string(),
+      token: token.to_string(),
+    };
+    let resp = self.cloud_client.post(&url).json(&params).send().await?;
+    let verify_resp = AppResponse::<VerifyOtpResponse>::from_response(resp)
+      .await?
+      .into_data()?;
+    if let Some(access_token_resp) = verify_resp.access_token_resp {
+      self.token.write().set(access_token_resp);
+    }
+    Ok(verify_resp.is_new)
+  }
+
+  /// Sends a nonce to the email of the user, to pass to [Client::change_password] when the server
+  /// requires the user to reauthenticate to change the password.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn reauthenticate(&self) -> Result<(), AppError> {
+    let url = format!("{}/api/user/reauthenticate", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn change_password(&self, password: &str, nonce: Option<&str>) -> Result<(), AppError> {
+    let url = format!("{}/api/user/password/change", self.base_url);
+    let params = ChangePasswordParams {
+      password: password.to_string(),
+      nonce: nonce.map(|nonce| nonce.to_string()),
+    };
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Requests the change of the email. The email is changed once the codes sent by email are
+  /// verified with [Client::verify_otp] and [OtpType::EmailChange].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn change_email(&self, email: &str) -> Result<(), AppError> {
+    let url = format!("{}/api/user/email", self.base_url);
+    let params = ChangeEmailParams {
+      email: email.to_string(),
+    };
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Returns the second factors of the user, including the unverified ones.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn list_factors(&self) -> Result<Vec<Factor>, AppError> {
+    let user = self.gotrue_client.user_info(&self.access_token()?).await?;
+    Ok(user.factors.unwrap_or_default())
+  }
+
+  /// Enrolls a TOTP factor. The returned secret, or its QR code, is added to the authenticator
+  /// app of the user, and the factor is verified with [Client::challenge_and_verify_factor].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn enroll_totp(&self, friendly_name: Option<&str>) -> Result<TotpEnrollment, AppError> {
+    let params = EnrollFactorParams {
+      factor_type: FactorType::Totp,
+      friendly_name: friendly_name.map(|name| name.to_string()),
+      issuer: None,
+    };
+    let enrollment = self
+      .gotrue_client
+      .enroll_factor(&self.access_token()?, &params)
+      .await?;
+    Ok(enrollment)
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn challenge_factor(&self, factor_id: &str) -> Result<FactorChallenge, AppError> {
+    let challenge = self
+      .gotrue_client
+      .challenge_factor(&self.access_token()?, factor_id)
+      .await?;
+    Ok(challenge)
+  }
+
+  /// Verifies the code of the challenge. On success, the client switches to the `aal2` token
+  /// that is required by the sensitive operations, e.g. changing the password.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn verify_factor(
+    &self,
+    factor_id: &str,
+    challenge_id: &str,
+    code: &str,
+  ) -> Result<(), AppError> {
+    let params = VerifyFactorParams {
+      challenge_id: challenge_id.to_string(),
+      code: code.to_string(),
+    };
+    let access_token_resp = self
+      .gotrue_client
+      .verify_factor(&self.access_token()?, factor_id, &params)
+      .await?;
+    self.token.write().set(access_token_resp);
+    Ok(())
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn challenge_and_verify_factor(
+    &self,
+    factor_id: &str,
+    code: &str,
+  ) -> Result<(), AppError> {
+    let challenge = self.challenge_factor(factor_id).await?;
+    self.verify_factor(factor_id, &challenge.id, code).await
+  }
+
+  /// Removing a verified factor requires the `aal2` token, see [Client::verify_factor].
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn unenroll_factor(&self, factor_id: &str) -> Result<(), AppError> {
+    self
+      .gotrue_client
+      .unenroll_factor(&self.access_token()?, factor_id)
+      .await?;
+    Ok(())
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub async fn create_collab(&self, params: InsertCollabParams) -> Result<(), AppError> {
     let url = format!(
@@ -800,7 +1385,170 @@ impl Client {
 
   pub fn ws_url(&self, device_id: &str) -> Result<String, AppError> {
     let access_token = self.access_token()?;
-    Ok(format!("{}/{}/{}", self.ws_addr, access_token, device_id))
+    let mut url = Url::parse(&format!("{}/{}/{}", self.ws_addr, access_token, device_id))?;
+    if let Some(client_version) = &self.client_version {
+      url
+        .query_pairs_mut()
+        .append_pair("client_version", client_version);
+    }
+    Ok(url.to_string())
+  }
+
+  /// Sets the parent of the collab object. The members of the parent inherit their access level
+  /// on the object, unless they are members of the object itself.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn set_collab_parent(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    parent_id: &str,
+  ) -> Result<AFCollabParent, AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/parent",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&SetCollabParentParams {
+        parent_id: parent_id.to_string(),
+      })
+      .send()
+      .await?;
+    AppResponse::<AFCollabParent>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_collab_parent(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<AFCollabParent, AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/parent",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFCollabParent>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn remove_collab_parent(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<(), AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/parent",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Publishes the collab object with a new share link. The returned plaintext token can't be
+  /// retrieved again.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn create_share_link(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    params: CreateShareLinkParams,
+  ) -> Result<AFShareLinkCreated, AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/share",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<AFShareLinkCreated>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_share_links(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<AFShareLinks, AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/share",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFShareLinks>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn revoke_share_link(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    share_id: &Uuid,
+  ) -> Result<(), AppError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/share/{}",
+      self.base_url, workspace_id, object_id, share_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Returns the collab object of a share link. It doesn't require the client to be logged in.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn get_shared_collab(
+    &self,
+    share_token: &str,
+    password: Option<&str>,
+  ) -> Result<AFSharedCollab, AppError> {
+    let url = format!("{}/api/share/{}", self.base_url, share_token);
+    let mut request = self.cloud_client.get(&url);
+    if let Some(password) = password {
+      request = request.header(SHARE_PASSWORD_HEADER, password);
+    }
+    let resp = request.send().await?;
+    AppResponse::<AFSharedCollab>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// The websocket url for the anonymous viewer of a share link. The connection is read only.
+  ///
+  /// The password of a password protected link is never put in the url. It must be sent with the
+  /// `X-Share-Password` header of the websocket handshake.
+  pub fn share_ws_url(&self, share_token: &str, device_id: &str) -> Result<String, AppError> {
+    let url = Url::parse(&format!(
+      "{}/share/{}/{}",
+      self.ws_addr, share_token, device_id
+    ))?;
+    Ok(url.to_string())
   }
 
   pub async fn put_blob<T: Into<Bytes>, M: ToString>(
@@ -874,6 +1622,184 @@ impl Client {
       .into_data()
   }
 
+  /// Uploads the blob directly to the bucket with a pre-signed url, so the content doesn't pass
+  /// through the server. Falls back to [Self::put_blob] when the server can't pre-sign urls.
+  /// Returns the url of the blob.
+  pub async fn put_blob_presigned<T: Into<Bytes>, M: ToString>(
+    &self,
+    workspace_id: &str,
+    data: T,
+    mime: M,
+  ) -> Result<String, AppError> {
+    let data = data.into();
+    let file_type = mime.to_string();
+    let file_id = URL_SAFE.encode(Sha256::digest(&data));
+    let params = PresignedUploadParams {
+      file_id: file_id.clone(),
+      file_type: file_type.clone(),
+      file_size: data.len() as i64,
+    };
+    match self.presign_blob_upload(workspace_id, &params).await? {
+      AFPresignedUpload::Exists(_) => {},
+      AFPresignedUpload::Unsupported => return self.put_blob(workspace_id, data, file_type).await,
+      AFPresignedUpload::Url { upload_id, url, .. } => {
+        self.put_presigned_url(&url, data).await?;
+        self
+          .complete_presigned_upload(workspace_id, &upload_id)
+          .await?;
+      },
+    }
+
+    Ok(format!(
+      "{}/api/file_storage/{}/blob/{}",
+      self.base_url, workspace_id, file_id
+    ))
+  }
+
+  /// Requests a pre-signed url for uploading the blob, see [Self::put_blob_presigned].
+  pub async fn presign_blob_upload(
+    &self,
+    workspace_id: &str,
+    params: &PresignedUploadParams,
+  ) -> Result<AFPresignedUpload, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/presigned/upload",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    AppResponse::<AFPresignedUpload>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Uploads the data to the bucket with the pre-signed url.
+  pub async fn put_presigned_url<T: Into<Bytes>>(
+    &self,
+    url: &str,
+    data: T,
+  ) -> Result<(), AppError> {
+    let data = data.into();
+    let resp = self
+      .cloud_client
+      .put(url)
+      .header(header::CONTENT_LENGTH, data.len())
+      .body(data)
+      .send()
+      .await?;
+    if !resp.status().is_success() {
+      return Err(AppError::new(
+        ErrorCode::S3Error,
+        format!("failed to upload blob, status code: {}", resp.status()),
+      ));
+    }
+    Ok(())
+  }
+
+  /// Confirms the upload once the blob is uploaded with the pre-signed url.
+  pub async fn complete_presigned_upload(
+    &self,
+    workspace_id: &str,
+    upload_id: &Uuid,
+  ) -> Result<AFBlobRecord, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/presigned/complete",
+      self.base_url, workspace_id
+    );
+    let params = CompletePresignedUploadParams {
+      upload_id: *upload_id,
+    };
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    AppResponse::<AFBlobRecord>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns a pre-signed url for downloading the blob directly from the bucket.
+  pub async fn get_blob_presigned_url(
+    &self,
+    workspace_id: &str,
+    file_id: &str,
+  ) -> Result<AFPresignedDownload, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/presigned/download/{}",
+      self.base_url, workspace_id, file_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFPresignedDownload>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Downloads the blob directly from the bucket with a pre-signed url. Falls back to
+  /// [Self::get_blob] when the server can't pre-sign urls.
+  pub async fn get_blob_presigned(
+    &self,
+    workspace_id: &str,
+    file_id: &str,
+  ) -> Result<Bytes, AppError> {
+    match self.get_blob_presigned_url(workspace_id, file_id).await? {
+      AFPresignedDownload::Unsupported => {
+        let url = format!(
+          "{}/api/file_storage/{}/blob/{}",
+          self.base_url, workspace_id, file_id
+        );
+        self.get_blob(url).await
+      },
+      AFPresignedDownload::Url { url, .. } => {
+        let resp = self.cloud_client.get(&url).send().await?;
+        if !resp.status().is_success() {
+          return Err(AppError::new(
+            ErrorCode::S3Error,
+            format!("failed to download blob, status code: {}", resp.status()),
+          ));
+        }
+        Ok(resp.bytes().await?)
+      },
+    }
+  }
+
+  /// Creates a link that downloads the blob once without signing in. The url of the link is
+  /// returned by [Self::get_blob_download_link_url].
+  pub async fn create_blob_download_link(
+    &self,
+    workspace_id: &str,
+    file_id: &str,
+  ) -> Result<AFBlobDownloadLink, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/download_link/{}",
+      self.base_url, workspace_id, file_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFBlobDownloadLink>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub fn get_blob_download_link_url(&self, file_id: &str, link: &AFBlobDownloadLink) -> String {
+    format!(
+      "{}/api/file_storage/download/{}?token={}",
+      self.base_url, file_id, link.token
+    )
+  }
+
   /// Get the file with the given url. The url should be in the format of
   /// `https://appflowy.io/api/file_storage/<workspace_id>/<file_id>`.
   pub async fn get_blob<T: AsRef<str>>(&self, url: T) -> Result<Bytes, AppError> {
@@ -901,6 +1827,18 @@ impl Client {
     }
   }
 
+  /// Get the thumbnail of the image with the given url. The smallest thumbnail that is at least
+  /// `size` pixels is returned, or the original image if there is no such thumbnail.
+  pub async fn get_blob_thumbnail<T: AsRef<str>>(
+    &self,
+    url: T,
+    size: u32,
+  ) -> Result<Bytes, AppError> {
+    let mut url = Url::parse(url.as_ref())?;
+    url.query_pairs_mut().append_pair("size", &size.to_string());
+    self.get_blob(url).await
+  }
+
   pub async fn get_blob_metadata<T: AsRef<str>>(&self, url: T) -> Result<AFBlobMetadata, AppError> {
     let resp = self
       .http_client_with_auth(Method::GET, url.as_ref())
@@ -937,6 +1875,48 @@ impl Client {
       .into_data()
   }
 
+  pub async fn get_workspace_usage_by_file_type(
+    &self,
+    workspace_id: &str,
+  ) -> Result<WorkspaceUsageByFileType, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/usage/file_types",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<WorkspaceUsageByFileType>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns a page of the blob metadata of the workspace. Pass the `next_cursor` of the returned
+  /// page as the cursor of the params to get the next page.
+  pub async fn get_workspace_blob_metadata(
+    &self,
+    workspace_id: &str,
+    params: &QueryBlobMetadataParams,
+  ) -> Result<WorkspaceBlobMetadataPage, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/blobs/page",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(params)
+      .send()
+      .await?;
+    AppResponse::<WorkspaceBlobMetadataPage>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the metadata of all blobs of the workspace. Use [Self::get_workspace_blob_metadata]
+  /// to fetch it one page at a time.
   pub async fn get_workspace_all_blob_metadata(
     &self,
     workspace_id: &str,
@@ -952,6 +1932,210 @@ impl Client {
       .into_data()
   }
 
+  pub async fn get_file_type_policy(
+    &self,
+    workspace_id: &str,
+  ) -> Result<AFFileTypePolicy, AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/file_type_policy",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFFileTypePolicy>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Replaces the file type policy of the workspace. Only the owner of the workspace can update
+  /// the policy.
+  pub async fn update_file_type_policy(
+    &self,
+    workspace_id: &str,
+    policy: &AFFileTypePolicy,
+  ) -> Result<(), AppError> {
+    let url = format!(
+      "{}/api/file_storage/{}/file_type_policy",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(policy)
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Returns what the blob garbage collector would delete, without deleting anything. Only the
+  /// admin can call this method. If `workspace_id` is None, all workspaces are checked.
+  pub async fn get_blob_gc_report(
+    &self,
+    workspace_id: Option<&str>,
+  ) -> Result<BlobGCReports, AppError> {
+    let url = format!("{}/api/admin/blob_gc/report", self.base_url);
+    let mut builder = self.http_client_with_auth(Method::GET, &url).await?;
+    if let Some(workspace_id) = workspace_id {
+      builder = builder.query(&[("workspace_id", workspace_id)]);
+    }
+    let resp = builder.send().await?;
+    AppResponse::<BlobGCReports>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Runs the blob garbage collector now and returns what it marked and deleted. Only the admin
+  /// can call this method. If `workspace_id` is None, all workspaces are collected.
+  pub async fn run_blob_gc(&self, workspace_id: Option<&str>) -> Result<BlobGCReports, AppError> {
+    let url = format!("{}/api/admin/blob_gc", self.base_url);
+    let mut builder = self.http_client_with_auth(Method::POST, &url).await?;
+    if let Some(workspace_id) = workspace_id {
+      builder = builder.query(&[("workspace_id", workspace_id)]);
+    }
+    let resp = builder.send().await?;
+    AppResponse::<BlobGCReports>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Deletes the accounts whose grace period is over now, instead of waiting for the next
+  /// scheduled run. Only the admin can call this method.
+  pub async fn run_user_deletion(&self) -> Result<(), AppError> {
+    let url = format!("{}/api/admin/user_deletion", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Returns the SSO provider that the user with the email signs in with. Returns
+  /// [ErrorCode::RecordNotFound] if the domain of the email doesn't have a provider.
+  #[instrument(level = "debug", skip_all, err)]
+  pub async fn discover_sso_provider(&self, email: &str) -> Result<OAuthProvider, AppError> {
+    let url = format!("{}/api/user/sso/discover", self.base_url);
+    let resp = self
+      .cloud_client
+      .get(&url)
+      .query(&[("email", email)])
+      .send()
+      .await?;
+    let provider = AppResponse::<SsoSignInProvider>::from_response(resp)
+      .await?
+      .into_data()?;
+    Ok(provider.into())
+  }
+
+  /// Lists the users page by page, along with their usage. Only the admin can list the users.
+  pub async fn admin_list_users(
+    &self,
+    query: &AdminListUsersQuery,
+  ) -> Result<AdminUserPage, AppError> {
+    let url = format!("{}/api/admin/user", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    AppResponse::<AdminUserPage>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Bans the user, see [BanUserParams]. Only the admin can ban a user.
+  pub async fn ban_user(
+    &self,
+    user_uuid: &Uuid,
+    params: &BanUserParams,
+  ) -> Result<AFUserBan, AppError> {
+    let url = format!("{}/api/admin/user/{}/ban", self.base_url, user_uuid);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    AppResponse::<AFUserBan>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn unban_user(&self, user_uuid: &Uuid) -> Result<(), AppError> {
+    let url = format!("{}/api/admin/user/{}/ban", self.base_url, user_uuid);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Registers the SSO provider of an email domain. Only the admin can call this method.
+  pub async fn create_sso_provider(
+    &self,
+    params: &CreateSsoProviderParams,
+  ) -> Result<AFSsoProvider, AppError> {
+    let url = format!("{}/api/admin/sso/provider", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    AppResponse::<AFSsoProvider>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn list_sso_providers(&self) -> Result<AFSsoProviders, AppError> {
+    let url = format!("{}/api/admin/sso/provider", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<AFSsoProviders>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn delete_sso_provider(&self, provider_id: &Uuid) -> Result<(), AppError> {
+    let url = format!("{}/api/admin/sso/provider/{}", self.base_url, provider_id);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Downloads the audit log in the requested format. Only the admin can export the audit log.
+  pub async fn export_audit_log(&self, params: &ExportAuditLogParams) -> Result<Bytes, AppError> {
+    let url = format!("{}/api/admin/audit_log/export", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(params)
+      .send()
+      .await?;
+
+    // The export is sent as an attachment, errors are sent as a regular response
+    if resp.headers().contains_key(header::CONTENT_DISPOSITION) {
+      Ok(resp.bytes().await?)
+    } else {
+      AppResponse::<()>::from_response(resp).await?.into_error()?;
+      Err(AppError::new(
+        ErrorCode::Unhandled,
+        "the audit log export is missing",
+      ))
+    }
+  }
+
   async fn http_client_with_auth(
     &self,
     method: Method,
@@ -1,5 +1,8 @@
 use crate::error::DatabaseError;
-use crate::pg_row::{AFBlobMetadataRow, AFUserProfileRow, AFWorkspaceRow};
+use crate::pg_row::{
+  AFApiTokenRow, AFAuditLogRow, AFBlobMetadataRow, AFShareLinkRow, AFSsoProviderRow, AFUserBanRow,
+  AFUserDataExportRow, AFUserDeletionRow, AFUserProfileRow, AFUserSessionRow, AFWorkspaceRow,
+};
 use anyhow::anyhow;
 use chrono::{DateTime, Utc};
 use collab_entity::CollabType;
@@ -7,6 +10,7 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 use tracing::error;
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
@@ -149,6 +153,110 @@ impl AFBlobRecord {
   }
 }
 
+/// Parameters to request a pre-signed upload url. The `file_id` is the URL safe base64 encoding,
+/// with padding, of the sha256 hash of the blob.
+#[derive(Debug, Clone, Validate, Serialize, Deserialize)]
+pub struct PresignedUploadParams {
+  #[validate(custom = "validate_not_empty_str")]
+  pub file_id: String,
+  #[validate(custom = "validate_not_empty_str")]
+  pub file_type: String,
+  pub file_size: i64,
+}
+
+/// Parameters to confirm that a blob was uploaded with a pre-signed url. The server verifies
+/// that the hash of the uploaded content matches the declared `file_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletePresignedUploadParams {
+  /// The `upload_id` returned with the pre-signed url.
+  pub upload_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AFPresignedUpload {
+  /// The workspace already stores the blob, it doesn't need to be uploaded again.
+  Exists(AFBlobRecord),
+  /// Upload the blob with a PUT request to the url, then confirm the upload with the
+  /// `upload_id` before the url expires.
+  Url {
+    upload_id: Uuid,
+    url: String,
+    expires_in_secs: u32,
+  },
+  /// The storage can't pre-sign urls. Upload the blob through the server instead.
+  Unsupported,
+}
+
+pub const DEFAULT_BLOB_METADATA_PAGE_SIZE: i64 = 100;
+pub const MAX_BLOB_METADATA_PAGE_SIZE: i64 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobMetadataOrderBy {
+  #[default]
+  ModifiedAt,
+  FileSize,
+}
+
+/// Parameters to list the blob metadata of a workspace one page at a time.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QueryBlobMetadataParams {
+  /// The `next_cursor` returned with the previous page. None to start from the first page.
+  pub cursor: Option<String>,
+  /// The number of items per page, at most [MAX_BLOB_METADATA_PAGE_SIZE].
+  pub limit: Option<i64>,
+  /// Either a full type like `image/png` or a type wildcard like `image/*`.
+  pub file_type: Option<String>,
+  pub modified_after: Option<DateTime<Utc>>,
+  pub modified_before: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub order_by: BlobMetadataOrderBy,
+  /// Sort in ascending order. The default is descending, newest or largest first.
+  #[serde(default)]
+  pub ascending: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFFileTypeUsage {
+  pub file_type: String,
+  pub file_count: i64,
+  pub total_size: i64,
+}
+
+/// Controls which types of blobs can be stored in a workspace. Patterns are either a full type
+/// like `image/png`, a type wildcard like `image/*` or `*`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AFFileTypePolicy {
+  /// If not empty, only the matching types are allowed.
+  pub allowed: Vec<String>,
+  /// The matching types are always denied.
+  pub denied: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AFPresignedDownload {
+  /// Download the blob with a GET request to the url.
+  Url { url: String, expires_in_secs: u32 },
+  /// The storage can't pre-sign urls. Download the blob through the server instead.
+  Unsupported,
+}
+
+/// The invitation of an email to a workspace. The invitee accepts it with the `workspace_id` and
+/// the `token`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFWorkspaceInvite {
+  pub workspace_id: Uuid,
+  pub token: String,
+  pub expires_at: DateTime<Utc>,
+}
+
+/// A link that downloads the blob once without signing in.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFBlobDownloadLink {
+  pub token: String,
+  pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub enum QueryCollabResult {
   Success { blob: RawData },
@@ -199,13 +307,9 @@ pub enum AFRole {
   Owner,
   Member,
   Guest,
-}
-
-impl AFRole {
-  /// The user can create a [Collab] if the user is [AFRole::Owner] or [AFRole::Member] of the workspace.
-  pub fn can_create_collab(&self) -> bool {
-    matches!(self, AFRole::Owner | AFRole::Member)
-  }
+  /// A role defined by the owner of the workspace. The value is the id of the role in `af_roles`.
+  /// The permissions of the role are stored in `af_role_permissions`.
+  Custom(i32),
 }
 
 impl From<i32> for AFRole {
@@ -215,10 +319,7 @@ impl From<i32> for AFRole {
       1 => AFRole::Owner,
       2 => AFRole::Member,
       3 => AFRole::Guest,
-      _ => {
-        error!("Invalid role id: {}", value);
-        AFRole::Guest
-      },
+      _ => AFRole::Custom(value),
     }
   }
 }
@@ -242,10 +343,40 @@ impl From<AFRole> for i32 {
       AFRole::Owner => 1,
       AFRole::Member => 2,
       AFRole::Guest => 3,
+      AFRole::Custom(id) => id,
     }
   }
 }
 
+/// A role of the workspace together with the permissions that are granted to it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFWorkspaceRole {
+  pub role: AFRole,
+  pub name: String,
+  pub permissions: Vec<AFPermission>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFWorkspaceRoles(pub Vec<AFWorkspaceRole>);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreateWorkspaceRoleParams {
+  #[validate(custom = "validate_not_empty_str")]
+  pub name: String,
+  /// The ids of the permissions in `af_permissions`.
+  pub permission_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UpdateWorkspaceRoleParams {
+  pub name: Option<String>,
+  /// Replaces the permissions of the role if set.
+  pub permission_ids: Option<Vec<i32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFPermissions(pub Vec<AFPermission>);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AFPermission {
   /// The permission id
@@ -255,7 +386,8 @@ pub struct AFPermission {
   pub description: String,
 }
 
-#[derive(Deserialize_repr, Serialize_repr, Eq, PartialEq, Debug, Clone)]
+/// The variants are declared from the lowest to the highest access level, so they can be compared.
+#[derive(Deserialize_repr, Serialize_repr, Eq, PartialEq, PartialOrd, Ord, Debug, Clone)]
 #[repr(i32)]
 pub enum AFAccessLevel {
   // Can't modify the value of the enum
@@ -321,6 +453,15 @@ pub struct AFUserProfile {
   pub encryption_sign: Option<String>,
   pub latest_workspace_id: Uuid,
   pub updated_at: i64,
+  /// See [user_avatar_url].
+  #[serde(default)]
+  pub avatar_url: Option<String>,
+}
+
+/// Returns the path of the avatar of the user, relative to the base url of the server. Anyone
+/// can download the avatar with it, but the id of the avatar can't be guessed.
+pub fn user_avatar_url(user_uuid: &Uuid, avatar_id: &Uuid) -> String {
+  format!("/api/user/avatar/{}/{}", user_uuid, avatar_id)
 }
 
 impl TryFrom<AFUserProfileRow> for AFUserProfile {
@@ -348,6 +489,9 @@ impl TryFrom<AFUserProfileRow> for AFUserProfile {
       encryption_sign: value.encryption_sign,
       latest_workspace_id,
       updated_at: value.updated_at.map(|v| v.timestamp()).unwrap_or(0),
+      avatar_url: value
+        .avatar_id
+        .map(|avatar_id| user_avatar_url(&uuid, &avatar_id)),
     })
   }
 }
@@ -404,9 +548,616 @@ pub struct AFWorkspaceMember {
   pub name: String,
   pub email: String,
   pub role: AFRole,
+  /// See [user_avatar_url].
   pub avatar_url: Option<String>,
 }
 
+/// The scope of a personal access token. It limits what the token can do on top of the
+/// permissions of the user that owns the token.
+#[derive(Deserialize_repr, Serialize_repr, Eq, PartialEq, Debug, Clone, Copy)]
+#[repr(i32)]
+pub enum AFApiTokenScope {
+  // Can't modify the value of the enum
+  /// Only allows reading.
+  ReadOnly = 1,
+  /// Allows reading and writing the collab objects.
+  CollabWrite = 2,
+  /// Allows everything the user can do.
+  Admin = 3,
+}
+
+impl From<i32> for AFApiTokenScope {
+  fn from(value: i32) -> Self {
+    // Can't modify the value of the enum
+    match value {
+      1 => AFApiTokenScope::ReadOnly,
+      2 => AFApiTokenScope::CollabWrite,
+      3 => AFApiTokenScope::Admin,
+      _ => {
+        error!("Invalid api token scope: {}", value);
+        AFApiTokenScope::ReadOnly
+      },
+    }
+  }
+}
+
+impl From<AFApiTokenScope> for i32 {
+  fn from(scope: AFApiTokenScope) -> Self {
+    scope as i32
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreateApiTokenParams {
+  #[validate(custom = "validate_not_empty_str")]
+  pub name: String,
+  /// If set, the token can only access this workspace.
+  pub workspace_id: Option<Uuid>,
+  pub scope: AFApiTokenScope,
+  /// If not set, the token never expires.
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFApiToken {
+  pub id: Uuid,
+  pub name: String,
+  pub workspace_id: Option<Uuid>,
+  pub scope: AFApiTokenScope,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<AFApiTokenRow> for AFApiToken {
+  fn from(row: AFApiTokenRow) -> Self {
+    Self {
+      id: row.id,
+      name: row.name,
+      workspace_id: row.workspace_id,
+      scope: row.scope.into(),
+      created_at: row.created_at,
+      expires_at: row.expires_at,
+      last_used_at: row.last_used_at,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFApiTokens(pub Vec<AFApiToken>);
+
+/// Returned when a token is created. The plaintext `token` can't be retrieved again.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFApiTokenCreated {
+  pub token: String,
+  pub info: AFApiToken,
+}
+
+/// A device that the user is signed in on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFUserSession {
+  pub device_id: String,
+  pub client_version: Option<String>,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub last_seen_at: DateTime<Utc>,
+  /// True if the device signed in with the session that made the request.
+  pub is_current: bool,
+}
+
+impl AFUserSession {
+  pub fn new(row: AFUserSessionRow, current_session_id: Option<&Uuid>) -> Self {
+    let is_current = row.session_id.is_some() && row.session_id.as_ref() == current_session_id;
+    Self {
+      device_id: row.device_id,
+      client_version: row.client_version,
+      ip: row.ip,
+      user_agent: row.user_agent,
+      created_at: row.created_at,
+      last_seen_at: row.last_seen_at,
+      is_current,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFUserSessions(pub Vec<AFUserSession>);
+
+/// The pending deletion of the account. The account can be restored until `scheduled_at`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFUserDeletion {
+  pub requested_at: DateTime<Utc>,
+  pub scheduled_at: DateTime<Utc>,
+}
+
+impl From<AFUserDeletionRow> for AFUserDeletion {
+  fn from(row: AFUserDeletionRow) -> Self {
+    Self {
+      requested_at: row.requested_at,
+      scheduled_at: row.scheduled_at,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AFUserDataExportStatus {
+  /// The archive is being built.
+  Pending,
+  /// The archive can be downloaded until it expires.
+  Completed,
+  Failed,
+  /// The archive was deleted after it expired.
+  Expired,
+}
+
+impl AFUserDataExportStatus {
+  // Can't modify the value of the status, it's stored in the database
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      AFUserDataExportStatus::Pending => "pending",
+      AFUserDataExportStatus::Completed => "completed",
+      AFUserDataExportStatus::Failed => "failed",
+      AFUserDataExportStatus::Expired => "expired",
+    }
+  }
+}
+
+impl FromStr for AFUserDataExportStatus {
+  type Err = DatabaseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "pending" => Ok(AFUserDataExportStatus::Pending),
+      "completed" => Ok(AFUserDataExportStatus::Completed),
+      "failed" => Ok(AFUserDataExportStatus::Failed),
+      "expired" => Ok(AFUserDataExportStatus::Expired),
+      _ => Err(DatabaseError::InvalidParams(format!(
+        "Invalid data export status: {}",
+        s
+      ))),
+    }
+  }
+}
+
+/// An export of the personal data of the user: the profile, the workspaces, the collabs owned by
+/// the user and the blobs of the workspaces owned by the user, packaged in a zip archive.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFUserDataExport {
+  pub export_id: Uuid,
+  pub status: AFUserDataExportStatus,
+  /// The size of the archive in bytes, once it's completed.
+  pub file_size: Option<i64>,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub completed_at: Option<DateTime<Utc>>,
+  /// The archive can't be downloaded after this time.
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<AFUserDataExportRow> for AFUserDataExport {
+  type Error = DatabaseError;
+
+  fn try_from(row: AFUserDataExportRow) -> Result<Self, Self::Error> {
+    Ok(Self {
+      export_id: row.export_id,
+      status: row.status.parse()?,
+      file_size: row.file_size,
+      error: row.error,
+      created_at: row.created_at,
+      completed_at: row.completed_at,
+      expires_at: row.expires_at,
+    })
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AFSsoProviderType {
+  Saml,
+  Oidc,
+}
+
+impl AFSsoProviderType {
+  // Can't modify the value of the type, it's stored in the database
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      AFSsoProviderType::Saml => "saml",
+      AFSsoProviderType::Oidc => "oidc",
+    }
+  }
+}
+
+impl FromStr for AFSsoProviderType {
+  type Err = DatabaseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "saml" => Ok(AFSsoProviderType::Saml),
+      "oidc" => Ok(AFSsoProviderType::Oidc),
+      _ => Err(DatabaseError::InvalidParams(format!(
+        "Invalid sso provider type: {}",
+        s
+      ))),
+    }
+  }
+}
+
+/// The SSO provider of an email domain. The users whose email belongs to the `domain` and who
+/// sign in through the provider are added to the workspace as members.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFSsoProvider {
+  pub provider_id: Uuid,
+  pub domain: String,
+  pub workspace_id: Uuid,
+  pub provider_type: AFSsoProviderType,
+  /// The id of the SAML provider in GoTrue, or the name of the OIDC provider configured in GoTrue.
+  pub gotrue_provider: String,
+  pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<AFSsoProviderRow> for AFSsoProvider {
+  type Error = DatabaseError;
+
+  fn try_from(row: AFSsoProviderRow) -> Result<Self, Self::Error> {
+    Ok(Self {
+      provider_id: row.provider_id,
+      domain: row.domain,
+      workspace_id: row.workspace_id,
+      provider_type: row.provider_type.parse()?,
+      gotrue_provider: row.gotrue_provider,
+      created_at: row.created_at,
+    })
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFSsoProviders(pub Vec<AFSsoProvider>);
+
+/// How the users of the domain sign in.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SsoProviderConfig {
+  /// Registers the SAML identity provider in GoTrue. Either the `metadata_url` or the
+  /// `metadata_xml` of the identity provider is required.
+  Saml {
+    metadata_url: Option<String>,
+    metadata_xml: Option<String>,
+    attribute_mapping: Option<serde_json::Value>,
+  },
+  /// An OpenID Connect provider that is configured in GoTrue under the given name.
+  Oidc { name: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateSsoProviderParams {
+  pub domain: String,
+  pub workspace_id: Uuid,
+  pub config: SsoProviderConfig,
+}
+
+/// A user banned by the admin. The user can't use the server until `banned_until`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFUserBan {
+  pub user_uuid: Uuid,
+  pub banned_until: DateTime<Utc>,
+  pub reason: Option<String>,
+  pub banned_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl From<AFUserBanRow> for AFUserBan {
+  fn from(row: AFUserBanRow) -> Self {
+    Self {
+      user_uuid: row.uuid,
+      banned_until: row.banned_until,
+      reason: row.reason,
+      banned_by: row.banned_by,
+      created_at: row.created_at,
+    }
+  }
+}
+
+/// Bans the user for `duration_secs`, or indefinitely if it's not set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BanUserParams {
+  pub duration_secs: Option<i64>,
+  pub reason: Option<String>,
+}
+
+/// Who made a change that is recorded in the audit log.
+#[derive(Debug, Clone, Default)]
+pub struct AuditActor {
+  pub user_uuid: Option<Uuid>,
+  /// Only used when the uuid of the user is unknown.
+  pub uid: Option<i64>,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+}
+
+impl AuditActor {
+  /// The server itself, e.g. a background job.
+  pub fn system() -> Self {
+    Self::default()
+  }
+
+  pub fn from_uuid(user_uuid: Uuid) -> Self {
+    Self {
+      user_uuid: Some(user_uuid),
+      ..Default::default()
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AFAuditAction {
+  AddWorkspaceMember,
+  RemoveWorkspaceMember,
+  UpdateWorkspaceMember,
+  CreateWorkspaceRole,
+  UpdateWorkspaceRole,
+  DeleteWorkspaceRole,
+  DeleteCollab,
+  DeleteBlob,
+  UpdateUser,
+  ChangePassword,
+  CreateShareLink,
+  RevokeShareLink,
+  SignOutDevice,
+  RequestUserDeletion,
+  CancelUserDeletion,
+  DeleteUser,
+  TransferWorkspaceOwnership,
+  DeleteWorkspace,
+  ExportUserData,
+  CreateSsoProvider,
+  DeleteSsoProvider,
+  BanUser,
+  UnbanUser,
+}
+
+impl AFAuditAction {
+  // Can't modify the value of the action, it's stored in the database
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      AFAuditAction::AddWorkspaceMember => "add_workspace_member",
+      AFAuditAction::RemoveWorkspaceMember => "remove_workspace_member",
+      AFAuditAction::UpdateWorkspaceMember => "update_workspace_member",
+      AFAuditAction::CreateWorkspaceRole => "create_workspace_role",
+      AFAuditAction::UpdateWorkspaceRole => "update_workspace_role",
+      AFAuditAction::DeleteWorkspaceRole => "delete_workspace_role",
+      AFAuditAction::DeleteCollab => "delete_collab",
+      AFAuditAction::DeleteBlob => "delete_blob",
+      AFAuditAction::UpdateUser => "update_user",
+      AFAuditAction::ChangePassword => "change_password",
+      AFAuditAction::CreateShareLink => "create_share_link",
+      AFAuditAction::RevokeShareLink => "revoke_share_link",
+      AFAuditAction::SignOutDevice => "sign_out_device",
+      AFAuditAction::RequestUserDeletion => "request_user_deletion",
+      AFAuditAction::CancelUserDeletion => "cancel_user_deletion",
+      AFAuditAction::DeleteUser => "delete_user",
+      AFAuditAction::TransferWorkspaceOwnership => "transfer_workspace_ownership",
+      AFAuditAction::DeleteWorkspace => "delete_workspace",
+      AFAuditAction::ExportUserData => "export_user_data",
+      AFAuditAction::CreateSsoProvider => "create_sso_provider",
+      AFAuditAction::DeleteSsoProvider => "delete_sso_provider",
+      AFAuditAction::BanUser => "ban_user",
+      AFAuditAction::UnbanUser => "unban_user",
+    }
+  }
+}
+
+impl FromStr for AFAuditAction {
+  type Err = DatabaseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let action = match s {
+      "add_workspace_member" => AFAuditAction::AddWorkspaceMember,
+      "remove_workspace_member" => AFAuditAction::RemoveWorkspaceMember,
+      "update_workspace_member" => AFAuditAction::UpdateWorkspaceMember,
+      "create_workspace_role" => AFAuditAction::CreateWorkspaceRole,
+      "update_workspace_role" => AFAuditAction::UpdateWorkspaceRole,
+      "delete_workspace_role" => AFAuditAction::DeleteWorkspaceRole,
+      "delete_collab" => AFAuditAction::DeleteCollab,
+      "delete_blob" => AFAuditAction::DeleteBlob,
+      "update_user" => AFAuditAction::UpdateUser,
+      "change_password" => AFAuditAction::ChangePassword,
+      "create_share_link" => AFAuditAction::CreateShareLink,
+      "revoke_share_link" => AFAuditAction::RevokeShareLink,
+      "sign_out_device" => AFAuditAction::SignOutDevice,
+      "request_user_deletion" => AFAuditAction::RequestUserDeletion,
+      "cancel_user_deletion" => AFAuditAction::CancelUserDeletion,
+      "delete_user" => AFAuditAction::DeleteUser,
+      "transfer_workspace_ownership" => AFAuditAction::TransferWorkspaceOwnership,
+      "delete_workspace" => AFAuditAction::DeleteWorkspace,
+      "export_user_data" => AFAuditAction::ExportUserData,
+      "create_sso_provider" => AFAuditAction::CreateSsoProvider,
+      "delete_sso_provider" => AFAuditAction::DeleteSsoProvider,
+      "ban_user" => AFAuditAction::BanUser,
+      "unban_user" => AFAuditAction::UnbanUser,
+      _ => {
+        return Err(DatabaseError::InvalidParams(format!(
+          "Invalid audit action: {}",
+          s
+        )))
+      },
+    };
+    Ok(action)
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFAuditLogEntry {
+  pub id: i64,
+  pub created_at: DateTime<Utc>,
+  /// None if the action was done by the server.
+  pub actor_uid: Option<i64>,
+  pub workspace_id: Option<Uuid>,
+  pub action: AFAuditAction,
+  /// The id of the object the action was applied to, e.g. the email of a member or a file id.
+  pub target: String,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+}
+
+impl TryFrom<AFAuditLogRow> for AFAuditLogEntry {
+  type Error = DatabaseError;
+
+  fn try_from(row: AFAuditLogRow) -> Result<Self, Self::Error> {
+    Ok(Self {
+      id: row.id,
+      created_at: row.created_at,
+      actor_uid: row.actor_uid,
+      workspace_id: row.workspace_id,
+      action: row.action.parse()?,
+      target: row.target,
+      ip: row.ip,
+      user_agent: row.user_agent,
+    })
+  }
+}
+
+pub const DEFAULT_AUDIT_LOG_PAGE_SIZE: i64 = 100;
+pub const MAX_AUDIT_LOG_PAGE_SIZE: i64 = 1000;
+
+/// Parameters to list the audit log one page at a time, newest first.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QueryAuditLogParams {
+  /// The `next_cursor` returned with the previous page. None to start from the first page.
+  pub cursor: Option<i64>,
+  /// The number of items per page, at most [MAX_AUDIT_LOG_PAGE_SIZE].
+  pub limit: Option<i64>,
+  pub action: Option<AFAuditAction>,
+  pub actor_uid: Option<i64>,
+  pub since: Option<DateTime<Utc>>,
+  pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFAuditLogPage {
+  pub items: Vec<AFAuditLogEntry>,
+  /// Pass it as the cursor of the next query to get the next page. None if this is the last page.
+  pub next_cursor: Option<i64>,
+}
+
+/// Sets the parent of a collab object. The members of the parent inherit their access level on
+/// the object, unless they are members of the object itself.
+#[derive(Debug, Clone, Validate, Serialize, Deserialize)]
+pub struct SetCollabParentParams {
+  #[validate(custom = "validate_not_empty_str")]
+  pub parent_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AFCollabParent {
+  pub object_id: String,
+  /// None if the object doesn't have a parent.
+  pub parent_id: Option<String>,
+}
+
+/// The collab types that can be published with a share link.
+pub const SHAREABLE_COLLAB_TYPES: [CollabType; 2] = [CollabType::Document, CollabType::Database];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateShareLinkParams {
+  /// Only [AFAccessLevel::ReadOnly] and [AFAccessLevel::ReadAndComment] are allowed.
+  pub access_level: AFAccessLevel,
+  pub collab_type: CollabType,
+  /// If not set, the link never expires.
+  pub expires_at: Option<DateTime<Utc>>,
+  /// If set, the viewer must provide the password to open the link.
+  pub password: Option<String>,
+}
+
+impl CreateShareLinkParams {
+  pub fn validate(&self) -> Result<(), DatabaseError> {
+    if self.access_level.can_write() {
+      return Err(DatabaseError::InvalidParams(
+        "A share link can only grant read or comment access".to_string(),
+      ));
+    }
+    if !SHAREABLE_COLLAB_TYPES.contains(&self.collab_type) {
+      return Err(DatabaseError::InvalidParams(format!(
+        "{:?} can't be shared with a link",
+        self.collab_type
+      )));
+    }
+    if let Some(expires_at) = self.expires_at {
+      if expires_at <= Utc::now() {
+        return Err(DatabaseError::InvalidParams(
+          "The expiry of a share link must be in the future".to_string(),
+        ));
+      }
+    }
+    if matches!(&self.password, Some(password) if password.is_empty()) {
+      return Err(DatabaseError::InvalidParams(
+        "The password of a share link can't be empty".to_string(),
+      ));
+    }
+    Ok(())
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AFShareLink {
+  pub id: Uuid,
+  pub object_id: String,
+  pub workspace_id: Uuid,
+  pub collab_type: CollabType,
+  pub access_level: AFAccessLevel,
+  pub has_password: bool,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<AFShareLinkRow> for AFShareLink {
+  type Error = DatabaseError;
+
+  fn try_from(row: AFShareLinkRow) -> Result<Self, Self::Error> {
+    Ok(Self {
+      id: row.id,
+      object_id: row.oid,
+      workspace_id: row.workspace_id,
+      collab_type: shareable_collab_type_from_value(row.collab_type)?,
+      access_level: row.access_level.into(),
+      has_password: row.password_hash.is_some(),
+      created_at: row.created_at,
+      expires_at: row.expires_at,
+    })
+  }
+}
+
+pub fn shareable_collab_type_from_value(value: i32) -> Result<CollabType, DatabaseError> {
+  SHAREABLE_COLLAB_TYPES
+    .iter()
+    .find(|ty| ty.value() == value)
+    .cloned()
+    .ok_or_else(|| DatabaseError::Internal(anyhow!("Invalid shared collab type: {}", value)))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFShareLinks(pub Vec<AFShareLink>);
+
+/// Returned when a share link is created. The plaintext `token` can't be retrieved again.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFShareLinkCreated {
+  pub token: String,
+  pub info: AFShareLink,
+}
+
+/// The collab object returned to the anonymous viewer of a share link.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AFSharedCollab {
+  pub object_id: String,
+  pub workspace_id: Uuid,
+  pub collab_type: CollabType,
+  pub access_level: AFAccessLevel,
+  /// The encoded collab, same as the one returned by the get collab api.
+  pub data: Vec<u8>,
+}
+
 /// ***************************************************************
 /// Make alias for the database entity. Hiding the Sqlx Rows type.
 pub type AFBlobMetadata = AFBlobMetadataRow;
@@ -6,6 +6,9 @@ pub enum DatabaseError {
   #[error("Record not found:{0}")]
   RecordNotFound(String),
 
+  #[error("Record already exists:{0}")]
+  RecordAlreadyExists(String),
+
   #[error(transparent)]
   UnexpectedData(#[from] validator::ValidationErrors),
 
@@ -27,6 +30,9 @@ pub enum DatabaseError {
   #[error("Not enough permission:{0}")]
   NotEnoughPermissions(String),
 
+  #[error("File type not allowed:{0}")]
+  FileTypeNotAllowed(String),
+
   #[error("Invalid params:{0}")]
   InvalidParams(String),
 
@@ -28,13 +28,16 @@ pub struct AFUserProfileRow {
   pub updated_at: Option<DateTime<Utc>>,
   pub created_at: Option<DateTime<Utc>>,
   pub latest_workspace_id: Option<Uuid>,
+  pub avatar_id: Option<Uuid>,
 }
 
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct AFWorkspaceMemberRow {
+  pub uuid: Uuid,
   pub name: String,
   pub email: String,
   pub role: AFRole,
+  pub avatar_id: Option<Uuid>,
 }
 
 #[derive(FromRow, Clone, Debug, Serialize, Deserialize)]
@@ -44,6 +47,13 @@ pub struct AFCollabMemberRow {
   pub permission_id: i64,
 }
 
+#[derive(FromRow, Clone, Debug, Serialize, Deserialize)]
+pub struct AFCollabParentRow {
+  pub oid: String,
+  pub parent_oid: String,
+  pub workspace_id: Uuid,
+}
+
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct AFBlobMetadataRow {
   pub workspace_id: Uuid,
@@ -52,3 +62,139 @@ pub struct AFBlobMetadataRow {
   pub file_size: i64,
   pub modified_at: DateTime<Utc>,
 }
+
+/// A pending upload of a blob with a pre-signed url.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobUploadRow {
+  pub upload_id: Uuid,
+  pub workspace_id: Uuid,
+  /// The file id declared by the client, verified when the upload is completed.
+  pub file_id: String,
+  pub file_type: String,
+  pub file_size: i64,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobThumbnailRow {
+  pub workspace_id: Uuid,
+  pub file_id: String,
+  /// The maximum width/height of the thumbnail in pixels.
+  pub size: i32,
+  /// The key of the thumbnail object in the bucket.
+  pub thumbnail_id: String,
+  pub file_type: String,
+  pub file_size: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFApiTokenRow {
+  pub id: Uuid,
+  pub uid: i64,
+  pub workspace_id: Option<Uuid>,
+  pub name: String,
+  pub scope: i32,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub last_used_at: Option<DateTime<Utc>>,
+  pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFUserSessionRow {
+  pub uid: i64,
+  pub device_id: String,
+  pub session_id: Option<Uuid>,
+  pub client_version: Option<String>,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub last_seen_at: DateTime<Utc>,
+  pub signed_out_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFUserDeletionRow {
+  pub uid: i64,
+  pub uuid: Uuid,
+  pub requested_at: DateTime<Utc>,
+  pub scheduled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFUserDataExportRow {
+  pub export_id: Uuid,
+  pub uid: i64,
+  pub status: String,
+  pub archive_key: Option<String>,
+  pub file_size: Option<i64>,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub completed_at: Option<DateTime<Utc>>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFSsoProviderRow {
+  pub provider_id: Uuid,
+  pub domain: String,
+  pub workspace_id: Uuid,
+  pub provider_type: String,
+  pub gotrue_provider: String,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFUserBanRow {
+  pub uuid: Uuid,
+  pub banned_until: DateTime<Utc>,
+  pub reason: Option<String>,
+  pub banned_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AFUserUsageRow {
+  pub uuid: Uuid,
+  pub uid: i64,
+  pub name: String,
+  pub workspace_count: i64,
+  /// The size, in bytes, of the blobs in the workspaces owned by the user.
+  pub storage_usage: i64,
+}
+
+/// A collab as it's stored in the database, used when the data is exported.
+#[derive(Debug, FromRow)]
+pub struct AFCollabDataRow {
+  pub oid: String,
+  pub workspace_id: Uuid,
+  pub partition_key: i32,
+  pub blob: Vec<u8>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFAuditLogRow {
+  pub id: i64,
+  pub created_at: DateTime<Utc>,
+  pub actor_uid: Option<i64>,
+  pub workspace_id: Option<Uuid>,
+  pub action: String,
+  pub target: String,
+  pub ip: Option<String>,
+  pub user_agent: Option<String>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFShareLinkRow {
+  pub id: Uuid,
+  pub oid: String,
+  pub workspace_id: Uuid,
+  pub collab_type: i32,
+  pub access_level: i32,
+  pub password_hash: Option<String>,
+  pub created_by: i64,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFApiTokenRow;
+use sqlx::{FromRow, PgPool, Row};
+use tracing::instrument;
+use uuid::Uuid;
+
+const API_TOKEN_COLUMNS: &str =
+  "id, uid, workspace_id, name, scope, created_at, expires_at, last_used_at, revoked_at";
+
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip(pg_pool, token_hash), err)]
+pub async fn insert_api_token(
+  pg_pool: &PgPool,
+  id: &Uuid,
+  uid: &i64,
+  workspace_id: Option<&Uuid>,
+  name: &str,
+  token_hash: &str,
+  scope: i32,
+  expires_at: Option<DateTime<Utc>>,
+) -> Result<AFApiTokenRow, DatabaseError> {
+  let row = sqlx::query_as::<_, AFApiTokenRow>(&format!(
+    r#"
+    INSERT INTO af_api_token (id, uid, workspace_id, name, token_hash, scope, expires_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+    RETURNING {}
+    "#,
+    API_TOKEN_COLUMNS
+  ))
+  .bind(id)
+  .bind(uid)
+  .bind(workspace_id)
+  .bind(name)
+  .bind(token_hash)
+  .bind(scope)
+  .bind(expires_at)
+  .fetch_one(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the tokens of the user that are not revoked, including the expired ones.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_api_tokens(
+  pg_pool: &PgPool,
+  uid: &i64,
+) -> Result<Vec<AFApiTokenRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFApiTokenRow>(&format!(
+    r#"
+    SELECT {} FROM af_api_token
+    WHERE uid = $1 AND revoked_at IS NULL
+    ORDER BY created_at DESC
+    "#,
+    API_TOKEN_COLUMNS
+  ))
+  .bind(uid)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Returns the token with the given hash together with the uuid of its owner. Revoked and
+/// expired tokens are not returned.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_active_api_token_by_hash(
+  pg_pool: &PgPool,
+  token_hash: &str,
+) -> Result<Option<(AFApiTokenRow, Uuid)>, DatabaseError> {
+  let row = sqlx::query(
+    r#"
+    SELECT t.id, t.uid, t.workspace_id, t.name, t.scope, t.created_at, t.expires_at,
+           t.last_used_at, t.revoked_at, u.uuid
+    FROM af_api_token t
+    JOIN af_user u ON u.uid = t.uid
+    WHERE t.token_hash = $1
+      AND t.revoked_at IS NULL
+      AND (t.expires_at IS NULL OR t.expires_at > NOW())
+    "#,
+  )
+  .bind(token_hash)
+  .fetch_optional(pg_pool)
+  .await?;
+
+  match row {
+    None => Ok(None),
+    Some(row) => {
+      let token = AFApiTokenRow::from_row(&row)?;
+      let user_uuid: Uuid = row.try_get("uuid")?;
+      Ok(Some((token, user_uuid)))
+    },
+  }
+}
+
+/// Marks the token as revoked. Returns false if the user doesn't have an active token with the
+/// given id.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn revoke_api_token(
+  pg_pool: &PgPool,
+  uid: &i64,
+  token_id: &Uuid,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    UPDATE af_api_token SET revoked_at = NOW()
+    WHERE id = $1 AND uid = $2 AND revoked_at IS NULL
+    "#,
+  )
+  .bind(token_id)
+  .bind(uid)
+  .execute(pg_pool)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn update_api_token_last_used(
+  pg_pool: &PgPool,
+  token_id: &Uuid,
+) -> Result<(), DatabaseError> {
+  sqlx::query(r#"UPDATE af_api_token SET last_used_at = NOW() WHERE id = $1"#)
+    .bind(token_id)
+    .execute(pg_pool)
+    .await?;
+  Ok(())
+}
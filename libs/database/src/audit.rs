@@ -0,0 +1,91 @@
+use database_entity::dto::{
+  AFAuditAction, AuditActor, QueryAuditLogParams, DEFAULT_AUDIT_LOG_PAGE_SIZE,
+  MAX_AUDIT_LOG_PAGE_SIZE,
+};
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFAuditLogRow;
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Appends an entry to the audit log. Pass the transaction of the change, so the entry is only
+/// recorded when the change is committed.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn insert_audit_log<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  actor: &AuditActor,
+  workspace_id: Option<&Uuid>,
+  action: AFAuditAction,
+  target: &str,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    INSERT INTO af_audit_log (actor_uid, workspace_id, action, target, ip, user_agent)
+    VALUES (COALESCE($1, (SELECT uid FROM af_user WHERE uuid = $2)), $3, $4, $5, $6, $7)
+    "#,
+  )
+  .bind(actor.uid)
+  .bind(actor.user_uuid)
+  .bind(workspace_id)
+  .bind(action.as_str())
+  .bind(target)
+  .bind(actor.ip.as_deref())
+  .bind(actor.user_agent.as_deref())
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Return a page of the audit log, newest first, along with the cursor of the next page. The
+/// cursor is None when there are no more pages. If the workspace_id is None, the entries of all
+/// workspaces are returned.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_audit_log_page(
+  pg_pool: &PgPool,
+  workspace_id: Option<&Uuid>,
+  params: &QueryAuditLogParams,
+) -> Result<(Vec<AFAuditLogRow>, Option<i64>), DatabaseError> {
+  let limit = params
+    .limit
+    .unwrap_or(DEFAULT_AUDIT_LOG_PAGE_SIZE)
+    .clamp(1, MAX_AUDIT_LOG_PAGE_SIZE);
+
+  let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM af_audit_log WHERE TRUE");
+  if let Some(workspace_id) = workspace_id {
+    builder
+      .push(" AND workspace_id = ")
+      .push_bind(*workspace_id);
+  }
+  if let Some(action) = params.action {
+    builder.push(" AND action = ").push_bind(action.as_str());
+  }
+  if let Some(actor_uid) = params.actor_uid {
+    builder.push(" AND actor_uid = ").push_bind(actor_uid);
+  }
+  if let Some(since) = params.since {
+    builder.push(" AND created_at >= ").push_bind(since);
+  }
+  if let Some(until) = params.until {
+    builder.push(" AND created_at < ").push_bind(until);
+  }
+  if let Some(cursor) = params.cursor {
+    builder.push(" AND id < ").push_bind(cursor);
+  }
+  builder
+    .push(" ORDER BY id DESC LIMIT ")
+    // Fetch one more item to know whether there is a next page
+    .push_bind(limit + 1);
+
+  let mut rows = builder
+    .build_query_as::<AFAuditLogRow>()
+    .fetch_all(pg_pool)
+    .await?;
+
+  let next_cursor = if rows.len() as i64 > limit {
+    rows.truncate(limit as usize);
+    rows.last().map(|row| row.id)
+  } else {
+    None
+  };
+  Ok((rows, next_cursor))
+}
@@ -7,7 +7,7 @@ use database_entity::dto::{
 use database_entity::error::DatabaseError;
 
 use sqlx::postgres::PgRow;
-use sqlx::{Error, PgPool, Row, Transaction};
+use sqlx::{Error, Executor, PgPool, Postgres, Row, Transaction};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::{ops::DerefMut, str::FromStr};
@@ -245,7 +245,10 @@ struct QueryCollabData {
 }
 
 #[inline]
-pub async fn delete_collab(pg_pool: &PgPool, object_id: &str) -> Result<(), sqlx::Error> {
+pub async fn delete_collab<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  object_id: &str,
+) -> Result<(), sqlx::Error> {
   sqlx::query!(
     r#"
         UPDATE af_collab
@@ -255,7 +258,7 @@ pub async fn delete_collab(pg_pool: &PgPool, object_id: &str) -> Result<(), sqlx
     object_id,
     chrono::Utc::now()
   )
-  .execute(pg_pool)
+  .execute(executor)
   .await?;
   Ok(())
 }
@@ -0,0 +1,153 @@
+use database_entity::dto::AFAccessLevel;
+use database_entity::error::DatabaseError;
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// The maximum number of ancestors that are visited when resolving the inherited access level.
+/// It bounds the recursive queries, the relations can't form a cycle anyway.
+pub const MAX_COLLAB_PARENT_DEPTH: i32 = 32;
+
+/// Sets the parent of the collab object, replacing the previous one. Both objects must belong to
+/// the workspace, and the parent can't be the object itself or one of its descendants.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn upsert_collab_parent(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  oid: &str,
+  parent_oid: &str,
+) -> Result<(), DatabaseError> {
+  let mut txn = pg_pool.begin().await?;
+  // Serialize the changes of the relations in the workspace, otherwise two concurrent
+  // changes could create a cycle.
+  sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1::TEXT))")
+    .bind(workspace_id)
+    .execute(txn.deref_mut())
+    .await?;
+
+  let found: i64 = sqlx::query_scalar(
+    r#"
+    SELECT COUNT(DISTINCT oid) FROM af_collab
+    WHERE oid = ANY($1) AND workspace_id = $2 AND deleted_at IS NULL
+    "#,
+  )
+  .bind(vec![oid.to_string(), parent_oid.to_string()])
+  .bind(workspace_id)
+  .fetch_one(txn.deref_mut())
+  .await?;
+  if found != 2 {
+    return Err(DatabaseError::RecordNotFound(format!(
+      "collab:{} or collab:{} is not found in workspace:{}",
+      oid, parent_oid, workspace_id
+    )));
+  }
+
+  let is_cycle: bool = sqlx::query_scalar(
+    r#"
+    WITH RECURSIVE ancestors AS (
+      SELECT $1::TEXT AS oid, 0 AS depth
+      UNION ALL
+      SELECT p.parent_oid, a.depth + 1
+      FROM af_collab_parent p
+      JOIN ancestors a ON p.oid = a.oid
+      WHERE a.depth < $3
+    )
+    SELECT EXISTS (SELECT 1 FROM ancestors WHERE oid = $2)
+    "#,
+  )
+  .bind(parent_oid)
+  .bind(oid)
+  .bind(MAX_COLLAB_PARENT_DEPTH)
+  .fetch_one(txn.deref_mut())
+  .await?;
+  if is_cycle {
+    return Err(DatabaseError::InvalidParams(format!(
+      "collab:{} can't be the parent of its ancestor:{}",
+      oid, parent_oid
+    )));
+  }
+
+  sqlx::query(
+    r#"
+    INSERT INTO af_collab_parent (oid, parent_oid, workspace_id)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (oid) DO UPDATE SET parent_oid = EXCLUDED.parent_oid,
+      workspace_id = EXCLUDED.workspace_id, created_at = NOW()
+    "#,
+  )
+  .bind(oid)
+  .bind(parent_oid)
+  .bind(workspace_id)
+  .execute(txn.deref_mut())
+  .await?;
+  txn.commit().await?;
+  Ok(())
+}
+
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_collab_parent(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  oid: &str,
+) -> Result<Option<String>, DatabaseError> {
+  let parent_oid = sqlx::query_scalar(
+    r#"SELECT parent_oid FROM af_collab_parent WHERE oid = $1 AND workspace_id = $2"#,
+  )
+  .bind(oid)
+  .bind(workspace_id)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(parent_oid)
+}
+
+/// Returns false if the collab object doesn't have a parent.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn delete_collab_parent(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  oid: &str,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(r#"DELETE FROM af_collab_parent WHERE oid = $1 AND workspace_id = $2"#)
+    .bind(oid)
+    .bind(workspace_id)
+    .execute(pg_pool)
+    .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Returns the access level that the user inherits on the collab object, which is the access
+/// level of the user in the nearest ancestor the user is a member of. Returns None if the user
+/// isn't a member of any ancestor.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_inherited_collab_access_level(
+  uid: &i64,
+  oid: &str,
+  pg_pool: &PgPool,
+) -> Result<Option<AFAccessLevel>, DatabaseError> {
+  let access_level: Option<i32> = sqlx::query_scalar(
+    r#"
+    WITH RECURSIVE ancestors AS (
+      SELECT parent_oid, 1 AS depth FROM af_collab_parent WHERE oid = $2
+      UNION ALL
+      SELECT p.parent_oid, a.depth + 1
+      FROM af_collab_parent p
+      JOIN ancestors a ON p.oid = a.parent_oid
+      WHERE a.depth < $3
+    )
+    SELECT af_permissions.access_level
+    FROM ancestors
+    JOIN af_collab_member ON af_collab_member.oid = ancestors.parent_oid
+      AND af_collab_member.uid = $1
+    JOIN af_permissions ON af_collab_member.permission_id = af_permissions.id
+    ORDER BY ancestors.depth
+    LIMIT 1
+    "#,
+  )
+  .bind(uid)
+  .bind(oid)
+  .bind(MAX_COLLAB_PARENT_DEPTH)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(access_level.map(AFAccessLevel::from))
+}
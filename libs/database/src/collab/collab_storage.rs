@@ -4,9 +4,8 @@ use async_trait::async_trait;
 use collab::core::collab::MutexCollab;
 use collab_entity::CollabType;
 use database_entity::dto::{
-  AFAccessLevel, AFCollabSnapshots, AFRole, BatchQueryCollab, InsertCollabParams,
-  InsertSnapshotParams, QueryCollabParams, QueryCollabResult, QueryObjectSnapshotParams,
-  QuerySnapshotParams, RawData,
+  AFAccessLevel, AFCollabSnapshots, BatchQueryCollab, InsertCollabParams, InsertSnapshotParams,
+  QueryCollabParams, QueryCollabResult, QueryObjectSnapshotParams, QuerySnapshotParams, RawData,
 };
 use database_entity::error::DatabaseError;
 use sqlx::types::Uuid;
@@ -28,8 +27,12 @@ pub trait CollabStorageAccessControl: Send + Sync + 'static {
     oid: &str,
   ) -> Result<AFAccessLevel, DatabaseError>;
 
-  /// Returns the role of the user in the workspace.
-  async fn get_user_role(&self, uid: &i64, workspace_id: &str) -> Result<AFRole, DatabaseError>;
+  /// Returns the access level that the user's role grants in the workspace.
+  async fn get_workspace_access_level(
+    &self,
+    uid: &i64,
+    workspace_id: &str,
+  ) -> Result<AFAccessLevel, DatabaseError>;
 }
 
 /// Represents a storage mechanism for collaborations.
@@ -1,5 +1,7 @@
 mod collab_db_ops;
+mod collab_parent;
 mod collab_storage;
 
 pub use collab_db_ops::*;
+pub use collab_parent::*;
 pub use collab_storage::*;
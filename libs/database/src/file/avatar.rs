@@ -0,0 +1,57 @@
+use anyhow::Context;
+use database_entity::error::DatabaseError;
+use image::imageops::FilterType;
+use image::io::Reader;
+use image::{GenericImageView, ImageOutputFormat};
+use std::io::Cursor;
+
+/// The content type of the stored avatars.
+pub const AVATAR_FILE_TYPE: &str = "image/png";
+
+/// The avatars are stored as squares of at most this many pixels.
+pub const AVATAR_SIZE: u32 = 256;
+
+/// Maximum size of the uploaded image in bytes.
+pub const MAX_AVATAR_UPLOAD_SIZE: usize = 5 * 1024 * 1024;
+
+/// The images with a larger width or height are rejected before they're decoded, so a small
+/// file can't expand to an image that exhausts the memory.
+const MAX_AVATAR_DIMENSION: u32 = 8192;
+
+/// Crops the image to a centered square and downscales it to [AVATAR_SIZE]. The smaller images
+/// aren't upscaled. Returns [DatabaseError::InvalidParams] if the data isn't a supported image.
+///
+/// This is CPU bound work, call it from a blocking context.
+pub fn resize_avatar(blob: &[u8]) -> Result<Vec<u8>, DatabaseError> {
+  let invalid_image = |err: image::ImageError| {
+    DatabaseError::InvalidParams(format!("the avatar isn't a valid image: {}", err))
+  };
+  let reader = Reader::new(Cursor::new(blob)).with_guessed_format()?;
+  if reader.format().is_none() {
+    return Err(DatabaseError::InvalidParams(
+      "the avatar isn't a supported image".to_string(),
+    ));
+  }
+  let (width, height) = reader.into_dimensions().map_err(invalid_image)?;
+  if width == 0 || height == 0 || width > MAX_AVATAR_DIMENSION || height > MAX_AVATAR_DIMENSION {
+    return Err(DatabaseError::InvalidParams(format!(
+      "the avatar can't be {}x{} pixels",
+      width, height
+    )));
+  }
+
+  let image = Reader::new(Cursor::new(blob))
+    .with_guessed_format()?
+    .decode()
+    .map_err(invalid_image)?;
+  let (width, height) = image.dimensions();
+  let side = width.min(height);
+  let size = side.min(AVATAR_SIZE);
+  let mut data = Vec::new();
+  image
+    .crop_imm((width - side) / 2, (height - side) / 2, side, side)
+    .resize_exact(size, size, FilterType::Lanczos3)
+    .write_to(&mut data, ImageOutputFormat::Png)
+    .context("failed to encode avatar")?;
+  Ok(data)
+}
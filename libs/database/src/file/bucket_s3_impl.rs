@@ -1,13 +1,19 @@
-use crate::file::{BucketClient, BucketStorage, ResponseBlob};
+use crate::file::{BucketClient, BucketStorage, BucketStorageConfig, ResponseBlob};
 use async_trait::async_trait;
 use database_entity::error::DatabaseError;
 use s3::error::S3Error;
+use std::collections::HashMap;
+use tokio::io::AsyncRead;
 
 pub type S3BucketStorage = BucketStorage<BucketClientS3Impl>;
 
 impl S3BucketStorage {
-  pub fn from_s3_bucket(bucket: s3::Bucket, pg_pool: sqlx::PgPool) -> Self {
-    Self::new(BucketClientS3Impl(bucket), pg_pool)
+  pub fn from_s3_bucket(
+    bucket: s3::Bucket,
+    pg_pool: sqlx::PgPool,
+    config: BucketStorageConfig,
+  ) -> Self {
+    Self::new(BucketClientS3Impl(bucket), pg_pool, config)
   }
 }
 
@@ -27,6 +33,16 @@ impl BucketClient for BucketClientS3Impl {
     Ok(())
   }
 
+  async fn put_blob_stream<P, R>(&self, id: P, reader: &mut R) -> Result<(), Self::Error>
+  where
+    P: AsRef<str> + Send,
+    R: AsyncRead + Unpin + Send,
+  {
+    let code = self.0.put_object_stream(reader, id).await?;
+    check_s3_status_code(code)?;
+    Ok(())
+  }
+
   async fn delete_blob<P>(&self, id: P) -> Result<Self::ResponseData, Self::Error>
   where
     P: AsRef<str> + Send,
@@ -43,6 +59,61 @@ impl BucketClient for BucketClientS3Impl {
     let response = self.0.get_object(id).await?;
     Ok(S3ResponseData(response))
   }
+
+  async fn get_blob_size<P>(&self, id: P) -> Result<Option<i64>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    match self.0.head_object(id).await {
+      Ok((head, code)) => {
+        check_s3_status_code(code)?;
+        Ok(Some(head.content_length.unwrap_or(0)))
+      },
+      Err(S3Error::Http(404, _)) => Ok(None),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  async fn presign_put_blob<P>(
+    &self,
+    id: P,
+    expires_in_secs: u32,
+  ) -> Result<Option<String>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    let url = self.0.presign_put(id, expires_in_secs, None)?;
+    Ok(Some(url))
+  }
+
+  async fn presign_get_blob<P>(
+    &self,
+    id: P,
+    expires_in_secs: u32,
+  ) -> Result<Option<String>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    let url = self.0.presign_get(id, expires_in_secs, None)?;
+    Ok(Some(url))
+  }
+
+  async fn presign_get_attachment<P>(
+    &self,
+    id: P,
+    file_name: &str,
+    expires_in_secs: u32,
+  ) -> Result<Option<String>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    let queries = HashMap::from([(
+      "response-content-disposition".to_string(),
+      format!("attachment; filename=\"{}\"", file_name),
+    )]);
+    let url = self.0.presign_get(id, expires_in_secs, Some(queries))?;
+    Ok(Some(url))
+  }
 }
 
 pub struct S3ResponseData(s3::request::ResponseData);
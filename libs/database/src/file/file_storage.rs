@@ -1,12 +1,32 @@
-use crate::file::utils::BlobStreamReader;
+use crate::audit::insert_audit_log;
+use crate::file::avatar::resize_avatar;
+use crate::file::file_type::{
+  default_file_type_policy, is_file_type_allowed, is_file_type_compatible,
+};
+use crate::file::thumbnail::{
+  generate_thumbnails, is_thumbnail_supported, thumbnail_id, Thumbnail, DEFAULT_THUMBNAIL_SIZES,
+  THUMBNAIL_FILE_TYPE,
+};
+use crate::file::utils::{BlobStreamReader, URL_SAFE_ENGINE};
 use crate::resource_usage::{
-  delete_blob_metadata, get_blob_metadata, get_workspace_usage_size, insert_blob_metadata,
-  is_blob_metadata_exists,
+  delete_blob_gc_marks, delete_blob_metadata, delete_blob_upload, delete_expired_blob_uploads,
+  get_blob_metadata, get_workspace_pending_upload_size, get_workspace_usage_size,
+  insert_blob_metadata, insert_blob_thumbnail, insert_blob_upload, is_blob_metadata_exists,
+  is_blob_object_referenced, lock_blob_object, select_blob_thumbnail, select_blob_thumbnails,
+  select_blob_upload, select_workspace_file_type_policy, upsert_workspace_file_type_policy,
 };
+use anyhow::Context;
 use async_trait::async_trait;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use database_entity::dto::{
+  AFAuditAction, AFBlobRecord, AFFileTypePolicy, AFPresignedDownload, AFPresignedUpload, AuditActor,
+};
 use database_entity::error::DatabaseError;
-use database_entity::pg_row::AFBlobMetadataRow;
+use database_entity::pg_row::{AFBlobMetadataRow, AFBlobThumbnailRow, AFBlobUploadRow};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use std::ops::DerefMut;
 use tokio::io::AsyncRead;
 use tracing::{event, instrument};
 use uuid::Uuid;
@@ -28,6 +48,13 @@ pub trait BucketClient {
   where
     P: AsRef<str> + Send;
 
+  /// Uploads the blob from the reader without reading it into memory. Large blobs are uploaded
+  /// in parts.
+  async fn put_blob_stream<P, R>(&self, id: P, reader: &mut R) -> Result<(), Self::Error>
+  where
+    P: AsRef<str> + Send,
+    R: AsyncRead + Unpin + Send;
+
   async fn delete_blob<P>(&self, id: P) -> Result<Self::ResponseData, Self::Error>
   where
     P: AsRef<str> + Send;
@@ -35,11 +62,78 @@ pub trait BucketClient {
   async fn get_blob<P>(&self, id: P) -> Result<Self::ResponseData, Self::Error>
   where
     P: AsRef<str> + Send;
+
+  /// Returns the size of the blob in bytes, or None if the blob doesn't exist.
+  async fn get_blob_size<P>(&self, id: P) -> Result<Option<i64>, Self::Error>
+  where
+    P: AsRef<str> + Send;
+
+  /// Returns a url that allows uploading the blob directly to the bucket. Returns None if the
+  /// bucket can't pre-sign urls.
+  async fn presign_put_blob<P>(
+    &self,
+    _id: P,
+    _expires_in_secs: u32,
+  ) -> Result<Option<String>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    Ok(None)
+  }
+
+  /// Returns a url that allows downloading the blob directly from the bucket. Returns None if
+  /// the bucket can't pre-sign urls.
+  async fn presign_get_blob<P>(
+    &self,
+    _id: P,
+    _expires_in_secs: u32,
+  ) -> Result<Option<String>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    Ok(None)
+  }
+
+  /// Like [BucketClient::presign_get_blob], but the bucket sends the blob as an attachment named
+  /// `file_name`.
+  async fn presign_get_attachment<P>(
+    &self,
+    _id: P,
+    _file_name: &str,
+    _expires_in_secs: u32,
+  ) -> Result<Option<String>, Self::Error>
+  where
+    P: AsRef<str> + Send,
+  {
+    Ok(None)
+  }
+}
+
+/// How long a pre-signed url stays valid.
+pub const PRESIGNED_URL_EXPIRES_IN_SECS: u32 = 15 * 60;
+
+/// How long a pre-signed upload can be completed. It's longer than the url, so an upload that
+/// started just before the url expired can still finish.
+const PRESIGNED_UPLOAD_EXPIRES_IN_SECS: i64 = 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct BucketStorageConfig {
+  /// The sizes, in pixels, of the thumbnails that are generated when an image is uploaded.
+  pub thumbnail_sizes: Vec<u32>,
+}
+
+impl Default for BucketStorageConfig {
+  fn default() -> Self {
+    Self {
+      thumbnail_sizes: DEFAULT_THUMBNAIL_SIZES.to_vec(),
+    }
+  }
 }
 
 pub struct BucketStorage<C> {
   client: C,
   pg_pool: PgPool,
+  config: BucketStorageConfig,
 }
 
 impl<C> BucketStorage<C>
@@ -47,8 +141,12 @@ where
   C: BucketClient,
   DatabaseError: From<<C as BucketClient>::Error>,
 {
-  pub fn new(client: C, pg_pool: PgPool) -> Self {
-    Self { client, pg_pool }
+  pub fn new(client: C, pg_pool: PgPool, config: BucketStorageConfig) -> Self {
+    Self {
+      client,
+      pg_pool,
+      config,
+    }
   }
 
   #[instrument(skip_all, err)]
@@ -63,11 +161,28 @@ where
   where
     R: AsyncRead + Unpin,
   {
-    let (blob, file_id) = BlobStreamReader::new(blob_stream).finish().await?;
+    let (blob, file_id, detected_file_type) = BlobStreamReader::new(blob_stream).finish().await?;
+
+    // Don't trust the type declared by the client, record the detected one instead.
+    let file_type = match detected_file_type {
+      None => file_type,
+      Some(detected) => {
+        if !is_file_type_compatible(&file_type, detected) {
+          return Err(DatabaseError::InvalidParams(format!(
+            "the declared file type: {} doesn't match the content: {}",
+            file_type, detected
+          )));
+        }
+        detected.to_string()
+      },
+    };
+    self.check_file_type(&workspace_id, &file_type).await?;
 
     // check file is exist or not
     if is_blob_metadata_exists(&self.pg_pool, &workspace_id, &file_id).await? {
       event!(tracing::Level::TRACE, "file:{} is already exist", file_id);
+      // The blob is uploaded again, so it's in use even if the garbage collector marked it.
+      delete_blob_gc_marks(&self.pg_pool, &workspace_id, &[file_id.clone()]).await?;
       return Ok(file_id);
     }
 
@@ -84,40 +199,387 @@ where
       return Err(DatabaseError::StorageSpaceNotEnough);
     }
 
-    self.client.put_blob(&file_id, blob).await?;
+    let thumbnails = self.create_thumbnails(&file_type, &blob).await;
 
-    // save the metadata
-    if let Err(err) = insert_blob_metadata(
-      &self.pg_pool,
+    // The metadata is saved in the same transaction as the object is put, so the object can't be
+    // deleted by another workspace in between. If putting the object fails, the metadata is
+    // rolled back.
+    let mut txn = self.pg_pool.begin().await?;
+    lock_blob_object(txn.deref_mut(), &file_id).await?;
+    insert_blob_metadata(
+      txn.deref_mut(),
       &file_id,
       &workspace_id,
       &file_type,
       file_size,
     )
-    .await
+    .await?;
+    self.client.put_blob(&file_id, blob).await?;
+    txn.commit().await?;
+
+    self
+      .save_thumbnails(&workspace_id, &file_id, thumbnails)
+      .await;
+    Ok(file_id)
+  }
+
+  /// Returns the file type policy of the workspace.
+  pub async fn get_file_type_policy(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<AFFileTypePolicy, DatabaseError> {
+    let policy = select_workspace_file_type_policy(&self.pg_pool, workspace_id)
+      .await?
+      .unwrap_or_else(default_file_type_policy);
+    Ok(policy)
+  }
+
+  pub async fn update_file_type_policy(
+    &self,
+    workspace_id: &Uuid,
+    policy: &AFFileTypePolicy,
+  ) -> Result<(), DatabaseError> {
+    upsert_workspace_file_type_policy(&self.pg_pool, workspace_id, policy).await
+  }
+
+  async fn check_file_type(
+    &self,
+    workspace_id: &Uuid,
+    file_type: &str,
+  ) -> Result<(), DatabaseError> {
+    let policy = self.get_file_type_policy(workspace_id).await?;
+    if is_file_type_allowed(&policy, file_type) {
+      Ok(())
+    } else {
+      Err(DatabaseError::FileTypeNotAllowed(format!(
+        "{} is not allowed in workspace:{}",
+        file_type, workspace_id
+      )))
+    }
+  }
+
+  /// Generates the thumbnails of the blob if it's an image. Failing to generate the thumbnails
+  /// doesn't fail the upload, the original blob is served instead.
+  async fn create_thumbnails(&self, file_type: &str, blob: &[u8]) -> Vec<Thumbnail> {
+    if self.config.thumbnail_sizes.is_empty() || !is_thumbnail_supported(file_type) {
+      return vec![];
+    }
+
+    let blob = blob.to_vec();
+    let sizes = self.config.thumbnail_sizes.clone();
+    match tokio::task::spawn_blocking(move || generate_thumbnails(&blob, &sizes)).await {
+      Ok(Ok(thumbnails)) => thumbnails,
+      Ok(Err(err)) => {
+        event!(
+          tracing::Level::WARN,
+          "failed to generate thumbnails: {}",
+          err
+        );
+        vec![]
+      },
+      Err(err) => {
+        event!(
+          tracing::Level::ERROR,
+          "thumbnail generation task failed: {}",
+          err
+        );
+        vec![]
+      },
+    }
+  }
+
+  async fn save_thumbnails(&self, workspace_id: &Uuid, file_id: &str, thumbnails: Vec<Thumbnail>) {
+    for thumbnail in thumbnails {
+      let id = thumbnail_id(file_id, thumbnail.size);
+      let file_size = thumbnail.data.len() as i64;
+      if let Err(err) = self.client.put_blob(&id, thumbnail.data).await {
+        let err: DatabaseError = err.into();
+        event!(
+          tracing::Level::ERROR,
+          "failed to put thumbnail: {}, err: {}",
+          id,
+          err
+        );
+        continue;
+      }
+
+      if let Err(err) = insert_blob_thumbnail(
+        &self.pg_pool,
+        workspace_id,
+        file_id,
+        thumbnail.size as i32,
+        &id,
+        THUMBNAIL_FILE_TYPE,
+        file_size,
+      )
+      .await
+      {
+        // The thumbnail object might be shared with other workspaces, it's deleted along with
+        // the blob once no workspace references it.
+        event!(
+          tracing::Level::ERROR,
+          "failed to save thumbnail metadata: {}, err: {}",
+          id,
+          err
+        );
+      }
+    }
+  }
+
+  /// Deletes the blob from the workspace, and records the deletion in the audit log. The object
+  /// is addressed by the hash of its content and shared by every workspace that stores the same
+  /// content, so it's only removed from the bucket once no workspace references it.
+  pub async fn delete_blob(
+    &self,
+    actor: &AuditActor,
+    workspace_id: &Uuid,
+    file_id: &str,
+  ) -> Result<AFBlobMetadataRow, DatabaseError> {
+    let mut txn = self.pg_pool.begin().await?;
+    // The thumbnail metadata is removed along with the blob metadata.
+    let thumbnails = select_blob_thumbnails(txn.deref_mut(), workspace_id, file_id).await?;
+    let resp = delete_blob_metadata(txn.deref_mut(), workspace_id, file_id).await?;
+    insert_audit_log(
+      txn.deref_mut(),
+      actor,
+      Some(workspace_id),
+      AFAuditAction::DeleteBlob,
+      file_id,
+    )
+    .await?;
+    txn.commit().await?;
+
+    let thumbnail_ids = thumbnails
+      .into_iter()
+      .map(|thumbnail| thumbnail.thumbnail_id)
+      .collect();
+    // The blob is already deleted from the workspace, failing to delete the object only leaves
+    // it in the bucket.
+    if let Err(err) = self
+      .delete_unreferenced_object(file_id, thumbnail_ids)
+      .await
     {
       event!(
         tracing::Level::ERROR,
-        "failed to save metadata, file_id: {}, err: {}",
+        "failed to delete the object of blob: {}, err: {}",
         file_id,
         err
       );
-      // if the metadata is not saved, delete the blob.
-      self.client.delete_blob(&file_id).await?;
-      return Err(err);
     }
+    Ok(resp)
+  }
 
-    Ok(file_id)
+  /// Removes the object of the blob and its thumbnails from the bucket if no workspace references
+  /// the blob anymore.
+  async fn delete_unreferenced_object(
+    &self,
+    file_id: &str,
+    mut thumbnail_ids: Vec<String>,
+  ) -> Result<(), DatabaseError> {
+    let mut txn = self.pg_pool.begin().await?;
+    lock_blob_object(txn.deref_mut(), file_id).await?;
+    if is_blob_object_referenced(txn.deref_mut(), file_id).await? {
+      return Ok(());
+    }
+
+    // Also delete the thumbnails of the current sizes, whose metadata might have failed to save.
+    thumbnail_ids.extend(
+      self
+        .config
+        .thumbnail_sizes
+        .iter()
+        .map(|size| thumbnail_id(file_id, *size)),
+    );
+    thumbnail_ids.sort();
+    thumbnail_ids.dedup();
+    for id in thumbnail_ids {
+      self.client.delete_blob(&id).await?;
+    }
+    self.client.delete_blob(file_id).await?;
+    // Keep the lock until the objects are deleted.
+    txn.commit().await?;
+    Ok(())
   }
 
-  pub async fn delete_blob(
+  /// Returns a pre-signed url for uploading the blob directly to the bucket. The blob is
+  /// uploaded to a staging object of the upload, and is not visible until the upload is
+  /// confirmed with [Self::complete_presigned_upload].
+  #[instrument(skip(self), err)]
+  pub async fn presign_put_blob(
     &self,
     workspace_id: &Uuid,
     file_id: &str,
+    file_type: &str,
+    file_size: i64,
+  ) -> Result<AFPresignedUpload, DatabaseError> {
+    if file_size < 0 || file_size as usize > MAX_BLOB_SIZE {
+      return Err(DatabaseError::InvalidParams(format!(
+        "file size must be between 0 and {} bytes",
+        MAX_BLOB_SIZE
+      )));
+    }
+
+    // The content is checked again when the upload is completed.
+    self.check_file_type(workspace_id, file_type).await?;
+
+    // Only the metadata of this workspace proves that the blob was uploaded to it. An object
+    // with the same key in the bucket might belong to another workspace.
+    if is_blob_metadata_exists(&self.pg_pool, workspace_id, file_id).await? {
+      delete_blob_gc_marks(&self.pg_pool, workspace_id, &[file_id.to_string()]).await?;
+      return Ok(AFPresignedUpload::Exists(AFBlobRecord::new(
+        file_id.to_string(),
+      )));
+    }
+
+    // The pending uploads are counted, otherwise the limit could be bypassed by requesting many
+    // urls at once.
+    let usage = get_workspace_usage_size(&self.pg_pool, workspace_id).await?
+      + get_workspace_pending_upload_size(&self.pg_pool, workspace_id).await?;
+    if usage > MAX_USAGE {
+      return Err(DatabaseError::StorageSpaceNotEnough);
+    }
+
+    let upload_id = Uuid::new_v4();
+    let url = self
+      .client
+      .presign_put_blob(
+        blob_upload_key(workspace_id, &upload_id),
+        PRESIGNED_URL_EXPIRES_IN_SECS,
+      )
+      .await?;
+    let url = match url {
+      None => return Ok(AFPresignedUpload::Unsupported),
+      Some(url) => url,
+    };
+
+    let expires_at = Utc::now() + Duration::seconds(PRESIGNED_UPLOAD_EXPIRES_IN_SECS);
+    insert_blob_upload(
+      &self.pg_pool,
+      &upload_id,
+      workspace_id,
+      file_id,
+      file_type,
+      file_size,
+      expires_at,
+    )
+    .await?;
+    Ok(AFPresignedUpload::Url {
+      upload_id,
+      url,
+      expires_in_secs: PRESIGNED_URL_EXPIRES_IN_SECS,
+    })
+  }
+
+  /// Stores the blob that was uploaded with the pre-signed url of the upload. The blob is read
+  /// back from the staging object and stored with [Self::put_blob], so its file id is the hash
+  /// of its content, whatever the client declared. The upload fails if the hash doesn't match
+  /// the declared file id.
+  #[instrument(skip(self), err)]
+  pub async fn complete_presigned_upload(
+    &self,
+    workspace_id: &Uuid,
+    upload_id: &Uuid,
   ) -> Result<AFBlobMetadataRow, DatabaseError> {
-    self.client.delete_blob(file_id).await?;
-    let resp = delete_blob_metadata(&self.pg_pool, workspace_id, file_id).await?;
-    Ok(resp)
+    let not_found =
+      || DatabaseError::RecordNotFound(format!("upload:{} is not found or expired", upload_id));
+    select_blob_upload(&self.pg_pool, workspace_id, upload_id)
+      .await?
+      .ok_or_else(not_found)?;
+
+    let key = blob_upload_key(workspace_id, upload_id);
+    let file_size = self.client.get_blob_size(&key).await?.ok_or_else(|| {
+      DatabaseError::RecordNotFound(format!("upload:{} is not uploaded", upload_id))
+    })?;
+
+    // Only one request completes the upload, and the staging object is removed whatever the
+    // outcome, so a rejected upload has to be started over.
+    let upload = delete_blob_upload(&self.pg_pool, workspace_id, upload_id)
+      .await?
+      .ok_or_else(not_found)?;
+    let result = self.store_uploaded_blob(&key, &upload, file_size).await;
+    if let Err(err) = self.client.delete_blob(&key).await {
+      let err: DatabaseError = err.into();
+      event!(
+        tracing::Level::ERROR,
+        "failed to delete the staging object: {}, err: {}",
+        key,
+        err
+      );
+    }
+    result
+  }
+
+  async fn store_uploaded_blob(
+    &self,
+    key: &str,
+    upload: &AFBlobUploadRow,
+    file_size: i64,
+  ) -> Result<AFBlobMetadataRow, DatabaseError> {
+    if file_size != upload.file_size {
+      return Err(DatabaseError::InvalidParams(format!(
+        "the uploaded size: {} doesn't match the declared size: {}",
+        file_size, upload.file_size
+      )));
+    }
+
+    let blob = self.get_object(key).await?;
+    if URL_SAFE_ENGINE.encode(Sha256::digest(&blob)) != upload.file_id {
+      return Err(DatabaseError::InvalidParams(format!(
+        "the hash of the uploaded content doesn't match the file id: {}",
+        upload.file_id
+      )));
+    }
+
+    // Stores the blob under the hash that put_blob computes from the content again.
+    let file_id = self
+      .put_blob(
+        blob.as_slice(),
+        upload.workspace_id,
+        upload.file_type.clone(),
+        file_size,
+      )
+      .await?;
+    get_blob_metadata(&self.pg_pool, &upload.workspace_id, &file_id).await
+  }
+
+  /// Deletes the uploads that were not completed before they expired, along with their staging
+  /// objects. Returns the number of deleted uploads.
+  pub async fn delete_expired_uploads(&self) -> Result<usize, DatabaseError> {
+    let mut deleted = 0;
+    loop {
+      let uploads = delete_expired_blob_uploads(&self.pg_pool, 100).await?;
+      if uploads.is_empty() {
+        return Ok(deleted);
+      }
+      for upload in &uploads {
+        let key = blob_upload_key(&upload.workspace_id, &upload.upload_id);
+        self.client.delete_blob(&key).await?;
+      }
+      deleted += uploads.len();
+    }
+  }
+
+  /// Returns a pre-signed url for downloading the blob directly from the bucket.
+  #[instrument(skip(self), err)]
+  pub async fn presign_get_blob(
+    &self,
+    workspace_id: &Uuid,
+    file_id: &str,
+  ) -> Result<AFPresignedDownload, DatabaseError> {
+    // Make sure the blob belongs to the workspace
+    get_blob_metadata(&self.pg_pool, workspace_id, file_id).await?;
+
+    let url = self
+      .client
+      .presign_get_blob(file_id, PRESIGNED_URL_EXPIRES_IN_SECS)
+      .await?;
+    match url {
+      None => Ok(AFPresignedDownload::Unsupported),
+      Some(url) => Ok(AFPresignedDownload::Url {
+        url,
+        expires_in_secs: PRESIGNED_URL_EXPIRES_IN_SECS,
+      }),
+    }
   }
 
   pub async fn get_blob_metadata(
@@ -129,8 +591,115 @@ where
     Ok(metadata)
   }
 
+  /// Returns the smallest thumbnail of the blob that is at least `size` pixels, or None if the
+  /// blob doesn't have one.
+  pub async fn get_blob_thumbnail(
+    &self,
+    workspace_id: &Uuid,
+    file_id: &str,
+    size: u32,
+  ) -> Result<Option<AFBlobThumbnailRow>, DatabaseError> {
+    let size = i32::try_from(size).unwrap_or(i32::MAX);
+    let thumbnail = select_blob_thumbnail(&self.pg_pool, workspace_id, file_id, size).await?;
+    Ok(thumbnail)
+  }
+
   pub async fn get_blob(&self, file_id: &str) -> Result<Vec<u8>, DatabaseError> {
     let blob = self.client.get_blob(file_id).await?.to_blob();
     Ok(blob)
   }
+
+  /// Stores an object that doesn't belong to a workspace, e.g. the archive of a data export. The
+  /// object isn't counted in the usage of any workspace. The key must contain a `/`, so it never
+  /// collides with a file id.
+  pub async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), DatabaseError> {
+    debug_assert!(key.contains('/'));
+    self.client.put_blob(key, data).await?;
+    Ok(())
+  }
+
+  pub async fn put_object_stream<R>(&self, key: &str, reader: &mut R) -> Result<(), DatabaseError>
+  where
+    R: AsyncRead + Unpin + Send,
+  {
+    debug_assert!(key.contains('/'));
+    self.client.put_blob_stream(key, reader).await?;
+    Ok(())
+  }
+
+  pub async fn get_object(&self, key: &str) -> Result<Vec<u8>, DatabaseError> {
+    let data = self.client.get_blob(key).await?.to_blob();
+    Ok(data)
+  }
+
+  /// Returns a pre-signed url for downloading the object directly from the bucket as an attachment
+  /// named `file_name`. Returns None if the bucket can't pre-sign urls.
+  pub async fn presign_get_object(
+    &self,
+    key: &str,
+    file_name: &str,
+  ) -> Result<Option<String>, DatabaseError> {
+    debug_assert!(key.contains('/'));
+    let url = self
+      .client
+      .presign_get_attachment(key, file_name, PRESIGNED_URL_EXPIRES_IN_SECS)
+      .await?;
+    Ok(url)
+  }
+
+  pub async fn delete_object(&self, key: &str) -> Result<(), DatabaseError> {
+    self.client.delete_blob(key).await?;
+    Ok(())
+  }
+
+  /// Resizes the image with [resize_avatar] and stores it as a new avatar of the user. The avatar
+  /// is kept in the namespace of the user, so it isn't counted in the usage of any workspace.
+  /// Returns the id of the avatar, which changes on every upload so the avatars can be cached.
+  #[instrument(skip(self, blob), err)]
+  pub async fn put_user_avatar(
+    &self,
+    user_uuid: &Uuid,
+    blob: Vec<u8>,
+  ) -> Result<Uuid, DatabaseError> {
+    let avatar = tokio::task::spawn_blocking(move || resize_avatar(&blob))
+      .await
+      .context("avatar resizing task failed")??;
+    let avatar_id = Uuid::new_v4();
+    self
+      .put_object(&user_avatar_key(user_uuid, &avatar_id), avatar)
+      .await?;
+    Ok(avatar_id)
+  }
+
+  pub async fn get_user_avatar(
+    &self,
+    user_uuid: &Uuid,
+    avatar_id: &Uuid,
+  ) -> Result<Vec<u8>, DatabaseError> {
+    self
+      .get_object(&user_avatar_key(user_uuid, avatar_id))
+      .await
+  }
+
+  pub async fn delete_user_avatar(
+    &self,
+    user_uuid: &Uuid,
+    avatar_id: &Uuid,
+  ) -> Result<(), DatabaseError> {
+    self
+      .delete_object(&user_avatar_key(user_uuid, avatar_id))
+      .await
+  }
+}
+
+/// The staging object of a pre-signed upload. The key contains a `/`, so it never collides with a
+/// file id.
+fn blob_upload_key(workspace_id: &Uuid, upload_id: &Uuid) -> String {
+  format!("upload/{}/{}", workspace_id, upload_id)
+}
+
+/// The objects of the user are stored under `user/{user_uuid}/`, apart from the blobs of the
+/// workspaces.
+fn user_avatar_key(user_uuid: &Uuid, avatar_id: &Uuid) -> String {
+  format!("user/{}/avatar/{}.png", user_uuid, avatar_id)
 }
@@ -0,0 +1,104 @@
+use database_entity::dto::AFFileTypePolicy;
+
+/// Types that are denied when a workspace doesn't have its own policy.
+pub const DEFAULT_DENIED_FILE_TYPES: [&str; 6] = [
+  "application/vnd.microsoft.portable-executable",
+  "application/x-msdownload",
+  "application/x-executable",
+  "application/x-mach-binary",
+  "application/x-sharedlib",
+  "application/x-sh",
+];
+
+/// Returns the type of the blob detected from its leading bytes, or None if it's unknown. Plain
+/// text formats don't have magic bytes and are never detected.
+pub fn sniff_file_type(head: &[u8]) -> Option<&'static str> {
+  infer::get(head).map(|ty| ty.mime_type())
+}
+
+/// Returns the type and subtype of the file type in lowercase, without parameters. For example,
+/// `text/plain; charset=utf-8` becomes `text/plain`.
+pub fn file_type_essence(file_type: &str) -> String {
+  let essence = file_type.split(';').next().unwrap_or_default().trim();
+  let essence = essence.to_ascii_lowercase();
+  match essence.as_str() {
+    "image/jpg" => "image/jpeg".to_string(),
+    "audio/mp3" => "audio/mpeg".to_string(),
+    "application/x-zip-compressed" => "application/zip".to_string(),
+    _ => essence,
+  }
+}
+
+/// Returns true if the type declared by the client agrees with the type detected from the
+/// content of the blob.
+pub fn is_file_type_compatible(declared: &str, detected: &str) -> bool {
+  let declared = file_type_essence(declared);
+  if declared.is_empty() || declared == mime::APPLICATION_OCTET_STREAM.essence_str() {
+    return true;
+  }
+  if declared == detected {
+    return true;
+  }
+
+  // Office documents, epub, jar, etc. are zip archives and might only be detected as such.
+  detected == "application/zip"
+    && (declared.ends_with("+zip")
+      || declared.starts_with("application/vnd.openxmlformats-officedocument")
+      || declared.starts_with("application/vnd.oasis.opendocument")
+      || declared == "application/java-archive")
+}
+
+/// Returns true if the blob can be rendered by the browser without the risk of running scripts.
+/// Other types are served as attachments.
+pub fn is_inline_safe_file_type(file_type: &str) -> bool {
+  let essence = file_type_essence(file_type);
+  match essence.split_once('/') {
+    Some(("image", subtype)) => subtype != "svg+xml",
+    Some(("video", _)) | Some(("audio", _)) => true,
+    _ => essence == "text/plain",
+  }
+}
+
+/// Returns true if the workspace's policy allows storing blobs of the given type. A type is
+/// allowed when it doesn't match any denied pattern and, if the allowed list is not empty,
+/// matches one of the allowed patterns.
+pub fn is_file_type_allowed(policy: &AFFileTypePolicy, file_type: &str) -> bool {
+  let essence = file_type_essence(file_type);
+  if policy
+    .denied
+    .iter()
+    .any(|pattern| file_type_matches(pattern, &essence))
+  {
+    return false;
+  }
+  policy.allowed.is_empty()
+    || policy
+      .allowed
+      .iter()
+      .any(|pattern| file_type_matches(pattern, &essence))
+}
+
+/// Matches the file type against a pattern like `image/png`, `image/*` or `*`.
+fn file_type_matches(pattern: &str, essence: &str) -> bool {
+  let pattern = pattern.trim().to_ascii_lowercase();
+  if pattern == "*" || pattern == "*/*" {
+    return true;
+  }
+  match pattern.strip_suffix("/*") {
+    Some(ty) => essence
+      .split_once('/')
+      .map(|(essence_ty, _)| essence_ty == ty)
+      .unwrap_or(false),
+    None => file_type_essence(&pattern) == essence,
+  }
+}
+
+pub fn default_file_type_policy() -> AFFileTypePolicy {
+  AFFileTypePolicy {
+    allowed: vec![],
+    denied: DEFAULT_DENIED_FILE_TYPES
+      .iter()
+      .map(|ty| ty.to_string())
+      .collect(),
+  }
+}
@@ -1,5 +1,10 @@
+mod avatar;
 pub mod bucket_s3_impl;
 mod file_storage;
+mod file_type;
+mod thumbnail;
 mod utils;
-
+pub use avatar::*;
 pub use file_storage::*;
+pub use file_type::*;
+pub use thumbnail::*;
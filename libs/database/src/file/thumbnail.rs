@@ -0,0 +1,55 @@
+use anyhow::Context;
+use database_entity::error::DatabaseError;
+use image::{GenericImageView, ImageOutputFormat};
+
+/// The content type of the generated thumbnails. PNG is used so that the transparency of the
+/// original image is preserved.
+pub const THUMBNAIL_FILE_TYPE: &str = "image/png";
+
+/// Default sizes, in pixels, of the thumbnails generated for an uploaded image.
+pub const DEFAULT_THUMBNAIL_SIZES: [u32; 2] = [128, 512];
+
+pub struct Thumbnail {
+  pub size: u32,
+  pub data: Vec<u8>,
+}
+
+/// Returns true if a thumbnail can be generated for a blob of the given content type.
+pub fn is_thumbnail_supported(file_type: &str) -> bool {
+  match file_type.parse::<mime::Mime>() {
+    Ok(mime) => mime.type_() == mime::IMAGE && mime.subtype() != mime::SVG,
+    Err(_) => false,
+  }
+}
+
+/// Returns the key of the thumbnail object in the bucket.
+pub fn thumbnail_id(file_id: &str, size: u32) -> String {
+  format!("{}_thumbnail_{}", file_id, size)
+}
+
+/// Generates a downscaled copy of the image for each of the given sizes. The aspect ratio of the
+/// image is preserved and the longest side of the thumbnail is at most `size` pixels. Sizes that
+/// are not smaller than the original image are skipped, because the original can be served
+/// instead.
+///
+/// This is CPU bound work, call it from a blocking context.
+pub fn generate_thumbnails(blob: &[u8], sizes: &[u32]) -> Result<Vec<Thumbnail>, DatabaseError> {
+  let image = image::load_from_memory(blob).context("failed to decode image")?;
+  let (width, height) = image.dimensions();
+  let longest_side = width.max(height);
+
+  let mut thumbnails = Vec::with_capacity(sizes.len());
+  for &size in sizes {
+    if size == 0 || size >= longest_side {
+      continue;
+    }
+
+    let mut data = Vec::new();
+    image
+      .thumbnail(size, size)
+      .write_to(&mut data, ImageOutputFormat::Png)
+      .context("failed to encode thumbnail")?;
+    thumbnails.push(Thumbnail { size, data });
+  }
+  Ok(thumbnails)
+}
@@ -1,3 +1,4 @@
+use crate::file::file_type::sniff_file_type;
 use base64::alphabet::URL_SAFE;
 use base64::engine::general_purpose::PAD;
 use base64::engine::GeneralPurpose;
@@ -8,9 +9,14 @@ use std::task::{Context, Poll};
 use tokio::io::{self, AsyncRead, AsyncReadExt, ReadBuf};
 
 pub const URL_SAFE_ENGINE: GeneralPurpose = GeneralPurpose::new(&URL_SAFE, PAD);
+
+/// The number of leading bytes that are kept to detect the type of the blob.
+const SNIFF_LEN: usize = 8192;
+
 pub struct BlobStreamReader<R> {
   reader: R,
   hasher: Sha256,
+  head: Vec<u8>,
 }
 
 impl<R> AsyncRead for BlobStreamReader<R>
@@ -26,7 +32,13 @@ where
     let poll = Pin::new(&mut self.reader).poll_read(cx, buf);
     let after = buf.filled().len();
     if after > before {
-      self.hasher.update(&buf.filled()[before..after]);
+      let filled = &buf.filled()[before..after];
+      self.hasher.update(filled);
+      let remaining = SNIFF_LEN.saturating_sub(self.head.len());
+      if remaining > 0 {
+        let len = remaining.min(filled.len());
+        self.head.extend_from_slice(&filled[..len]);
+      }
     }
     poll
   }
@@ -40,14 +52,24 @@ where
     Self {
       reader,
       hasher: Sha256::new(),
+      head: Vec::with_capacity(SNIFF_LEN),
     }
   }
 
-  pub async fn finish(mut self) -> io::Result<(Vec<u8>, String)> {
+  /// Returns the type detected from the magic bytes of the data read so far, or None if it's
+  /// unknown.
+  pub fn detected_file_type(&self) -> Option<&'static str> {
+    sniff_file_type(&self.head)
+  }
+
+  /// Reads the whole blob. Returns the data, its file id and the type detected from its magic
+  /// bytes.
+  pub async fn finish(mut self) -> io::Result<(Vec<u8>, String, Option<&'static str>)> {
     let mut buffer = Vec::new();
     let _ = self.read_to_end(&mut buffer).await?;
+    let file_type = self.detected_file_type();
     let hash = URL_SAFE_ENGINE.encode(self.hasher.finalize());
-    Ok((buffer, hash))
+    Ok((buffer, hash, file_type))
   }
 }
 
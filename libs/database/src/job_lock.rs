@@ -0,0 +1,31 @@
+use database_entity::error::DatabaseError;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::ops::DerefMut;
+use tracing::instrument;
+
+/// Makes sure a background job runs on one server at a time. Every server spawns the jobs, so
+/// without the lock the replicas would process the same rows concurrently.
+///
+/// The lock is a transaction level advisory lock, so it's released when the lock is dropped,
+/// even if the job panics, or when the connection to the database is lost.
+pub struct JobLock {
+  txn: Transaction<'static, Postgres>,
+}
+
+impl JobLock {
+  /// Returns None if another server holds the lock of the job.
+  #[instrument(level = "trace", skip(pg_pool), err)]
+  pub async fn try_acquire(pg_pool: &PgPool, job: &str) -> Result<Option<JobLock>, DatabaseError> {
+    let mut txn = pg_pool.begin().await?;
+    let acquired = sqlx::query_scalar::<_, bool>("SELECT pg_try_advisory_xact_lock(hashtext($1))")
+      .bind(job)
+      .fetch_one(txn.deref_mut())
+      .await?;
+    Ok(acquired.then_some(JobLock { txn }))
+  }
+
+  pub async fn release(self) -> Result<(), DatabaseError> {
+    self.txn.rollback().await?;
+    Ok(())
+  }
+}
@@ -1,5 +1,16 @@
+pub mod api_token;
+pub mod audit;
 pub mod collab;
 pub mod file;
+pub mod job_lock;
 pub mod resource_usage;
+pub mod role;
+pub mod share_link;
+pub mod snowflake_node;
+pub mod sso;
 pub mod user;
+pub mod user_ban;
+pub mod user_data_export;
+pub mod user_deletion;
+pub mod user_session;
 pub mod workspace;
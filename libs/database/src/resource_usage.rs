@@ -1,8 +1,19 @@
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use database_entity::dto::{
+  AFFileTypePolicy, AFFileTypeUsage, BlobMetadataOrderBy, QueryBlobMetadataParams,
+  DEFAULT_BLOB_METADATA_PAGE_SIZE, MAX_BLOB_METADATA_PAGE_SIZE,
+};
 use database_entity::error::DatabaseError;
-use database_entity::pg_row::AFBlobMetadataRow;
+use database_entity::pg_row::{AFBlobMetadataRow, AFBlobThumbnailRow, AFBlobUploadRow};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
 use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
 use sqlx::types::Decimal;
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder};
+use std::collections::HashMap;
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -31,8 +42,8 @@ pub async fn is_blob_metadata_exists(
 }
 
 #[instrument(level = "trace", skip_all, err)]
-pub async fn insert_blob_metadata(
-  pg_pool: &PgPool,
+pub async fn insert_blob_metadata<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
   file_id: &str,
   workspace_id: &Uuid,
   file_type: &str,
@@ -54,15 +65,15 @@ pub async fn insert_blob_metadata(
     file_type,
     file_size
   )
-  .fetch_one(pg_pool)
+  .fetch_one(executor)
   .await?;
   Ok(metadata)
 }
 
 #[instrument(level = "trace", skip_all, err)]
 #[inline]
-pub async fn delete_blob_metadata(
-  pg_pool: &PgPool,
+pub async fn delete_blob_metadata<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
   workspace_id: &Uuid,
   file_id: &str,
 ) -> Result<AFBlobMetadataRow, DatabaseError> {
@@ -76,7 +87,7 @@ pub async fn delete_blob_metadata(
     workspace_id,
     file_id,
   )
-  .fetch_one(pg_pool)
+  .fetch_one(executor)
   .await?;
   Ok(metadata)
 }
@@ -101,6 +112,38 @@ pub async fn get_blob_metadata(
   Ok(metadata)
 }
 
+/// Serializes the changes of the object of the blob until the transaction ends. The object is
+/// shared by every workspace that stores the same content, so it must not be deleted while
+/// another workspace is adding its metadata.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn lock_blob_object<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  file_id: &str,
+) -> Result<(), DatabaseError> {
+  sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+    .bind(file_id)
+    .execute(executor)
+    .await?;
+  Ok(())
+}
+
+/// Returns true if any workspace still has the metadata of the blob.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn is_blob_object_referenced<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  file_id: &str,
+) -> Result<bool, DatabaseError> {
+  let exists = sqlx::query_scalar::<_, bool>(
+    r#"
+    SELECT EXISTS (SELECT 1 FROM af_blob_metadata WHERE file_id = $1)
+    "#,
+  )
+  .bind(file_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(exists)
+}
+
 /// Return all blob metadata of a workspace
 #[instrument(level = "trace", skip_all, err)]
 #[inline]
@@ -121,6 +164,155 @@ pub async fn get_all_workspace_blob_metadata(
   Ok(all_metadata)
 }
 
+/// The position after the last item of a page. Items are ordered by the sort column and then by
+/// file id, so the position is unique even if several items have the same size or date.
+#[derive(Serialize, Deserialize)]
+struct BlobMetadataCursor {
+  modified_at: DateTime<Utc>,
+  file_size: i64,
+  file_id: String,
+}
+
+impl BlobMetadataCursor {
+  fn encode(&self) -> String {
+    URL_SAFE.encode(serde_json::to_vec(self).unwrap_or_default())
+  }
+
+  fn decode(cursor: &str) -> Result<Self, DatabaseError> {
+    URL_SAFE
+      .decode(cursor)
+      .ok()
+      .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+      .ok_or_else(|| DatabaseError::InvalidParams(format!("invalid cursor: {}", cursor)))
+  }
+}
+
+/// Return a page of the blob metadata of a workspace, along with the cursor of the next page.
+/// The cursor is None when there are no more pages.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_blob_metadata_page(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  params: &QueryBlobMetadataParams,
+) -> Result<(Vec<AFBlobMetadataRow>, Option<String>), DatabaseError> {
+  let limit = params
+    .limit
+    .unwrap_or(DEFAULT_BLOB_METADATA_PAGE_SIZE)
+    .clamp(1, MAX_BLOB_METADATA_PAGE_SIZE);
+
+  let mut builder =
+    QueryBuilder::<Postgres>::new("SELECT * FROM af_blob_metadata WHERE workspace_id = ");
+  builder.push_bind(workspace_id);
+
+  if let Some(file_type) = &params.file_type {
+    let file_type = file_type.trim().to_ascii_lowercase();
+    match file_type.strip_suffix("/*") {
+      Some(ty) => {
+        let pattern = format!(
+          "{}/%",
+          ty.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+        );
+        builder
+          .push(" AND lower(file_type) LIKE ")
+          .push_bind(pattern);
+      },
+      None => {
+        builder
+          .push(" AND lower(split_part(file_type, ';', 1)) = ")
+          .push_bind(file_type);
+      },
+    }
+  }
+  if let Some(modified_after) = params.modified_after {
+    builder
+      .push(" AND modified_at >= ")
+      .push_bind(modified_after);
+  }
+  if let Some(modified_before) = params.modified_before {
+    builder
+      .push(" AND modified_at < ")
+      .push_bind(modified_before);
+  }
+
+  let column = match params.order_by {
+    BlobMetadataOrderBy::ModifiedAt => "modified_at",
+    BlobMetadataOrderBy::FileSize => "file_size",
+  };
+  let (comparison, order) = if params.ascending {
+    (">", "ASC")
+  } else {
+    ("<", "DESC")
+  };
+
+  if let Some(cursor) = &params.cursor {
+    let cursor = BlobMetadataCursor::decode(cursor)?;
+    builder.push(format!(" AND ({}, file_id) {} (", column, comparison));
+    match params.order_by {
+      BlobMetadataOrderBy::ModifiedAt => builder.push_bind(cursor.modified_at),
+      BlobMetadataOrderBy::FileSize => builder.push_bind(cursor.file_size),
+    };
+    builder.push(", ").push_bind(cursor.file_id).push(")");
+  }
+
+  builder
+    .push(format!(
+      " ORDER BY {} {}, file_id {} LIMIT ",
+      column, order, order
+    ))
+    // Fetch one more item to know whether there is a next page
+    .push_bind(limit + 1);
+
+  let mut rows = builder
+    .build_query_as::<AFBlobMetadataRow>()
+    .fetch_all(pg_pool)
+    .await?;
+
+  let next_cursor = if rows.len() as i64 > limit {
+    rows.truncate(limit as usize);
+    rows.last().map(|row| {
+      BlobMetadataCursor {
+        modified_at: row.modified_at,
+        file_size: row.file_size,
+        file_id: row.file_id.clone(),
+      }
+      .encode()
+    })
+  } else {
+    None
+  };
+  Ok((rows, next_cursor))
+}
+
+/// Return the number of blobs and their total size for each file type of a workspace
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_usage_by_file_type(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFFileTypeUsage>, DatabaseError> {
+  let usage = sqlx::query_as::<_, (String, i64, i64)>(
+    r#"
+    SELECT split_part(file_type, ';', 1) AS essence, COUNT(*), SUM(file_size)::BIGINT
+    FROM af_blob_metadata
+    WHERE workspace_id = $1
+    GROUP BY essence
+    ORDER BY SUM(file_size) DESC
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?
+  .into_iter()
+  .map(|(file_type, file_count, total_size)| AFFileTypeUsage {
+    file_type,
+    file_count,
+    total_size,
+  })
+  .collect();
+  Ok(usage)
+}
+
 /// Return all blob ids of a workspace
 #[instrument(level = "trace", skip_all, err)]
 #[inline]
@@ -160,3 +352,324 @@ pub async fn get_workspace_usage_size(
     None => Ok(0),
   }
 }
+
+/// Return the total size of the pending uploads of a workspace that are not expired
+#[instrument(level = "trace", skip_all, err)]
+pub async fn get_workspace_pending_upload_size(
+  pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<u64, DatabaseError> {
+  let size: Option<Decimal> = sqlx::query_scalar(
+    r#"
+    SELECT SUM(file_size) FROM af_blob_upload
+    WHERE workspace_id = $1 AND expires_at > NOW()
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_one(pool)
+  .await?;
+  Ok(size.and_then(|size| size.to_u64()).unwrap_or(0))
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_blob_upload(
+  pg_pool: &PgPool,
+  upload_id: &Uuid,
+  workspace_id: &Uuid,
+  file_id: &str,
+  file_type: &str,
+  file_size: i64,
+  expires_at: DateTime<Utc>,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    INSERT INTO af_blob_upload (upload_id, workspace_id, file_id, file_type, file_size, expires_at)
+    VALUES ($1, $2, $3, $4, $5, $6)
+    "#,
+  )
+  .bind(upload_id)
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(file_type)
+  .bind(file_size)
+  .bind(expires_at)
+  .execute(pg_pool)
+  .await?;
+  Ok(())
+}
+
+/// Return the pending upload of the workspace, or None if it doesn't exist or is expired
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_upload(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  upload_id: &Uuid,
+) -> Result<Option<AFBlobUploadRow>, DatabaseError> {
+  let upload = sqlx::query_as::<_, AFBlobUploadRow>(
+    r#"
+    SELECT * FROM af_blob_upload
+    WHERE upload_id = $1 AND workspace_id = $2 AND expires_at > NOW()
+    "#,
+  )
+  .bind(upload_id)
+  .bind(workspace_id)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(upload)
+}
+
+/// Delete the pending upload of the workspace. Return None if it was already deleted, so only
+/// one caller can complete the upload.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_upload(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  upload_id: &Uuid,
+) -> Result<Option<AFBlobUploadRow>, DatabaseError> {
+  let upload = sqlx::query_as::<_, AFBlobUploadRow>(
+    r#"
+    DELETE FROM af_blob_upload
+    WHERE upload_id = $1 AND workspace_id = $2
+    RETURNING *
+    "#,
+  )
+  .bind(upload_id)
+  .bind(workspace_id)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(upload)
+}
+
+/// Delete at most `limit` expired uploads and return them, so their staging objects can be
+/// removed from the bucket.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_expired_blob_uploads(
+  pg_pool: &PgPool,
+  limit: i64,
+) -> Result<Vec<AFBlobUploadRow>, DatabaseError> {
+  let uploads = sqlx::query_as::<_, AFBlobUploadRow>(
+    r#"
+    DELETE FROM af_blob_upload
+    WHERE upload_id IN (
+      SELECT upload_id FROM af_blob_upload
+      WHERE expires_at <= NOW()
+      LIMIT $1
+    )
+    RETURNING *
+    "#,
+  )
+  .bind(limit)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(uploads)
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_blob_thumbnail(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+  size: i32,
+  thumbnail_id: &str,
+  file_type: &str,
+  file_size: i64,
+) -> Result<AFBlobThumbnailRow, DatabaseError> {
+  let thumbnail = sqlx::query_as::<_, AFBlobThumbnailRow>(
+    r#"
+        INSERT INTO af_blob_thumbnail
+        (workspace_id, file_id, size, thumbnail_id, file_type, file_size)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (workspace_id, file_id, size) DO UPDATE SET
+            thumbnail_id = $4,
+            file_type = $5,
+            file_size = $6
+        RETURNING *
+        "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(size)
+  .bind(thumbnail_id)
+  .bind(file_type)
+  .bind(file_size)
+  .fetch_one(pg_pool)
+  .await?;
+  Ok(thumbnail)
+}
+
+/// Return the smallest thumbnail of the blob that is at least `size` pixels. Return None if the
+/// blob has no thumbnail that large.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_thumbnail(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+  size: i32,
+) -> Result<Option<AFBlobThumbnailRow>, DatabaseError> {
+  let thumbnail = sqlx::query_as::<_, AFBlobThumbnailRow>(
+    r#"
+        SELECT * FROM af_blob_thumbnail
+        WHERE workspace_id = $1 AND file_id = $2 AND size >= $3
+        ORDER BY size ASC
+        LIMIT 1
+        "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(size)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(thumbnail)
+}
+
+/// Return all thumbnails of a blob
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_thumbnails<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Vec<AFBlobThumbnailRow>, DatabaseError> {
+  let thumbnails = sqlx::query_as::<_, AFBlobThumbnailRow>(
+    r#"
+        SELECT * FROM af_blob_thumbnail
+        WHERE workspace_id = $1 AND file_id = $2
+        "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(thumbnails)
+}
+
+/// Return the ids of all workspaces that own at least one blob
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_ids_with_blobs(pg_pool: &PgPool) -> Result<Vec<Uuid>, DatabaseError> {
+  let workspace_ids = sqlx::query_scalar::<_, Uuid>(
+    r#"
+    SELECT DISTINCT workspace_id FROM af_blob_metadata
+    "#,
+  )
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(workspace_ids)
+}
+
+/// Return a stream of the encoded data of every collab and every collab snapshot, across all
+/// workspaces. The deleted collabs are included, since they can still be restored, and so are the
+/// snapshots, since restoring a snapshot brings back the blobs it references.
+pub fn select_all_collab_blobs(pg_pool: &PgPool) -> BoxStream<'_, Result<Vec<u8>, DatabaseError>> {
+  sqlx::query_scalar::<_, Vec<u8>>(
+    r#"
+    SELECT blob FROM af_collab
+    UNION ALL
+    SELECT blob FROM af_collab_snapshot
+    "#,
+  )
+  .fetch(pg_pool)
+  .map(|result| result.map_err(DatabaseError::from))
+  .boxed()
+}
+
+/// Return the file ids of the workspace that are marked as unreferenced, along with the time
+/// they were marked.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_gc_marks(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<HashMap<String, DateTime<Utc>>, DatabaseError> {
+  let marks = sqlx::query_as::<_, (String, DateTime<Utc>)>(
+    r#"
+    SELECT file_id, marked_at FROM af_blob_gc_mark
+    WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?
+  .into_iter()
+  .collect();
+  Ok(marks)
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_blob_gc_marks(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    INSERT INTO af_blob_gc_mark (workspace_id, file_id)
+    SELECT $1, file_id FROM UNNEST($2::text[]) AS file_id
+    ON CONFLICT (workspace_id, file_id) DO NOTHING
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .execute(pg_pool)
+  .await?;
+  Ok(())
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_gc_marks<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    DELETE FROM af_blob_gc_mark
+    WHERE workspace_id = $1 AND file_id = ANY($2)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Return the file type policy of the workspace, or None if the workspace uses the default one.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_file_type_policy(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Option<AFFileTypePolicy>, DatabaseError> {
+  let policy = sqlx::query_as::<_, (Vec<String>, Vec<String>)>(
+    r#"
+    SELECT allowed, denied FROM af_workspace_file_type_policy
+    WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(pg_pool)
+  .await?
+  .map(|(allowed, denied)| AFFileTypePolicy { allowed, denied });
+  Ok(policy)
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn upsert_workspace_file_type_policy(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  policy: &AFFileTypePolicy,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    INSERT INTO af_workspace_file_type_policy (workspace_id, allowed, denied)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (workspace_id) DO UPDATE SET
+        allowed = $2,
+        denied = $3,
+        updated_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(&policy.allowed)
+  .bind(&policy.denied)
+  .execute(pg_pool)
+  .await?;
+  Ok(())
+}
@@ -0,0 +1,238 @@
+use database_entity::dto::{AFAccessLevel, AFPermission, AFRole, AFWorkspaceRole};
+use database_entity::error::DatabaseError;
+use sqlx::{Executor, PgPool, Postgres, Row, Transaction};
+use std::ops::DerefMut;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Returns the highest access level among the permissions of the role. Returns None if the role
+/// doesn't have any permission.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_role_access_level<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  role_id: i32,
+) -> Result<Option<AFAccessLevel>, DatabaseError> {
+  let level = sqlx::query_scalar::<_, Option<i32>>(
+    r#"
+    SELECT MAX(af_permissions.access_level)
+    FROM af_role_permissions
+      JOIN af_permissions ON af_role_permissions.permission_id = af_permissions.id
+    WHERE af_role_permissions.role_id = $1
+    "#,
+  )
+  .bind(role_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(level.map(AFAccessLevel::from))
+}
+
+pub async fn select_permissions(pg_pool: &PgPool) -> Result<Vec<AFPermission>, DatabaseError> {
+  let permissions = sqlx::query_as::<_, (i32, String, i32, Option<String>)>(
+    r#"SELECT id, name, access_level, description FROM af_permissions ORDER BY access_level"#,
+  )
+  .fetch_all(pg_pool)
+  .await?
+  .into_iter()
+  .map(|(id, name, access_level, description)| AFPermission {
+    id,
+    name,
+    access_level: access_level.into(),
+    description: description.unwrap_or_default(),
+  })
+  .collect();
+  Ok(permissions)
+}
+
+/// Returns the built-in roles and the custom roles of the workspace with their permissions.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_workspace_roles(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFWorkspaceRole>, DatabaseError> {
+  let rows = sqlx::query(
+    r#"
+    SELECT af_roles.id, af_roles.name, af_permissions.id, af_permissions.name,
+           af_permissions.access_level, af_permissions.description
+    FROM af_roles
+      LEFT JOIN af_role_permissions ON af_role_permissions.role_id = af_roles.id
+      LEFT JOIN af_permissions ON af_role_permissions.permission_id = af_permissions.id
+    WHERE af_roles.workspace_id IS NULL OR af_roles.workspace_id = $1
+    ORDER BY af_roles.id, af_permissions.access_level
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?;
+
+  let mut roles: Vec<AFWorkspaceRole> = vec![];
+  for row in rows {
+    let role_id: i32 = row.try_get(0)?;
+    if roles.last().map(|role| i32::from(role.role.clone())) != Some(role_id) {
+      roles.push(AFWorkspaceRole {
+        role: AFRole::from(role_id),
+        name: row.try_get(1)?,
+        permissions: vec![],
+      });
+    }
+
+    if let Some(permission_id) = row.try_get::<Option<i32>, _>(2)? {
+      let permission = AFPermission {
+        id: permission_id,
+        name: row.try_get(3)?,
+        access_level: AFAccessLevel::from(row.try_get::<i32, _>(4)?),
+        description: row.try_get::<Option<String>, _>(5)?.unwrap_or_default(),
+      };
+      if let Some(role) = roles.last_mut() {
+        role.permissions.push(permission);
+      }
+    }
+  }
+  Ok(roles)
+}
+
+/// Returns the workspace that the role belongs to. The outer option is None if the role doesn't
+/// exist, the inner option is None for the built-in roles.
+pub async fn select_role_workspace_id<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  role_id: i32,
+) -> Result<Option<Option<Uuid>>, DatabaseError> {
+  let workspace_id =
+    sqlx::query_scalar::<_, Option<Uuid>>(r#"SELECT workspace_id FROM af_roles WHERE id = $1"#)
+      .bind(role_id)
+      .fetch_optional(executor)
+      .await?;
+  Ok(workspace_id)
+}
+
+pub async fn insert_workspace_role(
+  txn: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  name: &str,
+) -> Result<i32, DatabaseError> {
+  let role_id = sqlx::query_scalar::<_, i32>(
+    r#"INSERT INTO af_roles (name, workspace_id) VALUES ($1, $2) RETURNING id"#,
+  )
+  .bind(name)
+  .bind(workspace_id)
+  .fetch_one(txn.deref_mut())
+  .await
+  .map_err(|err| role_name_error(err, name))?;
+  Ok(role_id)
+}
+
+pub async fn update_workspace_role_name(
+  txn: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  role_id: i32,
+  name: &str,
+) -> Result<(), DatabaseError> {
+  sqlx::query(r#"UPDATE af_roles SET name = $1 WHERE id = $2 AND workspace_id = $3"#)
+    .bind(name)
+    .bind(role_id)
+    .bind(workspace_id)
+    .execute(txn.deref_mut())
+    .await
+    .map_err(|err| role_name_error(err, name))?;
+  Ok(())
+}
+
+fn role_name_error(err: sqlx::Error, name: &str) -> DatabaseError {
+  match &err {
+    sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+      DatabaseError::RecordAlreadyExists(format!("role:{} already exists", name))
+    },
+    sqlx::Error::Database(db_err) if db_err.is_check_violation() => {
+      DatabaseError::InvalidParams(format!("role name:{} is reserved", name))
+    },
+    _ => err.into(),
+  }
+}
+
+/// Replaces the permissions of the role. Returns [DatabaseError::InvalidParams] if any of the
+/// permission ids doesn't exist.
+pub async fn replace_role_permissions(
+  txn: &mut Transaction<'_, Postgres>,
+  role_id: i32,
+  permission_ids: &[i32],
+) -> Result<(), DatabaseError> {
+  sqlx::query(r#"DELETE FROM af_role_permissions WHERE role_id = $1"#)
+    .bind(role_id)
+    .execute(txn.deref_mut())
+    .await?;
+
+  let inserted = sqlx::query(
+    r#"
+    INSERT INTO af_role_permissions (role_id, permission_id)
+    SELECT $1, id FROM af_permissions WHERE id = ANY($2)
+    "#,
+  )
+  .bind(role_id)
+  .bind(permission_ids)
+  .execute(txn.deref_mut())
+  .await?
+  .rows_affected();
+
+  let mut unique_ids = permission_ids.to_vec();
+  unique_ids.sort_unstable();
+  unique_ids.dedup();
+  if inserted != unique_ids.len() as u64 {
+    return Err(DatabaseError::InvalidParams(format!(
+      "invalid permission ids: {:?}",
+      permission_ids
+    )));
+  }
+  Ok(())
+}
+
+pub async fn is_role_assigned<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  role_id: i32,
+) -> Result<bool, DatabaseError> {
+  let exists = sqlx::query_scalar::<_, bool>(
+    r#"SELECT EXISTS (SELECT 1 FROM af_workspace_member WHERE role_id = $1)"#,
+  )
+  .bind(role_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(exists)
+}
+
+/// Deletes the custom role of the workspace. Returns false if the role doesn't exist.
+pub async fn delete_workspace_role<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  role_id: i32,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(r#"DELETE FROM af_roles WHERE id = $1 AND workspace_id = $2"#)
+    .bind(role_id)
+    .bind(workspace_id)
+    .execute(executor)
+    .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Updates the permission of the members with the given role on the workspace's own collab
+/// object, so that it follows the permissions of the role.
+pub async fn update_role_members_collab_permission(
+  txn: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  role_id: i32,
+  access_level: AFAccessLevel,
+) -> Result<(), DatabaseError> {
+  let access_level: i32 = access_level.into();
+  sqlx::query(
+    r#"
+    UPDATE af_collab_member
+    SET permission_id = (SELECT id FROM af_permissions WHERE access_level = $3)
+    WHERE oid = $1::TEXT AND uid IN (
+      SELECT uid FROM af_workspace_member WHERE workspace_id = $1 AND role_id = $2
+    )
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(role_id)
+  .bind(access_level)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(())
+}
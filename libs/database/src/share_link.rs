@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFShareLinkRow;
+use sqlx::{Executor, PgPool, Postgres};
+use tracing::instrument;
+use uuid::Uuid;
+
+const SHARE_LINK_COLUMNS: &str =
+  "id, oid, workspace_id, collab_type, access_level, password_hash, created_by, created_at, expires_at";
+
+/// Inserts a share link for the collab object. Returns None if the workspace doesn't have a
+/// collab object with the given id and type.
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip(executor, token_hash, password_hash), err)]
+pub async fn insert_share_link<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  id: &Uuid,
+  oid: &str,
+  workspace_id: &Uuid,
+  collab_type: i32,
+  token_hash: &str,
+  access_level: i32,
+  password_hash: Option<&str>,
+  created_by: &i64,
+  expires_at: Option<DateTime<Utc>>,
+) -> Result<Option<AFShareLinkRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFShareLinkRow>(&format!(
+    r#"
+    INSERT INTO af_collab_share_link
+      (id, oid, workspace_id, collab_type, token_hash, access_level, password_hash, created_by,
+       expires_at)
+    SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9
+    WHERE EXISTS (
+      SELECT 1 FROM af_collab
+      WHERE oid = $2 AND workspace_id = $3 AND partition_key = $4 AND deleted_at IS NULL
+    )
+    RETURNING {}
+    "#,
+    SHARE_LINK_COLUMNS
+  ))
+  .bind(id)
+  .bind(oid)
+  .bind(workspace_id)
+  .bind(collab_type)
+  .bind(token_hash)
+  .bind(access_level)
+  .bind(password_hash)
+  .bind(created_by)
+  .bind(expires_at)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns all the share links of the object, including the expired ones.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_share_links(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  oid: &str,
+) -> Result<Vec<AFShareLinkRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFShareLinkRow>(&format!(
+    r#"
+    SELECT {} FROM af_collab_share_link
+    WHERE workspace_id = $1 AND oid = $2
+    ORDER BY created_at DESC
+    "#,
+    SHARE_LINK_COLUMNS
+  ))
+  .bind(workspace_id)
+  .bind(oid)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Returns the share link with the given hash. Expired links are not returned.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_active_share_link_by_hash(
+  pg_pool: &PgPool,
+  token_hash: &str,
+) -> Result<Option<AFShareLinkRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFShareLinkRow>(&format!(
+    r#"
+    SELECT {} FROM af_collab_share_link
+    WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > NOW())
+    "#,
+    SHARE_LINK_COLUMNS
+  ))
+  .bind(token_hash)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Deletes the share link. Returns false if the object doesn't have a link with the given id.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn delete_share_link<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  oid: &str,
+  link_id: &Uuid,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    DELETE FROM af_collab_share_link
+    WHERE id = $1 AND workspace_id = $2 AND oid = $3
+    "#,
+  )
+  .bind(link_id)
+  .bind(workspace_id)
+  .bind(oid)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
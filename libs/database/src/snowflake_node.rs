@@ -0,0 +1,70 @@
+use database_entity::error::DatabaseError;
+use sqlx::{Executor, Postgres};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Leases the lowest node id that isn't leased, or whose lease has expired. Returns None if all
+/// the node ids up to `max_node_id` are leased, or if another server leased the same node id at
+/// the same time, in which case the caller can try again.
+///
+/// The time of the database is used, so the servers don't need synchronized clocks.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn lease_snowflake_node<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  instance_id: &Uuid,
+  max_node_id: i32,
+  lease_secs: f64,
+) -> Result<Option<i32>, DatabaseError> {
+  let node_id = sqlx::query_scalar::<_, i32>(
+    r#"
+    WITH candidate AS (
+      SELECT n AS node_id
+      FROM generate_series(0, $2) AS n
+      WHERE NOT EXISTS (
+        SELECT 1 FROM af_snowflake_node
+        WHERE node_id = n AND leased_until > NOW()
+      )
+      ORDER BY n
+      LIMIT 1
+    )
+    INSERT INTO af_snowflake_node (node_id, instance_id, leased_until)
+    SELECT node_id, $1, NOW() + make_interval(secs => $3)
+    FROM candidate
+    ON CONFLICT (node_id) DO UPDATE
+      SET instance_id = EXCLUDED.instance_id,
+          leased_until = EXCLUDED.leased_until
+      WHERE af_snowflake_node.leased_until <= NOW()
+    RETURNING node_id
+    "#,
+  )
+  .bind(instance_id)
+  .bind(max_node_id)
+  .bind(lease_secs)
+  .fetch_optional(executor)
+  .await?;
+  Ok(node_id)
+}
+
+/// Extends the lease of the node id. Returns false if the lease was lost, i.e. it expired and the
+/// node id was leased by another server.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn renew_snowflake_node<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  node_id: i32,
+  instance_id: &Uuid,
+  lease_secs: f64,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    UPDATE af_snowflake_node
+    SET leased_until = NOW() + make_interval(secs => $3)
+    WHERE node_id = $1 AND instance_id = $2
+    "#,
+  )
+  .bind(node_id)
+  .bind(instance_id)
+  .bind(lease_secs)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
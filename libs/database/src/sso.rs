@@ -0,0 +1,118 @@
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFSsoProviderRow;
+use sqlx::{Executor, Postgres};
+use tracing::instrument;
+use uuid::Uuid;
+
+const SSO_PROVIDER_COLUMNS: &str =
+  "provider_id, domain, workspace_id, provider_type, gotrue_provider, created_at";
+
+/// Inserts the SSO provider of the domain. Returns None if the domain already has a provider.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn insert_sso_provider<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  provider_id: &Uuid,
+  domain: &str,
+  workspace_id: &Uuid,
+  provider_type: &str,
+  gotrue_provider: &str,
+) -> Result<Option<AFSsoProviderRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFSsoProviderRow>(&format!(
+    r#"
+    INSERT INTO af_sso_provider (provider_id, domain, workspace_id, provider_type, gotrue_provider)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (domain) DO NOTHING
+    RETURNING {}
+    "#,
+    SSO_PROVIDER_COLUMNS
+  ))
+  .bind(provider_id)
+  .bind(domain)
+  .bind(workspace_id)
+  .bind(provider_type)
+  .bind(gotrue_provider)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_sso_providers<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+) -> Result<Vec<AFSsoProviderRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFSsoProviderRow>(&format!(
+    "SELECT {} FROM af_sso_provider ORDER BY domain",
+    SSO_PROVIDER_COLUMNS
+  ))
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_sso_provider_by_domain<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  domain: &str,
+) -> Result<Option<AFSsoProviderRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFSsoProviderRow>(&format!(
+    "SELECT {} FROM af_sso_provider WHERE domain = $1",
+    SSO_PROVIDER_COLUMNS
+  ))
+  .bind(domain)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Deletes the SSO provider and returns it. Returns None if the provider doesn't exist.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn delete_sso_provider<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  provider_id: &Uuid,
+) -> Result<Option<AFSsoProviderRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFSsoProviderRow>(&format!(
+    "DELETE FROM af_sso_provider WHERE provider_id = $1 RETURNING {}",
+    SSO_PROVIDER_COLUMNS
+  ))
+  .bind(provider_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns true if the user was already provisioned through the SSO provider.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_sso_member_exists<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  provider_id: &Uuid,
+  uid: &i64,
+) -> Result<bool, DatabaseError> {
+  let exists = sqlx::query_scalar::<_, bool>(
+    "SELECT EXISTS(SELECT 1 FROM af_sso_member WHERE provider_id = $1 AND uid = $2)",
+  )
+  .bind(provider_id)
+  .bind(uid)
+  .fetch_one(executor)
+  .await?;
+  Ok(exists)
+}
+
+/// Records that the user was provisioned through the SSO provider.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn insert_sso_member<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  provider_id: &Uuid,
+  uid: &i64,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    INSERT INTO af_sso_member (provider_id, uid) VALUES ($1, $2)
+    ON CONFLICT (provider_id, uid) DO NOTHING
+    "#,
+  )
+  .bind(provider_id)
+  .bind(uid)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
@@ -1,7 +1,9 @@
 use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFUserUsageRow;
 use sqlx::postgres::PgArguments;
 use sqlx::types::JsonValue;
-use sqlx::{Arguments, Executor, PgPool, Postgres};
+use sqlx::{Arguments, Executor, PgPool, Postgres, Transaction};
+use std::ops::DerefMut;
 use tracing::{instrument, warn};
 use uuid::Uuid;
 
@@ -13,7 +15,7 @@ use uuid::Uuid;
 ///
 /// # Arguments
 ///
-/// * `pool` - A reference to the database connection pool.
+/// * `executor` - The database connection pool or a transaction.
 /// * `user_uuid` - The UUID of the user to be updated.
 /// * `name` - An optional new name for the user.
 /// * `email` - An optional new email for the user.
@@ -21,8 +23,8 @@ use uuid::Uuid;
 ///
 #[instrument(skip_all, err)]
 #[inline]
-pub async fn update_user(
-  pool: &PgPool,
+pub async fn update_user<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
   user_uuid: &uuid::Uuid,
   name: Option<String>,
   email: Option<String>,
@@ -65,7 +67,7 @@ pub async fn update_user(
   );
   args.add(user_uuid);
 
-  sqlx::query_with(&query, args).execute(pool).await?;
+  sqlx::query_with(&query, args).execute(executor).await?;
   Ok(())
 }
 
@@ -183,3 +185,178 @@ pub async fn is_user_exist<'a, E: Executor<'a, Database = Postgres>>(
 
   Ok(exists.unwrap_or(false))
 }
+
+/// Returns true if the user has verified a second factor, e.g. a TOTP authenticator.
+///
+/// The query! macro can't be used because the auth schema is owned by GoTrue.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_user_has_verified_factor<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  user_uuid: &Uuid,
+) -> Result<bool, DatabaseError> {
+  let exists = sqlx::query_scalar::<_, bool>(
+    r#"
+    SELECT EXISTS(
+      SELECT 1
+      FROM auth.mfa_factors
+      WHERE user_id = $1 AND status = 'verified'
+    )
+    "#,
+  )
+  .bind(user_uuid)
+  .fetch_one(executor)
+  .await?;
+  Ok(exists)
+}
+
+/// Deletes the user along with the memberships of the workspaces and collabs that the user
+/// doesn't own. The workspaces owned by the user must be transferred or deleted before.
+#[instrument(level = "trace", skip(txn), err)]
+pub async fn delete_user(
+  txn: &mut Transaction<'_, sqlx::Postgres>,
+  uid: &i64,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    DELETE FROM af_collab_member WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .execute(txn.deref_mut())
+  .await?;
+
+  sqlx::query(
+    r#"
+    DELETE FROM af_workspace_member WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .execute(txn.deref_mut())
+  .await?;
+
+  sqlx::query(
+    r#"
+    DELETE FROM af_user WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(())
+}
+
+/// Returns the uid of the user with the given email if the user registered with the deprecated
+/// local password, i.e. the user has a password hash in `af_user` and hasn't been moved to GoTrue
+/// yet.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_legacy_password_uid<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  email: &str,
+) -> Result<Option<i64>, DatabaseError> {
+  let uid = sqlx::query_scalar::<_, i64>(
+    r#"
+    SELECT uid FROM af_user WHERE email = $1 AND password <> ''
+    "#,
+  )
+  .bind(email)
+  .fetch_optional(executor)
+  .await?;
+  Ok(uid)
+}
+
+/// Links the user who registered with the deprecated local password to the GoTrue user it was
+/// moved to. The local password is cleared, so it can't be used anymore. Returns false if the
+/// user was already linked.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn update_legacy_password_user_uuid<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: &i64,
+  user_uuid: &Uuid,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    UPDATE af_user SET uuid = $2, password = '' WHERE uid = $1 AND password <> ''
+    "#,
+  )
+  .bind(uid)
+  .bind(user_uuid)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Returns the uid, the number of workspaces and the storage usage of the users. The users that
+/// don't have a `af_user` yet are left out. The storage usage is the size of the blobs in the
+/// workspaces owned by the user.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_user_usages<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  user_uuids: &[Uuid],
+) -> Result<Vec<AFUserUsageRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFUserUsageRow>(
+    r#"
+    SELECT
+      u.uuid,
+      u.uid,
+      u.name,
+      (SELECT COUNT(*) FROM af_workspace_member m WHERE m.uid = u.uid) AS workspace_count,
+      (
+        SELECT COALESCE(SUM(b.file_size), 0)::BIGINT
+        FROM af_blob_metadata b
+        JOIN af_workspace w ON w.workspace_id = b.workspace_id
+        WHERE w.owner_uid = u.uid
+      ) AS storage_usage
+    FROM af_user u
+    WHERE u.uuid = ANY($1)
+    "#,
+  )
+  .bind(user_uuids)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Replaces the avatar of the user, or removes it if `avatar_id` is None. Returns the id of the
+/// previous avatar, whose object can then be deleted.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn update_user_avatar<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  user_uuid: &Uuid,
+  avatar_id: Option<&Uuid>,
+) -> Result<Option<Uuid>, DatabaseError> {
+  let previous_avatar_id = sqlx::query_scalar::<_, Option<Uuid>>(
+    r#"
+    WITH previous AS (
+      SELECT uid, avatar_id FROM af_user WHERE uuid = $1 FOR UPDATE
+    )
+    UPDATE af_user SET avatar_id = $2
+    FROM previous
+    WHERE af_user.uid = previous.uid
+    RETURNING previous.avatar_id
+    "#,
+  )
+  .bind(user_uuid)
+  .bind(avatar_id)
+  .fetch_optional(executor)
+  .await?
+  .ok_or_else(|| DatabaseError::RecordNotFound(format!("user:{} doesn't exist", user_uuid)))?;
+  Ok(previous_avatar_id)
+}
+
+/// Returns the id of the current avatar of the user, or None if the user has no avatar.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_user_avatar_id<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  user_uuid: &Uuid,
+) -> Result<Option<Uuid>, DatabaseError> {
+  let avatar_id = sqlx::query_scalar::<_, Option<Uuid>>(
+    r#"
+    SELECT avatar_id FROM af_user WHERE uuid = $1
+    "#,
+  )
+  .bind(user_uuid)
+  .fetch_optional(executor)
+  .await?
+  .flatten();
+  Ok(avatar_id)
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFUserBanRow;
+use sqlx::{Executor, Postgres};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Bans the user, or replaces the existing ban of the user.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn upsert_user_ban<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  user_uuid: &Uuid,
+  banned_until: DateTime<Utc>,
+  reason: Option<&str>,
+  banned_by: Option<&Uuid>,
+) -> Result<AFUserBanRow, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserBanRow>(
+    r#"
+    INSERT INTO af_user_ban (uuid, banned_until, reason, banned_by)
+    VALUES ($1, $2, $3, $4)
+    ON CONFLICT (uuid) DO UPDATE
+      SET banned_until = EXCLUDED.banned_until,
+          reason = EXCLUDED.reason,
+          banned_by = EXCLUDED.banned_by,
+          created_at = CURRENT_TIMESTAMP
+    RETURNING uuid, banned_until, reason, banned_by, created_at
+    "#,
+  )
+  .bind(user_uuid)
+  .bind(banned_until)
+  .bind(reason)
+  .bind(banned_by)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns false if the user wasn't banned.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn delete_user_ban<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  user_uuid: &Uuid,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query("DELETE FROM af_user_ban WHERE uuid = $1")
+    .bind(user_uuid)
+    .execute(executor)
+    .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Returns the bans that haven't expired yet.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_active_user_bans<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+) -> Result<Vec<AFUserBanRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFUserBanRow>(
+    r#"
+    SELECT uuid, banned_until, reason, banned_by, created_at
+    FROM af_user_ban
+    WHERE banned_until > CURRENT_TIMESTAMP
+    ORDER BY created_at DESC
+    "#,
+  )
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+use database_entity::dto::AFUserDataExportStatus;
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::{AFCollabDataRow, AFUserDataExportRow};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use sqlx::{PgPool, Row};
+use tracing::instrument;
+use uuid::Uuid;
+
+const USER_DATA_EXPORT_COLUMNS: &str =
+  "export_id, uid, status, archive_key, file_size, error, created_at, completed_at, expires_at";
+
+/// Starts a new export of the data of the user. Returns None if the user already has an export
+/// in progress.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn insert_user_data_export(
+  pg_pool: &PgPool,
+  export_id: &Uuid,
+  uid: &i64,
+) -> Result<Option<AFUserDataExportRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserDataExportRow>(&format!(
+    r#"
+    INSERT INTO af_user_data_export (export_id, uid, status)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (uid) WHERE status = 'pending' DO NOTHING
+    RETURNING {}
+    "#,
+    USER_DATA_EXPORT_COLUMNS
+  ))
+  .bind(export_id)
+  .bind(uid)
+  .bind(AFUserDataExportStatus::Pending.as_str())
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the export of the user that is still in progress, if any.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_pending_user_data_export(
+  pg_pool: &PgPool,
+  uid: &i64,
+) -> Result<Option<AFUserDataExportRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserDataExportRow>(&format!(
+    r#"
+    SELECT {} FROM af_user_data_export WHERE uid = $1 AND status = $2
+    "#,
+    USER_DATA_EXPORT_COLUMNS
+  ))
+  .bind(uid)
+  .bind(AFUserDataExportStatus::Pending.as_str())
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_user_data_export(
+  pg_pool: &PgPool,
+  uid: &i64,
+  export_id: &Uuid,
+) -> Result<Option<AFUserDataExportRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserDataExportRow>(&format!(
+    r#"
+    SELECT {} FROM af_user_data_export WHERE uid = $1 AND export_id = $2
+    "#,
+    USER_DATA_EXPORT_COLUMNS
+  ))
+  .bind(uid)
+  .bind(export_id)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Marks the pending export as completed. Returns false if the export is no longer pending, e.g.
+/// because it was marked as interrupted by [fail_stale_user_data_exports].
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn update_user_data_export_completed(
+  pg_pool: &PgPool,
+  export_id: &Uuid,
+  archive_key: &str,
+  file_size: i64,
+  expires_at: DateTime<Utc>,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    UPDATE af_user_data_export
+    SET status = $2, archive_key = $3, file_size = $4, completed_at = NOW(), expires_at = $5
+    WHERE export_id = $1 AND status = $6
+    "#,
+  )
+  .bind(export_id)
+  .bind(AFUserDataExportStatus::Completed.as_str())
+  .bind(archive_key)
+  .bind(file_size)
+  .bind(expires_at)
+  .bind(AFUserDataExportStatus::Pending.as_str())
+  .execute(pg_pool)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn update_user_data_export_failed(
+  pg_pool: &PgPool,
+  export_id: &Uuid,
+  error: &str,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    UPDATE af_user_data_export SET status = $2, error = $3, completed_at = NOW()
+    WHERE export_id = $1 AND status = $4
+    "#,
+  )
+  .bind(export_id)
+  .bind(AFUserDataExportStatus::Failed.as_str())
+  .bind(error)
+  .bind(AFUserDataExportStatus::Pending.as_str())
+  .execute(pg_pool)
+  .await?;
+  Ok(())
+}
+
+/// Fails the exports that have been pending since before the given time, e.g. because the
+/// server that was building the archive was stopped.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn fail_stale_user_data_exports(
+  pg_pool: &PgPool,
+  created_before: DateTime<Utc>,
+) -> Result<u64, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    UPDATE af_user_data_export SET status = $2, error = 'the export was interrupted', completed_at = NOW()
+    WHERE status = $3 AND created_at < $1
+    "#,
+  )
+  .bind(created_before)
+  .bind(AFUserDataExportStatus::Failed.as_str())
+  .bind(AFUserDataExportStatus::Pending.as_str())
+  .execute(pg_pool)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+/// Marks the completed exports that expired as expired, and returns the keys of their archives
+/// so they can be removed from the bucket.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn expire_user_data_exports(pg_pool: &PgPool) -> Result<Vec<String>, DatabaseError> {
+  let archive_keys = sqlx::query(
+    r#"
+    UPDATE af_user_data_export SET status = $1
+    WHERE status = $2 AND expires_at <= NOW()
+    RETURNING archive_key
+    "#,
+  )
+  .bind(AFUserDataExportStatus::Expired.as_str())
+  .bind(AFUserDataExportStatus::Completed.as_str())
+  .fetch_all(pg_pool)
+  .await?
+  .into_iter()
+  .filter_map(|row| row.get::<Option<String>, _>("archive_key"))
+  .collect();
+  Ok(archive_keys)
+}
+
+/// Return a stream of the collabs owned by the user that are not deleted, in every workspace.
+pub fn select_user_collabs<'a>(
+  pg_pool: &'a PgPool,
+  uid: &'a i64,
+) -> BoxStream<'a, Result<AFCollabDataRow, DatabaseError>> {
+  sqlx::query_as::<_, AFCollabDataRow>(
+    r#"
+    SELECT oid, workspace_id, partition_key, blob FROM af_collab
+    WHERE owner_uid = $1 AND deleted_at IS NULL
+    "#,
+  )
+  .bind(uid)
+  .fetch(pg_pool)
+  .map(|result| result.map_err(DatabaseError::from))
+  .boxed()
+}
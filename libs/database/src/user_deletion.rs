@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFUserDeletionRow;
+use sqlx::{Executor, PgPool, Postgres};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Schedules the deletion of the user. If the deletion is already scheduled, the existing
+/// schedule is kept and returned.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn insert_user_deletion<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: &i64,
+  uuid: &Uuid,
+  scheduled_at: DateTime<Utc>,
+) -> Result<AFUserDeletionRow, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserDeletionRow>(
+    r#"
+    INSERT INTO af_user_deletion (uid, uuid, scheduled_at)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (uid) DO UPDATE SET scheduled_at = af_user_deletion.scheduled_at
+    RETURNING uid, uuid, requested_at, scheduled_at
+    "#,
+  )
+  .bind(uid)
+  .bind(uuid)
+  .bind(scheduled_at)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_user_deletion(
+  pg_pool: &PgPool,
+  uid: &i64,
+) -> Result<Option<AFUserDeletionRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserDeletionRow>(
+    r#"
+    SELECT uid, uuid, requested_at, scheduled_at FROM af_user_deletion WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Cancels the scheduled deletion. Returns false if no deletion was scheduled.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn delete_user_deletion<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: &i64,
+) -> Result<bool, DatabaseError> {
+  let result = sqlx::query(
+    r#"
+    DELETE FROM af_user_deletion WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Returns the deletions whose grace period is over, the oldest first.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_due_user_deletions(
+  pg_pool: &PgPool,
+  limit: i64,
+) -> Result<Vec<AFUserDeletionRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFUserDeletionRow>(
+    r#"
+    SELECT uid, uuid, requested_at, scheduled_at FROM af_user_deletion
+    WHERE scheduled_at <= NOW()
+    ORDER BY scheduled_at ASC
+    LIMIT $1
+    "#,
+  )
+  .bind(limit)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use database_entity::error::DatabaseError;
+use database_entity::pg_row::AFUserSessionRow;
+use sqlx::{Executor, PgPool, Postgres};
+use tracing::instrument;
+use uuid::Uuid;
+
+const USER_SESSION_COLUMNS: &str = "uid, device_id, session_id, client_version, ip, user_agent, \
+   created_at, last_seen_at, signed_out_at";
+
+/// Records that the device of the user is connected. Returns None if the device was signed out
+/// after the access token was issued, which means the device has to sign in again. A token of
+/// another session, or issued after the sign out, signs the device back in.
+#[allow(clippy::too_many_arguments)]
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn upsert_user_session(
+  pg_pool: &PgPool,
+  uid: &i64,
+  device_id: &str,
+  session_id: Option<&Uuid>,
+  issued_at: Option<DateTime<Utc>>,
+  client_version: Option<&str>,
+  ip: Option<&str>,
+  user_agent: Option<&str>,
+) -> Result<Option<AFUserSessionRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserSessionRow>(&format!(
+    r#"
+    INSERT INTO af_user_session (uid, device_id, session_id, client_version, ip, user_agent)
+    VALUES ($1, $2, $3, $4, $5, $6)
+    ON CONFLICT (uid, device_id) DO UPDATE SET
+      session_id = EXCLUDED.session_id,
+      client_version = COALESCE(EXCLUDED.client_version, af_user_session.client_version),
+      ip = EXCLUDED.ip,
+      user_agent = EXCLUDED.user_agent,
+      last_seen_at = NOW(),
+      signed_out_at = NULL
+    WHERE af_user_session.signed_out_at IS NULL
+      OR af_user_session.signed_out_at < $7
+      OR (EXCLUDED.session_id IS NOT NULL
+        AND af_user_session.session_id IS DISTINCT FROM EXCLUDED.session_id)
+    RETURNING {}
+    "#,
+    USER_SESSION_COLUMNS
+  ))
+  .bind(uid)
+  .bind(device_id)
+  .bind(session_id)
+  .bind(client_version)
+  .bind(ip)
+  .bind(user_agent)
+  .bind(issued_at)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the devices of the user that are not signed out, the most recently seen first.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_active_user_sessions(
+  pg_pool: &PgPool,
+  uid: &i64,
+) -> Result<Vec<AFUserSessionRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFUserSessionRow>(&format!(
+    r#"
+    SELECT {} FROM af_user_session
+    WHERE uid = $1 AND signed_out_at IS NULL
+    ORDER BY last_seen_at DESC
+    "#,
+    USER_SESSION_COLUMNS
+  ))
+  .bind(uid)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Returns the devices that were signed out of a GoTrue session since the given time.
+#[instrument(level = "trace", skip(pg_pool), err)]
+pub async fn select_signed_out_user_sessions(
+  pg_pool: &PgPool,
+  since: DateTime<Utc>,
+) -> Result<Vec<AFUserSessionRow>, DatabaseError> {
+  let rows = sqlx::query_as::<_, AFUserSessionRow>(&format!(
+    r#"
+    SELECT {} FROM af_user_session
+    WHERE session_id IS NOT NULL AND signed_out_at > $1
+    "#,
+    USER_SESSION_COLUMNS
+  ))
+  .bind(since)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Marks the device as signed out. Returns None if the user doesn't have an active session on
+/// the device.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn sign_out_user_session<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: &i64,
+  device_id: &str,
+) -> Result<Option<AFUserSessionRow>, DatabaseError> {
+  let row = sqlx::query_as::<_, AFUserSessionRow>(&format!(
+    r#"
+    UPDATE af_user_session SET signed_out_at = NOW()
+    WHERE uid = $1 AND device_id = $2 AND signed_out_at IS NULL
+    RETURNING {}
+    "#,
+    USER_SESSION_COLUMNS
+  ))
+  .bind(uid)
+  .bind(device_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
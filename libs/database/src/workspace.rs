@@ -1,7 +1,7 @@
 use database_entity::dto::AFRole;
 use sqlx::{
   types::{uuid, Uuid},
-  Executor, PgPool, Postgres, Transaction,
+  Executor, PgConnection, PgPool, Postgres, Transaction,
 };
 use std::ops::DerefMut;
 use tracing::{event, instrument};
@@ -142,9 +142,9 @@ pub async fn insert_workspace_member_with_txn(
 }
 
 #[inline]
-#[instrument(level = "trace", skip(pool, email, role), err)]
+#[instrument(level = "trace", skip(conn, email, role), err)]
 pub async fn upsert_workspace_member(
-  pool: &PgPool,
+  conn: &mut PgConnection,
   workspace_id: &Uuid,
   email: &str,
   role: Option<AFRole>,
@@ -157,7 +157,7 @@ pub async fn upsert_workspace_member(
     tracing::Level::TRACE,
     "update workspace member: workspace_id:{}, uid {:?}, role:{:?}",
     workspace_id,
-    select_uid_from_email(pool, email).await,
+    select_uid_from_email(&mut *conn, email).await,
     role
   );
 
@@ -175,7 +175,7 @@ pub async fn upsert_workspace_member(
     workspace_id,
     email
   )
-  .execute(pool)
+  .execute(conn)
   .await?;
 
   Ok(())
@@ -246,8 +246,8 @@ pub async fn select_workspace_member_list(
   let members = sqlx::query_as!(
     AFWorkspaceMemberRow,
     r#"
-    SELECT af_user.name, af_user.email,
-    af_workspace_member.role_id AS role
+    SELECT af_user.uuid, af_user.name, af_user.email,
+    af_workspace_member.role_id AS role, af_user.avatar_id
     FROM public.af_workspace_member
         JOIN public.af_user ON af_workspace_member.uid = af_user.uid
     WHERE af_workspace_member.workspace_id = $1
@@ -269,11 +269,12 @@ pub async fn select_workspace_member(
   let member = sqlx::query_as!(
     AFWorkspaceMemberRow,
     r#"
-    SELECT af_user.name, af_user.email, af_workspace_member.role_id AS role
+    SELECT af_user.uuid, af_user.name, af_user.email,
+    af_workspace_member.role_id AS role, af_user.avatar_id
     FROM public.af_workspace_member
       JOIN public.af_user ON af_workspace_member.uid = af_user.uid
-    WHERE af_workspace_member.workspace_id = $1 
-    AND af_workspace_member.uid = $2 
+    WHERE af_workspace_member.workspace_id = $1
+    AND af_workspace_member.uid = $2
     "#,
     workspace_id,
     uid,
@@ -378,3 +379,96 @@ pub async fn select_all_user_workspaces(
   .await?;
   Ok(workspaces)
 }
+
+/// Returns the member that takes over the workspace when its owner leaves: another owner if there
+/// is one, otherwise the member that joined first. Guests never take over a workspace.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn select_workspace_successor<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  owner_uid: &i64,
+) -> Result<Option<i64>, DatabaseError> {
+  let owner_role: i32 = AFRole::Owner.into();
+  let guest_role: i32 = AFRole::Guest.into();
+  let uid = sqlx::query_scalar::<_, i64>(
+    r#"
+    SELECT uid FROM af_workspace_member
+    WHERE workspace_id = $1 AND uid <> $2 AND role_id <> $4
+    ORDER BY (role_id = $3) DESC, created_at ASC
+    LIMIT 1
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(owner_uid)
+  .bind(owner_role)
+  .bind(guest_role)
+  .fetch_optional(executor)
+  .await?;
+  Ok(uid)
+}
+
+/// Makes the member the owner of the workspace. Pass a transaction, the ownership, the role of
+/// the member and its permission on the workspace's own collab object are updated separately.
+#[instrument(level = "trace", skip(txn), err)]
+pub async fn transfer_workspace_ownership(
+  txn: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  new_owner_uid: &i64,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    UPDATE af_workspace SET owner_uid = $2 WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(new_owner_uid)
+  .execute(txn.deref_mut())
+  .await?;
+
+  let owner_role: i32 = AFRole::Owner.into();
+  sqlx::query(
+    r#"
+    UPDATE af_workspace_member SET role_id = $3, updated_at = CURRENT_TIMESTAMP
+    WHERE workspace_id = $1 AND uid = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(new_owner_uid)
+  .bind(owner_role)
+  .execute(txn.deref_mut())
+  .await?;
+
+  // The collab permission of a member follows the role, like when the workspace is created.
+  sqlx::query(
+    r#"
+    INSERT INTO af_collab_member (uid, oid, permission_id)
+    VALUES ($2, $1::TEXT, (SELECT permission_id FROM af_role_permissions WHERE role_id = $3))
+    ON CONFLICT (uid, oid)
+    DO UPDATE SET permission_id = EXCLUDED.permission_id
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(new_owner_uid)
+  .bind(owner_role)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(())
+}
+
+/// Deletes the workspace along with its members, collabs and blob metadata. The blobs must be
+/// removed from the bucket before.
+#[instrument(level = "trace", skip(executor), err)]
+pub async fn delete_workspace<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<(), DatabaseError> {
+  sqlx::query(
+    r#"
+    DELETE FROM af_workspace WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
@@ -16,6 +16,9 @@ pub struct Identity {
 pub struct AdminListUsersResponse {
   pub users: Vec<User>,
   pub aud: String,
+  /// The number of the users that match the filter, read from the `X-Total-Count` header.
+  #[serde(default)]
+  pub total: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -71,6 +74,42 @@ pub struct Factor {
   pub factor_type: String,
 }
 
+impl Factor {
+  pub fn is_verified(&self) -> bool {
+    self.status == "verified"
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FactorType {
+  Totp,
+}
+
+/// Returned when a TOTP factor is enrolled. The factor stays unverified until the first code
+/// generated with the secret is verified.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TotpEnrollment {
+  pub id: String,
+  #[serde(rename = "type")]
+  pub factor_type: FactorType,
+  pub totp: TotpSecret,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TotpSecret {
+  /// SVG image of the QR code, encoded as a data URI.
+  pub qr_code: String,
+  pub secret: String,
+  pub uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FactorChallenge {
+  pub id: String,
+  pub expires_at: i64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AccessTokenResponse {
   pub access_token: String,
@@ -83,6 +122,29 @@ pub struct AccessTokenResponse {
   pub provider_refresh_token: Option<String>,
 }
 
+/// The kind of the one-time password sent by email, see the `/verify` endpoint of GoTrue.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OtpType {
+  Signup,
+  Invite,
+  #[serde(rename = "magiclink")]
+  MagicLink,
+  Recovery,
+  EmailChange,
+  Email,
+}
+
+/// Returned when a one-time password is verified. Changing the email with the secure email change
+/// enabled sends a code to both the current and the new address, and the first verified code only
+/// returns a message.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum VerifyResponse {
+  Authenticated(AccessTokenResponse),
+  Pending { msg: String },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GoTrueSettings {
   pub external: GoTrueOAuthProviderSettings,
@@ -129,11 +191,21 @@ pub enum OAuthProvider {
   Email,
   Phone,
   Zoom,
+  /// The SAML provider registered for the domain of the email, see `gotrue::api::Client::sso_url`.
+  Saml {
+    domain: String,
+  },
+  /// An OpenID Connect provider configured in GoTrue under the given name.
+  Oidc {
+    name: String,
+  },
 }
 
 impl OAuthProvider {
   pub fn as_str(&self) -> &str {
     match self {
+      OAuthProvider::Saml { .. } => "saml",
+      OAuthProvider::Oidc { name } => name,
       OAuthProvider::Apple => "apple",
       OAuthProvider::Azure => "azure",
       OAuthProvider::Bitbucket => "bitbucket",
@@ -192,6 +264,36 @@ impl OAuthProvider {
 pub struct OAuthURL {
   pub url: String,
 }
+
+/// A SAML identity provider. The users whose email belongs to one of the `domains` sign in
+/// through it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SsoProvider {
+  pub id: String,
+  pub saml: Option<SamlProvider>,
+  #[serde(default)]
+  pub domains: Vec<SsoDomain>,
+  pub created_at: String,
+  pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SamlProvider {
+  pub entity_id: String,
+  pub metadata_url: Option<String>,
+  pub metadata_xml: Option<String>,
+  pub attribute_mapping: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SsoDomain {
+  pub domain: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SsoProviders {
+  pub items: Vec<SsoProvider>,
+}
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum SignUpResponse {
@@ -225,4 +327,11 @@ impl UpdateGotrueUserParams {
     self.password = password.map(|v| v.to_string());
     self
   }
+
+  /// The code sent by [reauthenticate](https://github.com/supabase/gotrue#get-reauthenticate),
+  /// required to change the password when GoTrue is configured with `SECURE_PASSWORD_CHANGE`.
+  pub fn with_opt_nonce<T: ToString>(mut self, nonce: Option<T>) -> Self {
+    self.nonce = nonce.map(|v| v.to_string()).unwrap_or_default();
+    self
+  }
 }
@@ -12,10 +12,16 @@ pub struct GoTrueJWTClaims {
   pub nbf: Option<i64>,
   pub sub: Option<String>,
 
+  // The tokens of the issuers other than GoTrue may leave out the claims below.
+  #[serde(default)]
   pub email: String,
+  #[serde(default)]
   pub phone: String,
+  #[serde(default)]
   pub app_metadata: serde_json::Value,
+  #[serde(default)]
   pub user_metadata: serde_json::Value,
+  #[serde(default)]
   pub role: String,
   pub aal: Option<String>,
   pub amr: Option<Vec<Amr>>,
@@ -33,8 +39,24 @@ lazy_static::lazy_static! {
   pub static ref VALIDATION: Validation = Validation::new(Algorithm::HS256);
 }
 
+/// How strongly the user was authenticated in the session of the token. The session is upgraded
+/// to AAL2 after the user verifies a second factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AuthenticatorAssuranceLevel {
+  Aal1,
+  Aal2,
+}
+
 impl GoTrueJWTClaims {
   pub fn verify(token: &str, secret: &[u8]) -> Result<Self, jsonwebtoken::errors::Error> {
     Ok(decode(token, &DecodingKey::from_secret(secret), &VALIDATION)?.claims)
   }
+
+  /// The tokens without the `aal` claim are AAL1.
+  pub fn assurance_level(&self) -> AuthenticatorAssuranceLevel {
+    match self.aal.as_deref() {
+      Some("aal2") => AuthenticatorAssuranceLevel::Aal2,
+      _ => AuthenticatorAssuranceLevel::Aal1,
+    }
+  }
 }
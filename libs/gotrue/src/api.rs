@@ -1,11 +1,14 @@
 use super::grant::Grant;
 use crate::params::{
-  AdminDeleteUserParams, AdminUserParams, GenerateLinkParams, GenerateLinkResponse, MagicLinkParams,
+  AdminDeleteUserParams, AdminListUsersParams, AdminUserParams, CreateSsoProviderParams,
+  EnrollFactorParams, GenerateLinkParams, GenerateLinkResponse, MagicLinkParams, RecoverParams,
+  SsoParams, VerifyFactorParams, VerifyParams,
 };
 use anyhow::Context;
 use gotrue_entity::dto::{
-  AccessTokenResponse, AdminListUsersResponse, GoTrueSettings, OAuthProvider, SignUpResponse,
-  UpdateGotrueUserParams, User,
+  AccessTokenResponse, AdminListUsersResponse, FactorChallenge, GoTrueSettings, OAuthProvider,
+  OAuthURL, SignUpResponse, SsoProvider, SsoProviders, TotpEnrollment, UpdateGotrueUserParams,
+  User, VerifyResponse,
 };
 use gotrue_entity::error::{GoTrueError, OAuthError};
 use infra::reqwest::{check_response, from_body, from_response};
@@ -61,6 +64,24 @@ impl Client {
     to_gotrue_result(resp).await
   }
 
+  /// Signs up with the given user metadata, e.g. the name of the user.
+  #[tracing::instrument(skip_all, err)]
+  pub async fn sign_up_with_metadata(
+    &self,
+    email: &str,
+    password: &str,
+    user_metadata: &serde_json::Value,
+  ) -> Result<SignUpResponse, GoTrueError> {
+    let payload = serde_json::json!({
+        "email": email,
+        "password": password,
+        "data": user_metadata,
+    });
+    let url: String = format!("{}/signup", self.base_url);
+    let resp = self.client.post(&url).json(&payload).send().await?;
+    to_gotrue_result(resp).await
+  }
+
   #[tracing::instrument(skip_all, err)]
   pub async fn token(&self, grant: &Grant) -> Result<AccessTokenResponse, GoTrueError> {
     let url = format!("{}/token?grant_type={}", self.base_url, grant.type_as_str());
@@ -87,6 +108,18 @@ impl Client {
     Ok(check_response(resp).await?)
   }
 
+  /// Logs out the session of the access token, and keeps the other sessions of the user.
+  #[tracing::instrument(skip_all, err)]
+  pub async fn logout_session(&self, access_token: &str) -> Result<(), GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/logout?scope=local", self.base_url))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .send()
+      .await?;
+    Ok(check_response(resp).await?)
+  }
+
   #[tracing::instrument(skip_all, err)]
   pub async fn user_info(&self, access_token: &str) -> Result<User, GoTrueError> {
     let url = format!("{}/user", self.base_url);
@@ -115,17 +148,66 @@ impl Client {
     to_gotrue_result(resp).await
   }
 
+  /// Sends the password recovery email. GoTrue doesn't tell whether the email belongs to a user,
+  /// so the call succeeds for unknown emails too.
+  #[tracing::instrument(skip_all, err)]
+  pub async fn recover(&self, recover_params: &RecoverParams) -> Result<(), GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/recover", self.base_url))
+      .json(recover_params)
+      .send()
+      .await?;
+    check_gotrue_result(resp).await
+  }
+
+  /// Verifies the one-time password sent by email, e.g. by [Client::recover] or by changing the
+  /// email with [Client::update_user].
+  #[tracing::instrument(skip_all, err)]
+  pub async fn verify(&self, verify_params: &VerifyParams) -> Result<VerifyResponse, GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/verify", self.base_url))
+      .json(verify_params)
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  /// Sends a nonce to the email of the user, to be passed to [Client::update_user] to change the
+  /// password.
+  #[tracing::instrument(skip_all, err)]
+  pub async fn reauthenticate(&self, access_token: &str) -> Result<(), GoTrueError> {
+    let resp = self
+      .client
+      .get(format!("{}/reauthenticate", self.base_url))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .send()
+      .await?;
+    check_gotrue_result(resp).await
+  }
+
   pub async fn admin_list_user(
     &self,
     access_token: &str,
+    list_users_params: &AdminListUsersParams,
   ) -> Result<AdminListUsersResponse, GoTrueError> {
     let resp = self
       .client
       .get(format!("{}/admin/users", self.base_url))
       .header("Authorization", format!("Bearer {}", access_token))
+      .query(list_users_params)
       .send()
       .await?;
-    to_gotrue_result(resp).await
+    // GoTrue sends the number of the users that match the filter in a header.
+    let total = resp
+      .headers()
+      .get("X-Total-Count")
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse().ok());
+    let mut list_users_resp: AdminListUsersResponse = to_gotrue_result(resp).await?;
+    list_users_resp.total = total;
+    Ok(list_users_resp)
   }
 
   pub async fn admin_user_details(
@@ -218,6 +300,126 @@ impl Client {
       .await?;
     check_gotrue_result(resp).await
   }
+
+  /// Returns the url that signs the user in with the SAML provider. `skip_http_redirect` must be
+  /// set, otherwise GoTrue redirects to the url instead of returning it.
+  #[tracing::instrument(skip_all, err)]
+  pub async fn sso_url(&self, sso_params: &SsoParams) -> Result<OAuthURL, GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/sso", self.base_url))
+      .json(sso_params)
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  pub async fn admin_create_sso_provider(
+    &self,
+    access_token: &str,
+    create_sso_provider_params: &CreateSsoProviderParams,
+  ) -> Result<SsoProvider, GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/admin/sso/providers", self.base_url))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .json(create_sso_provider_params)
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  pub async fn admin_list_sso_providers(
+    &self,
+    access_token: &str,
+  ) -> Result<SsoProviders, GoTrueError> {
+    let resp = self
+      .client
+      .get(format!("{}/admin/sso/providers", self.base_url))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  pub async fn admin_delete_sso_provider(
+    &self,
+    access_token: &str,
+    provider_id: &str,
+  ) -> Result<SsoProvider, GoTrueError> {
+    let resp = self
+      .client
+      .delete(format!(
+        "{}/admin/sso/providers/{}",
+        self.base_url, provider_id
+      ))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  pub async fn enroll_factor(
+    &self,
+    access_token: &str,
+    enroll_factor_params: &EnrollFactorParams,
+  ) -> Result<TotpEnrollment, GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/factors", self.base_url))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .json(&enroll_factor_params)
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  pub async fn challenge_factor(
+    &self,
+    access_token: &str,
+    factor_id: &str,
+  ) -> Result<FactorChallenge, GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/factors/{}/challenge", self.base_url, factor_id))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  /// Verifies the code of the challenge. The returned token is issued for the same session, with
+  /// the `aal2` assurance level. The first successful verification also verifies the factor.
+  pub async fn verify_factor(
+    &self,
+    access_token: &str,
+    factor_id: &str,
+    verify_factor_params: &VerifyFactorParams,
+  ) -> Result<AccessTokenResponse, GoTrueError> {
+    let resp = self
+      .client
+      .post(format!("{}/factors/{}/verify", self.base_url, factor_id))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .json(&verify_factor_params)
+      .send()
+      .await?;
+    to_gotrue_result(resp).await
+  }
+
+  /// Removing a verified factor requires an `aal2` token.
+  pub async fn unenroll_factor(
+    &self,
+    access_token: &str,
+    factor_id: &str,
+  ) -> Result<(), GoTrueError> {
+    let resp = self
+      .client
+      .delete(format!("{}/factors/{}", self.base_url, factor_id))
+      .header("Authorization", format!("Bearer {}", access_token))
+      .send()
+      .await?;
+    check_gotrue_result(resp).await
+  }
 }
 
 async fn to_gotrue_result<T>(resp: reqwest::Response) -> Result<T, GoTrueError>
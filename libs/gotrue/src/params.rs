@@ -1,6 +1,6 @@
 use std::collections::btree_map::BTreeMap;
 
-use gotrue_entity::dto::{Factor, Identity};
+use gotrue_entity::dto::{Factor, FactorType, Identity, OtpType};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -105,3 +105,85 @@ pub struct GenerateLinkResponse {
   pub verification_type: String,
   pub redirect_to: String,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EnrollFactorParams {
+  pub factor_type: FactorType,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub friendly_name: Option<String>,
+  /// Shown by the authenticator app next to the account. GoTrue uses its site url if not set.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub issuer: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VerifyFactorParams {
+  pub challenge_id: String,
+  pub code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RecoverParams {
+  pub email: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VerifyParams {
+  #[serde(rename = "type")]
+  pub type_: OtpType,
+  pub email: String,
+  pub token: String,
+}
+
+/// Registers a SAML identity provider, GoTrue doesn't support the other types. Either the
+/// `metadata_url` or the `metadata_xml` of the provider is required.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateSsoProviderParams {
+  #[serde(rename = "type")]
+  pub type_: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata_url: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata_xml: Option<String>,
+  pub domains: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub attribute_mapping: Option<serde_json::Value>,
+}
+
+impl CreateSsoProviderParams {
+  pub fn saml(domains: Vec<String>) -> Self {
+    Self {
+      type_: "saml".to_string(),
+      metadata_url: None,
+      metadata_xml: None,
+      domains,
+      attribute_mapping: None,
+    }
+  }
+}
+
+/// Either the `provider_id` or the `domain` of the SAML provider is required.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SsoParams {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub provider_id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub domain: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub redirect_to: Option<String>,
+  /// Returns the url instead of redirecting to it.
+  pub skip_http_redirect: bool,
+}
+
+/// Lists all the users if the `page` is None.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct AdminListUsersParams {
+  /// Starts from 1.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub page: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub per_page: Option<u32>,
+  /// Only returns the users whose email or name contains the filter.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub filter: Option<String>,
+}
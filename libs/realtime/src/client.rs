@@ -1,4 +1,6 @@
-use crate::entities::{ClientMessage, Connect, Disconnect, RealtimeMessage, RealtimeUser};
+use crate::entities::{
+  ClientMessage, CloseSession, Connect, Disconnect, RealtimeMessage, RealtimeUser,
+};
 use std::fmt::{Display, Formatter};
 
 use actix::{
@@ -11,6 +13,7 @@ use std::ops::Deref;
 
 use crate::collaborate::{CollabAccessControl, CollabServer};
 use crate::error::RealtimeError;
+use crate::util::rate_limit::TokenBucket;
 
 use actix_web_actors::ws::ProtocolError;
 use database::collab::CollabStorage;
@@ -18,6 +21,9 @@ use realtime_entity::collab_msg::CollabMessage;
 use std::time::{Duration, Instant};
 use tracing::{error, warn};
 
+/// The connection is closed once this many messages in a row were dropped by the rate limit.
+const MAX_DROPPED_MESSAGES: u32 = 100;
+
 pub struct ClientSession<
   U: Unpin + RealtimeUser,
   S: Unpin + 'static,
@@ -28,6 +34,8 @@ pub struct ClientSession<
   pub server: Addr<CollabServer<S, U, P>>,
   heartbeat_interval: Duration,
   client_timeout: Duration,
+  message_limit: Option<TokenBucket>,
+  dropped_messages: u32,
 }
 
 impl<U, S, P> ClientSession<U, S, P>
@@ -48,7 +56,42 @@ where
       server,
       heartbeat_interval,
       client_timeout,
+      message_limit: None,
+      dropped_messages: 0,
+    }
+  }
+
+  /// Limits the rate of the binary messages sent by the client. The messages over the limit are
+  /// dropped, and the connection is closed if the client keeps sending them.
+  pub fn with_message_rate_limit(mut self, capacity: u32, refill_per_sec: f64) -> Self {
+    self.message_limit = Some(TokenBucket::new(capacity, refill_per_sec));
+    self
+  }
+
+  /// Returns false if the message should be dropped.
+  fn check_message_rate(&mut self, ctx: &mut ws::WebsocketContext<Self>) -> bool {
+    let allowed = self
+      .message_limit
+      .as_mut()
+      .is_none_or(|limit| limit.try_acquire());
+    if allowed {
+      self.dropped_messages = 0;
+      return true;
+    }
+
+    self.dropped_messages += 1;
+    if self.dropped_messages == 1 {
+      warn!("{} exceeds the message rate limit", self.user);
+    }
+    if self.dropped_messages >= MAX_DROPPED_MESSAGES {
+      warn!("close the connection of {}: too many messages", self.user);
+      ctx.close(Some(ws::CloseReason {
+        code: ws::CloseCode::Policy,
+        description: Some("too many messages".to_string()),
+      }));
+      ctx.stop();
     }
+    false
   }
 
   fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
@@ -146,6 +189,23 @@ where
   }
 }
 
+impl<U, S, P> Handler<CloseSession> for ClientSession<U, S, P>
+where
+  U: Unpin + RealtimeUser,
+  S: Unpin + CollabStorage,
+  P: CollabAccessControl + Unpin,
+{
+  type Result = ();
+
+  fn handle(&mut self, msg: CloseSession, ctx: &mut Self::Context) {
+    ctx.close(Some(ws::CloseReason {
+      code: ws::CloseCode::Policy,
+      description: Some(msg.reason),
+    }));
+    ctx.stop();
+  }
+}
+
 /// WebSocket message handler
 impl<U, S, P> StreamHandler<Result<ws::Message, ws::ProtocolError>> for ClientSession<U, S, P>
 where
@@ -173,7 +233,9 @@ where
       ws::Message::Pong(_) => self.hb = Instant::now(),
       ws::Message::Text(_) => {},
       ws::Message::Binary(bytes) => {
-        let _ = self.forward_binary(bytes);
+        if self.check_message_rate(ctx) {
+          let _ = self.forward_binary(bytes);
+        }
       },
       ws::Message::Close(reason) => {
         ctx.close(reason);
@@ -57,6 +57,11 @@ pub trait CollabAccessControl: Sync + Send + 'static {
   ///
   /// The user can recv the message if the user is the member of the collab object
   async fn can_receive_collab_update(&self, uid: &i64, oid: &str) -> Result<bool, Self::Error>;
+
+  /// Return the uid that is used to load and save the collab object when the user opens it.
+  /// It's the uid of the user itself, except for the anonymous viewers of a share link, whose
+  /// collab objects are read on behalf of the user that created the link.
+  async fn get_storage_uid(&self, uid: &i64, oid: &str) -> Result<i64, Self::Error>;
 }
 //
 #[async_trait]
@@ -93,4 +98,8 @@ where
   async fn can_receive_collab_update(&self, uid: &i64, oid: &str) -> Result<bool, Self::Error> {
     self.as_ref().can_receive_collab_update(uid, oid).await
   }
+
+  async fn get_storage_uid(&self, uid: &i64, oid: &str) -> Result<i64, Self::Error> {
+    self.as_ref().get_storage_uid(uid, oid).await
+  }
 }
@@ -69,10 +69,20 @@ where
         // When create a group, the message must be the init sync message.
         match &self.client_msg.content {
           CollabMessage::ClientInit(client_init) => {
-            let uid = client_init
-              .origin
-              .client_user_id()
-              .ok_or(RealtimeError::UnexpectedData("The client user id is empty"))?;
+            let client_uid = self.client_msg.user.uid();
+            let uid = self
+              .access_control
+              .get_storage_uid(&client_uid, object_id)
+              .await
+              .map_err(|err| {
+                trace!(
+                  "user:{} can't open object:{}: {}",
+                  client_uid,
+                  object_id,
+                  err
+                );
+                RealtimeError::NotEnoughPermissionToRead(client_uid)
+              })?;
 
             self
               .groups
@@ -38,6 +38,13 @@ pub struct Disconnect<U> {
   pub user: U,
 }
 
+/// Closes the websocket connection of the client, e.g. after its device is signed out.
+#[derive(Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct CloseSession {
+  pub reason: String,
+}
+
 #[derive(Debug, Clone, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum BusinessID {
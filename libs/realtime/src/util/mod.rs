@@ -1 +1,2 @@
 pub mod channel_ext;
+pub mod rate_limit;
@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+/// A token bucket that is owned by a single connection, so it doesn't need to be shared.
+pub(crate) struct TokenBucket {
+  capacity: f64,
+  refill_per_sec: f64,
+  tokens: f64,
+  last_refill: Instant,
+}
+
+impl TokenBucket {
+  pub(crate) fn new(capacity: u32, refill_per_sec: f64) -> Self {
+    Self {
+      capacity: capacity as f64,
+      refill_per_sec: refill_per_sec.max(0.0),
+      tokens: capacity as f64,
+      last_refill: Instant::now(),
+    }
+  }
+
+  /// Takes a token from the bucket. Returns false if the bucket is empty.
+  pub(crate) fn try_acquire(&mut self) -> bool {
+    self.try_acquire_at(Instant::now())
+  }
+
+  fn try_acquire_at(&mut self, now: Instant) -> bool {
+    let elapsed = now
+      .saturating_duration_since(self.last_refill)
+      .as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+    self.last_refill = now;
+
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::TokenBucket;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn token_bucket_refill_test() {
+    let mut bucket = TokenBucket::new(2, 1.0);
+    let now = Instant::now();
+    assert!(bucket.try_acquire_at(now));
+    assert!(bucket.try_acquire_at(now));
+    assert!(!bucket.try_acquire_at(now));
+
+    assert!(bucket.try_acquire_at(now + Duration::from_secs(1)));
+    assert!(!bucket.try_acquire_at(now + Duration::from_secs(1)));
+
+    // The bucket never holds more than its capacity.
+    let later = now + Duration::from_secs(60);
+    assert!(bucket.try_acquire_at(later));
+    assert!(bucket.try_acquire_at(later));
+    assert!(!bucket.try_acquire_at(later));
+  }
+}
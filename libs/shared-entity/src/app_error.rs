@@ -64,12 +64,15 @@ impl From<DatabaseError> for AppError {
   fn from(value: DatabaseError) -> Self {
     match value {
       DatabaseError::RecordNotFound(msg) => AppError::new(ErrorCode::RecordNotFound, msg),
+      DatabaseError::RecordAlreadyExists(msg) => AppError::new(ErrorCode::RecordAlreadyExists, msg),
       DatabaseError::UnexpectedData(msg) => {
         AppError::new(ErrorCode::InvalidRequestParams, msg.to_string())
       },
       DatabaseError::NotEnoughPermissions(msg) => {
         AppError::new(ErrorCode::NotEnoughPermissions, msg)
       },
+      DatabaseError::InvalidParams(msg) => AppError::new(ErrorCode::InvalidRequestParams, msg),
+      DatabaseError::FileTypeNotAllowed(msg) => AppError::new(ErrorCode::FileTypeNotAllowed, msg),
       DatabaseError::StorageSpaceNotEnough => {
         AppError::new(ErrorCode::StorageSpaceNotEnough, value)
       },
@@ -133,6 +133,19 @@ where
 {
   pub async fn from_response(resp: reqwest::Response) -> Result<Self, anyhow::Error> {
     let status_code = resp.status();
+    if status_code == reqwest::StatusCode::TOO_MANY_REQUESTS {
+      let retry_after = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(1);
+      return Err(anyhow::Error::new(AppError::new(
+        ErrorCode::TooManyRequests,
+        format!("too many requests, retry after {} seconds", retry_after),
+      )));
+    }
+
     if !status_code.is_success() {
       let body = resp.text().await?;
       anyhow::bail!("got error code: {}, body: {}", status_code, body)
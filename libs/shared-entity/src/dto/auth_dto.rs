@@ -1,6 +1,6 @@
 // Data Transfer Objects (DTO)
 
-use gotrue_entity::dto::AccessTokenResponse;
+use gotrue_entity::dto::{AccessTokenResponse, OAuthProvider, OtpType, User};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -65,3 +65,87 @@ pub struct SignInPasswordResponse {
 pub struct SignInTokenResponse {
   pub is_new: bool,
 }
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct PasswordRecoveryParams {
+  pub email: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct VerifyOtpParams {
+  pub otp_type: OtpType,
+  pub email: String,
+  pub token: String,
+}
+
+/// The `access_token_resp` is None if the code was verified but doesn't sign the user in yet,
+/// e.g. when the code sent to the other address of an email change still needs to be verified.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct VerifyOtpResponse {
+  pub access_token_resp: Option<AccessTokenResponse>,
+  pub is_new: bool,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ChangePasswordParams {
+  pub password: String,
+  /// The code sent by the reauthentication, required if GoTrue is configured to require it.
+  pub nonce: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ChangeEmailParams {
+  pub email: String,
+}
+
+/// The SSO provider that the users of an email domain sign in with.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SsoSignInProvider {
+  Saml { domain: String },
+  Oidc { name: String },
+}
+
+impl From<SsoSignInProvider> for OAuthProvider {
+  fn from(value: SsoSignInProvider) -> Self {
+    match value {
+      SsoSignInProvider::Saml { domain } => OAuthProvider::Saml { domain },
+      SsoSignInProvider::Oidc { name } => OAuthProvider::Oidc { name },
+    }
+  }
+}
+
+pub const DEFAULT_ADMIN_USER_PAGE_SIZE: u32 = 20;
+pub const MAX_ADMIN_USER_PAGE_SIZE: u32 = 100;
+
+/// Parameters to list the users as the admin.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+pub struct AdminListUsersQuery {
+  /// Starts from 1.
+  pub page: Option<u32>,
+  /// The number of users per page, at most [MAX_ADMIN_USER_PAGE_SIZE].
+  pub per_page: Option<u32>,
+  /// Only returns the users whose email or name contains the filter.
+  pub filter: Option<String>,
+}
+
+/// The GoTrue user along with what AppFlowy Cloud knows about the user.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct AdminUser {
+  pub user: User,
+  /// None if the user has never signed in to AppFlowy Cloud.
+  pub uid: Option<i64>,
+  pub name: Option<String>,
+  pub workspace_count: i64,
+  /// The size, in bytes, of the blobs in the workspaces owned by the user.
+  pub storage_usage: i64,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct AdminUserPage {
+  pub items: Vec<AdminUser>,
+  pub page: u32,
+  pub per_page: u32,
+  /// The number of users that match the filter.
+  pub total: u64,
+}
@@ -1,7 +1,9 @@
-use database_entity::dto::AFRole;
+use chrono::{DateTime, Utc};
+use database_entity::dto::{AFAuditAction, AFFileTypeUsage, AFRole};
 use database_entity::pg_row::AFBlobMetadataRow;
 use serde::{Deserialize, Serialize};
 use std::ops::Deref;
+use uuid::Uuid;
 
 #[derive(Deserialize, Serialize)]
 pub struct WorkspaceMembers(pub Vec<WorkspaceMember>);
@@ -34,6 +36,17 @@ pub struct CreateWorkspaceMember {
   pub role: AFRole,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct AcceptWorkspaceInvite {
+  pub workspace_id: Uuid,
+  pub token: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RevokeWorkspaceInvite {
+  pub token: String,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct WorkspaceMemberChangeset {
   pub email: String,
@@ -67,3 +80,53 @@ pub struct WorkspaceSpaceUsage {
 
 #[derive(Serialize, Deserialize)]
 pub struct WorkspaceBlobMetadata(pub Vec<AFBlobMetadataRow>);
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkspaceBlobMetadataPage {
+  pub items: Vec<AFBlobMetadataRow>,
+  /// Pass it as the cursor of the next query to get the next page. None if this is the last page.
+  pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkspaceUsageByFileType(pub Vec<AFFileTypeUsage>);
+
+/// The result of collecting the unreferenced blobs of a workspace. When produced by a dry run,
+/// the report describes what would happen without changing anything.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlobGCReport {
+  pub workspace_id: Uuid,
+  /// Blobs that became unreferenced since the last run and start their grace period.
+  pub marked: Vec<String>,
+  /// Blobs that were referenced again and are no longer scheduled for deletion.
+  pub unmarked: Vec<String>,
+  /// Blobs that stayed unreferenced for longer than the grace period and are deleted.
+  pub deleted: Vec<String>,
+  /// The total size, in bytes, of the deleted blobs.
+  pub reclaimed_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlobGCReports(pub Vec<BlobGCReport>);
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditLogExportFormat {
+  #[default]
+  Json,
+  Csv,
+}
+
+/// Parameters to export the audit log. All the entries that match the filters are exported,
+/// newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportAuditLogParams {
+  /// If not set, the entries of all workspaces are exported.
+  pub workspace_id: Option<Uuid>,
+  #[serde(default)]
+  pub format: AuditLogExportFormat,
+  pub action: Option<AFAuditAction>,
+  pub actor_uid: Option<i64>,
+  pub since: Option<DateTime<Utc>>,
+  pub until: Option<DateTime<Utc>>,
+}
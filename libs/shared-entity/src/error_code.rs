@@ -69,6 +69,27 @@ pub enum ErrorCode {
 
   #[error("io error")]
   IO = 1017,
+
+  #[error("File type not allowed")]
+  FileTypeNotAllowed = 1018,
+
+  #[error("Too many requests")]
+  TooManyRequests = 1019,
+
+  #[error("Two-factor authentication required")]
+  MfaRequired = 1020,
+
+  #[error("The user is banned")]
+  UserBanned = 1021,
+
+  #[error("The token is expired")]
+  TokenExpired = 1022,
+
+  #[error("The token is issued for another purpose")]
+  TokenPurposeMismatch = 1023,
+
+  #[error("The token is revoked")]
+  TokenRevoked = 1024,
 }
 
 /// Implements conversion from `anyhow::Error` to `ErrorCode`.
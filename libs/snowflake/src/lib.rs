@@ -1,4 +1,4 @@
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 const EPOCH: u64 = 1637806706000;
 const NODE_ID_BITS: u64 = 10;
@@ -6,32 +6,94 @@ const SEQUENCE_BITS: u64 = 12;
 const NODE_ID_SHIFT: u64 = SEQUENCE_BITS;
 const TIMESTAMP_SHIFT: u64 = NODE_ID_BITS + SEQUENCE_BITS;
 const SEQUENCE_MASK: u64 = (1 << SEQUENCE_BITS) - 1;
+const NODE_ID_MASK: u64 = (1 << NODE_ID_BITS) - 1;
+
+/// The node ids go from 0 to [MAX_NODE_ID]. Every generator that runs at the same time must use a
+/// different node id, otherwise they generate the same ids.
+pub const MAX_NODE_ID: u64 = NODE_ID_MASK;
+
+/// The callers can wait for the clock and try again when it moves backwards by up to this many
+/// milliseconds, e.g. after a small NTP adjustment.
+pub const MAX_CLOCK_BACKWARDS_MILLIS: u64 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnowflakeError {
+  /// No id is generated until the clock catches up with the last id, otherwise the ids could
+  /// repeat.
+  #[error("Clock moved backwards by {0}ms")]
+  ClockMovedBackwards(u64),
+
+  #[error("Clock is set before the epoch of the ids")]
+  ClockBeforeEpoch,
+
+  /// The lease of the node id expired, so another generator may be using the node id.
+  #[error("Lease of the node id expired")]
+  LeaseExpired,
+}
 
 pub struct Snowflake {
   node_id: u64,
   sequence: u64,
   last_timestamp: u64,
+  /// The node id can only be used until then, if the node id is leased.
+  lease_deadline: Option<Instant>,
 }
 
 impl Snowflake {
   pub fn new(node_id: u64) -> Snowflake {
+    assert!(node_id <= MAX_NODE_ID, "invalid node id: {}", node_id);
     Snowflake {
       node_id,
       sequence: 0,
       last_timestamp: 0,
+      lease_deadline: None,
     }
   }
 
-  pub fn next_id(&mut self) -> i64 {
-    let timestamp = self.timestamp();
+  /// Creates the generator with a leased node id, see [Snowflake::set_lease].
+  pub fn with_lease(node_id: u64, lease_deadline: Instant) -> Snowflake {
+    let mut snowflake = Snowflake::new(node_id);
+    snowflake.lease_deadline = Some(lease_deadline);
+    snowflake
+  }
+
+  pub fn node_id(&self) -> u64 {
+    self.node_id
+  }
+
+  /// Sets the leased node id, e.g. after the lease is renewed or a new node id is leased. The
+  /// [Snowflake::next_id] fails with [SnowflakeError::LeaseExpired] after the `lease_deadline`.
+  pub fn set_lease(&mut self, node_id: u64, lease_deadline: Instant) {
+    assert!(node_id <= MAX_NODE_ID, "invalid node id: {}", node_id);
+    self.node_id = node_id;
+    self.lease_deadline = Some(lease_deadline);
+  }
+
+  /// Stops generating the ids with the node id, e.g. after the lease is lost.
+  pub fn end_lease(&mut self) {
+    self.lease_deadline = Some(Instant::now());
+  }
+
+  pub fn next_id(&mut self) -> Result<i64, SnowflakeError> {
+    if let Some(lease_deadline) = self.lease_deadline {
+      if Instant::now() >= lease_deadline {
+        return Err(SnowflakeError::LeaseExpired);
+      }
+    }
+    let mut timestamp = current_millis();
+    if timestamp < EPOCH {
+      return Err(SnowflakeError::ClockBeforeEpoch);
+    }
     if timestamp < self.last_timestamp {
-      panic!("Clock moved backwards!");
+      return Err(SnowflakeError::ClockMovedBackwards(
+        self.last_timestamp - timestamp,
+      ));
     }
 
     if timestamp == self.last_timestamp {
       self.sequence = (self.sequence + 1) & SEQUENCE_MASK;
       if self.sequence == 0 {
-        self.wait_next_millis();
+        timestamp = self.wait_next_millis();
       }
     } else {
       self.sequence = 0;
@@ -39,34 +101,111 @@ impl Snowflake {
 
     self.last_timestamp = timestamp;
     let id = (timestamp - EPOCH) << TIMESTAMP_SHIFT | self.node_id << NODE_ID_SHIFT | self.sequence;
-    id as i64
+    Ok(id as i64)
   }
 
-  fn wait_next_millis(&self) {
-    let mut timestamp = self.timestamp();
-    while timestamp == self.last_timestamp {
-      timestamp = self.timestamp();
+  /// Returns the first timestamp after the last one.
+  fn wait_next_millis(&self) -> u64 {
+    let mut timestamp = current_millis();
+    while timestamp <= self.last_timestamp {
+      timestamp = current_millis();
     }
+    timestamp
   }
+}
 
-  fn timestamp(&self) -> u64 {
-    SystemTime::now()
-      .duration_since(SystemTime::UNIX_EPOCH)
-      .expect("Clock moved backwards!")
-      .as_millis() as u64
+fn current_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map(|duration| duration.as_millis() as u64)
+    // The clock is set before the unix epoch, which is caught as [SnowflakeError::ClockBeforeEpoch].
+    .unwrap_or(0)
+}
+
+/// The parts of an id generated by [Snowflake::next_id].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SnowflakeId {
+  /// Milliseconds since the unix epoch.
+  pub timestamp: u64,
+  pub node_id: u64,
+  pub sequence: u64,
+}
+
+impl SnowflakeId {
+  pub fn time(&self) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(self.timestamp)
+  }
+}
+
+/// Extracts the time and the node id of the id, e.g. to find out which server generated it.
+pub fn decode(id: i64) -> SnowflakeId {
+  let id = id as u64;
+  SnowflakeId {
+    timestamp: (id >> TIMESTAMP_SHIFT) + EPOCH,
+    node_id: (id >> NODE_ID_SHIFT) & NODE_ID_MASK,
+    sequence: id & SEQUENCE_MASK,
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::Snowflake;
+  use crate::{decode, Snowflake, SnowflakeError};
+  use std::time::{Duration, Instant};
 
   #[test]
   fn gen_id() {
     let mut snow_flake = Snowflake::new(1);
-    let id_1 = snow_flake.next_id();
-    let id_2 = snow_flake.next_id();
+    let id_1 = snow_flake.next_id().unwrap();
+    let id_2 = snow_flake.next_id().unwrap();
 
     assert_ne!(id_1, id_2);
   }
+
+  #[test]
+  fn gen_unique_ids_after_sequence_overflow() {
+    let mut snow_flake = Snowflake::new(1);
+    let mut ids = (0..10_000)
+      .map(|_| snow_flake.next_id().unwrap())
+      .collect::<Vec<_>>();
+    ids.dedup();
+    assert_eq!(ids.len(), 10_000);
+  }
+
+  #[test]
+  fn decode_id() {
+    let mut snow_flake = Snowflake::new(42);
+    let id = snow_flake.next_id().unwrap();
+    let decoded = decode(id);
+    assert_eq!(decoded.node_id, 42);
+    assert_eq!(decoded.timestamp, snow_flake.last_timestamp);
+    assert_eq!(decoded.sequence, 0);
+  }
+
+  #[test]
+  fn error_on_clock_regression() {
+    let mut snow_flake = Snowflake::new(1);
+    snow_flake.next_id().unwrap();
+    // Pretends the clock moved back by 60s since the last id.
+    snow_flake.last_timestamp += 60_000;
+    assert!(matches!(
+      snow_flake.next_id(),
+      Err(SnowflakeError::ClockMovedBackwards(backwards)) if backwards > 59_000
+    ));
+  }
+
+  #[test]
+  fn error_after_lease_expired() {
+    let mut snow_flake = Snowflake::with_lease(1, Instant::now() + Duration::from_secs(60));
+    snow_flake.next_id().unwrap();
+
+    snow_flake.end_lease();
+    assert!(matches!(
+      snow_flake.next_id(),
+      Err(SnowflakeError::LeaseExpired)
+    ));
+
+    snow_flake.set_lease(2, Instant::now() + Duration::from_secs(60));
+    let id = snow_flake.next_id().unwrap();
+    assert_eq!(decode(id).node_id, 2);
+  }
 }
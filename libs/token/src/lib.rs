@@ -1,9 +1,10 @@
 use chrono::{DateTime, Duration, Utc};
 use hmac::digest::KeyInit;
 use hmac::Hmac;
-use jwt::{SignWithKey, VerifyWithKey};
+use jwt::{AlgorithmType, Header, SignWithKey, Token, Unverified, VerifyWithKey};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TokenError {
@@ -12,11 +13,45 @@ pub enum TokenError {
 
   #[error("Token expired")]
   Expired,
+
+  /// The token was issued for another [TokenType] or audience.
+  #[error("Token is issued for {token_type:?}:{audience} instead")]
+  WrongPurpose {
+    token_type: TokenType,
+    audience: String,
+  },
+
+  /// The single-use token was already used, or the token was revoked before it expired.
+  #[error("Token revoked")]
+  Revoked,
+
+  /// The key that signed the token isn't one of the active keys, e.g. after it was rotated out.
+  #[error("Token is signed with an unknown key")]
+  UnknownKey,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+/// The purpose of the token. A token is only accepted for the purpose it's issued for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum TokenType {
   AccessToken,
+  /// Verifies the email of the user invited to a workspace.
+  WorkspaceInviteEmail,
+  /// Downloads a blob without signing in.
+  BlobDownload,
+  /// Opens the shared object without signing in. The share links of the collabs don't use it,
+  /// they're stored in `af_collab_share_link`, so they can be listed and revoked one by one.
+  ShareLink,
+}
+
+impl TokenType {
+  /// The single-use tokens are rejected with [TokenError::Revoked] once they're used. The crate
+  /// doesn't keep track of the used tokens, that's up to the caller, see [ParsedToken::id].
+  pub fn is_single_use(&self) -> bool {
+    match self {
+      TokenType::AccessToken | TokenType::ShareLink => false,
+      TokenType::WorkspaceInviteEmail | TokenType::BlobDownload => true,
+    }
+  }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,35 +59,155 @@ struct TokenFields<T> {
   #[serde(rename = "d")]
   data: T,
 
+  #[serde(rename = "typ")]
+  token_type: TokenType,
+
+  #[serde(rename = "aud")]
+  audience: String,
+
+  #[serde(rename = "jti")]
+  id: String,
+
   #[serde(rename = "exp")]
   expire_at: DateTime<Utc>,
 }
 
+/// The token that passed the checks of [TokenKeys::parse_token].
+#[derive(Debug)]
+pub struct ParsedToken<T> {
+  pub data: T,
+  /// Unique to each token, used to track the single-use and the revoked tokens.
+  pub id: String,
+  pub token_type: TokenType,
+  pub expire_at: DateTime<Utc>,
+}
+
+/// A `server_key` used to sign the tokens. The id of the key is derived from the key, and is
+/// written in the header of the token, so the token is verified with the key that signed it.
+#[derive(Clone)]
+pub struct TokenKey {
+  id: String,
+  hmac: Hmac<Sha256>,
+}
+
+impl TokenKey {
+  pub fn new(server_key: &str) -> Self {
+    let digest = Sha256::digest(server_key.as_bytes());
+    let id = digest[..8]
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect::<String>();
+    Self {
+      id,
+      hmac: generate_hmac_key(server_key),
+    }
+  }
+
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+}
+
+/// The active `server_key`s. The tokens are signed with the current key, and verified with any of
+/// the keys, so a key can be rotated without invalidating the tokens that were signed with the
+/// previous key. The previous key is removed once all its tokens are expired.
+#[derive(Clone)]
+pub struct TokenKeys {
+  current: TokenKey,
+  previous: Vec<TokenKey>,
+}
+
+impl TokenKeys {
+  pub fn new(current: &str, previous: &[&str]) -> Self {
+    Self {
+      current: TokenKey::new(current),
+      previous: previous.iter().map(|key| TokenKey::new(key)).collect(),
+    }
+  }
+
+  /// Creates a token that is only accepted for the `token_type` and the `audience`, e.g. the
+  /// id of the workspace or the blob the token gives access to.
+  pub fn create_token(
+    &self,
+    token_type: TokenType,
+    audience: &str,
+    data: impl Serialize,
+    expire_duration: Duration,
+  ) -> Result<String, TokenError> {
+    let header = Header {
+      algorithm: AlgorithmType::Hs256,
+      key_id: Some(self.current.id.clone()),
+      ..Default::default()
+    };
+    let fields = TokenFields {
+      data,
+      token_type,
+      audience: audience.to_string(),
+      id: Uuid::new_v4().to_string(),
+      expire_at: Utc::now() + expire_duration,
+    };
+    let token = Token::new(header, fields).sign_with_key(&self.current.hmac)?;
+    Ok(token.as_str().to_string())
+  }
+
+  /// Verifies the signature and the expiry of the token, and that it's issued for the
+  /// `token_type` and the `audience`.
+  pub fn parse_token<T: DeserializeOwned>(
+    &self,
+    token: &str,
+    token_type: TokenType,
+    audience: &str,
+  ) -> Result<ParsedToken<T>, TokenError> {
+    let unverified: Token<Header, TokenFields<T>, Unverified<'_>> = Token::parse_unverified(token)?;
+    let key = unverified
+      .header()
+      .key_id
+      .as_deref()
+      .and_then(|key_id| self.key(key_id))
+      .ok_or(TokenError::UnknownKey)?;
+    let (_, fields): (Header, TokenFields<T>) = unverified.verify_with_key(&key.hmac)?.into();
+
+    if fields.expire_at < Utc::now() {
+      return Err(TokenError::Expired);
+    }
+    if fields.token_type != token_type || fields.audience != audience {
+      return Err(TokenError::WrongPurpose {
+        token_type: fields.token_type,
+        audience: fields.audience,
+      });
+    }
+    Ok(ParsedToken {
+      data: fields.data,
+      id: fields.id,
+      token_type: fields.token_type,
+      expire_at: fields.expire_at,
+    })
+  }
+
+  fn key(&self, key_id: &str) -> Option<&TokenKey> {
+    std::iter::once(&self.current)
+      .chain(self.previous.iter())
+      .find(|key| key.id == key_id)
+  }
+}
+
+/// Creates a [TokenType::AccessToken] signed with the `server_key`.
 pub fn create_token(
   server_key: &str,
   data: impl Serialize,
   expire_duration: Duration,
 ) -> Result<String, TokenError> {
-  Ok(
-    TokenFields {
-      data,
-      expire_at: Utc::now() + expire_duration,
-    }
-    .sign_with_key(&generate_hmac_key(server_key))?,
-  )
+  TokenKeys::new(server_key, &[]).create_token(TokenType::AccessToken, "", data, expire_duration)
 }
 
 fn generate_hmac_key(server_key: &str) -> Hmac<Sha256> {
   Hmac::<Sha256>::new_from_slice(server_key.as_bytes()).expect("invalid server key")
 }
 
+/// Parses the [TokenType::AccessToken] created by [create_token].
 pub fn parse_token<T: DeserializeOwned>(server_key: &str, token: &str) -> Result<T, TokenError> {
-  let fields =
-    VerifyWithKey::<TokenFields<T>>::verify_with_key(token, &generate_hmac_key(server_key))?;
-  if fields.expire_at < Utc::now() {
-    return Err(TokenError::Expired);
-  }
-  Ok(fields.data)
+  let token = TokenKeys::new(server_key, &[]).parse_token(token, TokenType::AccessToken, "")?;
+  Ok(token.data)
 }
 
 #[cfg(test)]
@@ -75,4 +230,58 @@ mod tests {
     let token = create_token(server_key, "hello", Duration::days(2)).unwrap();
     let _ = parse_token::<String>("abcdef", &token).unwrap();
   }
+
+  #[test]
+  fn parse_expired_token() {
+    let keys = TokenKeys::new("server_key", &[]);
+    let token = keys
+      .create_token(
+        TokenType::BlobDownload,
+        "blob",
+        "hello",
+        Duration::seconds(-1),
+      )
+      .unwrap();
+    let err = keys
+      .parse_token::<String>(&token, TokenType::BlobDownload, "blob")
+      .unwrap_err();
+    assert!(matches!(err, TokenError::Expired));
+  }
+
+  #[test]
+  fn parse_token_with_wrong_purpose() {
+    let keys = TokenKeys::new("server_key", &[]);
+    let token = keys
+      .create_token(TokenType::ShareLink, "object", "hello", Duration::days(2))
+      .unwrap();
+
+    let err = keys
+      .parse_token::<String>(&token, TokenType::BlobDownload, "object")
+      .unwrap_err();
+    assert!(matches!(err, TokenError::WrongPurpose { .. }));
+    let err = keys
+      .parse_token::<String>(&token, TokenType::ShareLink, "other object")
+      .unwrap_err();
+    assert!(matches!(err, TokenError::WrongPurpose { .. }));
+  }
+
+  #[test]
+  fn parse_token_after_key_rotation() {
+    let old_keys = TokenKeys::new("old_key", &[]);
+    let token = old_keys
+      .create_token(TokenType::ShareLink, "object", "hello", Duration::days(2))
+      .unwrap();
+
+    let rotated_keys = TokenKeys::new("new_key", &["old_key"]);
+    let parsed = rotated_keys
+      .parse_token::<String>(&token, TokenType::ShareLink, "object")
+      .unwrap();
+    assert_eq!(parsed.data, "hello");
+
+    let new_keys = TokenKeys::new("new_key", &[]);
+    let err = new_keys
+      .parse_token::<String>(&token, TokenType::ShareLink, "object")
+      .unwrap_err();
+    assert!(matches!(err, TokenError::UnknownKey));
+  }
 }
@@ -0,0 +1,13 @@
+-- Downscaled previews generated for image blobs. Each row points at the key of the thumbnail
+-- object in the bucket and is removed together with the original blob's metadata.
+CREATE TABLE IF NOT EXISTS af_blob_thumbnail (
+    workspace_id UUID NOT NULL,
+    file_id VARCHAR NOT NULL,
+    size INTEGER NOT NULL,
+    thumbnail_id VARCHAR NOT NULL,
+    file_type VARCHAR NOT NULL,
+    file_size BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (workspace_id, file_id, size),
+    FOREIGN KEY (workspace_id, file_id) REFERENCES af_blob_metadata(workspace_id, file_id) ON DELETE CASCADE
+);
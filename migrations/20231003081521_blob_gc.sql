@@ -0,0 +1,9 @@
+-- Blobs that are no longer referenced by any collab in their workspace. The garbage collector
+-- deletes a blob once it has stayed unreferenced for longer than the grace period.
+CREATE TABLE IF NOT EXISTS af_blob_gc_mark (
+    workspace_id UUID NOT NULL,
+    file_id VARCHAR NOT NULL,
+    marked_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (workspace_id, file_id),
+    FOREIGN KEY (workspace_id, file_id) REFERENCES af_blob_metadata(workspace_id, file_id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Controls which types of blobs can be uploaded to a workspace. Workspaces without a row use the
+-- server's default policy, which denies executables.
+CREATE TABLE IF NOT EXISTS af_workspace_file_type_policy (
+    workspace_id UUID PRIMARY KEY REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+    allowed TEXT[] NOT NULL DEFAULT '{}',
+    denied TEXT[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,17 @@
+-- Long-lived personal access tokens used by bots and integrations. Only the sha256 hash of the
+-- token is stored, the plaintext is returned to the user once when the token is created.
+CREATE TABLE IF NOT EXISTS af_api_token (
+    id UUID PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+    -- If not null, the token can only access this workspace.
+    workspace_id UUID REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- 1: read only, 2: collab write, 3: admin
+    scope INT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX IF NOT EXISTS idx_af_api_token_uid ON af_api_token(uid);
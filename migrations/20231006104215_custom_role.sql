@@ -0,0 +1,18 @@
+-- Workspaces can define custom roles on top of the built-in Owner, Member and Guest roles. The
+-- built-in roles have a null workspace_id. The permissions of a role are stored in
+-- af_role_permissions.
+ALTER TABLE af_roles
+ADD COLUMN IF NOT EXISTS workspace_id UUID REFERENCES af_workspace(workspace_id) ON DELETE CASCADE;
+ALTER TABLE af_roles DROP CONSTRAINT IF EXISTS af_roles_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_af_roles_builtin_name ON af_roles(name)
+WHERE workspace_id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_af_roles_workspace_name ON af_roles(workspace_id, name)
+WHERE workspace_id IS NOT NULL;
+-- The built-in roles are looked up by name, so custom roles can't reuse their names.
+ALTER TABLE af_roles
+ADD CONSTRAINT af_roles_custom_name_check CHECK (
+        workspace_id IS NULL
+        OR name NOT IN ('Owner', 'Member', 'Guest')
+    );
+ALTER TABLE af_role_permissions DROP CONSTRAINT IF EXISTS af_role_permissions_role_id_fkey,
+    ADD CONSTRAINT af_role_permissions_role_id_fkey FOREIGN KEY (role_id) REFERENCES af_roles(id) ON DELETE CASCADE;
@@ -0,0 +1,15 @@
+-- Records who did what to a workspace. The table has no foreign keys on purpose: the entries
+-- must outlive the users and workspaces they refer to.
+CREATE TABLE IF NOT EXISTS af_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    -- Null when the action was done by the server itself, e.g. the blob garbage collector.
+    actor_uid BIGINT,
+    workspace_id UUID,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    ip TEXT,
+    user_agent TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_af_audit_log_workspace_id ON af_audit_log(workspace_id, id);
+CREATE INDEX IF NOT EXISTS idx_af_audit_log_created_at ON af_audit_log(created_at);
@@ -0,0 +1,18 @@
+-- Links that publish a collab object to anyone who knows the token. Only the sha256 hash of the
+-- token is stored, the plaintext is returned to the owner once when the link is created.
+CREATE TABLE IF NOT EXISTS af_collab_share_link (
+    id UUID PRIMARY KEY,
+    oid TEXT NOT NULL,
+    workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+    -- The value of the CollabType of the object
+    collab_type INT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- 10: read only, 20: read and comment
+    access_level INT NOT NULL CHECK (access_level IN (10, 20)),
+    -- The argon2 hash of the password, if the link is protected by a password
+    password_hash TEXT,
+    created_by BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX IF NOT EXISTS idx_af_collab_share_link_oid ON af_collab_share_link(oid);
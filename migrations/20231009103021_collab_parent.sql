@@ -0,0 +1,37 @@
+-- The parent of a collab object, e.g. the database of a database row or the document that a
+-- document is nested in. The members of the parent inherit their access level on the children,
+-- unless they are members of the child itself.
+CREATE TABLE IF NOT EXISTS af_collab_parent (
+    oid TEXT PRIMARY KEY,
+    parent_oid TEXT NOT NULL,
+    workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CHECK (oid <> parent_oid)
+);
+CREATE INDEX IF NOT EXISTS idx_af_collab_parent_parent_oid ON af_collab_parent(parent_oid);
+
+-- Listener
+DROP TRIGGER IF EXISTS af_collab_parent_change_trigger ON af_collab_parent;
+
+CREATE OR REPLACE FUNCTION notify_af_collab_parent_change() RETURNS trigger AS $$
+DECLARE
+payload TEXT;
+BEGIN
+    payload := json_build_object(
+            'old', row_to_json(OLD),
+            'new', row_to_json(NEW),
+            'action_type', TG_OP
+            )::text;
+
+    PERFORM pg_notify('af_collab_parent_channel', payload);
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+ELSE
+        RETURN NEW;
+END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER af_collab_parent_change_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON af_collab_parent
+    FOR EACH ROW EXECUTE FUNCTION notify_af_collab_parent_change();
@@ -0,0 +1,33 @@
+-- The devices that the users signed in on. A device is recorded, or refreshed, when it opens its
+-- websocket connection. Signing a device out keeps the row, so the device can't reconnect with
+-- the access token of the signed out session.
+CREATE TABLE IF NOT EXISTS af_user_session (
+    uid BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+    device_id TEXT NOT NULL,
+    -- The id of the GoTrue session that the device signed in with.
+    session_id UUID,
+    client_version TEXT,
+    ip TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_seen_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    signed_out_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (uid, device_id)
+);
+
+-- Listener. Only the sign out is notified, so every server can close the websocket connection
+-- of the device.
+DROP TRIGGER IF EXISTS af_user_session_sign_out_trigger ON af_user_session;
+
+CREATE OR REPLACE FUNCTION notify_af_user_session_sign_out() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('af_user_session_channel', row_to_json(NEW)::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER af_user_session_sign_out_trigger
+    AFTER UPDATE OF signed_out_at ON af_user_session
+    FOR EACH ROW
+    WHEN (OLD.signed_out_at IS NULL AND NEW.signed_out_at IS NOT NULL)
+    EXECUTE FUNCTION notify_af_user_session_sign_out();
@@ -0,0 +1,9 @@
+-- Accounts that their users asked to delete. The account is deleted once scheduled_at is
+-- reached, unless the user cancels the deletion before.
+CREATE TABLE IF NOT EXISTS af_user_deletion (
+    uid BIGINT PRIMARY KEY REFERENCES af_user(uid) ON DELETE CASCADE,
+    uuid UUID NOT NULL,
+    requested_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    scheduled_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_af_user_deletion_scheduled_at ON af_user_deletion(scheduled_at);
@@ -0,0 +1,18 @@
+-- The archives of the personal data that the users asked to export. The archive is stored in
+-- the bucket under archive_key until expires_at.
+CREATE TABLE IF NOT EXISTS af_user_data_export (
+    export_id UUID PRIMARY KEY,
+    uid BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+    -- pending, completed, failed or expired
+    status TEXT NOT NULL DEFAULT 'pending',
+    archive_key TEXT,
+    file_size BIGINT,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX IF NOT EXISTS idx_af_user_data_export_uid ON af_user_data_export(uid, created_at);
+-- A user can only have one export in progress.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_af_user_data_export_pending ON af_user_data_export(uid)
+    WHERE status = 'pending';
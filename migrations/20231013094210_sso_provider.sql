@@ -0,0 +1,13 @@
+-- The SSO providers registered for the email domains. The users whose email belongs to the
+-- domain and who sign in through the provider are added to the workspace.
+CREATE TABLE IF NOT EXISTS af_sso_provider (
+    provider_id UUID PRIMARY KEY,
+    domain TEXT NOT NULL UNIQUE,
+    workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+    -- saml or oidc
+    provider_type TEXT NOT NULL,
+    -- The id of the SAML provider in GoTrue, or the name of the OIDC provider configured in GoTrue.
+    gotrue_provider TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_af_sso_provider_workspace_id ON af_sso_provider(workspace_id);
@@ -0,0 +1,45 @@
+-- The users banned by the admin. The ban is also set in GoTrue, which stops the user from signing
+-- in or refreshing the token, while the servers reject the access tokens issued before the ban.
+CREATE TABLE IF NOT EXISTS af_user_ban (
+    -- The id of the GoTrue user, which is the sub of the access token.
+    uuid UUID PRIMARY KEY,
+    banned_until TIMESTAMP WITH TIME ZONE NOT NULL,
+    reason TEXT,
+    banned_by UUID,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- Listener. Every server keeps the ban list in memory, and closes the websocket connections of
+-- the banned user. The banned_until is null when the user is unbanned.
+DROP TRIGGER IF EXISTS af_user_ban_change_trigger ON af_user_ban;
+
+CREATE OR REPLACE FUNCTION notify_af_user_ban_change() RETURNS trigger AS $$
+DECLARE
+    payload TEXT;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        payload := json_build_object(
+                'uuid', OLD.uuid,
+                'uid', (SELECT uid FROM af_user WHERE uuid = OLD.uuid),
+                'banned_until', NULL
+                )::text;
+    ELSE
+        payload := json_build_object(
+                'uuid', NEW.uuid,
+                'uid', (SELECT uid FROM af_user WHERE uuid = NEW.uuid),
+                'banned_until', NEW.banned_until
+                )::text;
+    END IF;
+
+    PERFORM pg_notify('af_user_ban_channel', payload);
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    ELSE
+        RETURN NEW;
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER af_user_ban_change_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON af_user_ban
+    FOR EACH ROW EXECUTE FUNCTION notify_af_user_ban_change();
@@ -0,0 +1,9 @@
+-- The node ids of the snowflake id generators. Every server leases a node id at startup and renews
+-- the lease while it's running, so the servers never generate ids with the same node id.
+CREATE TABLE IF NOT EXISTS af_snowflake_node (
+    node_id INTEGER PRIMARY KEY,
+    -- Random for every start of a server, so the lease isn't renewed by another server.
+    instance_id UUID NOT NULL,
+    -- The node id is free again once the lease expires.
+    leased_until TIMESTAMP WITH TIME ZONE NOT NULL
+);
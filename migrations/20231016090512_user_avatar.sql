@@ -0,0 +1,20 @@
+-- The id of the avatar uploaded by the user. The avatar is stored in the bucket under the uuid of
+-- the user, and the id changes on every upload.
+ALTER TABLE af_user ADD COLUMN IF NOT EXISTS avatar_id UUID;
+
+-- The columns of the view are fixed when it's created, so it's created again to add the avatar_id.
+DROP VIEW IF EXISTS af_user_profile_view;
+CREATE VIEW af_user_profile_view AS
+SELECT u.*,
+    w.workspace_id AS latest_workspace_id
+FROM af_user u
+    INNER JOIN (
+        SELECT uid,
+            workspace_id,
+            rank() OVER (
+                PARTITION BY uid
+                ORDER BY updated_at DESC
+            ) AS rn
+        FROM af_workspace_member
+    ) w ON u.uid = w.uid
+    AND w.rn = 1;
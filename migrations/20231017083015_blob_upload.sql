@@ -0,0 +1,14 @@
+-- Uploads issued with a pre-signed url. The blob is uploaded to a staging object of the upload,
+-- and only stored under the key of its content once the server verified the hash of the content.
+-- The pending uploads count towards the usage of the workspace until they expire.
+CREATE TABLE IF NOT EXISTS af_blob_upload (
+    upload_id UUID PRIMARY KEY,
+    workspace_id UUID REFERENCES af_workspace(workspace_id) ON DELETE CASCADE NOT NULL,
+    file_id VARCHAR NOT NULL,
+    file_type VARCHAR NOT NULL,
+    file_size BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_af_blob_upload_workspace_id ON af_blob_upload(workspace_id);
+CREATE INDEX IF NOT EXISTS idx_af_blob_upload_expires_at ON af_blob_upload(expires_at);
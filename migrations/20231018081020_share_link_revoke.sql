@@ -0,0 +1,14 @@
+-- Listener. Every server closes the websocket connections of the anonymous viewers of a share
+-- link once the link is deleted, including when its workspace or its creator is deleted.
+DROP TRIGGER IF EXISTS af_collab_share_link_delete_trigger ON af_collab_share_link;
+
+CREATE OR REPLACE FUNCTION notify_af_collab_share_link_delete() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('af_collab_share_link_channel', json_build_object('id', OLD.id)::text);
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER af_collab_share_link_delete_trigger
+    AFTER DELETE ON af_collab_share_link
+    FOR EACH ROW EXECUTE FUNCTION notify_af_collab_share_link_delete();
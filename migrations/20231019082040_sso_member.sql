@@ -0,0 +1,10 @@
+-- The users who were provisioned in the workspace of a SSO provider. A user is only added to the
+-- workspace on the first sign-in through the provider, so the members who were removed from the
+-- workspace aren't added back on their next sign-in.
+CREATE TABLE IF NOT EXISTS af_sso_member (
+    provider_id UUID NOT NULL REFERENCES af_sso_provider(provider_id) ON DELETE CASCADE,
+    uid BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (provider_id, uid)
+);
+CREATE INDEX IF NOT EXISTS idx_af_sso_member_uid ON af_sso_member(uid);
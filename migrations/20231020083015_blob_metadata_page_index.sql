@@ -0,0 +1,8 @@
+-- The blob metadata of a workspace is listed page by page, ordered by the modification time or
+-- the size, with the file id as the tie-breaker. The indexes match the order and the cursor of
+-- the pages, so a page doesn't sort all the blobs of the workspace. They're scanned backwards for
+-- the descending order.
+CREATE INDEX IF NOT EXISTS idx_af_blob_metadata_workspace_modified_at
+    ON af_blob_metadata (workspace_id, modified_at, file_id);
+CREATE INDEX IF NOT EXISTS idx_af_blob_metadata_workspace_file_size
+    ON af_blob_metadata (workspace_id, file_size, file_id);
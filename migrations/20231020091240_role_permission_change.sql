@@ -0,0 +1,18 @@
+-- Listener. Every server caches the access level of each role, which is invalidated once the
+-- permissions of the role change, including when the role is deleted.
+DROP TRIGGER IF EXISTS af_role_permissions_change_trigger ON af_role_permissions;
+
+CREATE OR REPLACE FUNCTION notify_af_role_permissions_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('af_role_permissions_channel', json_build_object('role_id', OLD.role_id)::text);
+        RETURN OLD;
+    END IF;
+    PERFORM pg_notify('af_role_permissions_channel', json_build_object('role_id', NEW.role_id)::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER af_role_permissions_change_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON af_role_permissions
+    FOR EACH ROW EXECUTE FUNCTION notify_af_role_permissions_change();
@@ -0,0 +1,265 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self, Data, Json};
+use actix_web::{HttpResponse, Result, Scope};
+use database::job_lock::JobLock;
+use database_entity::dto::{
+  AFSsoProvider, AFSsoProviders, AFUserBan, BanUserParams, CreateSsoProviderParams,
+};
+use serde::Deserialize;
+use shared_entity::app_error::AppError;
+use shared_entity::data::{AppResponse, JsonAppResponse};
+use shared_entity::dto::auth_dto::{AdminListUsersQuery, AdminUserPage};
+use shared_entity::dto::workspace_dto::{
+  AuditLogExportFormat, BlobGCReports, ExportAuditLogParams,
+};
+use shared_entity::error_code::ErrorCode;
+use sqlx::types::Uuid;
+use tracing::instrument;
+
+use crate::biz::audit::export_audit_log;
+use crate::biz::file_storage::gc::{collect_all_workspaces, collect_workspace_blobs, BLOB_GC_JOB};
+use crate::biz::sso::{create_sso_provider, delete_sso_provider, get_sso_providers};
+use crate::biz::user::admin_list_users;
+use crate::biz::user_ban::{ban_user, unban_user};
+use crate::biz::user_deletion::{delete_due_users, USER_DELETION_JOB};
+use crate::component::audit::RequestActor;
+use crate::component::auth::jwt::Authorization;
+use crate::state::AppState;
+
+pub fn admin_scope() -> Scope {
+  web::scope("/api/admin")
+    .service(web::resource("/blob_gc").route(web::post().to(run_blob_gc_handler)))
+    .service(web::resource("/blob_gc/report").route(web::get().to(blob_gc_report_handler)))
+    .service(web::resource("/user_deletion").route(web::post().to(run_user_deletion_handler)))
+    .service(web::resource("/audit_log/export").route(web::get().to(export_audit_log_handler)))
+    .service(web::resource("/user").route(web::get().to(list_users_handler)))
+    .service(
+      web::resource("/user/{user_uuid}/ban")
+        .route(web::post().to(ban_user_handler))
+        .route(web::delete().to(unban_user_handler)),
+    )
+    .service(
+      web::resource("/sso/provider")
+        .route(web::get().to(list_sso_providers_handler))
+        .route(web::post().to(create_sso_provider_handler)),
+    )
+    .service(
+      web::resource("/sso/provider/{provider_id}")
+        .route(web::delete().to(delete_sso_provider_handler)),
+    )
+}
+
+fn require_admin(auth: &Authorization) -> Result<(), AppError> {
+  if auth.is_admin() {
+    Ok(())
+  } else {
+    Err(AppError::new(
+      ErrorCode::NotEnoughPermissions,
+      "only the admin can access this resource",
+    ))
+  }
+}
+
+#[derive(Deserialize, Debug)]
+struct BlobGCReportQuery {
+  workspace_id: Option<Uuid>,
+}
+
+/// Returns what the blob garbage collector would mark and delete, without changing anything.
+#[instrument(skip(state, auth), err)]
+async fn blob_gc_report_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  query: web::Query<BlobGCReportQuery>,
+) -> Result<JsonAppResponse<BlobGCReports>> {
+  require_admin(&auth)?;
+  let setting = &state.config.blob_gc;
+  let reports = match query.into_inner().workspace_id {
+    None => collect_all_workspaces(&state.pg_pool, &state.bucket_storage, setting, true).await?,
+    Some(workspace_id) => vec![
+      collect_workspace_blobs(
+        &state.pg_pool,
+        &state.bucket_storage,
+        setting,
+        &workspace_id,
+        true,
+      )
+      .await?,
+    ],
+  };
+  Ok(AppResponse::Ok().with_data(BlobGCReports(reports)).into())
+}
+
+/// Runs the blob garbage collector now, instead of waiting for the next scheduled run. Fails if
+/// the garbage collector is already running on any server.
+#[instrument(skip(state, auth), err)]
+async fn run_blob_gc_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  query: web::Query<BlobGCReportQuery>,
+) -> Result<JsonAppResponse<BlobGCReports>> {
+  require_admin(&auth)?;
+  let lock = JobLock::try_acquire(&state.pg_pool, BLOB_GC_JOB)
+    .await?
+    .ok_or_else(|| AppError::new(ErrorCode::TooManyRequests, "the blob gc is already running"))?;
+  let setting = &state.config.blob_gc;
+  let result = match query.into_inner().workspace_id {
+    None => collect_all_workspaces(&state.pg_pool, &state.bucket_storage, setting, false).await,
+    Some(workspace_id) => collect_workspace_blobs(
+      &state.pg_pool,
+      &state.bucket_storage,
+      setting,
+      &workspace_id,
+      false,
+    )
+    .await
+    .map(|report| vec![report]),
+  };
+  lock.release().await?;
+  Ok(AppResponse::Ok().with_data(BlobGCReports(result?)).into())
+}
+
+/// Deletes the accounts whose grace period is over now, instead of waiting for the next scheduled
+/// run. Fails if the accounts are already being deleted on any server.
+#[instrument(skip(state, auth), err)]
+async fn run_user_deletion_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  require_admin(&auth)?;
+  let lock = JobLock::try_acquire(&state.pg_pool, USER_DELETION_JOB)
+    .await?
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::TooManyRequests,
+        "the user deletion is already running",
+      )
+    })?;
+  let result = delete_due_users(&state).await;
+  lock.release().await?;
+  result?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Downloads the audit log as a json array or a csv file.
+#[instrument(skip(state, auth), err)]
+async fn export_audit_log_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  query: web::Query<ExportAuditLogParams>,
+) -> Result<HttpResponse> {
+  require_admin(&auth)?;
+  let params = query.into_inner();
+  let (content_type, extension) = match params.format {
+    AuditLogExportFormat::Json => ("application/json", "json"),
+    AuditLogExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+  };
+  let disposition = ContentDisposition {
+    disposition: DispositionType::Attachment,
+    parameters: vec![DispositionParam::Filename(format!(
+      "audit_log.{}",
+      extension
+    ))],
+  };
+  Ok(
+    HttpResponse::Ok()
+      .content_type(content_type)
+      .insert_header(disposition)
+      .streaming(export_audit_log(state.pg_pool.clone(), params)),
+  )
+}
+
+/// Lists the users page by page. The users can be filtered by their email or name.
+#[instrument(skip(state, auth), err)]
+async fn list_users_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  query: web::Query<AdminListUsersQuery>,
+) -> Result<JsonAppResponse<AdminUserPage>> {
+  require_admin(&auth)?;
+  let page = admin_list_users(
+    &state.pg_pool,
+    &state.gotrue_client,
+    &auth.token,
+    query.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(page).into())
+}
+
+/// Bans the user. The user is signed out of the websocket connections right away, and the
+/// requests of the user are rejected until the ban expires.
+#[instrument(skip(state, auth, actor), err)]
+async fn ban_user_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  user_uuid: web::Path<Uuid>,
+  payload: Json<BanUserParams>,
+) -> Result<JsonAppResponse<AFUserBan>> {
+  require_admin(&auth)?;
+  let ban = ban_user(&state, &actor, &user_uuid, payload.into_inner()).await?;
+  Ok(AppResponse::Ok().with_data(ban).into())
+}
+
+#[instrument(skip(state, auth, actor), err)]
+async fn unban_user_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  user_uuid: web::Path<Uuid>,
+) -> Result<JsonAppResponse<()>> {
+  require_admin(&auth)?;
+  unban_user(&state, &actor, &user_uuid).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[instrument(skip(state, auth), err)]
+async fn list_sso_providers_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFSsoProviders>> {
+  require_admin(&auth)?;
+  let providers = get_sso_providers(&state.pg_pool).await?;
+  Ok(AppResponse::Ok().with_data(providers).into())
+}
+
+/// Registers the SSO provider of an email domain. The users of the domain who sign in through the
+/// provider join the workspace of the provider.
+#[instrument(skip(state, auth, actor), err)]
+async fn create_sso_provider_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  payload: Json<CreateSsoProviderParams>,
+) -> Result<JsonAppResponse<AFSsoProvider>> {
+  require_admin(&auth)?;
+  let provider = create_sso_provider(
+    &state.pg_pool,
+    &state.gotrue_client,
+    &state.config.gotrue,
+    &actor,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(provider).into())
+}
+
+#[instrument(skip(state, auth, actor), err)]
+async fn delete_sso_provider_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  provider_id: web::Path<Uuid>,
+) -> Result<JsonAppResponse<()>> {
+  require_admin(&auth)?;
+  delete_sso_provider(
+    &state.pg_pool,
+    &state.gotrue_client,
+    &state.config.gotrue,
+    &actor,
+    &provider_id,
+  )
+  .await?;
+  Ok(AppResponse::Ok().into())
+}
@@ -1,7 +1,7 @@
 use actix_http::body::BoxBody;
 use actix_web::http::header::{
-  ContentLength, ContentType, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
-  LAST_MODIFIED,
+  ContentDisposition, ContentLength, ContentType, DispositionType, CACHE_CONTROL, CONTENT_LENGTH,
+  CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, LAST_MODIFIED, X_CONTENT_TYPE_OPTIONS,
 };
 use actix_web::web::{Json, Payload};
 use actix_web::{
@@ -9,28 +9,47 @@ use actix_web::{
   HttpRequest, Scope,
 };
 use actix_web::{HttpResponse, Result};
-use chrono::DateTime;
-use database::file::{MAX_BLOB_SIZE, MAX_USAGE};
-use database::resource_usage::{get_all_workspace_blob_metadata, get_workspace_usage_size};
-use database_entity::dto::AFBlobRecord;
+use chrono::{DateTime, Duration, Utc};
+use database::file::{is_inline_safe_file_type, MAX_BLOB_SIZE, MAX_USAGE};
+use database::resource_usage::{
+  get_all_workspace_blob_metadata, get_workspace_usage_size, select_workspace_blob_metadata_page,
+  select_workspace_usage_by_file_type,
+};
+use database_entity::dto::{
+  AFBlobDownloadLink, AFBlobRecord, AFFileTypePolicy, AFPresignedDownload, AFPresignedUpload,
+  CompletePresignedUploadParams, PresignedUploadParams, QueryBlobMetadataParams,
+};
 use database_entity::pg_row::AFBlobMetadataRow;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use shared_entity::app_error::AppError;
 use shared_entity::data::{AppResponse, JsonAppResponse};
-use shared_entity::dto::workspace_dto::{WorkspaceBlobMetadata, WorkspaceSpaceUsage};
+use shared_entity::dto::workspace_dto::{
+  WorkspaceBlobMetadata, WorkspaceBlobMetadataPage, WorkspaceSpaceUsage, WorkspaceUsageByFileType,
+};
 use shared_entity::error_code::ErrorCode;
 use sqlx::types::Uuid;
 use std::pin::Pin;
+use token::TokenType;
 use tokio::io::AsyncRead;
 use tokio_stream::StreamExt;
 use tokio_util::io::StreamReader;
 use tracing::{event, instrument};
 use tracing_actix_web::RequestId;
+use validator::Validate;
 
+use crate::component::audit::RequestActor;
+use crate::component::auth::signed_token::{
+  create_signed_token, parse_signed_token, use_signed_token,
+};
 use crate::state::AppState;
 
 pub fn file_storage_scope() -> Scope {
   web::scope("/api/file_storage")
+    // Anonymous, the token is the authorization. It's registered first, so the path isn't taken
+    // for a workspace id by the routes below.
+    .service(
+      web::resource("/download/{file_id:.*}").route(web::get().to(download_blob_with_link_handler)),
+    )
     .service(web::resource("/{workspace_id}/blob").route(web::put().to(put_blob_handler)))
     .service(
       web::resource("/{workspace_id}/blob/{file_id:.*}")
@@ -44,18 +63,67 @@ pub fn file_storage_scope() -> Scope {
     .service(
       web::resource("/{workspace_id}/usage").route(web::get().to(get_workspace_usage_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/usage/file_types")
+        .route(web::get().to(get_workspace_usage_by_file_type_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/blobs")
         .route(web::get().to(get_all_workspace_blob_metadata_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/blobs/page")
+        .route(web::get().to(get_workspace_blob_metadata_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/file_type_policy")
+        .route(web::get().to(get_file_type_policy_handler))
+        .route(web::put().to(update_file_type_policy_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/presigned/upload")
+        .route(web::post().to(presign_upload_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/presigned/complete")
+        .route(web::post().to(complete_presigned_upload_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/presigned/download/{file_id:.*}")
+        .route(web::get().to(presign_download_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/download_link/{file_id:.*}")
+        .route(web::post().to(create_blob_download_link_handler)),
+    )
 }
 
+/// The download links expire after 1 hour.
+const BLOB_DOWNLOAD_LINK_EXPIRES_SECS: i64 = 3600;
+
 #[derive(Deserialize, Debug)]
 struct PathInfo {
   workspace_id: Uuid,
   file_id: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+struct BlobDownloadLinkData {
+  workspace_id: Uuid,
+}
+
+#[derive(Deserialize)]
+struct BlobDownloadLinkQuery {
+  token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct BlobQuery {
+  /// When set, the smallest thumbnail of the image that is at least `size` pixels is returned
+  /// instead of the original blob. The original is returned if no such thumbnail exists.
+  size: Option<u32>,
+}
+
 #[instrument(skip(state, payload), err)]
 async fn put_blob_handler(
   state: Data<AppState>,
@@ -99,6 +167,7 @@ async fn put_blob_handler(
 
 #[instrument(level = "debug", skip(state), err)]
 async fn delete_blob_handler(
+  actor: RequestActor,
   state: Data<AppState>,
   path: web::Path<PathInfo>,
 ) -> Result<JsonAppResponse<()>> {
@@ -109,7 +178,7 @@ async fn delete_blob_handler(
 
   state
     .bucket_storage
-    .delete_blob(&workspace_id, &file_id)
+    .delete_blob(&actor, &workspace_id, &file_id)
     .await
     .map_err(AppError::from)?;
   Ok(AppResponse::Ok().into())
@@ -119,6 +188,7 @@ async fn delete_blob_handler(
 async fn get_blob_handler(
   state: Data<AppState>,
   path: web::Path<PathInfo>,
+  query: web::Query<BlobQuery>,
   request_id: RequestId,
   req: HttpRequest,
 ) -> Result<HttpResponse<BoxBody>> {
@@ -126,6 +196,7 @@ async fn get_blob_handler(
     workspace_id,
     file_id,
   } = path.into_inner();
+  let BlobQuery { size } = query.into_inner();
 
   // Get the metadata
   let result = state
@@ -153,21 +224,130 @@ async fn get_blob_handler(
       return Ok(HttpResponse::NotModified().finish());
     }
   }
+
+  let thumbnail = match size {
+    None => None,
+    Some(size) => state
+      .bucket_storage
+      .get_blob_thumbnail(&workspace_id, &file_id, size)
+      .await
+      .map_err(AppError::from)?,
+  };
+  let (object_id, file_type) = match thumbnail {
+    None => (file_id, metadata.file_type),
+    Some(thumbnail) => (thumbnail.thumbnail_id, thumbnail.file_type),
+  };
+
   let blob = state
     .bucket_storage
-    .get_blob(&file_id)
+    .get_blob(&object_id)
     .await
     .map_err(AppError::from)?;
 
-  let response = HttpResponse::Ok()
-    .append_header((ETAG, file_id))
-    .append_header((CONTENT_TYPE, metadata.file_type))
-    .append_header((LAST_MODIFIED, metadata.modified_at.to_rfc2822()))
+  // 31536000 seconds = 1 year
+  Ok(blob_response(
+    object_id,
+    file_type,
+    metadata.modified_at,
+    blob,
+    "public, immutable, max-age=31536000",
+  ))
+}
+
+fn blob_response(
+  object_id: String,
+  file_type: String,
+  modified_at: DateTime<Utc>,
+  blob: Vec<u8>,
+  cache_control: &'static str,
+) -> HttpResponse<BoxBody> {
+  let mut response = HttpResponse::Ok();
+  // Types that the browser could execute, such as html or svg, are downloaded instead of rendered.
+  if !is_inline_safe_file_type(&file_type) {
+    response
+      .append_header(ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![],
+      })
+      .append_header((X_CONTENT_TYPE_OPTIONS, "nosniff"));
+  }
+  response
+    .append_header((ETAG, object_id))
+    .append_header((CONTENT_TYPE, file_type))
+    .append_header((LAST_MODIFIED, modified_at.to_rfc2822()))
     .append_header((CONTENT_LENGTH, blob.len()))
-    .append_header((CACHE_CONTROL, "public, immutable, max-age=31536000"))// 31536000 seconds = 1 year
-    .body(blob);
+    .append_header((CACHE_CONTROL, cache_control))
+    .body(blob)
+}
+
+/// Creates a link that downloads the blob once without signing in, e.g. to open the file in
+/// another application. The link expires after [BLOB_DOWNLOAD_LINK_EXPIRES_SECS].
+#[instrument(level = "debug", skip(state), err)]
+async fn create_blob_download_link_handler(
+  state: Data<AppState>,
+  path: web::Path<PathInfo>,
+) -> Result<JsonAppResponse<AFBlobDownloadLink>> {
+  let PathInfo {
+    workspace_id,
+    file_id,
+  } = path.into_inner();
+  // Fails with RecordNotFound if the blob doesn't exist.
+  state
+    .bucket_storage
+    .get_blob_metadata(&workspace_id, &file_id)
+    .await
+    .map_err(AppError::from)?;
+
+  let expire_duration = Duration::seconds(BLOB_DOWNLOAD_LINK_EXPIRES_SECS);
+  let token = create_signed_token(
+    &state,
+    TokenType::BlobDownload,
+    &file_id,
+    BlobDownloadLinkData { workspace_id },
+    expire_duration,
+  )?;
+  let link = AFBlobDownloadLink {
+    token,
+    expires_at: Utc::now() + expire_duration,
+  };
+  Ok(AppResponse::Ok().with_data(link).into())
+}
 
-  Ok(response)
+/// Downloads the blob with the token of [create_blob_download_link_handler]. The token is only
+/// accepted once, and only for the blob it's created for.
+#[instrument(skip(state, query), err)]
+async fn download_blob_with_link_handler(
+  state: Data<AppState>,
+  file_id: web::Path<String>,
+  query: web::Query<BlobDownloadLinkQuery>,
+) -> Result<HttpResponse<BoxBody>> {
+  let file_id = file_id.into_inner();
+  let token = parse_signed_token::<BlobDownloadLinkData>(
+    &state,
+    &query.token,
+    TokenType::BlobDownload,
+    &file_id,
+  )
+  .await?;
+  let metadata = state
+    .bucket_storage
+    .get_blob_metadata(&token.data.workspace_id, &file_id)
+    .await
+    .map_err(AppError::from)?;
+  let blob = state
+    .bucket_storage
+    .get_blob(&file_id)
+    .await
+    .map_err(AppError::from)?;
+  // The link can be used again if the blob couldn't be fetched.
+  use_signed_token(&state, &token).await?;
+  Ok(blob_response(
+    file_id,
+    metadata.file_type,
+    metadata.modified_at,
+    blob,
+    "private, no-store",
+  ))
 }
 
 #[instrument(skip(state), err)]
@@ -205,7 +385,8 @@ async fn get_workspace_usage_handler(
   Ok(AppResponse::Ok().with_data(usage).into())
 }
 
-// TODO(nathan): implement pagination
+/// Returns the metadata of all blobs of the workspace. Use [get_workspace_blob_metadata_handler]
+/// to fetch it one page at a time.
 #[instrument(level = "debug", skip(state), err)]
 async fn get_all_workspace_blob_metadata_handler(
   state: Data<AppState>,
@@ -220,6 +401,128 @@ async fn get_all_workspace_blob_metadata_handler(
       .into(),
   )
 }
+
+#[instrument(level = "debug", skip(state), err)]
+async fn get_workspace_blob_metadata_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+  query: web::Query<QueryBlobMetadataParams>,
+) -> Result<JsonAppResponse<WorkspaceBlobMetadataPage>> {
+  let (items, next_cursor) =
+    select_workspace_blob_metadata_page(&state.pg_pool, &workspace_id, &query)
+      .await
+      .map_err(AppError::from)?;
+  Ok(
+    AppResponse::Ok()
+      .with_data(WorkspaceBlobMetadataPage { items, next_cursor })
+      .into(),
+  )
+}
+
+#[instrument(level = "debug", skip(state), err)]
+async fn get_workspace_usage_by_file_type_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+) -> Result<JsonAppResponse<WorkspaceUsageByFileType>> {
+  let usage = select_workspace_usage_by_file_type(&state.pg_pool, &workspace_id)
+    .await
+    .map_err(AppError::from)?;
+  Ok(
+    AppResponse::Ok()
+      .with_data(WorkspaceUsageByFileType(usage))
+      .into(),
+  )
+}
+
+#[instrument(level = "debug", skip(state), err)]
+async fn get_file_type_policy_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+) -> Result<JsonAppResponse<AFFileTypePolicy>> {
+  let policy = state
+    .bucket_storage
+    .get_file_type_policy(&workspace_id)
+    .await
+    .map_err(AppError::from)?;
+  Ok(AppResponse::Ok().with_data(policy).into())
+}
+
+#[instrument(level = "debug", skip(state), err)]
+async fn update_file_type_policy_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+  policy: Json<AFFileTypePolicy>,
+) -> Result<JsonAppResponse<()>> {
+  state
+    .bucket_storage
+    .update_file_type_policy(&workspace_id, &policy)
+    .await
+    .map_err(AppError::from)?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Returns a pre-signed url that the client uses to upload the blob directly to the bucket.
+/// [AFPresignedUpload::Unsupported] is returned when the bucket can't pre-sign urls, in which case
+/// the client falls back to [put_blob_handler].
+#[instrument(level = "debug", skip(state), err)]
+async fn presign_upload_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+  params: Json<PresignedUploadParams>,
+) -> Result<JsonAppResponse<AFPresignedUpload>> {
+  let params = params.into_inner();
+  params.validate().map_err(AppError::from)?;
+  let upload = state
+    .bucket_storage
+    .presign_put_blob(
+      &workspace_id,
+      &params.file_id,
+      &params.file_type,
+      params.file_size,
+    )
+    .await
+    .map_err(AppError::from)?;
+  Ok(AppResponse::Ok().with_data(upload).into())
+}
+
+#[instrument(level = "debug", skip(state), err)]
+async fn complete_presigned_upload_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+  params: Json<CompletePresignedUploadParams>,
+) -> Result<JsonAppResponse<AFBlobRecord>> {
+  let metadata = state
+    .bucket_storage
+    .complete_presigned_upload(&workspace_id, &params.upload_id)
+    .await
+    .map_err(AppError::from)?;
+  Ok(
+    AppResponse::Ok()
+      .with_data(AFBlobRecord::new(metadata.file_id))
+      .into(),
+  )
+}
+
+/// Returns a pre-signed url that the client uses to download the blob directly from the bucket.
+/// [AFPresignedDownload::Unsupported] is returned when the bucket can't pre-sign urls, in which
+/// case the client falls back to [get_blob_handler].
+#[instrument(level = "debug", skip(state), err)]
+async fn presign_download_handler(
+  state: Data<AppState>,
+  path: web::Path<PathInfo>,
+) -> Result<JsonAppResponse<AFPresignedDownload>> {
+  let PathInfo {
+    workspace_id,
+    file_id,
+  } = path.into_inner();
+  let download = state
+    .bucket_storage
+    .presign_get_blob(&workspace_id, &file_id)
+    .await
+    .map_err(AppError::from)?;
+  Ok(AppResponse::Ok().with_data(download).into())
+}
+
 fn payload_to_async_read(payload: Payload) -> Pin<Box<dyn AsyncRead>> {
   let mapped =
     payload.map(|chunk| chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
@@ -1,4 +1,6 @@
+pub mod admin;
 pub mod file_storage;
+pub mod share;
 pub mod user;
 pub mod workspace;
 pub mod ws;
@@ -0,0 +1,52 @@
+use crate::component::auth::share_link::ShareLinkAuth;
+use crate::state::AppState;
+use actix_web::web::{Data, Json};
+use actix_web::Result;
+use actix_web::{web, Scope};
+use database::collab::CollabStorage;
+use database_entity::dto::{AFSharedCollab, QueryCollabParams};
+use database_entity::error::DatabaseError;
+use shared_entity::app_error::AppError;
+use shared_entity::data::AppResponse;
+use shared_entity::error_code::ErrorCode;
+use tracing::instrument;
+
+/// The share link token in the path of the anonymous requests. It's resolved by the
+/// [WorkspaceAccessControlMiddleware](crate::middleware::access_control_mw::WorkspaceAccessControlMiddleware).
+pub const SHARE_TOKEN_PATH: &str = "share_token";
+
+pub fn share_scope() -> Scope {
+  web::scope("/api/share")
+    .service(web::resource("{share_token}").route(web::get().to(get_shared_collab_handler)))
+}
+
+/// Returns the collab object of the share link. It doesn't require the viewer to be logged in.
+#[instrument(skip_all, err)]
+async fn get_shared_collab_handler(
+  share_link: ShareLinkAuth,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<AFSharedCollab>>> {
+  // Read on behalf of the creator of the link, so the link stops working once the creator
+  // loses access to the object.
+  let params = QueryCollabParams {
+    object_id: share_link.oid.clone(),
+    workspace_id: share_link.workspace_id.to_string(),
+    collab_type: share_link.collab_type.clone(),
+  };
+  let data = state
+    .collab_storage
+    .get_collab(&share_link.created_by, params)
+    .await
+    .map_err(|err| match err {
+      DatabaseError::RecordNotFound(msg) => AppError::new(ErrorCode::RecordNotFound, msg),
+      _ => AppError::new(ErrorCode::DBError, err.to_string()),
+    })?;
+
+  Ok(Json(AppResponse::Ok().with_data(AFSharedCollab {
+    object_id: share_link.oid,
+    workspace_id: share_link.workspace_id,
+    collab_type: share_link.collab_type,
+    access_level: share_link.access_level,
+    data,
+  })))
+}
@@ -1,23 +1,38 @@
 use crate::biz;
+use crate::biz::user_data_export::DataExportDownload;
 use crate::component::auth::{
-  change_password, logged_user_from_request, login, logout, register, ChangePasswordRequest,
-  RegisterRequest,
+  change_password, login, logout, register, ChangePasswordRequest, RegisterRequest,
 };
 
 use crate::component::auth::{InputParamsError, LoginRequest};
 
-use crate::component::token_state::SessionToken;
+use crate::component::audit::RequestActor;
 use crate::domain::{UserEmail, UserName, UserPassword};
 use crate::state::AppState;
 use shared_entity::data::{AppResponse, JsonAppResponse};
-use shared_entity::dto::auth_dto::{SignInTokenResponse, UpdateUserParams};
+use shared_entity::dto::auth_dto::{
+  ChangeEmailParams, ChangePasswordParams, PasswordRecoveryParams, SignInPasswordResponse,
+  SignInTokenResponse, SsoSignInProvider, UpdateUserParams, VerifyOtpParams, VerifyOtpResponse,
+};
 
 use crate::component::auth::jwt::{Authorization, UserUuid};
+use actix_web::http::header::{
+  ContentDisposition, DispositionParam, DispositionType, CACHE_CONTROL, LOCATION,
+};
 use actix_web::web::{Data, Json};
-use actix_web::HttpRequest;
 use actix_web::Result;
 use actix_web::{web, HttpResponse, Scope};
-use database_entity::dto::{AFUserProfile, AFUserWorkspaceInfo};
+use database::audit::insert_audit_log;
+use database::file::{AVATAR_FILE_TYPE, MAX_AVATAR_UPLOAD_SIZE};
+use database_entity::dto::{
+  AFApiTokenCreated, AFApiTokens, AFAuditAction, AFUserDataExport, AFUserDeletion, AFUserProfile,
+  AFUserSessions, AFUserWorkspaceInfo, CreateApiTokenParams,
+};
+use gotrue_entity::dto::SignUpResponse;
+use serde::Deserialize;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use uuid::Uuid;
 
 use tracing_actix_web::RequestId;
 
@@ -26,8 +41,49 @@ pub fn user_scope() -> Scope {
     // auth server integration
     .service(web::resource("/verify/{access_token}").route(web::get().to(verify_user_handler)))
     .service(web::resource("/update").route(web::post().to(update_user_handler)))
+    .service(
+      web::resource("/password/recover").route(web::post().to(send_password_recovery_handler)),
+    )
+    .service(web::resource("/password/change").route(web::post().to(update_password_handler)))
+    .service(web::resource("/email").route(web::post().to(change_email_handler)))
+    .service(web::resource("/otp/verify").route(web::post().to(verify_otp_handler)))
+    .service(web::resource("/reauthenticate").route(web::post().to(reauthenticate_handler)))
+    .service(web::resource("/sso/discover").route(web::get().to(discover_sso_provider_handler)))
     .service(web::resource("/profile").route(web::get().to(get_user_profile_handler)))
+    .service(
+      web::resource("/avatar")
+        .app_data(web::PayloadConfig::new(MAX_AVATAR_UPLOAD_SIZE))
+        .route(web::post().to(upload_user_avatar_handler))
+        .route(web::delete().to(delete_user_avatar_handler)),
+    )
+    // Anonymous, so the avatars can be shown with an image url.
+    .service(
+      web::resource("/avatar/{user_uuid}/{avatar_id}")
+        .route(web::get().to(get_user_avatar_handler)),
+    )
       .service(web::resource("/workspace").route(web::get().to(get_user_workspace_info_handler)))
+    .service(
+      web::resource("/token")
+        .route(web::get().to(list_api_tokens_handler))
+        .route(web::post().to(create_api_token_handler)),
+    )
+    .service(web::resource("/token/{token_id}").route(web::delete().to(revoke_api_token_handler)))
+    .service(web::resource("/session").route(web::get().to(list_user_sessions_handler)))
+    .service(
+      web::resource("/session/{device_id}").route(web::delete().to(sign_out_device_handler)),
+    )
+    .service(
+      web::resource("/deletion")
+        .route(web::get().to(get_user_deletion_handler))
+        .route(web::post().to(request_user_deletion_handler))
+        .route(web::delete().to(cancel_user_deletion_handler)),
+    )
+    .service(web::resource("/export").route(web::post().to(request_user_data_export_handler)))
+    .service(web::resource("/export/{export_id}").route(web::get().to(get_user_data_export_handler)))
+    .service(
+      web::resource("/export/{export_id}/download")
+        .route(web::get().to(download_user_data_export_handler)),
+    )
 
     // deprecated
     .service(web::resource("/login").route(web::post().to(login_handler)))
@@ -43,13 +99,21 @@ async fn verify_user_handler(
   request_id: RequestId,
 ) -> Result<JsonAppResponse<SignInTokenResponse>> {
   let access_token = path.into_inner();
-  let is_new = biz::user::verify_token(
-    &state.pg_pool,
-    &state.id_gen,
-    &state.gotrue_client,
-    &access_token,
-  )
-  .await?;
+  // The tokens of GoTrue, and the ones that can't be verified locally, are checked by GoTrue
+  let is_new = match state.jwt_verifier.verify_external(&access_token) {
+    Ok(Some(claims)) => {
+      biz::user::verify_external_token(&state.pg_pool, &state.id_gen, &claims).await?
+    },
+    _ => {
+      biz::user::verify_token(
+        &state.pg_pool,
+        &state.id_gen,
+        &state.gotrue_client,
+        &access_token,
+      )
+      .await?
+    },
+  };
   let resp = SignInTokenResponse { is_new };
   Ok(AppResponse::Ok().with_data(resp).into())
 }
@@ -64,6 +128,50 @@ async fn get_user_profile_handler(
   Ok(AppResponse::Ok().with_data(profile).into())
 }
 
+/// Sets the avatar of the user. The body is the image, which is cropped to a square and resized.
+#[tracing::instrument(skip(state, payload), err)]
+async fn upload_user_avatar_handler(
+  uuid: UserUuid,
+  actor: RequestActor,
+  payload: web::Bytes,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFUserProfile>> {
+  let profile =
+    biz::user_avatar::upload_user_avatar(&state, &actor, &uuid, payload.to_vec()).await?;
+  Ok(AppResponse::Ok().with_data(profile).into())
+}
+
+#[tracing::instrument(skip(state), err)]
+async fn delete_user_avatar_handler(
+  uuid: UserUuid,
+  actor: RequestActor,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  biz::user_avatar::delete_user_avatar(&state, &actor, &uuid).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[tracing::instrument(skip(state), err)]
+async fn get_user_avatar_handler(
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<HttpResponse> {
+  let (user_uuid, avatar_id) = path.into_inner();
+  match biz::user_avatar::get_user_avatar(&state, &user_uuid, &avatar_id).await {
+    // The id changes whenever the avatar changes, so the avatar can be cached for good.
+    Ok(avatar) => Ok(
+      HttpResponse::Ok()
+        .content_type(AVATAR_FILE_TYPE)
+        .insert_header((CACHE_CONTROL, "public, immutable, max-age=31536000"))
+        .body(avatar),
+    ),
+    Err(err) if err.code == ErrorCode::RecordNotFound => Ok(HttpResponse::NotFound().finish()),
+    Err(err) => Err(err.into()),
+  }
+}
+
 #[tracing::instrument(skip(state), err)]
 async fn get_user_workspace_info_handler(
   uuid: UserUuid,
@@ -77,21 +185,299 @@ async fn get_user_workspace_info_handler(
 #[tracing::instrument(skip(state, auth, payload), err)]
 async fn update_user_handler(
   auth: Authorization,
+  actor: RequestActor,
   payload: Json<UpdateUserParams>,
   state: Data<AppState>,
   request_id: RequestId,
 ) -> Result<JsonAppResponse<()>> {
   let params = payload.into_inner();
-  biz::user::update_user(&state.pg_pool, auth.uuid()?, params).await?;
+  if params.password.is_some() || params.email.is_some() {
+    auth.require_aal2(&state.pg_pool).await?;
+  }
+  biz::user::update_user(&state.pg_pool, &actor, auth.uuid()?, params).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[tracing::instrument(skip(state, payload), err)]
+async fn send_password_recovery_handler(
+  payload: Json<PasswordRecoveryParams>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  biz::user_credential::send_password_recovery(&state.gotrue_client, payload.into_inner()).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Verifies the code sent by the password recovery, the email change or the sign up. The returned
+/// token signs the user in.
+#[tracing::instrument(skip(state, payload), err)]
+async fn verify_otp_handler(
+  actor: RequestActor,
+  payload: Json<VerifyOtpParams>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<VerifyOtpResponse>> {
+  let resp = biz::user_credential::verify_otp(
+    &state.pg_pool,
+    &state.id_gen,
+    &state.gotrue_client,
+    &actor,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(resp).into())
+}
+
+#[derive(Deserialize, Debug)]
+struct DiscoverSsoProviderQuery {
+  email: String,
+}
+
+/// Returns the SSO provider that the user signs in with, according to the domain of the email.
+#[tracing::instrument(skip(state), err)]
+async fn discover_sso_provider_handler(
+  query: web::Query<DiscoverSsoProviderQuery>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<SsoSignInProvider>> {
+  let provider = biz::sso::discover_sso_provider(&state.pg_pool, &query.email).await?;
+  Ok(AppResponse::Ok().with_data(provider).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn reauthenticate_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  biz::user_credential::reauthenticate(&state.gotrue_client, &auth.token).await?;
   Ok(AppResponse::Ok().into())
 }
 
+/// Changes the password of the GoTrue user. Unlike the deprecated `/password`, the current
+/// password isn't required, but GoTrue can be configured to require the nonce sent by
+/// `/reauthenticate`, which also allows to reset a forgotten password.
+#[tracing::instrument(skip(state, auth, payload), err)]
+async fn update_password_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  payload: Json<ChangePasswordParams>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  auth.require_aal2(&state.pg_pool).await?;
+  biz::user_credential::change_password(
+    &state.pg_pool,
+    &state.gotrue_client,
+    &actor,
+    &auth.uuid()?,
+    &auth.token,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[tracing::instrument(skip(state, auth, payload), err)]
+async fn change_email_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  payload: Json<ChangeEmailParams>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  auth.require_aal2(&state.pg_pool).await?;
+  biz::user_credential::change_email(
+    &state.pg_pool,
+    &state.gotrue_client,
+    &actor,
+    &auth.token,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Personal access tokens can only be managed with a user session, so the handlers take the
+/// [Authorization] instead of the [UserUuid].
+#[tracing::instrument(skip(state, auth, payload), err)]
+async fn create_api_token_handler(
+  auth: Authorization,
+  payload: Json<CreateApiTokenParams>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFApiTokenCreated>> {
+  auth.require_aal2(&state.pg_pool).await?;
+  let token =
+    biz::user::create_api_token(&state.pg_pool, &auth.uuid()?, payload.into_inner()).await?;
+  Ok(AppResponse::Ok().with_data(token).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn list_api_tokens_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFApiTokens>> {
+  let tokens = biz::user::list_api_tokens(&state.pg_pool, &auth.uuid()?).await?;
+  Ok(AppResponse::Ok().with_data(tokens).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn revoke_api_token_handler(
+  auth: Authorization,
+  token_id: web::Path<Uuid>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  biz::user::revoke_api_token(&state.pg_pool, &auth.uuid()?, &token_id).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Lists the devices that the user is signed in on. Like the personal access tokens, the
+/// sessions can only be managed with a user session.
+#[tracing::instrument(skip(state, auth), err)]
+async fn list_user_sessions_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFUserSessions>> {
+  let current_session_id = auth
+    .claims
+    .session_id
+    .as_deref()
+    .and_then(|id| Uuid::parse_str(id).ok());
+  let sessions = biz::user_session::list_user_sessions(
+    &state.pg_pool,
+    &auth.uuid()?,
+    current_session_id.as_ref(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(sessions).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn sign_out_device_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  device_id: web::Path<String>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  auth.require_aal2(&state.pg_pool).await?;
+  let uid = biz::user_session::sign_out_device(&state, &actor, &auth.uuid()?, &device_id).await?;
+  state.realtime_sessions.close(uid, &device_id).await;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Schedules the deletion of the account. The account is deleted after the grace period, unless
+/// the deletion is cancelled before.
+#[tracing::instrument(skip(state, auth), err)]
+async fn request_user_deletion_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFUserDeletion>> {
+  auth.require_aal2(&state.pg_pool).await?;
+  let deletion = biz::user_deletion::request_user_deletion(
+    &state.pg_pool,
+    &actor,
+    &auth.uuid()?,
+    &state.config.user_deletion,
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(deletion).into())
+}
+
+#[tracing::instrument(skip(state), err)]
+async fn get_user_deletion_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFUserDeletion>> {
+  let deletion = biz::user_deletion::get_user_deletion(&state.pg_pool, &uuid).await?;
+  Ok(AppResponse::Ok().with_data(deletion).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn cancel_user_deletion_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<()>> {
+  biz::user_deletion::cancel_user_deletion(&state.pg_pool, &actor, &auth.uuid()?).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Starts exporting the data of the user. The archive is built in the background, the status
+/// of the export tells when it can be downloaded.
+#[tracing::instrument(skip(state, auth), err)]
+async fn request_user_data_export_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFUserDataExport>> {
+  auth.require_aal2(&state.pg_pool).await?;
+  let export =
+    biz::user_data_export::request_user_data_export(&state, &actor, &auth.uuid()?).await?;
+  Ok(AppResponse::Ok().with_data(export).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn get_user_data_export_handler(
+  auth: Authorization,
+  export_id: web::Path<Uuid>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<JsonAppResponse<AFUserDataExport>> {
+  let export =
+    biz::user_data_export::get_user_data_export(&state, &auth.uuid()?, &export_id).await?;
+  Ok(AppResponse::Ok().with_data(export).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn download_user_data_export_handler(
+  auth: Authorization,
+  export_id: web::Path<Uuid>,
+  state: Data<AppState>,
+  request_id: RequestId,
+) -> Result<HttpResponse> {
+  let download =
+    biz::user_data_export::download_user_data_export(&state, &auth.uuid()?, &export_id).await?;
+  // The archive can be large, so it's downloaded from the bucket directly when possible.
+  let archive = match download {
+    DataExportDownload::Url(url) => {
+      return Ok(
+        HttpResponse::TemporaryRedirect()
+          .insert_header((LOCATION, url))
+          .finish(),
+      )
+    },
+    DataExportDownload::Archive(archive) => archive,
+  };
+  let disposition = ContentDisposition {
+    disposition: DispositionType::Attachment,
+    parameters: vec![DispositionParam::Filename(
+      biz::user_data_export::data_export_file_name(&export_id),
+    )],
+  };
+  Ok(
+    HttpResponse::Ok()
+      .content_type("application/zip")
+      .insert_header(disposition)
+      .body(archive),
+  )
+}
+
+/// Signs in with GoTrue, see [login].
 #[tracing::instrument(skip_all)]
 async fn login_handler(
   req: Json<LoginRequest>,
   state: Data<AppState>,
-  session: SessionToken,
-) -> Result<HttpResponse> {
+) -> Result<JsonAppResponse<SignInPasswordResponse>> {
   let req = req.into_inner();
   let email = UserEmail::parse(req.email)
     .map_err(InputParamsError::InvalidEmail)?
@@ -99,30 +485,22 @@ async fn login_handler(
   let password = UserPassword::parse(req.password)
     .map_err(InputParamsError::InvalidPassword)?
     .0;
-  let (resp, token) = login(email, password, &state).await?;
-
-  // Renews the session key, assigning existing session state to new key.
-  session.renew();
-  if let Err(err) = session.insert_token(token) {
-    // It needs to navigate to login page in web application
-    tracing::error!("Insert session failed: {:?}", err);
-  }
-
-  Ok(HttpResponse::Ok().json(resp))
+  let resp = login(email, password, &state).await?;
+  Ok(AppResponse::Ok().with_data(resp).into())
 }
 
-#[tracing::instrument(level = "debug", skip(state))]
-async fn logout_handler(req: HttpRequest, state: Data<AppState>) -> Result<HttpResponse> {
-  let logged_user = logged_user_from_request(&req, &state.config.application.server_key)?;
-  logout(logged_user, state.user.clone()).await;
-  Ok(HttpResponse::Ok().finish())
+#[tracing::instrument(level = "debug", skip(state, auth))]
+async fn logout_handler(auth: Authorization, state: Data<AppState>) -> Result<JsonAppResponse<()>> {
+  logout(&auth, &state).await?;
+  Ok(AppResponse::Ok().into())
 }
 
+/// Signs up with GoTrue, see [register].
 #[tracing::instrument(level = "debug", skip(state))]
 async fn register_handler(
   req: Json<RegisterRequest>,
   state: Data<AppState>,
-) -> Result<HttpResponse> {
+) -> Result<JsonAppResponse<SignUpResponse>> {
   let req = req.into_inner();
   let name = UserName::parse(req.name)
     .map_err(InputParamsError::InvalidName)?
@@ -135,17 +513,18 @@ async fn register_handler(
     .0;
 
   let resp = register(name, email, password, &state).await?;
-  Ok(HttpResponse::Ok().json(resp))
+  Ok(AppResponse::Ok().with_data(resp).into())
 }
 
+/// Changes the password of the GoTrue user, see [change_password].
 #[tracing::instrument(level = "debug", skip_all)]
 async fn change_password_handler(
-  req: HttpRequest,
+  auth: Authorization,
+  actor: RequestActor,
   payload: Json<ChangePasswordRequest>,
-  // session: SessionToken,
   state: Data<AppState>,
-) -> Result<HttpResponse> {
-  let logged_user = logged_user_from_request(&req, &state.config.application.server_key)?;
+) -> Result<JsonAppResponse<()>> {
+  auth.require_aal2(&state.pg_pool).await?;
   let payload = payload.into_inner();
   if payload.new_password != payload.new_password_confirm {
     return Err(InputParamsError::PasswordNotMatch.into());
@@ -155,13 +534,18 @@ async fn change_password_handler(
     .map_err(InputParamsError::InvalidPassword)?
     .0;
 
-  change_password(
-    state.pg_pool.clone(),
-    logged_user.clone(),
-    payload.current_password,
-    new_password,
+  change_password(&auth, payload.current_password, new_password, &state).await?;
+
+  let user_uuid = auth.uuid()?;
+  insert_audit_log(
+    &state.pg_pool,
+    &actor,
+    None,
+    AFAuditAction::ChangePassword,
+    &user_uuid.to_string(),
   )
-  .await?;
+  .await
+  .map_err(AppError::from)?;
 
-  Ok(HttpResponse::Ok().finish())
+  Ok(AppResponse::Ok().into())
 }
@@ -1,7 +1,8 @@
 use crate::biz;
 
 use crate::biz::workspace;
-use crate::component::auth::jwt::UserUuid;
+use crate::component::audit::RequestActor;
+use crate::component::auth::jwt::{Authorization, UserUuid};
 use crate::state::AppState;
 use actix_web::web::{Data, Json};
 use actix_web::Result;
@@ -25,6 +26,8 @@ pub const COLLAB_OBJECT_ID_PATH: &str = "object_id";
 pub fn workspace_scope() -> Scope {
   web::scope("/api/workspace")
     .service(web::resource("list").route(web::get().to(list_handler)))
+    .service(web::resource("permission").route(web::get().to(get_permissions_handler)))
+    .service(web::resource("invite/accept").route(web::post().to(accept_workspace_invite_handler)))
     .service(web::resource("{workspace_id}/open").route(web::put().to(open_workspace_handler)))
     .service(
       web::resource("{workspace_id}/member")
@@ -33,6 +36,22 @@ pub fn workspace_scope() -> Scope {
         .route(web::put().to(update_workspace_member_handler))
         .route(web::delete().to(remove_workspace_member_handler)),
     )
+    .service(
+      web::resource("{workspace_id}/invite")
+        .route(web::post().to(create_workspace_invite_handler))
+        .route(web::delete().to(revoke_workspace_invite_handler)),
+    )
+    .service(
+      web::resource("{workspace_id}/role")
+        .route(web::get().to(get_workspace_roles_handler))
+        .route(web::post().to(create_workspace_role_handler)),
+    )
+    .service(
+      web::resource("{workspace_id}/role/{role_id}")
+        .route(web::put().to(update_workspace_role_handler))
+        .route(web::delete().to(delete_workspace_role_handler)),
+    )
+    .service(web::resource("{workspace_id}/audit_log").route(web::get().to(get_audit_log_handler)))
     .service(
       web::resource("{workspace_id}/collab/{object_id}")
         .route(web::post().to(create_collab_handler))
@@ -51,6 +70,21 @@ pub fn workspace_scope() -> Scope {
       web::resource("{workspace_id}/collab/{object_id}/member/list")
         .route(web::get().to(get_collab_member_list_handler)),
     )
+    .service(
+      web::resource("{workspace_id}/collab/{object_id}/parent")
+        .route(web::get().to(get_collab_parent_handler))
+        .route(web::put().to(set_collab_parent_handler))
+        .route(web::delete().to(remove_collab_parent_handler)),
+    )
+    .service(
+      web::resource("{workspace_id}/collab/{object_id}/share")
+        .route(web::get().to(get_share_links_handler))
+        .route(web::post().to(create_share_link_handler)),
+    )
+    .service(
+      web::resource("{workspace_id}/collab/{object_id}/share/{share_id}")
+        .route(web::delete().to(revoke_share_link_handler)),
+    )
     .service(
       web::resource("{workspace_id}/collab_list").route(web::get().to(batch_get_collab_handler)),
     )
@@ -84,19 +118,15 @@ async fn list_handler(
 #[instrument(skip(payload, state), err)]
 async fn add_workspace_members_handler(
   request_id: RequestId,
-  user_uuid: UserUuid,
+  actor: RequestActor,
   workspace_id: web::Path<Uuid>,
   payload: Json<CreateWorkspaceMembers>,
   state: Data<AppState>,
 ) -> Result<JsonAppResponse<()>> {
   let create_members = payload.into_inner();
-  let role_by_uid = workspace::ops::add_workspace_members(
-    &state.pg_pool,
-    &user_uuid,
-    &workspace_id,
-    create_members.0,
-  )
-  .await?;
+  let role_by_uid =
+    workspace::ops::add_workspace_members(&state.pg_pool, &actor, &workspace_id, create_members.0)
+      .await?;
 
   for (uid, role) in role_by_uid {
     state
@@ -107,6 +137,49 @@ async fn add_workspace_members_handler(
   Ok(AppResponse::Ok().into())
 }
 
+/// Invites the email to the workspace, see [workspace::invite::create_workspace_invite].
+#[instrument(skip(payload, state), err)]
+async fn create_workspace_invite_handler(
+  workspace_id: web::Path<Uuid>,
+  payload: Json<CreateWorkspaceMember>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFWorkspaceInvite>> {
+  let invite =
+    workspace::invite::create_workspace_invite(&state, &workspace_id, payload.into_inner())?;
+  Ok(AppResponse::Ok().with_data(invite).into())
+}
+
+#[instrument(skip(payload, state), err)]
+async fn revoke_workspace_invite_handler(
+  workspace_id: web::Path<Uuid>,
+  payload: Json<RevokeWorkspaceInvite>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  workspace::invite::revoke_workspace_invite(&state, &workspace_id, &payload.token).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Joins the workspace with the invitation sent to the email of the user. The user isn't a
+/// member yet, so the workspace isn't part of the path and its access control doesn't apply.
+#[instrument(skip(auth, payload, state), err)]
+async fn accept_workspace_invite_handler(
+  auth: Authorization,
+  actor: RequestActor,
+  payload: Json<AcceptWorkspaceInvite>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  let params = payload.into_inner();
+  workspace::invite::accept_workspace_invite(
+    &state,
+    &actor,
+    &auth.claims.email,
+    &params.workspace_id,
+    &params.token,
+  )
+  .await?;
+  Ok(AppResponse::Ok().into())
+}
+
 #[instrument(skip_all, err)]
 async fn get_workspace_members_handler(
   user_uuid: UserUuid,
@@ -120,7 +193,9 @@ async fn get_workspace_members_handler(
       name: member.name,
       email: member.email,
       role: member.role,
-      avatar_url: None,
+      avatar_url: member
+        .avatar_id
+        .map(|avatar_id| user_avatar_url(&member.uuid, &avatar_id)),
     })
     .collect();
 
@@ -130,6 +205,7 @@ async fn get_workspace_members_handler(
 #[instrument(skip_all, err)]
 async fn remove_workspace_member_handler(
   user_uuid: UserUuid,
+  actor: RequestActor,
   payload: Json<WorkspaceMembers>,
   state: Data<AppState>,
   workspace_id: web::Path<Uuid>,
@@ -142,6 +218,7 @@ async fn remove_workspace_member_handler(
     .collect::<Vec<String>>();
   workspace::ops::remove_workspace_members(
     &user_uuid,
+    &actor,
     &state.pg_pool,
     &workspace_id,
     &member_emails,
@@ -176,13 +253,15 @@ async fn open_workspace_handler(
 
 #[instrument(skip_all, err)]
 async fn update_workspace_member_handler(
+  actor: RequestActor,
   payload: Json<WorkspaceMemberChangeset>,
   state: Data<AppState>,
   workspace_id: web::Path<Uuid>,
 ) -> Result<JsonAppResponse<()>> {
   let workspace_id = workspace_id.into_inner();
   let changeset = payload.into_inner();
-  workspace::ops::update_workspace_member(&state.pg_pool, &workspace_id, &changeset).await?;
+  workspace::ops::update_workspace_member(&state.pg_pool, &actor, &workspace_id, &changeset)
+    .await?;
 
   if let Some(role) = changeset.role {
     let uid = select_uid_from_email(&state.pg_pool, &changeset.email)
@@ -197,6 +276,84 @@ async fn update_workspace_member_handler(
   Ok(AppResponse::Ok().into())
 }
 
+#[instrument(skip_all, err)]
+async fn get_permissions_handler(
+  _user_uuid: UserUuid,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFPermissions>> {
+  let permissions = workspace::ops::get_permissions(&state.pg_pool).await?;
+  Ok(AppResponse::Ok().with_data(permissions).into())
+}
+
+#[instrument(skip_all, err)]
+async fn get_workspace_roles_handler(
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+) -> Result<JsonAppResponse<AFWorkspaceRoles>> {
+  let roles = workspace::ops::get_workspace_roles(&state.pg_pool, &workspace_id).await?;
+  Ok(AppResponse::Ok().with_data(roles).into())
+}
+
+#[instrument(skip(state, payload), err)]
+async fn create_workspace_role_handler(
+  actor: RequestActor,
+  workspace_id: web::Path<Uuid>,
+  payload: Json<CreateWorkspaceRoleParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFWorkspaceRole>> {
+  let role = workspace::ops::create_workspace_role(
+    &state.pg_pool,
+    &actor,
+    &workspace_id,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(role).into())
+}
+
+#[instrument(skip(state, payload), err)]
+async fn update_workspace_role_handler(
+  actor: RequestActor,
+  path: web::Path<(Uuid, i32)>,
+  payload: Json<UpdateWorkspaceRoleParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFWorkspaceRole>> {
+  let (workspace_id, role_id) = path.into_inner();
+  let role = workspace::ops::update_workspace_role(
+    &state.pg_pool,
+    &actor,
+    &workspace_id,
+    role_id,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(role).into())
+}
+
+#[instrument(skip(state), err)]
+async fn delete_workspace_role_handler(
+  actor: RequestActor,
+  path: web::Path<(Uuid, i32)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  let (workspace_id, role_id) = path.into_inner();
+  workspace::ops::delete_workspace_role(&state.pg_pool, &actor, &workspace_id, role_id).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Returns a page of the audit log of the workspace, newest first.
+#[instrument(skip(state), err)]
+async fn get_audit_log_handler(
+  workspace_id: web::Path<Uuid>,
+  query: web::Query<QueryAuditLogParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFAuditLogPage>> {
+  let page =
+    workspace::ops::get_workspace_audit_log(&state.pg_pool, &workspace_id, &query.into_inner())
+      .await?;
+  Ok(AppResponse::Ok().with_data(page).into())
+}
+
 #[instrument(skip(state, payload), err)]
 async fn create_collab_handler(
   user_uuid: UserUuid,
@@ -263,12 +420,12 @@ async fn update_collab_handler(
 
 #[instrument(level = "info", skip(state, payload), err)]
 async fn delete_collab_handler(
-  user_uuid: UserUuid,
+  actor: RequestActor,
   required_id: RequestId,
   payload: Json<DeleteCollabParams>,
   state: Data<AppState>,
 ) -> Result<Json<AppResponse<()>>> {
-  biz::collab::ops::delete_collab(&state.pg_pool, &user_uuid, &payload.into_inner()).await?;
+  biz::collab::ops::delete_collab(&state.pg_pool, &actor, &payload.into_inner()).await?;
   Ok(AppResponse::Ok().into())
 }
 
@@ -367,3 +524,95 @@ async fn get_collab_member_list_handler(
     biz::collab::ops::get_collab_member_list(&state.pg_pool, &payload.into_inner()).await?;
   Ok(Json(AppResponse::Ok().with_data(AFCollabMembers(members))))
 }
+
+#[instrument(skip(state), err)]
+async fn get_collab_parent_handler(
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFCollabParent>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let parent =
+    biz::collab::ops::get_collab_parent(&state.pg_pool, &workspace_id, &object_id).await?;
+  Ok(AppResponse::Ok().with_data(parent).into())
+}
+
+#[instrument(skip(state, payload), err)]
+async fn set_collab_parent_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  payload: Json<SetCollabParentParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFCollabParent>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let parent = biz::collab::ops::set_collab_parent(
+    &state.pg_pool,
+    &user_uuid,
+    &workspace_id,
+    &object_id,
+    &payload,
+  )
+  .await?;
+  state.collab_access_control.on_parent_changed().await;
+  Ok(AppResponse::Ok().with_data(parent).into())
+}
+
+#[instrument(skip(state), err)]
+async fn remove_collab_parent_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  let (workspace_id, object_id) = path.into_inner();
+  biz::collab::ops::remove_collab_parent(&state.pg_pool, &user_uuid, &workspace_id, &object_id)
+    .await?;
+  state.collab_access_control.on_parent_changed().await;
+  Ok(AppResponse::Ok().into())
+}
+
+#[instrument(skip(state), err)]
+async fn get_share_links_handler(
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFShareLinks>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let links = biz::collab::ops::get_share_links(&state.pg_pool, &workspace_id, &object_id).await?;
+  Ok(AppResponse::Ok().with_data(links).into())
+}
+
+#[instrument(skip(state, payload), err)]
+async fn create_share_link_handler(
+  user_uuid: UserUuid,
+  actor: RequestActor,
+  path: web::Path<(Uuid, String)>,
+  payload: Json<CreateShareLinkParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFShareLinkCreated>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let link = biz::collab::ops::create_share_link(
+    &state.pg_pool,
+    &actor,
+    &user_uuid,
+    &workspace_id,
+    &object_id,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(link).into())
+}
+
+#[instrument(skip(state), err)]
+async fn revoke_share_link_handler(
+  actor: RequestActor,
+  path: web::Path<(Uuid, String, Uuid)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  let (workspace_id, object_id, share_id) = path.into_inner();
+  biz::collab::ops::revoke_share_link(&state.pg_pool, &actor, &workspace_id, &object_id, &share_id)
+    .await?;
+  // Disconnect the anonymous viewers that opened the link over the websocket.
+  state
+    .collab_access_control
+    .remove_share_link_viewers(&share_id)
+    .await;
+  Ok(AppResponse::Ok().into())
+}
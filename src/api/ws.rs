@@ -1,23 +1,33 @@
 use crate::state::AppState;
 use actix::Addr;
-use actix_web::web::{Data, Path, Payload};
+use actix_web::http::header::USER_AGENT;
+use actix_web::web::{Data, Path, Payload, Query};
 use actix_web::{get, web, HttpRequest, HttpResponse, Result, Scope};
 use actix_web_actors::ws;
+use chrono::{TimeZone, Utc};
 use std::sync::Arc;
 
 use realtime::client::{ClientSession, RealtimeUserImpl};
 use realtime::collaborate::CollabServer;
 
-use crate::biz::collab::access_control::CollabAccessControlImpl;
+use crate::biz::collab::access_control::{CollabAccessControlImpl, ShareViewer};
 use crate::biz::collab::storage::CollabPostgresDBStorage;
+use crate::biz::user_session::record_user_session;
 use crate::component::auth::jwt::{authorization_from_token, UserUuid};
+use crate::component::auth::share_link::ShareLinkAuth;
+use crate::component::client_ip::client_ip;
 use database::user::select_uid_from_uuid;
+use realtime::collaborate::CollabAccessControl;
+use serde::Deserialize;
 use shared_entity::app_error::AppError;
 use std::time::Duration;
 use tracing::instrument;
+use uuid::Uuid;
 
 pub fn ws_scope() -> Scope {
-  web::scope("/ws").service(establish_ws_connection)
+  web::scope("/ws")
+    .service(establish_ws_connection)
+    .service(establish_share_ws_connection)
 }
 
 const MAX_FRAME_SIZE: usize = 65_536; // 64 KiB
@@ -26,35 +36,151 @@ type CollabServerData = Data<
   Addr<CollabServer<CollabPostgresDBStorage, Arc<RealtimeUserImpl>, Arc<CollabAccessControlImpl>>>,
 >;
 
+type RealtimeClientSession =
+  ClientSession<Arc<RealtimeUserImpl>, CollabPostgresDBStorage, Arc<CollabAccessControlImpl>>;
+
+#[derive(Deserialize, Debug)]
+pub struct WsConnectQuery {
+  /// The version of the app that opens the connection, which is shown in the device list.
+  client_version: Option<String>,
+}
+
+/// Opens the websocket connection of a device. The device is recorded in the user's session
+/// list, and the connection is closed when the device is signed out.
 #[instrument(skip_all, err)]
 #[get("/{token}/{device_id}")]
 pub async fn establish_ws_connection(
   request: HttpRequest,
   payload: Payload,
   path: Path<(String, String)>,
+  query: Query<WsConnectQuery>,
   state: Data<AppState>,
   server: CollabServerData,
 ) -> Result<HttpResponse> {
   tracing::info!("receive ws connect: {:?}", request);
   let (token, device_id) = path.into_inner();
   let auth = authorization_from_token(token.as_str(), &state)?;
+  state
+    .signed_out_sessions
+    .check(auth.claims.session_id.as_deref())?;
+  let issued_at = auth
+    .claims
+    .iat
+    .and_then(|iat| Utc.timestamp_opt(iat, 0).single());
+  let session_id = auth
+    .claims
+    .session_id
+    .as_deref()
+    .and_then(|id| Uuid::parse_str(id).ok());
   let user_uuid = UserUuid::from_auth(auth)?;
+  state.user_ban_list.check(&user_uuid)?;
   let uid = select_uid_from_uuid(&state.pg_pool, &user_uuid)
     .await
     .map_err(AppError::from)?;
-  let realtime_user = Arc::new(RealtimeUserImpl::new(uid, user_uuid.to_string(), device_id));
-  let client = ClientSession::new(
+
+  let user_agent = request
+    .headers()
+    .get(USER_AGENT)
+    .and_then(|value| value.to_str().ok());
+  record_user_session(
+    &state.pg_pool,
+    &uid,
+    &device_id,
+    session_id.as_ref(),
+    issued_at,
+    query.client_version.as_deref(),
+    client_ip(&request).as_deref(),
+    user_agent,
+  )
+  .await?;
+
+  let realtime_user = Arc::new(RealtimeUserImpl::new(
+    uid,
+    user_uuid.to_string(),
+    device_id.clone(),
+  ));
+  let (session, response) = start_client_session(request, payload, realtime_user, &state, server)?;
+  state
+    .realtime_sessions
+    .insert(uid, device_id, session.recipient())
+    .await;
+  Ok(response)
+}
+
+/// Opens a read only websocket connection for the anonymous viewer of a share link. The link is
+/// resolved by the access control middleware. The viewer gets a negative uid, which never clashes
+/// with the uid of a user, and can only subscribe to the object of the link.
+#[instrument(skip_all, err)]
+#[get("/share/{share_token}/{device_id}")]
+pub async fn establish_share_ws_connection(
+  request: HttpRequest,
+  payload: Payload,
+  path: Path<(String, String)>,
+  share_link: ShareLinkAuth,
+  state: Data<AppState>,
+  server: CollabServerData,
+) -> Result<HttpResponse> {
+  let (_, device_id) = path.into_inner();
+  // The link stops working once its creator loses access to the object.
+  state
+    .collab_access_control
+    .get_collab_access_level((&share_link.created_by).into(), &share_link.oid)
+    .await?;
+
+  let uid = -state.next_user_id().await?;
+  let realtime_user = Arc::new(RealtimeUserImpl::new(
+    uid,
+    format!("share:{}", share_link.link_id),
+    device_id,
+  ));
+  let user = Arc::downgrade(&realtime_user);
+  // The viewer is added before the response is returned, so the client can't send a message
+  // before the viewer is known.
+  let (session, response) = start_client_session(request, payload, realtime_user, &state, server)?;
+  state
+    .collab_access_control
+    .add_share_viewer(
+      uid,
+      ShareViewer {
+        link_id: share_link.link_id,
+        oid: share_link.oid,
+        access_level: share_link.access_level,
+        created_by: share_link.created_by,
+        expires_at: share_link.expires_at,
+        user,
+        session: session.recipient(),
+      },
+    )
+    .await;
+  Ok(response)
+}
+
+fn start_client_session(
+  request: HttpRequest,
+  payload: Payload,
+  realtime_user: Arc<RealtimeUserImpl>,
+  state: &AppState,
+  server: CollabServerData,
+) -> Result<(Addr<RealtimeClientSession>, HttpResponse)> {
+  let mut client = ClientSession::new(
     realtime_user,
     server.get_ref().clone(),
     Duration::from_secs(state.config.websocket.heartbeat_interval as u64),
     Duration::from_secs(state.config.websocket.client_timeout as u64),
   );
+  let rate_limit = &state.config.rate_limit;
+  if rate_limit.enable {
+    client = client.with_message_rate_limit(
+      rate_limit.websocket.capacity,
+      rate_limit.websocket.refill_per_sec,
+    );
+  }
 
   match ws::WsResponseBuilder::new(client, &request, payload)
     .frame_size(MAX_FRAME_SIZE)
-    .start()
+    .start_with_addr()
   {
-    Ok(response) => Ok(response),
+    Ok(started) => Ok(started),
     Err(e) => {
       tracing::error!("🔴ws connection error: {:?}", e);
       Err(e)
@@ -1,3 +1,4 @@
+use crate::component::auth::jwt_verifier::JwtVerifier;
 use crate::component::auth::HEADER_TOKEN;
 use crate::config::config::{Config, DatabaseSetting, GoTrueSetting, S3Setting, TlsConfig};
 use crate::middleware::cors_mw::default_cors;
@@ -15,27 +16,37 @@ use anyhow::{Context, Error};
 use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
 use openssl::x509::X509;
 use secrecy::{ExposeSecret, Secret};
-use snowflake::Snowflake;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::net::TcpListener;
 use std::sync::Arc;
 
-use tokio::sync::RwLock;
+use token::TokenKeys;
 
+use crate::api::admin::admin_scope;
 use crate::api::file_storage::file_storage_scope;
+use crate::api::share::share_scope;
 use crate::api::user::user_scope;
 use crate::api::workspace::workspace_scope;
 use crate::api::ws::ws_scope;
 use crate::biz::collab::access_control::{CollabAccessControlImpl, CollabHttpAccessControl};
 use crate::biz::collab::storage::init_collab_storage;
+use crate::biz::file_storage::gc::{spawn_blob_gc, spawn_blob_upload_cleanup};
 use crate::biz::pg_listener::PgListeners;
+use crate::biz::snowflake::init_snowflake;
+use crate::biz::user_ban::UserBanList;
+use crate::biz::user_data_export::spawn_user_data_export_cleanup;
+use crate::biz::user_deletion::spawn_user_deletion;
+use crate::biz::user_session::{RealtimeSessions, SignedOutSessions};
 use crate::biz::workspace::access_control::{
   WorkspaceAccessControlImpl, WorkspaceHttpAccessControl,
 };
 
 use crate::middleware::access_control_mw::WorkspaceAccessControl;
+use crate::middleware::access_policy::AccessPolicy;
+use crate::middleware::rate_limit_mw::RateLimiter;
 
 use database::file::bucket_s3_impl::S3BucketStorage;
+use database::file::BucketStorageConfig;
 use realtime::client::RealtimeUserImpl;
 use realtime::collaborate::CollabServer;
 use tracing_actix_web::TracingLogger;
@@ -92,11 +103,18 @@ pub async fn run(
   .unwrap()
   .start();
 
+  spawn_blob_gc(state.clone());
+  spawn_blob_upload_cleanup(state.clone());
+  spawn_user_deletion(state.clone());
+  spawn_user_data_export_cleanup(state.clone());
+
   let access_control = WorkspaceAccessControl::new()
+    .with_policy(AccessPolicy::default())
     .with_acs(WorkspaceHttpAccessControl(
       state.workspace_access_control.clone(),
     ))
     .with_acs(CollabHttpAccessControl(state.collab_access_control.clone()));
+  let rate_limiter = RateLimiter::new(state.config.rate_limit.clone(), state.redis_client.clone());
 
   let mut server = HttpServer::new(move || {
     App::new()
@@ -108,12 +126,15 @@ pub async fn run(
       )
       .wrap(default_cors())
       .wrap(access_control.clone())
+      .wrap(rate_limiter.clone())
       .wrap(Compat::new(TracingLogger::default()))
       .app_data(web::JsonConfig::default().limit(4096))
       .service(user_scope())
       .service(workspace_scope())
       .service(ws_scope())
       .service(file_storage_scope())
+      .service(admin_scope())
+      .service(share_scope())
       .app_data(Data::new(collab_server.clone()))
       .app_data(Data::new(state.clone()))
       .app_data(Data::new(storage.clone()))
@@ -144,23 +165,48 @@ pub async fn init_state(config: &Config) -> Result<AppState, Error> {
 
   // Bucket storage
   let s3_bucket = get_aws_s3_bucket(&config.s3).await?;
-  let bucket_storage = Arc::new(S3BucketStorage::from_s3_bucket(s3_bucket, pg_pool.clone()));
+  let bucket_storage = Arc::new(S3BucketStorage::from_s3_bucket(
+    s3_bucket,
+    pg_pool.clone(),
+    BucketStorageConfig {
+      thumbnail_sizes: config.s3.thumbnail_sizes.clone(),
+    },
+  ));
 
   // Gotrue
   let gotrue_client = get_gotrue_client(&config.gotrue).await?;
   setup_admin_account(&gotrue_client, &pg_pool, &config.gotrue).await?;
+  let jwt_verifier = Arc::new(JwtVerifier::new(&config.gotrue).await?);
 
   // Redis
   let redis_client = get_redis_client(config.redis_uri.expose_secret()).await?;
 
+  // Signed tokens
+  let previous_server_keys = config
+    .application
+    .previous_server_keys
+    .iter()
+    .map(|key| key.expose_secret().as_str())
+    .collect::<Vec<_>>();
+  let token_keys = Arc::new(TokenKeys::new(
+    config.application.server_key.expose_secret(),
+    &previous_server_keys,
+  ));
+
+  // Uid generator
+  let id_gen = init_snowflake(&pg_pool).await?;
+
   // Pg listeners
   let pg_listeners = Arc::new(PgListeners::new(&pg_pool).await?);
 
   // Collab access control
   let collab_member_listener = pg_listeners.subscribe_collab_member_change();
+  let collab_parent_listener = pg_listeners.subscribe_collab_parent_change();
   let collab_access_control = Arc::new(CollabAccessControlImpl::new(
     pg_pool.clone(),
     collab_member_listener,
+    collab_parent_listener,
+    pg_listeners.subscribe_share_link_revoke(),
   ));
 
   // Workspace access control
@@ -168,8 +214,29 @@ pub async fn init_state(config: &Config) -> Result<AppState, Error> {
   let workspace_access_control = Arc::new(WorkspaceAccessControlImpl::new(
     pg_pool.clone(),
     workspace_member_listener,
+    pg_listeners.subscribe_role_permission_change(),
   ));
 
+  // Websocket connections of the devices
+  let realtime_sessions = Arc::new(RealtimeSessions::new(
+    pg_listeners.subscribe_user_session_sign_out(),
+  ));
+
+  // Banned users
+  let user_ban_list = Arc::new(
+    UserBanList::new(
+      &pg_pool,
+      pg_listeners.subscribe_user_ban_change(),
+      realtime_sessions.clone(),
+    )
+    .await?,
+  );
+
+  // Signed out sessions
+  let signed_out_sessions = Arc::new(
+    SignedOutSessions::new(&pg_pool, pg_listeners.subscribe_user_session_sign_out()).await?,
+  );
+
   let collab_storage = Arc::new(
     init_collab_storage(
       pg_pool.clone(),
@@ -182,15 +249,19 @@ pub async fn init_state(config: &Config) -> Result<AppState, Error> {
   Ok(AppState {
     pg_pool,
     config: Arc::new(config.clone()),
-    user: Arc::new(Default::default()),
-    id_gen: Arc::new(RwLock::new(Snowflake::new(1))),
+    id_gen,
     gotrue_client,
+    jwt_verifier,
     redis_client,
+    token_keys,
     collab_storage,
     collab_access_control,
     workspace_access_control,
     bucket_storage,
     pg_listeners,
+    realtime_sessions,
+    user_ban_list,
+    signed_out_sessions,
   })
 }
 
@@ -0,0 +1,138 @@
+use bytes::Bytes;
+use database::audit::select_audit_log_page;
+use database_entity::dto::{AFAuditLogEntry, QueryAuditLogParams, MAX_AUDIT_LOG_PAGE_SIZE};
+use futures_util::{stream, Stream};
+use shared_entity::app_error::AppError;
+use shared_entity::dto::workspace_dto::{AuditLogExportFormat, ExportAuditLogParams};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const CSV_HEADER: &str = "id,created_at,actor_uid,workspace_id,action,target,ip,user_agent\n";
+
+struct ExportState {
+  params: QueryAuditLogParams,
+  is_first_page: bool,
+}
+
+/// Streams the audit log entries that match the params, one page at a time, so the whole log
+/// is never held in memory.
+pub fn export_audit_log(
+  pg_pool: PgPool,
+  params: ExportAuditLogParams,
+) -> impl Stream<Item = Result<Bytes, AppError>> {
+  let workspace_id = params.workspace_id;
+  let format = params.format;
+  let state = ExportState {
+    params: QueryAuditLogParams {
+      cursor: None,
+      limit: Some(MAX_AUDIT_LOG_PAGE_SIZE),
+      action: params.action,
+      actor_uid: params.actor_uid,
+      since: params.since,
+      until: params.until,
+    },
+    is_first_page: true,
+  };
+
+  stream::unfold(Some(state), move |state| {
+    let pg_pool = pg_pool.clone();
+    async move {
+      let mut state = state?;
+      match export_page(&pg_pool, workspace_id.as_ref(), format, &state).await {
+        Ok((chunk, next_cursor)) => {
+          let next_state = next_cursor.map(|cursor| {
+            state.params.cursor = Some(cursor);
+            state.is_first_page = false;
+            state
+          });
+          Some((Ok(chunk), next_state))
+        },
+        Err(err) => Some((Err(err), None)),
+      }
+    }
+  })
+}
+
+async fn export_page(
+  pg_pool: &PgPool,
+  workspace_id: Option<&Uuid>,
+  format: AuditLogExportFormat,
+  state: &ExportState,
+) -> Result<(Bytes, Option<i64>), AppError> {
+  let (rows, next_cursor) = select_audit_log_page(pg_pool, workspace_id, &state.params).await?;
+  let mut buf = String::new();
+  match format {
+    AuditLogExportFormat::Json => {
+      // The pages are written as a single json array
+      if state.is_first_page {
+        buf.push('[');
+      }
+      for (i, row) in rows.into_iter().enumerate() {
+        if !state.is_first_page || i > 0 {
+          buf.push(',');
+        }
+        buf.push_str(&serde_json::to_string(&AFAuditLogEntry::try_from(row)?)?);
+      }
+      if next_cursor.is_none() {
+        buf.push(']');
+      }
+    },
+    AuditLogExportFormat::Csv => {
+      if state.is_first_page {
+        buf.push_str(CSV_HEADER);
+      }
+      for row in rows {
+        let entry = AFAuditLogEntry::try_from(row)?;
+        let fields = [
+          entry.id.to_string(),
+          entry.created_at.to_rfc3339(),
+          entry
+            .actor_uid
+            .map(|uid| uid.to_string())
+            .unwrap_or_default(),
+          entry
+            .workspace_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+          entry.action.as_str().to_string(),
+          csv_field(&entry.target),
+          csv_field(entry.ip.as_deref().unwrap_or_default()),
+          csv_field(entry.user_agent.as_deref().unwrap_or_default()),
+        ];
+        buf.push_str(&fields.join(","));
+        buf.push('\n');
+      }
+    },
+  }
+  Ok((Bytes::from(buf), next_cursor))
+}
+
+/// Quotes the field if needed. Fields that a spreadsheet would evaluate as a formula are
+/// prefixed with a `'`, since the user agent and the target are controlled by the clients.
+fn csv_field(value: &str) -> String {
+  let value = if value.starts_with(['=', '+', '-', '@']) {
+    format!("'{}", value)
+  } else {
+    value.to_string()
+  };
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::csv_field;
+
+  #[test]
+  fn csv_field_test() {
+    assert_eq!(csv_field("hello"), "hello");
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    assert_eq!(csv_field("=SUM(A1)"), "'=SUM(A1)");
+    assert_eq!(csv_field("@cmd,x"), "\"'@cmd,x\"");
+  }
+}
@@ -1,22 +1,29 @@
 use crate::biz::collab::member_listener::{CollabMemberAction, CollabMemberChange};
+use crate::biz::collab::parent_listener::CollabParentChange;
+use crate::biz::collab::share_link_listener::ShareLinkRevoke;
 use crate::biz::workspace::access_control::WorkspaceAccessControl;
 use crate::middleware::access_control_mw::{AccessResource, HttpAccessControlService};
+use crate::middleware::access_policy::{AccessDeniedReason, AccessRequirement};
+use actix::Recipient;
 use actix_router::{Path, Url};
 use actix_web::http::Method;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use database::collab::CollabStorageAccessControl;
 use database::user::select_uid_from_uuid;
-use database_entity::dto::{AFAccessLevel, AFRole};
+use database_entity::dto::AFAccessLevel;
 use database_entity::error::DatabaseError;
+use realtime::client::RealtimeUserImpl;
 use realtime::collaborate::{CollabAccessControl, CollabUserId};
+use realtime::entities::CloseSession;
 use shared_entity::app_error::AppError;
 use shared_entity::error_code::ErrorCode;
 use sqlx::PgPool;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use tokio::sync::{broadcast, RwLock};
-use tracing::{instrument, warn};
+use tracing::{instrument, trace, warn};
 use uuid::Uuid;
 
 /// Represents the access level of a collaboration object identified by its OID.
@@ -37,11 +44,53 @@ type MemberStatusByUid = HashMap<i64, CollabMemberStatusByOid>;
 /// The cache will be updated after the user's access level for a collaboration object is changed.
 /// The change is broadcasted by the `CollabMemberListener` or set by the [CollabAccessControlImpl::update_member] method.
 ///
+/// A user that is not a member of a collab inherits the access level of the nearest parent the
+/// user is a member of. Being a member of the collab itself overrides the inherited access level,
+/// in both directions. The inherited access levels are dropped from the cache whenever a member or
+/// a parent changes, since a single change can affect the whole subtree.
+///
 /// TODO(nathan): broadcast the member access level changes to all connected devices
 ///
 pub struct CollabAccessControlImpl {
   pg_pool: PgPool,
   member_status_by_uid: Arc<RwLock<MemberStatusByUid>>,
+  /// The anonymous viewers of share links that are connected over the websocket.
+  /// - Key: the uid assigned to the viewer when it connected.
+  share_viewer_by_uid: Arc<RwLock<HashMap<i64, ShareViewer>>>,
+}
+
+/// An anonymous user that opened a collab object with a share link. The viewer can only access
+/// the object of the link, with the access level of the link.
+#[derive(Clone, Debug)]
+pub struct ShareViewer {
+  pub link_id: Uuid,
+  pub oid: String,
+  pub access_level: AFAccessLevel,
+  /// The uid of the user that created the link.
+  pub created_by: i64,
+  pub expires_at: Option<DateTime<Utc>>,
+  /// The viewer is forgotten once its websocket session is dropped.
+  pub user: Weak<RealtimeUserImpl>,
+  /// The websocket session of the viewer, which is closed when the link is revoked.
+  pub session: Recipient<CloseSession>,
+}
+
+impl ShareViewer {
+  fn check_access(&self, uid: &i64, oid: &str) -> Result<(), AppError> {
+    if self.oid != oid {
+      return Err(AppError::new(
+        ErrorCode::NotEnoughPermissions,
+        format!("share viewer:{} can't access collab:{}", uid, oid),
+      ));
+    }
+    if matches!(self.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+      return Err(AppError::new(
+        ErrorCode::NotEnoughPermissions,
+        format!("the share link of viewer:{} is expired", uid),
+      ));
+    }
+    Ok(())
+  }
 }
 
 #[derive(Clone, Debug)]
@@ -52,20 +101,43 @@ enum MemberStatus {
   Deleted,
   /// The user is the member of the collab
   Valid(AFAccessLevel),
+  /// The user is not the member of the collab, but is the member of one of its parents
+  Inherited(AFAccessLevel),
 }
 
 impl CollabAccessControlImpl {
-  pub fn new(pg_pool: PgPool, listener: broadcast::Receiver<CollabMemberChange>) -> Self {
+  pub fn new(
+    pg_pool: PgPool,
+    listener: broadcast::Receiver<CollabMemberChange>,
+    parent_listener: broadcast::Receiver<CollabParentChange>,
+    share_link_listener: broadcast::Receiver<ShareLinkRevoke>,
+  ) -> Self {
     let member_status_by_uid = Arc::new(RwLock::new(HashMap::new()));
+    let share_viewer_by_uid = Arc::new(RwLock::new(HashMap::new()));
 
     // Listen to the changes of the collab member and update the memory cache
     spawn_listen_on_collab_member_change(listener, pg_pool.clone(), member_status_by_uid.clone());
+    spawn_listen_on_collab_parent_change(parent_listener, member_status_by_uid.clone());
+    spawn_listen_on_share_link_revoke(share_link_listener, share_viewer_by_uid.clone());
     Self {
       pg_pool,
       member_status_by_uid,
+      share_viewer_by_uid,
     }
   }
 
+  pub async fn add_share_viewer(&self, uid: i64, viewer: ShareViewer) {
+    let mut viewers = self.share_viewer_by_uid.write().await;
+    viewers.retain(|_, viewer| viewer.user.strong_count() > 0);
+    viewers.insert(uid, viewer);
+  }
+
+  /// Removes the viewers that opened the link and closes their connections, without waiting for
+  /// the PostgreSQL notification.
+  pub async fn remove_share_link_viewers(&self, link_id: &Uuid) {
+    remove_share_link_viewers(link_id, &self.share_viewer_by_uid).await;
+  }
+
   /// The member's access level may be altered by PostgreSQL notifications. However, there are instances
   /// where these notifications aren't received promptly, leading to potential inconsistencies in the user's access level.
   /// Therefore, it's essential to update the user's access level in the cache whenever there's a change.
@@ -74,19 +146,41 @@ impl CollabAccessControlImpl {
   }
 
   pub async fn remove_member(&self, uid: &i64, oid: &str) {
-    if let Some(inner_map) = self.member_status_by_uid.write().await.get_mut(uid) {
-      if let Entry::Occupied(mut entry) = inner_map.entry(oid.to_string()) {
-        entry.insert(MemberStatus::Deleted);
-      }
+    if let Err(err) =
+      remove_collab_member_status(uid, oid, &self.pg_pool, &self.member_status_by_uid).await
+    {
+      warn!(
+        "Failed to remove the collab member status of user:{} for collab:{}: {}",
+        uid, oid, err
+      );
     }
   }
 
+  /// Drops the inherited access levels after the parent of a collab is changed. Like
+  /// [Self::update_member], it doesn't wait for the PostgreSQL notification.
+  pub async fn on_parent_changed(&self) {
+    forget_inherited_member_status(None, &self.member_status_by_uid).await;
+  }
+
   #[inline]
   async fn get_user_collab_access_level(
     &self,
     uid: &i64,
     oid: &str,
   ) -> Result<AFAccessLevel, AppError> {
+    if let Some(viewer) = self.share_viewer_by_uid.read().await.get(uid) {
+      viewer.check_access(uid, oid)?;
+      return Ok(viewer.access_level.clone());
+    }
+    // The negative uids are only assigned to the share viewers, so the viewer's link was revoked.
+    // It must not fall through to the member lookup, which treats a missing record as allowed.
+    if *uid < 0 {
+      return Err(AppError::new(
+        ErrorCode::NotEnoughPermissions,
+        format!("the share link of viewer:{} is revoked", uid),
+      ));
+    }
+
     let member_status = self
       .member_status_by_uid
       .read()
@@ -107,7 +201,7 @@ impl CollabAccessControlImpl {
         ErrorCode::NotEnoughPermissions,
         format!("user:{} is not a member of collab:{}", uid, oid),
       )),
-      MemberStatus::Valid(access_level) => Ok(access_level),
+      MemberStatus::Valid(access_level) | MemberStatus::Inherited(access_level) => Ok(access_level),
     }
   }
 }
@@ -122,6 +216,7 @@ fn spawn_listen_on_collab_member_change(
       match change.action_type {
         CollabMemberAction::INSERT | CollabMemberAction::UPDATE => {
           if let (Some(oid), Some(uid)) = (change.new_oid(), change.new_uid()) {
+            forget_inherited_member_status(Some(uid), &member_status_by_uid).await;
             if let Err(err) =
               reload_collab_member_status_from_db(uid, oid, &pg_pool, &member_status_by_uid).await
             {
@@ -136,8 +231,13 @@ fn spawn_listen_on_collab_member_change(
         },
         CollabMemberAction::DELETE => {
           if let (Some(oid), Some(uid)) = (change.old_oid(), change.old_uid()) {
-            if let Some(inner_map) = member_status_by_uid.write().await.get_mut(uid) {
-              inner_map.insert(oid.to_string(), MemberStatus::Deleted);
+            if let Err(err) =
+              remove_collab_member_status(uid, oid, &pg_pool, &member_status_by_uid).await
+            {
+              warn!(
+                "Failed to remove the collab member status: {:?}, error: {}",
+                change, err
+              );
             }
           } else {
             warn!("The oid or uid is None")
@@ -148,6 +248,65 @@ fn spawn_listen_on_collab_member_change(
   });
 }
 
+fn spawn_listen_on_share_link_revoke(
+  mut listener: broadcast::Receiver<ShareLinkRevoke>,
+  share_viewer_by_uid: Arc<RwLock<HashMap<i64, ShareViewer>>>,
+) {
+  tokio::spawn(async move {
+    while let Ok(change) = listener.recv().await {
+      remove_share_link_viewers(&change.id, &share_viewer_by_uid).await;
+    }
+  });
+}
+
+async fn remove_share_link_viewers(
+  link_id: &Uuid,
+  share_viewer_by_uid: &Arc<RwLock<HashMap<i64, ShareViewer>>>,
+) {
+  share_viewer_by_uid.write().await.retain(|uid, viewer| {
+    if &viewer.link_id != link_id {
+      return true;
+    }
+    trace!("close the connection of share viewer:{}", uid);
+    viewer.session.do_send(CloseSession {
+      reason: "the share link is revoked".to_string(),
+    });
+    false
+  });
+}
+
+fn spawn_listen_on_collab_parent_change(
+  mut listener: broadcast::Receiver<CollabParentChange>,
+  member_status_by_uid: Arc<RwLock<MemberStatusByUid>>,
+) {
+  tokio::spawn(async move {
+    while listener.recv().await.is_ok() {
+      forget_inherited_member_status(None, &member_status_by_uid).await;
+    }
+  });
+}
+
+/// Drops the inherited access levels of the user, or of all the users if `uid` is None. The
+/// [MemberStatus::Deleted] entries are dropped as well, since the user may now inherit an access
+/// level on those collabs.
+async fn forget_inherited_member_status(
+  uid: Option<&i64>,
+  member_status_by_uid: &Arc<RwLock<MemberStatusByUid>>,
+) {
+  let is_member = |status: &MemberStatus| matches!(status, MemberStatus::Valid(_));
+  let mut outer_map = member_status_by_uid.write().await;
+  match uid {
+    None => outer_map
+      .values_mut()
+      .for_each(|inner_map| inner_map.retain(|_, status| is_member(status))),
+    Some(uid) => {
+      if let Some(inner_map) = outer_map.get_mut(uid) {
+        inner_map.retain(|_, status| is_member(status));
+      }
+    },
+  }
+}
+
 #[inline]
 async fn update_collab_member_status(
   uid: &i64,
@@ -155,11 +314,34 @@ async fn update_collab_member_status(
   access_level: AFAccessLevel,
   member_status_by_uid: &Arc<RwLock<MemberStatusByUid>>,
 ) {
+  forget_inherited_member_status(Some(uid), member_status_by_uid).await;
   let mut outer_map = member_status_by_uid.write().await;
   let inner_map = outer_map.entry(*uid).or_insert_with(HashMap::new);
   inner_map.insert(oid.to_string(), MemberStatus::Valid(access_level));
 }
 
+/// Marks the user as no longer a member of the collab. The user keeps the access level inherited
+/// from the parents of the collab, if any.
+async fn remove_collab_member_status(
+  uid: &i64,
+  oid: &str,
+  pg_pool: &PgPool,
+  member_status_by_uid: &Arc<RwLock<MemberStatusByUid>>,
+) -> Result<(), AppError> {
+  forget_inherited_member_status(Some(uid), member_status_by_uid).await;
+  let status =
+    match database::collab::select_inherited_collab_access_level(uid, oid, pg_pool).await? {
+      None => MemberStatus::Deleted,
+      Some(access_level) => MemberStatus::Inherited(access_level),
+    };
+  if let Some(inner_map) = member_status_by_uid.write().await.get_mut(uid) {
+    if let Entry::Occupied(mut entry) = inner_map.entry(oid.to_string()) {
+      entry.insert(status);
+    }
+  }
+  Ok(())
+}
+
 #[inline]
 async fn reload_collab_member_status_from_db(
   uid: &i64,
@@ -167,15 +349,22 @@ async fn reload_collab_member_status_from_db(
   pg_pool: &PgPool,
   member_status_by_uid: &Arc<RwLock<MemberStatusByUid>>,
 ) -> Result<MemberStatus, AppError> {
-  let member = database::collab::select_collab_member(uid, oid, pg_pool).await?;
-  let status = MemberStatus::Valid(member.permission.access_level.clone());
-  update_collab_member_status(
-    uid,
-    oid,
-    member.permission.access_level,
-    member_status_by_uid,
-  )
-  .await;
+  let status = match database::collab::select_collab_member(uid, oid, pg_pool).await {
+    Ok(member) => MemberStatus::Valid(member.permission.access_level),
+    Err(err) if err.is_record_not_found() => {
+      match database::collab::select_inherited_collab_access_level(uid, oid, pg_pool).await? {
+        None => return Err(err.into()),
+        Some(access_level) => MemberStatus::Inherited(access_level),
+      }
+    },
+    Err(err) => return Err(err.into()),
+  };
+  member_status_by_uid
+    .write()
+    .await
+    .entry(*uid)
+    .or_insert_with(HashMap::new)
+    .insert(oid.to_string(), status.clone());
   Ok(status)
 }
 
@@ -254,6 +443,20 @@ impl CollabAccessControl for CollabAccessControlImpl {
         .is_ok(),
     )
   }
+
+  async fn get_storage_uid(&self, uid: &i64, oid: &str) -> Result<i64, Self::Error> {
+    match self.share_viewer_by_uid.read().await.get(uid) {
+      None if *uid < 0 => Err(AppError::new(
+        ErrorCode::NotEnoughPermissions,
+        format!("the share link of viewer:{} is revoked", uid),
+      )),
+      None => Ok(*uid),
+      Some(viewer) => {
+        viewer.check_access(uid, oid)?;
+        Ok(viewer.created_by)
+      },
+    }
+  }
 }
 
 #[derive(Clone)]
@@ -273,25 +476,20 @@ where
     &self,
     oid: &str,
     user_uuid: &Uuid,
-    method: Method,
+    requirement: &AccessRequirement,
     _path: Path<Url>,
-  ) -> Result<(), AppError> {
-    let can_access = self
+  ) -> Result<(), AccessDeniedReason> {
+    match self
       .0
-      .can_access_http_method(CollabUserId::UserUuid(user_uuid), oid, &method)
+      .get_collab_access_level(CollabUserId::UserUuid(user_uuid), oid)
       .await
-      .map_err(AppError::from)?;
-
-    if !can_access {
-      return Err(AppError::new(
-        ErrorCode::NotEnoughPermissions,
-        format!(
-          "Not enough permissions to access the collab: {} with http method: {}",
-          oid, method
-        ),
-      ));
+      .map_err(AppError::from)
+    {
+      Ok(level) => requirement.check_access_level(&level),
+      // The collab doesn't exist yet, the user is creating it.
+      Err(err) if err.is_record_not_found() => Ok(()),
+      Err(err) => Err(AccessDeniedReason::NotMember(err.message.to_string())),
     }
-    Ok(())
   }
 }
 
@@ -330,16 +528,20 @@ where
     Ok(level)
   }
 
-  async fn get_user_role(&self, uid: &i64, workspace_id: &str) -> Result<AFRole, DatabaseError> {
+  async fn get_workspace_access_level(
+    &self,
+    uid: &i64,
+    workspace_id: &str,
+  ) -> Result<AFAccessLevel, DatabaseError> {
     self
       .workspace_access_control
-      .get_role_from_uid(uid, &workspace_id.parse()?)
+      .get_access_level_from_uid(uid, &workspace_id.parse()?)
       .await
       .map_err(|err| {
         app_err_to_database_error(
           err,
           format!(
-            "failed to get the role of the user:{} in workspace:{}",
+            "failed to get the access level of the user:{} in workspace:{}",
             uid, workspace_id
           ),
         )
@@ -1,4 +1,6 @@
 pub mod access_control;
 pub mod member_listener;
 pub mod ops;
+pub mod parent_listener;
+pub mod share_link_listener;
 pub mod storage;
@@ -1,12 +1,20 @@
+use crate::component::auth::compute_hash_password;
+use crate::component::auth::share_link::generate_share_token;
+use crate::telemetry::spawn_blocking_with_tracing;
+use anyhow::Context;
+use database::audit::insert_audit_log;
 use database::user;
 
 use database_entity::dto::{
-  AFCollabMember, AFCollabSnapshots, CollabMemberIdentify, DeleteCollabParams,
-  InsertCollabMemberParams, InsertCollabParams, QueryCollabMembers, QueryObjectSnapshotParams,
-  QuerySnapshotParams, UpdateCollabMemberParams,
+  AFAccessLevel, AFAuditAction, AFCollabMember, AFCollabParent, AFCollabSnapshots, AFShareLink,
+  AFShareLinkCreated, AFShareLinks, AuditActor, CollabMemberIdentify, CreateShareLinkParams,
+  DeleteCollabParams, InsertCollabMemberParams, InsertCollabParams, QueryCollabMembers,
+  QueryObjectSnapshotParams, QuerySnapshotParams, SetCollabParentParams, UpdateCollabMemberParams,
 };
+use secrecy::ExposeSecret;
 use shared_entity::{app_error::AppError, error_code::ErrorCode};
 use sqlx::{types::Uuid, PgPool};
+use std::ops::DerefMut;
 use tracing::trace;
 use validator::Validate;
 
@@ -55,11 +63,22 @@ pub async fn get_all_collab_snapshot(
 }
 pub async fn delete_collab(
   pg_pool: &PgPool,
-  _user_uuid: &Uuid,
+  actor: &AuditActor,
   params: &DeleteCollabParams,
 ) -> Result<(), AppError> {
   params.validate()?;
-  database::collab::delete_collab(pg_pool, &params.object_id).await?;
+  let workspace_id = Uuid::parse_str(&params.workspace_id)?;
+  let mut txn = pg_pool.begin().await?;
+  database::collab::delete_collab(txn.deref_mut(), &params.object_id).await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(&workspace_id),
+    AFAuditAction::DeleteCollab,
+    &params.object_id,
+  )
+  .await?;
+  txn.commit().await?;
   Ok(())
 }
 
@@ -129,3 +148,199 @@ pub async fn get_collab_member_list(
   let collab_member = database::collab::select_collab_members(&params.object_id, pg_pool).await?;
   Ok(collab_member)
 }
+
+/// Publishes the collab object with a new share link. The plaintext token is only returned here.
+pub async fn create_share_link(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+  workspace_id: &Uuid,
+  object_id: &str,
+  params: CreateShareLinkParams,
+) -> Result<AFShareLinkCreated, AppError> {
+  params.validate()?;
+  let uid = user::select_uid_from_uuid(pg_pool, user_uuid).await?;
+  // The access control lets the requests on missing objects through, so check the membership
+  // here as well.
+  require_full_access(pg_pool, &uid, object_id).await?;
+
+  let password_hash = match params.password {
+    None => None,
+    Some(password) => {
+      let hash = spawn_blocking_with_tracing(move || compute_hash_password(password.as_bytes()))
+        .await
+        .context("Failed to hash password")??;
+      Some(hash.expose_secret().clone())
+    },
+  };
+
+  let (token, token_hash) = generate_share_token();
+  let mut txn = pg_pool.begin().await?;
+  let row = database::share_link::insert_share_link(
+    txn.deref_mut(),
+    &Uuid::new_v4(),
+    object_id,
+    workspace_id,
+    params.collab_type.value(),
+    &token_hash,
+    params.access_level.into(),
+    password_hash.as_deref(),
+    &uid,
+    params.expires_at,
+  )
+  .await?
+  .ok_or_else(|| {
+    AppError::new(
+      ErrorCode::RecordNotFound,
+      format!(
+        "collab:{} of type {:?} is not found in workspace:{}",
+        object_id, params.collab_type, workspace_id
+      ),
+    )
+  })?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(workspace_id),
+    AFAuditAction::CreateShareLink,
+    object_id,
+  )
+  .await?;
+  txn.commit().await?;
+
+  Ok(AFShareLinkCreated {
+    token,
+    info: AFShareLink::try_from(row)?,
+  })
+}
+
+pub async fn get_share_links(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<AFShareLinks, AppError> {
+  let links = database::share_link::select_share_links(pg_pool, workspace_id, object_id)
+    .await?
+    .into_iter()
+    .map(AFShareLink::try_from)
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(AFShareLinks(links))
+}
+
+/// Deletes the share link. Returns [ErrorCode::RecordNotFound] if the object doesn't have a link
+/// with the given id.
+pub async fn revoke_share_link(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  workspace_id: &Uuid,
+  object_id: &str,
+  link_id: &Uuid,
+) -> Result<(), AppError> {
+  let mut txn = pg_pool.begin().await?;
+  if !database::share_link::delete_share_link(txn.deref_mut(), workspace_id, object_id, link_id)
+    .await?
+  {
+    return Err(AppError::new(
+      ErrorCode::RecordNotFound,
+      format!("share link:{} is not found", link_id),
+    ));
+  }
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(workspace_id),
+    AFAuditAction::RevokeShareLink,
+    object_id,
+  )
+  .await?;
+  txn.commit().await?;
+  Ok(())
+}
+
+/// Sets the parent of the collab object. The members of the parent inherit their access level
+/// on the object. The user must be able to read the parent, so an object can't be moved under a
+/// parent the user doesn't know about.
+pub async fn set_collab_parent(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+  workspace_id: &Uuid,
+  object_id: &str,
+  params: &SetCollabParentParams,
+) -> Result<AFCollabParent, AppError> {
+  params.validate()?;
+  let uid = user::select_uid_from_uuid(pg_pool, user_uuid).await?;
+  require_full_access(pg_pool, &uid, object_id).await?;
+  if get_user_access_level(pg_pool, &uid, &params.parent_id)
+    .await?
+    .is_none()
+  {
+    return Err(AppError::new(
+      ErrorCode::NotEnoughPermissions,
+      format!("user:{} can't access collab:{}", uid, params.parent_id),
+    ));
+  }
+
+  database::collab::upsert_collab_parent(pg_pool, workspace_id, object_id, &params.parent_id)
+    .await?;
+  Ok(AFCollabParent {
+    object_id: object_id.to_string(),
+    parent_id: Some(params.parent_id.clone()),
+  })
+}
+
+pub async fn get_collab_parent(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<AFCollabParent, AppError> {
+  let parent_id = database::collab::select_collab_parent(pg_pool, workspace_id, object_id).await?;
+  Ok(AFCollabParent {
+    object_id: object_id.to_string(),
+    parent_id,
+  })
+}
+
+/// Removes the parent of the collab object. Returns [ErrorCode::RecordNotFound] if the object
+/// doesn't have a parent.
+pub async fn remove_collab_parent(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<(), AppError> {
+  let uid = user::select_uid_from_uuid(pg_pool, user_uuid).await?;
+  require_full_access(pg_pool, &uid, object_id).await?;
+  if !database::collab::delete_collab_parent(pg_pool, workspace_id, object_id).await? {
+    return Err(AppError::new(
+      ErrorCode::RecordNotFound,
+      format!("collab:{} doesn't have a parent", object_id),
+    ));
+  }
+  Ok(())
+}
+
+/// Returns the access level of the user in the collab, either as a member of the collab or
+/// inherited from its parents. Returns None if the user can't access the collab.
+async fn get_user_access_level(
+  pg_pool: &PgPool,
+  uid: &i64,
+  oid: &str,
+) -> Result<Option<AFAccessLevel>, AppError> {
+  match database::collab::select_collab_member(uid, oid, pg_pool).await {
+    Ok(member) => Ok(Some(member.permission.access_level)),
+    Err(err) if err.is_record_not_found() => {
+      Ok(database::collab::select_inherited_collab_access_level(uid, oid, pg_pool).await?)
+    },
+    Err(err) => Err(err.into()),
+  }
+}
+
+async fn require_full_access(pg_pool: &PgPool, uid: &i64, oid: &str) -> Result<(), AppError> {
+  match get_user_access_level(pg_pool, uid, oid).await? {
+    Some(AFAccessLevel::FullAccess) => Ok(()),
+    _ => Err(AppError::new(
+      ErrorCode::NotEnoughPermissions,
+      format!("user:{} doesn't have full access to collab:{}", uid, oid),
+    )),
+  }
+}
@@ -0,0 +1,16 @@
+use crate::biz::collab::member_listener::CollabMemberAction;
+use crate::biz::pg_listener::PostgresDBListener;
+use database_entity::pg_row::AFCollabParentRow;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CollabParentChange {
+  /// The old will be None if the object didn't have a parent
+  pub old: Option<AFCollabParentRow>,
+  /// The new will be None if the parent is removed
+  pub new: Option<AFCollabParentRow>,
+  /// Represent the action of the database. Such as INSERT, UPDATE, DELETE
+  pub action_type: CollabMemberAction,
+}
+
+pub type CollabParentListener = PostgresDBListener<CollabParentChange>;
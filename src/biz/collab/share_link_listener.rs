@@ -0,0 +1,12 @@
+use crate::biz::pg_listener::PostgresDBListener;
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Notified when a share link is revoked, or deleted along with its workspace or its creator.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ShareLinkRevoke {
+  /// The id of the share link
+  pub id: Uuid,
+}
+
+pub type ShareLinkRevokeListener = PostgresDBListener<ShareLinkRevoke>;
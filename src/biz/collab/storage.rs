@@ -112,19 +112,19 @@ where
       level.can_write()
     } else {
       // If the collab doesn't exist, check if the user has enough permissions to create collab.
-      // If the user is the owner or member of the workspace, the user can create collab.
-      let role = self
+      // If the user's role in the workspace can write, the user can create collab.
+      let level = self
         .access_control
-        .get_user_role(uid, &params.workspace_id)
+        .get_workspace_access_level(uid, &params.workspace_id)
         .await?;
       event!(
         tracing::Level::TRACE,
         "[{:?}]user:{} try to insert new collab:{}",
-        role,
+        level,
         uid,
         params.object_id
       );
-      role.can_create_collab()
+      level.can_write()
     };
 
     if !has_permission {
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::Utc;
+use database::file::bucket_s3_impl::S3BucketStorage;
+use database::job_lock::JobLock;
+use database::resource_usage::{
+  delete_blob_gc_marks, get_all_workspace_blob_metadata, insert_blob_gc_marks,
+  select_all_collab_blobs, select_blob_gc_marks, select_workspace_ids_with_blobs,
+};
+use database_entity::dto::AuditActor;
+use shared_entity::app_error::AppError;
+use shared_entity::dto::workspace_dto::BlobGCReport;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use tokio_stream::StreamExt;
+use tracing::{error, info, instrument};
+
+use crate::config::config::BlobGCSetting;
+use crate::state::AppState;
+
+/// The length of a file id without its padding. A file id is the URL safe base64 encoding of the
+/// sha256 hash of the blob, which is 43 characters followed by a single `=`.
+const FILE_ID_LEN: usize = 43;
+
+pub const BLOB_GC_JOB: &str = "blob_gc";
+
+const UPLOAD_CLEANUP_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Spawns a task that periodically deletes the blobs that are no longer referenced by any collab.
+pub fn spawn_blob_gc(state: AppState) {
+  let setting = state.config.blob_gc.clone();
+  if !setting.enable {
+    return;
+  }
+
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(setting.interval_secs));
+    // The first tick completes immediately, skip it so that the server finishes starting up first.
+    interval.tick().await;
+    loop {
+      interval.tick().await;
+      // Only one server collects the blobs at a time.
+      let lock = match JobLock::try_acquire(&state.pg_pool, BLOB_GC_JOB).await {
+        Ok(Some(lock)) => lock,
+        Ok(None) => continue,
+        Err(err) => {
+          error!("failed to acquire the blob gc lock: {}", err);
+          continue;
+        },
+      };
+      match collect_all_workspaces(&state.pg_pool, &state.bucket_storage, &setting, false).await {
+        Ok(reports) => {
+          let deleted = reports.iter().map(|r| r.deleted.len()).sum::<usize>();
+          let reclaimed_size = reports.iter().map(|r| r.reclaimed_size).sum::<i64>();
+          info!(
+            "blob gc deleted {} blobs, reclaimed {} bytes",
+            deleted, reclaimed_size
+          );
+        },
+        Err(err) => error!("blob gc failed: {}", err),
+      }
+      if let Err(err) = lock.release().await {
+        error!("failed to release the blob gc lock: {}", err);
+      }
+    }
+  });
+}
+
+/// Spawns a task that periodically deletes the pre-signed uploads that were never completed.
+/// Every upload is claimed by deleting its row, so the servers don't need to take turns.
+pub fn spawn_blob_upload_cleanup(state: AppState) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(UPLOAD_CLEANUP_INTERVAL_SECS));
+    loop {
+      interval.tick().await;
+      match state.bucket_storage.delete_expired_uploads().await {
+        Ok(0) => {},
+        Ok(deleted) => info!("deleted {} expired blob uploads", deleted),
+        Err(err) => error!("failed to delete the expired blob uploads: {}", err),
+      }
+    }
+  });
+}
+
+/// Runs the garbage collector on every workspace that owns blobs. When `dry_run` is true,
+/// nothing is marked or deleted and the reports describe what would happen.
+pub async fn collect_all_workspaces(
+  pg_pool: &PgPool,
+  bucket_storage: &S3BucketStorage,
+  setting: &BlobGCSetting,
+  dry_run: bool,
+) -> Result<Vec<BlobGCReport>, AppError> {
+  let referenced = select_referenced_file_ids(pg_pool).await?;
+  let workspace_ids = select_workspace_ids_with_blobs(pg_pool).await?;
+  let mut reports = Vec::with_capacity(workspace_ids.len());
+  for workspace_id in workspace_ids {
+    let report = collect_unreferenced_blobs(
+      pg_pool,
+      bucket_storage,
+      setting,
+      &workspace_id,
+      &referenced,
+      dry_run,
+    )
+    .await?;
+    reports.push(report);
+  }
+  Ok(reports)
+}
+
+/// Collects the unreferenced blobs of the workspace. Deleting a blob only removes it from this
+/// workspace, see [S3BucketStorage::delete_blob].
+#[instrument(level = "debug", skip(pg_pool, bucket_storage, setting), err)]
+pub async fn collect_workspace_blobs(
+  pg_pool: &PgPool,
+  bucket_storage: &S3BucketStorage,
+  setting: &BlobGCSetting,
+  workspace_id: &Uuid,
+  dry_run: bool,
+) -> Result<BlobGCReport, AppError> {
+  let referenced = select_referenced_file_ids(pg_pool).await?;
+  collect_unreferenced_blobs(
+    pg_pool,
+    bucket_storage,
+    setting,
+    workspace_id,
+    &referenced,
+    dry_run,
+  )
+  .await
+}
+
+/// Returns the file ids that appear in any collab or collab snapshot of any workspace, including
+/// the deleted collabs. A document can embed the url of a blob owned by another workspace, so the
+/// references aren't limited to the workspace that owns the blob.
+///
+/// The file ids are found with [extract_file_ids], which is best-effort: the collabs aren't
+/// decoded, so a file id that was typed into a text piece by piece, and is split across several
+/// items of the encoded update, isn't found. The blobs inserted by the clients are stored as a
+/// single string, e.g. the url of an image block, which is always found. The long grace period
+/// of [BlobGCSetting] leaves time to notice and restore the blobs that are missed.
+async fn select_referenced_file_ids(pg_pool: &PgPool) -> Result<HashSet<String>, AppError> {
+  let mut referenced = HashSet::new();
+  let mut collab_blobs = select_all_collab_blobs(pg_pool);
+  while let Some(data) = collab_blobs.next().await {
+    referenced.extend(extract_file_ids(&data?));
+  }
+  Ok(referenced)
+}
+
+/// Unreferenced blobs are marked first and only deleted once they have stayed unreferenced for
+/// longer than the grace period, so a blob that is re-inserted into a document, for example by
+/// undoing a deletion, is kept.
+async fn collect_unreferenced_blobs(
+  pg_pool: &PgPool,
+  bucket_storage: &S3BucketStorage,
+  setting: &BlobGCSetting,
+  workspace_id: &Uuid,
+  referenced: &HashSet<String>,
+  dry_run: bool,
+) -> Result<BlobGCReport, AppError> {
+  let grace_period = chrono::Duration::seconds(setting.grace_period_secs as i64);
+  let deadline = Utc::now() - grace_period;
+  let marks = select_blob_gc_marks(pg_pool, workspace_id).await?;
+  let mut report = BlobGCReport {
+    workspace_id: *workspace_id,
+    marked: vec![],
+    unmarked: vec![],
+    deleted: vec![],
+    reclaimed_size: 0,
+  };
+
+  let mut deleted_size = vec![];
+  for metadata in get_all_workspace_blob_metadata(pg_pool, workspace_id).await? {
+    let is_referenced = referenced.contains(&metadata.file_id);
+    match marks.get(&metadata.file_id) {
+      Some(_) if is_referenced => report.unmarked.push(metadata.file_id),
+      Some(marked_at) if *marked_at < deadline => {
+        deleted_size.push(metadata.file_size);
+        report.deleted.push(metadata.file_id);
+      },
+      Some(_) => {},
+      // Blobs that were uploaded recently might not be saved in the collab yet.
+      None if !is_referenced && metadata.modified_at < deadline => {
+        report.marked.push(metadata.file_id)
+      },
+      None => {},
+    }
+  }
+
+  if dry_run {
+    report.reclaimed_size = deleted_size.into_iter().sum();
+    return Ok(report);
+  }
+
+  insert_blob_gc_marks(pg_pool, workspace_id, &report.marked).await?;
+  delete_blob_gc_marks(pg_pool, workspace_id, &report.unmarked).await?;
+
+  let mut deleted = Vec::with_capacity(report.deleted.len());
+  for (file_id, file_size) in report.deleted.drain(..).zip(deleted_size) {
+    match bucket_storage
+      .delete_blob(&AuditActor::system(), workspace_id, &file_id)
+      .await
+    {
+      Ok(_) => {
+        report.reclaimed_size += file_size;
+        deleted.push(file_id);
+      },
+      Err(err) => error!("failed to delete unreferenced blob {}: {}", file_id, err),
+    }
+  }
+  report.deleted = deleted;
+  Ok(report)
+}
+
+/// Returns every substring of the data that looks like a file id. Collabs store blobs either by
+/// their file id or by their url, which ends with the file id, so both are found.
+fn extract_file_ids(data: &[u8]) -> HashSet<String> {
+  let is_url_safe = |b: &u8| b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_';
+  let mut file_ids = HashSet::new();
+  let mut run_start = 0;
+  for (i, byte) in data.iter().enumerate() {
+    if is_url_safe(byte) {
+      continue;
+    }
+    if *byte == b'=' && i - run_start >= FILE_ID_LEN {
+      let file_id = &data[i - FILE_ID_LEN..=i];
+      // The bytes are ASCII, so the conversion never fails.
+      if let Ok(file_id) = std::str::from_utf8(file_id) {
+        file_ids.insert(file_id.to_string());
+      }
+    }
+    run_start = i + 1;
+  }
+  file_ids
+}
+
+#[cfg(test)]
+mod tests {
+  use super::extract_file_ids;
+
+  const FILE_ID: &str = "uU0nuZNNPgilLlLX2n2r-sSE7-N6U4DukIj3rOLvzek=";
+
+  #[test]
+  fn extract_file_id_from_url() {
+    let data = format!(
+      "\x01\x7f{{\"url\":\"http://localhost:8000/api/file_storage/ws/blob/{}\"}}",
+      FILE_ID
+    );
+    let file_ids = extract_file_ids(data.as_bytes());
+    assert_eq!(file_ids.len(), 1);
+    assert!(file_ids.contains(FILE_ID));
+  }
+
+  #[test]
+  fn extract_length_prefixed_file_id() {
+    // Encoded strings are prefixed with their length, which can be an alphanumeric byte.
+    let data = format!("\x00x{}\x00", FILE_ID);
+    let file_ids = extract_file_ids(data.as_bytes());
+    assert!(file_ids.contains(FILE_ID));
+  }
+
+  #[test]
+  fn ignore_short_tokens() {
+    let file_ids = extract_file_ids(b"abc= hello world==");
+    assert!(file_ids.is_empty());
+  }
+}
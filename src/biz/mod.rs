@@ -1,5 +1,15 @@
+pub mod audit;
 pub mod collab;
+pub mod file_storage;
 pub mod pg_listener;
+pub mod snowflake;
+pub mod sso;
 pub mod user;
+pub mod user_avatar;
+pub mod user_ban;
+pub mod user_credential;
+pub mod user_data_export;
+pub mod user_deletion;
+pub mod user_session;
 pub mod utils;
 pub(crate) mod workspace;
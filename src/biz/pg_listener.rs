@@ -1,6 +1,12 @@
 use crate::biz::collab::member_listener::{CollabMemberChange, CollabMemberListener};
+use crate::biz::collab::parent_listener::{CollabParentChange, CollabParentListener};
+use crate::biz::collab::share_link_listener::{ShareLinkRevoke, ShareLinkRevokeListener};
+use crate::biz::user_ban::{UserBanChange, UserBanListener};
+use crate::biz::user_session::UserSessionListener;
 use crate::biz::workspace::member_listener::{WorkspaceMemberChange, WorkspaceMemberListener};
+use crate::biz::workspace::role_listener::{RolePermissionChange, RolePermissionListener};
 use anyhow::Error;
+use database_entity::pg_row::AFUserSessionRow;
 use serde::de::DeserializeOwned;
 use sqlx::postgres::PgListener;
 use sqlx::PgPool;
@@ -9,7 +15,12 @@ use tracing::error;
 
 pub struct PgListeners {
   workspace_member_listener: WorkspaceMemberListener,
+  role_permission_listener: RolePermissionListener,
   collab_member_listener: CollabMemberListener,
+  collab_parent_listener: CollabParentListener,
+  share_link_revoke_listener: ShareLinkRevokeListener,
+  user_session_listener: UserSessionListener,
+  user_ban_listener: UserBanListener,
 }
 
 impl PgListeners {
@@ -17,12 +28,31 @@ impl PgListeners {
     let workspace_member_listener =
       WorkspaceMemberListener::new(pg_pool, "af_workspace_member_channel").await?;
 
+    let role_permission_listener =
+      RolePermissionListener::new(pg_pool, "af_role_permissions_channel").await?;
+
     let collab_member_listener =
       CollabMemberListener::new(pg_pool, "af_collab_member_channel").await?;
 
+    let collab_parent_listener =
+      CollabParentListener::new(pg_pool, "af_collab_parent_channel").await?;
+
+    let share_link_revoke_listener =
+      ShareLinkRevokeListener::new(pg_pool, "af_collab_share_link_channel").await?;
+
+    let user_session_listener =
+      UserSessionListener::new(pg_pool, "af_user_session_channel").await?;
+
+    let user_ban_listener = UserBanListener::new(pg_pool, "af_user_ban_channel").await?;
+
     Ok(Self {
       workspace_member_listener,
+      role_permission_listener,
       collab_member_listener,
+      collab_parent_listener,
+      share_link_revoke_listener,
+      user_session_listener,
+      user_ban_listener,
     })
   }
 
@@ -30,9 +60,29 @@ impl PgListeners {
     self.workspace_member_listener.notify.subscribe()
   }
 
+  pub fn subscribe_role_permission_change(&self) -> broadcast::Receiver<RolePermissionChange> {
+    self.role_permission_listener.notify.subscribe()
+  }
+
   pub fn subscribe_collab_member_change(&self) -> broadcast::Receiver<CollabMemberChange> {
     self.collab_member_listener.notify.subscribe()
   }
+
+  pub fn subscribe_collab_parent_change(&self) -> broadcast::Receiver<CollabParentChange> {
+    self.collab_parent_listener.notify.subscribe()
+  }
+
+  pub fn subscribe_share_link_revoke(&self) -> broadcast::Receiver<ShareLinkRevoke> {
+    self.share_link_revoke_listener.notify.subscribe()
+  }
+
+  pub fn subscribe_user_session_sign_out(&self) -> broadcast::Receiver<AFUserSessionRow> {
+    self.user_session_listener.notify.subscribe()
+  }
+
+  pub fn subscribe_user_ban_change(&self) -> broadcast::Receiver<UserBanChange> {
+    self.user_ban_listener.notify.subscribe()
+  }
 }
 
 pub struct PostgresDBListener<T: Clone> {
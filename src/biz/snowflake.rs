@@ -0,0 +1,111 @@
+use anyhow::anyhow;
+use database::snowflake_node::{lease_snowflake_node, renew_snowflake_node};
+use snowflake::{Snowflake, SnowflakeError, MAX_CLOCK_BACKWARDS_MILLIS, MAX_NODE_ID};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// The node id is leased for a minute, and the lease is renewed every 20 seconds, so a couple of
+/// failed renewals don't lose it.
+const NODE_ID_LEASE_SECS: u64 = 60;
+const NODE_ID_RENEW_INTERVAL_SECS: u64 = 20;
+
+/// Leasing fails when another server leases the same node id at the same time, which is retried.
+const MAX_LEASE_ATTEMPTS: usize = 5;
+
+/// Creates the uid generator with a node id that no other running server uses. The lease of the
+/// node id is renewed for as long as the server runs, and the generator stops generating ids once
+/// the lease expires without being renewed.
+pub async fn init_snowflake(pg_pool: &PgPool) -> Result<Arc<RwLock<Snowflake>>, anyhow::Error> {
+  let instance_id = Uuid::new_v4();
+  let lease_deadline = next_lease_deadline();
+  let node_id = lease_node_id(pg_pool, &instance_id).await?;
+  info!("leased snowflake node id: {}", node_id);
+  let id_gen = Arc::new(RwLock::new(Snowflake::with_lease(node_id, lease_deadline)));
+  spawn_renew_node_id(pg_pool.clone(), instance_id, id_gen.clone());
+  Ok(id_gen)
+}
+
+/// Generates the next id. The generator is only locked while the id is generated: when the clock
+/// moved backwards a little, the lock is released while waiting for the clock to catch up.
+pub async fn next_id(id_gen: &RwLock<Snowflake>) -> Result<i64, SnowflakeError> {
+  loop {
+    let result = id_gen.write().await.next_id();
+    match result {
+      Err(SnowflakeError::ClockMovedBackwards(backwards))
+        if backwards <= MAX_CLOCK_BACKWARDS_MILLIS =>
+      {
+        tokio::time::sleep(Duration::from_millis(backwards)).await;
+      },
+      result => return result,
+    }
+  }
+}
+
+/// The lease is counted from before it's requested, so the local deadline is never later than
+/// the one recorded in the database.
+fn next_lease_deadline() -> Instant {
+  Instant::now() + Duration::from_secs(NODE_ID_LEASE_SECS)
+}
+
+async fn lease_node_id(pg_pool: &PgPool, instance_id: &Uuid) -> Result<u64, anyhow::Error> {
+  for _ in 0..MAX_LEASE_ATTEMPTS {
+    if let Some(node_id) = lease_snowflake_node(
+      pg_pool,
+      instance_id,
+      MAX_NODE_ID as i32,
+      NODE_ID_LEASE_SECS as f64,
+    )
+    .await?
+    {
+      return Ok(node_id as u64);
+    }
+  }
+  Err(anyhow!("no snowflake node id is available"))
+}
+
+fn spawn_renew_node_id(pg_pool: PgPool, instance_id: Uuid, id_gen: Arc<RwLock<Snowflake>>) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(NODE_ID_RENEW_INTERVAL_SECS));
+    // The first tick completes immediately, and the node id was just leased.
+    interval.tick().await;
+    loop {
+      interval.tick().await;
+      let node_id = id_gen.read().await.node_id();
+      let lease_deadline = next_lease_deadline();
+      match renew_snowflake_node(
+        &pg_pool,
+        node_id as i32,
+        &instance_id,
+        NODE_ID_LEASE_SECS as f64,
+      )
+      .await
+      {
+        Ok(true) => id_gen.write().await.set_lease(node_id, lease_deadline),
+        Ok(false) => {
+          // The lease expired and the node id was leased by another server, so no more ids are
+          // generated with it until a new one is leased.
+          error!("lost the lease of snowflake node id: {}", node_id);
+          id_gen.write().await.end_lease();
+          let lease_deadline = next_lease_deadline();
+          match lease_node_id(&pg_pool, &instance_id).await {
+            Ok(node_id) => {
+              info!("leased snowflake node id: {}", node_id);
+              id_gen.write().await.set_lease(node_id, lease_deadline);
+            },
+            // Retried on the next tick, since the renewal of the lost node id fails again.
+            Err(err) => error!("failed to lease a snowflake node id: {}", err),
+          }
+        },
+        // The lease expires unless one of the next renewals succeeds in time.
+        Err(err) => error!(
+          "failed to renew the lease of snowflake node id {}: {}",
+          node_id, err
+        ),
+      }
+    }
+  });
+}
@@ -0,0 +1,274 @@
+use database::audit::insert_audit_log;
+use database::sso::{
+  delete_sso_provider as delete_sso_provider_row, insert_sso_member, insert_sso_provider,
+  select_sso_member_exists, select_sso_provider_by_domain, select_sso_providers,
+};
+use database::user::select_uid_from_uuid;
+use database::workspace::{select_user_role, select_workspace};
+use database_entity::dto::{
+  AFAuditAction, AFRole, AFSsoProvider, AFSsoProviderType, AFSsoProviders, AuditActor,
+  CreateSsoProviderParams, SsoProviderConfig,
+};
+use gotrue::api::Client;
+use gotrue_entity::dto::{OAuthProvider, User};
+use shared_entity::app_error::AppError;
+use shared_entity::dto::auth_dto::SsoSignInProvider;
+use shared_entity::dto::workspace_dto::CreateWorkspaceMember;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::biz::user::gotrue_admin_token;
+use crate::biz::workspace::ops::add_workspace_members;
+use crate::config::config::GoTrueSetting;
+
+/// GoTrue names the identities of the SAML users after the id of the SAML provider.
+const SAML_IDENTITY_PROVIDER_PREFIX: &str = "sso:";
+
+/// Registers the SSO provider of the domain. The SAML identity provider is registered in GoTrue,
+/// while the OIDC provider must already be enabled in the GoTrue settings.
+#[instrument(skip(pg_pool, gotrue_client, gotrue_setting), err)]
+pub async fn create_sso_provider(
+  pg_pool: &PgPool,
+  gotrue_client: &Client,
+  gotrue_setting: &GoTrueSetting,
+  actor: &AuditActor,
+  params: CreateSsoProviderParams,
+) -> Result<AFSsoProvider, AppError> {
+  let domain = normalize_domain(&params.domain)?;
+  // Fails with RecordNotFound if the workspace doesn't exist.
+  select_workspace(pg_pool, &params.workspace_id).await?;
+  if select_sso_provider_by_domain(pg_pool, &domain)
+    .await?
+    .is_some()
+  {
+    return Err(domain_already_registered(&domain));
+  }
+
+  let settings = gotrue_client.settings().await?;
+  let (provider_type, gotrue_provider) = match params.config {
+    SsoProviderConfig::Saml {
+      metadata_url,
+      metadata_xml,
+      attribute_mapping,
+    } => {
+      if !settings.saml_enabled {
+        return Err(AppError::new(
+          ErrorCode::InvalidOAuthProvider,
+          "SAML isn't enabled in GoTrue",
+        ));
+      }
+      if metadata_url.is_none() && metadata_xml.is_none() {
+        return Err(AppError::new(
+          ErrorCode::InvalidRequestParams,
+          "either the metadata_url or the metadata_xml is required",
+        ));
+      }
+      let admin_token = gotrue_admin_token(gotrue_client, gotrue_setting).await?;
+      let gotrue_params = gotrue::params::CreateSsoProviderParams {
+        metadata_url,
+        metadata_xml,
+        attribute_mapping,
+        ..gotrue::params::CreateSsoProviderParams::saml(vec![domain.clone()])
+      };
+      let saml_provider = gotrue_client
+        .admin_create_sso_provider(&admin_token, &gotrue_params)
+        .await?;
+      (AFSsoProviderType::Saml, saml_provider.id)
+    },
+    SsoProviderConfig::Oidc { name } => {
+      if !settings
+        .external
+        .has_provider(&OAuthProvider::Oidc { name: name.clone() })
+      {
+        return Err(AppError::new(
+          ErrorCode::InvalidOAuthProvider,
+          format!("{} isn't enabled in GoTrue", name),
+        ));
+      }
+      (AFSsoProviderType::Oidc, name)
+    },
+  };
+
+  let row = insert_sso_provider(
+    pg_pool,
+    &Uuid::new_v4(),
+    &domain,
+    &params.workspace_id,
+    provider_type.as_str(),
+    &gotrue_provider,
+  )
+  .await?
+  .ok_or_else(|| domain_already_registered(&domain))?;
+  insert_audit_log(
+    pg_pool,
+    actor,
+    Some(&row.workspace_id),
+    AFAuditAction::CreateSsoProvider,
+    &domain,
+  )
+  .await?;
+  Ok(AFSsoProvider::try_from(row)?)
+}
+
+pub async fn get_sso_providers(pg_pool: &PgPool) -> Result<AFSsoProviders, AppError> {
+  let providers = select_sso_providers(pg_pool)
+    .await?
+    .into_iter()
+    .map(AFSsoProvider::try_from)
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(AFSsoProviders(providers))
+}
+
+/// Deletes the SSO provider, along with the SAML identity provider registered in GoTrue. The
+/// members who joined the workspace through the provider stay in the workspace.
+#[instrument(skip(pg_pool, gotrue_client, gotrue_setting), err)]
+pub async fn delete_sso_provider(
+  pg_pool: &PgPool,
+  gotrue_client: &Client,
+  gotrue_setting: &GoTrueSetting,
+  actor: &AuditActor,
+  provider_id: &Uuid,
+) -> Result<(), AppError> {
+  let provider = delete_sso_provider_row(pg_pool, provider_id)
+    .await?
+    .map(AFSsoProvider::try_from)
+    .transpose()?
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("sso provider:{} doesn't exist", provider_id),
+      )
+    })?;
+  if provider.provider_type == AFSsoProviderType::Saml {
+    let admin_token = gotrue_admin_token(gotrue_client, gotrue_setting).await?;
+    gotrue_client
+      .admin_delete_sso_provider(&admin_token, &provider.gotrue_provider)
+      .await?;
+  }
+  insert_audit_log(
+    pg_pool,
+    actor,
+    Some(&provider.workspace_id),
+    AFAuditAction::DeleteSsoProvider,
+    &provider.domain,
+  )
+  .await?;
+  Ok(())
+}
+
+/// Returns the provider that the user with the email signs in with.
+pub async fn discover_sso_provider(
+  pg_pool: &PgPool,
+  email: &str,
+) -> Result<SsoSignInProvider, AppError> {
+  let domain = email_domain(email)
+    .ok_or_else(|| AppError::new(ErrorCode::InvalidEmail, format!("invalid email: {}", email)))?;
+  let provider = select_sso_provider_by_domain(pg_pool, &domain)
+    .await?
+    .map(AFSsoProvider::try_from)
+    .transpose()?
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("{} doesn't have a sso provider", domain),
+      )
+    })?;
+  Ok(match provider.provider_type {
+    AFSsoProviderType::Saml => SsoSignInProvider::Saml {
+      domain: provider.domain,
+    },
+    AFSsoProviderType::Oidc => SsoSignInProvider::Oidc {
+      name: provider.gotrue_provider,
+    },
+  })
+}
+
+/// Adds the user to the workspace of the SSO provider registered for the domain of the email, if
+/// the user signed in through that provider. The users who signed in some other way, e.g. with a
+/// password, are never added: the email alone doesn't prove that the user belongs to the domain.
+///
+/// The user is only added on the first sign-in through the provider, which is recorded in
+/// `af_sso_member`, so the members who were removed from the workspace stay removed.
+#[instrument(skip_all, err)]
+pub async fn join_sso_workspace(pg_pool: &PgPool, user: &User) -> Result<(), AppError> {
+  let domain = match email_domain(&user.email) {
+    Some(domain) => domain,
+    None => return Ok(()),
+  };
+  let provider = match select_sso_provider_by_domain(pg_pool, &domain).await? {
+    Some(row) => AFSsoProvider::try_from(row)?,
+    None => return Ok(()),
+  };
+  let identity_provider = match provider.provider_type {
+    AFSsoProviderType::Saml => format!(
+      "{}{}",
+      SAML_IDENTITY_PROVIDER_PREFIX, provider.gotrue_provider
+    ),
+    AFSsoProviderType::Oidc => provider.gotrue_provider.clone(),
+  };
+  let signed_in_with_provider = user
+    .identities
+    .iter()
+    .flatten()
+    .any(|identity| identity.provider == identity_provider);
+  if !signed_in_with_provider {
+    return Ok(());
+  }
+
+  let user_uuid = Uuid::parse_str(&user.id)?;
+  let uid = select_uid_from_uuid(pg_pool, &user_uuid).await?;
+  if select_sso_member_exists(pg_pool, &provider.provider_id, &uid).await? {
+    return Ok(());
+  }
+  match select_user_role(pg_pool, &uid, &provider.workspace_id).await {
+    // Keeps the role of the existing members.
+    Ok(_) => {},
+    Err(err) if err.is_record_not_found() => {
+      add_workspace_members(
+        pg_pool,
+        &AuditActor::system(),
+        &provider.workspace_id,
+        vec![CreateWorkspaceMember {
+          email: user.email.clone(),
+          role: AFRole::Member,
+        }],
+      )
+      .await?;
+      info!(
+        "user:{} joined workspace:{} through the sso provider of {}",
+        uid, provider.workspace_id, domain
+      );
+    },
+    Err(err) => return Err(err.into()),
+  }
+  // Recorded after the user is added, so a failed join is retried on the next sign-in.
+  insert_sso_member(pg_pool, &provider.provider_id, &uid).await?;
+  Ok(())
+}
+
+fn normalize_domain(domain: &str) -> Result<String, AppError> {
+  let domain = domain.trim().trim_start_matches('@').to_lowercase();
+  if domain.is_empty() || !domain.contains('.') || domain.contains(['@', '/', ' ']) {
+    return Err(AppError::new(
+      ErrorCode::InvalidRequestParams,
+      format!("invalid domain: {}", domain),
+    ));
+  }
+  Ok(domain)
+}
+
+fn email_domain(email: &str) -> Option<String> {
+  email
+    .rsplit_once('@')
+    .map(|(_, domain)| domain.trim().to_lowercase())
+    .filter(|domain| !domain.is_empty())
+}
+
+fn domain_already_registered(domain: &str) -> AppError {
+  AppError::new(
+    ErrorCode::RecordAlreadyExists,
+    format!("{} already has a sso provider", domain),
+  )
+}
@@ -1,23 +1,67 @@
 use anyhow::{Context, Result};
 use gotrue::api::Client;
+use gotrue::grant::{Grant, PasswordGrant};
 use serde_json::json;
 use shared_entity::app_error::AppError;
 use std::ops::DerefMut;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use database::workspace::{select_user_profile, select_user_workspace, select_workspace};
-use database_entity::dto::{AFUserProfile, AFUserWorkspaceInfo, AFWorkspace};
+use database::workspace::{
+  select_user_profile, select_user_role, select_user_workspace, select_workspace,
+};
+use database_entity::dto::{
+  AFApiToken, AFApiTokenCreated, AFApiTokens, AFAuditAction, AFUserProfile, AFUserWorkspaceInfo,
+  AFWorkspace, AuditActor, CreateApiTokenParams,
+};
 
-use database::user::{create_user, is_user_exist};
-use shared_entity::dto::auth_dto::UpdateUserParams;
+use crate::biz::snowflake::next_id;
+use crate::biz::sso::join_sso_workspace;
+use crate::component::auth::api_token::generate_api_token;
+use crate::config::config::GoTrueSetting;
+use chrono::Utc;
+use database::api_token::{insert_api_token, select_api_tokens};
+use database::audit::insert_audit_log;
+use database::user::{
+  create_user, is_user_exist, select_uid_from_uuid, select_user_usages,
+  update_legacy_password_user_uuid,
+};
+use gotrue::params::AdminListUsersParams;
+use gotrue_entity::dto::User;
+use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
+use shared_entity::dto::auth_dto::{
+  AdminListUsersQuery, AdminUser, AdminUserPage, UpdateUserParams, DEFAULT_ADMIN_USER_PAGE_SIZE,
+  MAX_ADMIN_USER_PAGE_SIZE,
+};
+use shared_entity::error_code::ErrorCode;
 use snowflake::Snowflake;
 use sqlx::{types::uuid, PgPool};
+use std::collections::HashMap;
 use tokio::sync::RwLock;
-use tracing::instrument;
+use tracing::{info, instrument};
+use validator::Validate;
+
+/// Signs in as the GoTrue admin, which is required by the admin endpoints of GoTrue.
+pub async fn gotrue_admin_token(
+  gotrue_client: &Client,
+  setting: &GoTrueSetting,
+) -> Result<String, AppError> {
+  let admin_token = gotrue_client
+    .token(&Grant::Password(PasswordGrant {
+      email: setting.admin_email.clone(),
+      password: setting.admin_password.clone(),
+    }))
+    .await?;
+  Ok(admin_token.access_token)
+}
+
+/// The key of the GoTrue `app_metadata` that records the uid of the user who was moved from the
+/// deprecated local password. Only the GoTrue admin can set the `app_metadata`.
+pub(crate) const LEGACY_UID_KEY: &str = "legacy_uid";
 
 /// Verify the token from the gotrue server and create the user if it is a new user
 /// Return true if the user is a new user
+/// The user who signed in through the SSO provider of its domain joins the workspace of the provider
 ///
 #[instrument(skip_all, err)]
 pub async fn verify_token(
@@ -29,13 +73,68 @@ pub async fn verify_token(
   let user = gotrue_client.user_info(access_token).await?;
   let user_uuid = uuid::Uuid::parse_str(&user.id)?;
   let name = name_from_user_metadata(&user.user_metadata);
+  // Also completes the moves that failed after the GoTrue user was created.
+  link_legacy_user(pg_pool, &user).await?;
 
   let mut txn = pg_pool.begin().await?;
   let is_new = !is_user_exist(txn.deref_mut(), &user_uuid).await?;
   if is_new {
-    let new_uid = id_gen.write().await.next_id();
+    let new_uid = next_id(id_gen)
+      .await
+      .context("failed to generate the uid")?;
     create_user(pg_pool, new_uid, &user_uuid, &user.email, &name).await?;
   }
+  txn
+    .commit()
+    .await
+    .context("fail to commit transaction to verify token")?;
+  // The user can still sign in if the workspace of the SSO provider can't be joined, the error is
+  // logged by join_sso_workspace.
+  let _ = join_sso_workspace(pg_pool, &user).await;
+  Ok(is_new)
+}
+
+/// Creates the user of a token signed by an issuer other than GoTrue, which has no GoTrue user
+/// to look up. The user is identified by the namespaced uuid in the `sub` of the verified claims.
+/// Return true if the user is a new user
+#[instrument(skip_all, err)]
+pub async fn verify_external_token(
+  pg_pool: &PgPool,
+  id_gen: &Arc<RwLock<Snowflake>>,
+  claims: &GoTrueJWTClaims,
+) -> Result<bool, AppError> {
+  if claims.email.is_empty() {
+    return Err(AppError::new(
+      ErrorCode::InvalidRequestParams,
+      "the token must have the email of the user",
+    ));
+  }
+  let user_uuid = claims
+    .sub
+    .as_deref()
+    .map(Uuid::parse_str)
+    .transpose()?
+    .ok_or(AppError::new(
+      ErrorCode::InvalidRequestParams,
+      "the token must have the subject of the user",
+    ))?;
+  let name = name_from_user_metadata(&claims.user_metadata);
+
+  let mut txn = pg_pool.begin().await?;
+  let is_new = !is_user_exist(txn.deref_mut(), &user_uuid).await?;
+  if is_new {
+    let new_uid = next_id(id_gen)
+      .await
+      .context("failed to generate the uid")?;
+    create_user(txn.deref_mut(), new_uid, &user_uuid, &claims.email, &name).await?;
+    // The user isn't created if the email belongs to another user
+    if !is_user_exist(txn.deref_mut(), &user_uuid).await? {
+      return Err(AppError::new(
+        ErrorCode::RecordAlreadyExists,
+        format!("the email {} is used by another user", claims.email),
+      ));
+    }
+  }
   txn
     .commit()
     .await
@@ -43,6 +142,93 @@ pub async fn verify_token(
   Ok(is_new)
 }
 
+/// Points the `af_user` of the user who was moved from the deprecated local password to its GoTrue
+/// user, see [LEGACY_UID_KEY].
+async fn link_legacy_user(pg_pool: &PgPool, user: &User) -> Result<(), AppError> {
+  let uid = match user
+    .app_metadata
+    .get(LEGACY_UID_KEY)
+    .and_then(|v| v.as_i64())
+  {
+    Some(uid) => uid,
+    None => return Ok(()),
+  };
+  let user_uuid = Uuid::parse_str(&user.id)?;
+  if update_legacy_password_user_uuid(pg_pool, &uid, &user_uuid).await? {
+    info!("user:{} is moved to gotrue user:{}", uid, user_uuid);
+  }
+  Ok(())
+}
+
+/// Lists the GoTrue users page by page, along with their usage of AppFlowy Cloud. The
+/// `access_token` must belong to the GoTrue admin.
+#[instrument(skip(pg_pool, gotrue_client, access_token), err)]
+pub async fn admin_list_users(
+  pg_pool: &PgPool,
+  gotrue_client: &Client,
+  access_token: &str,
+  query: AdminListUsersQuery,
+) -> Result<AdminUserPage, AppError> {
+  let page = query.page.unwrap_or(1).max(1);
+  let per_page = query
+    .per_page
+    .unwrap_or(DEFAULT_ADMIN_USER_PAGE_SIZE)
+    .clamp(1, MAX_ADMIN_USER_PAGE_SIZE);
+  let filter = query
+    .filter
+    .map(|filter| filter.trim().to_string())
+    .filter(|filter| !filter.is_empty());
+  let params = AdminListUsersParams {
+    page: Some(page),
+    per_page: Some(per_page),
+    filter,
+  };
+  let resp = gotrue_client.admin_list_user(access_token, &params).await?;
+
+  let user_uuids = resp
+    .users
+    .iter()
+    .flat_map(|user| Uuid::parse_str(&user.id).ok())
+    .collect::<Vec<_>>();
+  let mut usage_by_uuid = select_user_usages(pg_pool, &user_uuids)
+    .await?
+    .into_iter()
+    .map(|row| (row.uuid, row))
+    .collect::<HashMap<_, _>>();
+  let total = resp.total.unwrap_or(resp.users.len() as u64);
+  let items = resp
+    .users
+    .into_iter()
+    .map(|user| {
+      let usage = Uuid::parse_str(&user.id)
+        .ok()
+        .and_then(|uuid| usage_by_uuid.remove(&uuid));
+      match usage {
+        Some(usage) => AdminUser {
+          user,
+          uid: Some(usage.uid),
+          name: Some(usage.name),
+          workspace_count: usage.workspace_count,
+          storage_usage: usage.storage_usage,
+        },
+        None => AdminUser {
+          user,
+          uid: None,
+          name: None,
+          workspace_count: 0,
+          storage_usage: 0,
+        },
+      }
+    })
+    .collect();
+  Ok(AdminUserPage {
+    items,
+    page,
+    per_page,
+    total,
+  })
+}
+
 pub async fn get_profile(pg_pool: &PgPool, uuid: &Uuid) -> Result<AFUserProfile, AppError> {
   let row = select_user_profile(pg_pool, uuid)
     .await?
@@ -93,13 +279,117 @@ pub async fn get_user_workspace_info(
   })
 }
 
+/// Updates the profile of the user. The password isn't changed here, see
+/// [change_password](crate::biz::user_credential::change_password).
 pub async fn update_user(
   pg_pool: &PgPool,
+  actor: &AuditActor,
   user_uuid: Uuid,
   params: UpdateUserParams,
 ) -> Result<(), AppError> {
+  if params.name.is_none() && params.email.is_none() && params.metadata.is_none() {
+    return Ok(());
+  }
+
   let metadata = params.metadata.map(|m| json!(m.into_inner()));
-  Ok(database::user::update_user(pg_pool, &user_uuid, params.name, params.email, metadata).await?)
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to update user")?;
+  database::user::update_user(
+    txn.deref_mut(),
+    &user_uuid,
+    params.name,
+    params.email,
+    metadata,
+  )
+  .await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::UpdateUser,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to update user")?;
+  Ok(())
+}
+
+/// Creates a personal access token for the user. The plaintext token is only returned here.
+#[instrument(skip(pg_pool), err)]
+pub async fn create_api_token(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+  params: CreateApiTokenParams,
+) -> Result<AFApiTokenCreated, AppError> {
+  params.validate()?;
+  if matches!(params.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+    return Err(AppError::new(
+      ErrorCode::InvalidRequestParams,
+      "expires_at must be in the future",
+    ));
+  }
+
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  if let Some(workspace_id) = &params.workspace_id {
+    // Only members of the workspace can create tokens for it.
+    select_user_role(pg_pool, &uid, workspace_id)
+      .await
+      .map_err(|_| {
+        AppError::new(
+          ErrorCode::NotEnoughPermissions,
+          format!("user is not a member of workspace:{}", workspace_id),
+        )
+      })?;
+  }
+
+  let (token, token_hash) = generate_api_token();
+  let row = insert_api_token(
+    pg_pool,
+    &Uuid::new_v4(),
+    &uid,
+    params.workspace_id.as_ref(),
+    &params.name,
+    &token_hash,
+    params.scope.into(),
+    params.expires_at,
+  )
+  .await?;
+
+  Ok(AFApiTokenCreated {
+    token,
+    info: AFApiToken::from(row),
+  })
+}
+
+pub async fn list_api_tokens(pg_pool: &PgPool, user_uuid: &Uuid) -> Result<AFApiTokens, AppError> {
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  let tokens = select_api_tokens(pg_pool, &uid)
+    .await?
+    .into_iter()
+    .map(AFApiToken::from)
+    .collect();
+  Ok(AFApiTokens(tokens))
+}
+
+#[instrument(skip(pg_pool), err)]
+pub async fn revoke_api_token(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+  token_id: &Uuid,
+) -> Result<(), AppError> {
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  if !database::api_token::revoke_api_token(pg_pool, &uid, token_id).await? {
+    return Err(AppError::new(
+      ErrorCode::RecordNotFound,
+      format!("api token:{} not found", token_id),
+    ));
+  }
+  Ok(())
 }
 
 // Best effort to get user's name after oauth
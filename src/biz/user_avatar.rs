@@ -0,0 +1,102 @@
+use database::audit::insert_audit_log;
+use database::user::{select_user_avatar_id, update_user_avatar};
+use database_entity::dto::{AFAuditAction, AFUserProfile, AuditActor};
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::biz::user::get_profile;
+use crate::state::AppState;
+
+/// Resizes the image and sets it as the avatar of the user. The previous avatar is deleted.
+#[instrument(skip(state, blob), err)]
+pub async fn upload_user_avatar(
+  state: &AppState,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+  blob: Vec<u8>,
+) -> Result<AFUserProfile, AppError> {
+  let avatar_id = state
+    .bucket_storage
+    .put_user_avatar(user_uuid, blob)
+    .await?;
+  let previous_avatar_id =
+    match update_user_avatar(&state.pg_pool, user_uuid, Some(&avatar_id)).await {
+      Ok(previous_avatar_id) => previous_avatar_id,
+      Err(err) => {
+        delete_avatar_object(state, user_uuid, &avatar_id).await;
+        return Err(err.into());
+      },
+    };
+  if let Some(previous_avatar_id) = previous_avatar_id {
+    delete_avatar_object(state, user_uuid, &previous_avatar_id).await;
+  }
+  insert_audit_log(
+    &state.pg_pool,
+    actor,
+    None,
+    AFAuditAction::UpdateUser,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  get_profile(&state.pg_pool, user_uuid).await
+}
+
+#[instrument(skip(state), err)]
+pub async fn delete_user_avatar(
+  state: &AppState,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+) -> Result<(), AppError> {
+  let avatar_id = update_user_avatar(&state.pg_pool, user_uuid, None)
+    .await?
+    .ok_or_else(|| avatar_not_found(user_uuid))?;
+  delete_avatar_object(state, user_uuid, &avatar_id).await;
+  insert_audit_log(
+    &state.pg_pool,
+    actor,
+    None,
+    AFAuditAction::UpdateUser,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  Ok(())
+}
+
+/// Returns the avatar if it's the current avatar of the user.
+pub async fn get_user_avatar(
+  state: &AppState,
+  user_uuid: &Uuid,
+  avatar_id: &Uuid,
+) -> Result<Vec<u8>, AppError> {
+  if select_user_avatar_id(&state.pg_pool, user_uuid).await? != Some(*avatar_id) {
+    return Err(avatar_not_found(user_uuid));
+  }
+  let avatar = state
+    .bucket_storage
+    .get_user_avatar(user_uuid, avatar_id)
+    .await?;
+  Ok(avatar)
+}
+
+/// The avatar is no longer referenced, so failing to delete it only leaves an orphan object.
+async fn delete_avatar_object(state: &AppState, user_uuid: &Uuid, avatar_id: &Uuid) {
+  if let Err(err) = state
+    .bucket_storage
+    .delete_user_avatar(user_uuid, avatar_id)
+    .await
+  {
+    error!(
+      "failed to delete avatar:{} of user:{}: {}",
+      avatar_id, user_uuid, err
+    );
+  }
+}
+
+fn avatar_not_found(user_uuid: &Uuid) -> AppError {
+  AppError::new(
+    ErrorCode::RecordNotFound,
+    format!("the avatar of user:{} doesn't exist", user_uuid),
+  )
+}
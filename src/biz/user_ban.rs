@@ -0,0 +1,247 @@
+use crate::biz::pg_listener::PostgresDBListener;
+use crate::biz::user::gotrue_admin_token;
+use crate::biz::user_session::RealtimeSessions;
+use crate::state::AppState;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use database::audit::insert_audit_log;
+use database::user_ban::{delete_user_ban, select_active_user_bans, upsert_user_ban};
+use database_entity::dto::{AFAuditAction, AFUserBan, AuditActor, BanUserParams};
+use gotrue::params::AdminUserParams;
+use serde::Deserialize;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::sync::{Arc, PoisonError, RwLock};
+use tokio::sync::broadcast;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+/// GoTrue doesn't support banning a user indefinitely, so the user is banned for 100 years.
+const INDEFINITE_BAN_HOURS: i64 = 876_000;
+
+/// Notified when a user is banned or unbanned.
+pub type UserBanListener = PostgresDBListener<UserBanChange>;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UserBanChange {
+  pub uuid: Uuid,
+  /// None if the user has never signed in to the server.
+  pub uid: Option<i64>,
+  /// None if the user is unbanned.
+  pub banned_until: Option<DateTime<Utc>>,
+}
+
+type BannedUntilByUuid = HashMap<Uuid, DateTime<Utc>>;
+
+/// The users who are banned, by their uuid. It's checked on every request, so it's kept in memory
+/// and updated when a user is banned or unbanned on any of the servers.
+pub struct UserBanList {
+  banned_until_by_uuid: Arc<RwLock<BannedUntilByUuid>>,
+}
+
+impl UserBanList {
+  pub async fn new(
+    pg_pool: &PgPool,
+    listener: broadcast::Receiver<UserBanChange>,
+    realtime_sessions: Arc<RealtimeSessions>,
+  ) -> Result<Self, anyhow::Error> {
+    let banned_until_by_uuid = select_active_user_bans(pg_pool)
+      .await?
+      .into_iter()
+      .map(|row| (row.uuid, row.banned_until))
+      .collect::<BannedUntilByUuid>();
+    let banned_until_by_uuid = Arc::new(RwLock::new(banned_until_by_uuid));
+    spawn_listen_on_user_ban_change(listener, banned_until_by_uuid.clone(), realtime_sessions);
+    Ok(Self {
+      banned_until_by_uuid,
+    })
+  }
+
+  /// Returns [ErrorCode::UserBanned] if the user is banned.
+  pub fn check(&self, user_uuid: &Uuid) -> Result<(), AppError> {
+    let banned_until = self
+      .banned_until_by_uuid
+      .read()
+      .unwrap_or_else(PoisonError::into_inner)
+      .get(user_uuid)
+      .copied();
+    match banned_until {
+      Some(banned_until) if banned_until > Utc::now() => Err(AppError::new(
+        ErrorCode::UserBanned,
+        format!("the user is banned until {}", banned_until),
+      )),
+      _ => Ok(()),
+    }
+  }
+
+  /// Updates the ban list without waiting for the PostgreSQL notification.
+  fn set(&self, user_uuid: Uuid, banned_until: Option<DateTime<Utc>>) {
+    set_banned_until(&self.banned_until_by_uuid, user_uuid, banned_until);
+  }
+}
+
+fn spawn_listen_on_user_ban_change(
+  mut listener: broadcast::Receiver<UserBanChange>,
+  banned_until_by_uuid: Arc<RwLock<BannedUntilByUuid>>,
+  realtime_sessions: Arc<RealtimeSessions>,
+) {
+  tokio::spawn(async move {
+    while let Ok(change) = listener.recv().await {
+      set_banned_until(&banned_until_by_uuid, change.uuid, change.banned_until);
+      if let (Some(uid), Some(_)) = (change.uid, change.banned_until) {
+        realtime_sessions
+          .close_user(uid, "the user is banned")
+          .await;
+      }
+    }
+  });
+}
+
+fn set_banned_until(
+  banned_until_by_uuid: &RwLock<BannedUntilByUuid>,
+  user_uuid: Uuid,
+  banned_until: Option<DateTime<Utc>>,
+) {
+  let mut banned_until_by_uuid = banned_until_by_uuid
+    .write()
+    .unwrap_or_else(PoisonError::into_inner);
+  let now = Utc::now();
+  banned_until_by_uuid.retain(|_, banned_until| *banned_until > now);
+  match banned_until {
+    Some(banned_until) => banned_until_by_uuid.insert(user_uuid, banned_until),
+    None => banned_until_by_uuid.remove(&user_uuid),
+  };
+}
+
+/// Bans the user in GoTrue, so the user can't sign in or refresh the access token, and on the
+/// servers, which reject the access tokens issued before the ban and close the websocket
+/// connections of the user.
+#[instrument(skip(state), err)]
+pub async fn ban_user(
+  state: &AppState,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+  params: BanUserParams,
+) -> Result<AFUserBan, AppError> {
+  if actor.user_uuid.as_ref() == Some(user_uuid) {
+    return Err(AppError::new(
+      ErrorCode::InvalidRequestParams,
+      "the admin can't ban itself",
+    ));
+  }
+  let (ban_duration, banned_until) = match params.duration_secs {
+    Some(secs) if secs <= 0 => {
+      return Err(AppError::new(
+        ErrorCode::InvalidRequestParams,
+        "the duration of the ban must be positive",
+      ))
+    },
+    Some(secs) => {
+      let secs = secs.min(INDEFINITE_BAN_HOURS * 3600);
+      (format!("{}s", secs), Utc::now() + Duration::seconds(secs))
+    },
+    None => (
+      format!("{}h", INDEFINITE_BAN_HOURS),
+      Utc::now() + Duration::hours(INDEFINITE_BAN_HOURS),
+    ),
+  };
+
+  let admin_token = gotrue_admin_token(&state.gotrue_client, &state.config.gotrue).await?;
+  state
+    .gotrue_client
+    .admin_update_user(
+      &admin_token,
+      &user_uuid.to_string(),
+      &AdminUserParams {
+        ban_duration,
+        ..Default::default()
+      },
+    )
+    .await?;
+
+  let mut txn = state
+    .pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to ban the user")?;
+  let row = upsert_user_ban(
+    txn.deref_mut(),
+    user_uuid,
+    banned_until,
+    params.reason.as_deref(),
+    actor.user_uuid.as_ref(),
+  )
+  .await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::BanUser,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to ban the user")?;
+
+  state.user_ban_list.set(*user_uuid, Some(row.banned_until));
+  if let Ok(uid) = database::user::select_uid_from_uuid(&state.pg_pool, user_uuid).await {
+    state
+      .realtime_sessions
+      .close_user(uid, "the user is banned")
+      .await;
+  }
+  info!("user:{} is banned until {}", user_uuid, row.banned_until);
+  Ok(AFUserBan::from(row))
+}
+
+#[instrument(skip(state), err)]
+pub async fn unban_user(
+  state: &AppState,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+) -> Result<(), AppError> {
+  let admin_token = gotrue_admin_token(&state.gotrue_client, &state.config.gotrue).await?;
+  state
+    .gotrue_client
+    .admin_update_user(
+      &admin_token,
+      &user_uuid.to_string(),
+      &AdminUserParams {
+        ban_duration: "none".to_string(),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+  let mut txn = state
+    .pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to unban the user")?;
+  if !delete_user_ban(txn.deref_mut(), user_uuid).await? {
+    return Err(AppError::new(
+      ErrorCode::RecordNotFound,
+      format!("user:{} is not banned", user_uuid),
+    ));
+  }
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::UnbanUser,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to unban the user")?;
+
+  state.user_ban_list.set(*user_uuid, None);
+  Ok(())
+}
@@ -0,0 +1,173 @@
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+use database::audit::insert_audit_log;
+use database_entity::dto::{AFAuditAction, AuditActor};
+use gotrue::api::Client;
+use gotrue::params::{RecoverParams, VerifyParams};
+use gotrue_entity::dto::{OtpType, UpdateGotrueUserParams, User, VerifyResponse};
+use shared_entity::app_error::AppError;
+use shared_entity::dto::auth_dto::{
+  ChangeEmailParams, ChangePasswordParams, PasswordRecoveryParams, VerifyOtpParams,
+  VerifyOtpResponse,
+};
+use shared_entity::error_code::{invalid_email_error, ErrorCode};
+use snowflake::Snowflake;
+use sqlx::PgPool;
+use tokio::sync::RwLock;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::biz::user::verify_token;
+use crate::domain::{UserEmail, UserPassword};
+
+/// Sends the password recovery email. The email contains a link that signs the user in, and the
+/// code to pass to [verify_otp] with [OtpType::Recovery]. The user can then set a new password
+/// with [change_password].
+#[instrument(skip_all, err)]
+pub async fn send_password_recovery(
+  gotrue_client: &Client,
+  params: PasswordRecoveryParams,
+) -> Result<(), AppError> {
+  let email = UserEmail::parse(params.email.clone())
+    .map_err(|_| invalid_email_error(&params.email))?
+    .0;
+  gotrue_client.recover(&RecoverParams { email }).await?;
+  Ok(())
+}
+
+/// Verifies the code sent by email. If the code signs the user in, the user is created if it's a
+/// new user, and the email of the user is updated after an email change.
+#[instrument(skip_all, fields(otp_type = ?params.otp_type), err)]
+pub async fn verify_otp(
+  pg_pool: &PgPool,
+  id_gen: &Arc<RwLock<Snowflake>>,
+  gotrue_client: &Client,
+  actor: &AuditActor,
+  params: VerifyOtpParams,
+) -> Result<VerifyOtpResponse, AppError> {
+  let verify_params = VerifyParams {
+    type_: params.otp_type,
+    email: params.email,
+    token: params.token,
+  };
+  let access_token_resp = match gotrue_client.verify(&verify_params).await? {
+    VerifyResponse::Authenticated(access_token_resp) => access_token_resp,
+    VerifyResponse::Pending { .. } => {
+      return Ok(VerifyOtpResponse {
+        access_token_resp: None,
+        is_new: false,
+      })
+    },
+  };
+
+  let is_new = verify_token(
+    pg_pool,
+    id_gen,
+    gotrue_client,
+    &access_token_resp.access_token,
+  )
+  .await?;
+  if params.otp_type == OtpType::EmailChange {
+    // The request isn't authenticated, so the actor is the user who verified the code.
+    let user_uuid = Uuid::parse_str(&access_token_resp.user.id)?;
+    let actor = AuditActor {
+      user_uuid: Some(user_uuid),
+      ..actor.clone()
+    };
+    sync_user_email(pg_pool, &actor, &access_token_resp.user).await?;
+  }
+  Ok(VerifyOtpResponse {
+    access_token_resp: Some(access_token_resp),
+    is_new,
+  })
+}
+
+/// Sends the nonce required by [change_password] to the email of the user.
+#[instrument(skip_all, err)]
+pub async fn reauthenticate(gotrue_client: &Client, access_token: &str) -> Result<(), AppError> {
+  gotrue_client.reauthenticate(access_token).await?;
+  Ok(())
+}
+
+#[instrument(skip(pg_pool, gotrue_client, access_token, params), err)]
+pub async fn change_password(
+  pg_pool: &PgPool,
+  gotrue_client: &Client,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+  access_token: &str,
+  params: ChangePasswordParams,
+) -> Result<(), AppError> {
+  // The password is left out of the error, which ends up in the logs.
+  let password = UserPassword::parse(params.password)
+    .map_err(|err| AppError::new(ErrorCode::InvalidPassword, err))?
+    .0;
+  let gotrue_params = UpdateGotrueUserParams::new()
+    .with_opt_password(Some(password))
+    .with_opt_nonce(params.nonce);
+  gotrue_client
+    .update_user(access_token, &gotrue_params)
+    .await?;
+  insert_audit_log(
+    pg_pool,
+    actor,
+    None,
+    AFAuditAction::ChangePassword,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  Ok(())
+}
+
+/// Requests the change of the email. GoTrue sends a code to the new address, and to the current
+/// one as well if the secure email change is enabled. The email is only changed once the codes are
+/// verified with [verify_otp], unless GoTrue confirms the emails automatically.
+#[instrument(skip(pg_pool, gotrue_client, access_token, params), err)]
+pub async fn change_email(
+  pg_pool: &PgPool,
+  gotrue_client: &Client,
+  actor: &AuditActor,
+  access_token: &str,
+  params: ChangeEmailParams,
+) -> Result<(), AppError> {
+  let email = UserEmail::parse(params.email.clone())
+    .map_err(|_| invalid_email_error(&params.email))?
+    .0;
+  let gotrue_params = UpdateGotrueUserParams::new().with_opt_email(Some(&email));
+  let user = gotrue_client
+    .update_user(access_token, &gotrue_params)
+    .await?;
+  if user.email == email {
+    sync_user_email(pg_pool, actor, &user).await?;
+  }
+  Ok(())
+}
+
+/// Copies the email of the GoTrue user to the `af_user` table.
+async fn sync_user_email(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  user: &User,
+) -> Result<(), AppError> {
+  let user_uuid = Uuid::parse_str(&user.id)?;
+  let mut txn = pg_pool.begin().await?;
+  database::user::update_user(
+    txn.deref_mut(),
+    &user_uuid,
+    None,
+    Some(user.email.clone()),
+    None,
+  )
+  .await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::UpdateUser,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  txn.commit().await?;
+  Ok(())
+}
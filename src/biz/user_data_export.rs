@@ -0,0 +1,373 @@
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use collab::core::collab::Collab;
+use collab::core::origin::CollabOrigin;
+use collab_entity::CollabType;
+use database::audit::insert_audit_log;
+use database::resource_usage::get_all_workspace_blob_metadata;
+use database::user::select_uid_from_uuid;
+use database::user_data_export::{
+  expire_user_data_exports, fail_stale_user_data_exports, insert_user_data_export,
+  select_pending_user_data_export, select_user_collabs, select_user_data_export,
+  update_user_data_export_completed, update_user_data_export_failed,
+};
+use database::workspace::{
+  select_all_user_workspaces, select_user_profile, select_user_role, select_user_workspace,
+};
+use database_entity::dto::{
+  AFAuditAction, AFUserDataExport, AFUserDataExportStatus, AFUserProfile, AFWorkspace, AuditActor,
+};
+use serde_json::json;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use tokio_stream::StreamExt;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::state::AppState;
+
+/// An export that is still pending after this long was interrupted, e.g. by a restart.
+const STALE_EXPORT_SECS: i64 = 60 * 60;
+
+/// Spawns a task that periodically removes the expired archives from the bucket.
+pub fn spawn_user_data_export_cleanup(state: AppState) {
+  let setting = state.config.data_export.clone();
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(setting.cleanup_interval_secs));
+    loop {
+      interval.tick().await;
+      if let Err(err) = cleanup_user_data_exports(&state).await {
+        error!("failed to clean up the data exports: {}", err);
+      }
+    }
+  });
+}
+
+async fn cleanup_user_data_exports(state: &AppState) -> Result<(), AppError> {
+  let created_before = Utc::now() - chrono::Duration::seconds(STALE_EXPORT_SECS);
+  let interrupted = fail_stale_user_data_exports(&state.pg_pool, created_before).await?;
+  if interrupted > 0 {
+    info!("{} data exports were interrupted", interrupted);
+  }
+
+  for archive_key in expire_user_data_exports(&state.pg_pool).await? {
+    if let Err(err) = state.bucket_storage.delete_object(&archive_key).await {
+      error!("failed to delete the archive {}: {}", archive_key, err);
+    }
+  }
+  Ok(())
+}
+
+/// Starts building the archive of the data of the user in the background. If an export is
+/// already in progress, it's returned instead of starting another one.
+#[instrument(skip(state), err)]
+pub async fn request_user_data_export(
+  state: &AppState,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+) -> Result<AFUserDataExport, AppError> {
+  let uid = select_uid_from_uuid(&state.pg_pool, user_uuid).await?;
+  let row = match insert_user_data_export(&state.pg_pool, &Uuid::new_v4(), &uid).await? {
+    Some(row) => row,
+    None => {
+      let row = select_pending_user_data_export(&state.pg_pool, &uid)
+        .await?
+        .ok_or_else(|| {
+          // The export in progress completed in the meantime.
+          AppError::new(
+            ErrorCode::RecordAlreadyExists,
+            "an export was just completed, please try again",
+          )
+        })?;
+      return Ok(AFUserDataExport::try_from(row)?);
+    },
+  };
+  insert_audit_log(
+    &state.pg_pool,
+    actor,
+    None,
+    AFAuditAction::ExportUserData,
+    &row.export_id.to_string(),
+  )
+  .await?;
+
+  let export_id = row.export_id;
+  let cloned_state = state.clone();
+  let user_uuid = *user_uuid;
+  tokio::spawn(async move {
+    run_user_data_export(&cloned_state, &export_id, uid, &user_uuid).await;
+  });
+  Ok(AFUserDataExport::try_from(row)?)
+}
+
+pub async fn get_user_data_export(
+  state: &AppState,
+  user_uuid: &Uuid,
+  export_id: &Uuid,
+) -> Result<AFUserDataExport, AppError> {
+  let uid = select_uid_from_uuid(&state.pg_pool, user_uuid).await?;
+  let row = select_user_data_export(&state.pg_pool, &uid, export_id)
+    .await?
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("data export:{} not found", export_id),
+      )
+    })?;
+  Ok(AFUserDataExport::try_from(row)?)
+}
+
+/// How the archive of an export is downloaded.
+pub enum DataExportDownload {
+  /// A short-lived pre-signed url of the archive in the bucket.
+  Url(String),
+  /// The archive itself, when the bucket can't pre-sign urls.
+  Archive(Vec<u8>),
+}
+
+/// The name of the archive file that the user downloads.
+pub fn data_export_file_name(export_id: &Uuid) -> String {
+  format!("appflowy_data_{}.zip", export_id)
+}
+
+/// Returns how to download the archive of the export. It can only be downloaded until the export
+/// expires.
+pub async fn download_user_data_export(
+  state: &AppState,
+  user_uuid: &Uuid,
+  export_id: &Uuid,
+) -> Result<DataExportDownload, AppError> {
+  let uid = select_uid_from_uuid(&state.pg_pool, user_uuid).await?;
+  let row = select_user_data_export(&state.pg_pool, &uid, export_id)
+    .await?
+    .filter(|row| row.status == AFUserDataExportStatus::Completed.as_str())
+    .filter(|row| matches!(row.expires_at, Some(expires_at) if expires_at > Utc::now()))
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("data export:{} is not available for download", export_id),
+      )
+    })?;
+  let archive_key = row
+    .archive_key
+    .context("the completed export has no archive")?;
+  let url = state
+    .bucket_storage
+    .presign_get_object(&archive_key, &data_export_file_name(export_id))
+    .await?;
+  match url {
+    Some(url) => Ok(DataExportDownload::Url(url)),
+    None => Ok(DataExportDownload::Archive(
+      state.bucket_storage.get_object(&archive_key).await?,
+    )),
+  }
+}
+
+async fn run_user_data_export(state: &AppState, export_id: &Uuid, uid: i64, user_uuid: &Uuid) {
+  let result = async {
+    let mut archive = ArchiveWriter::new()?;
+    write_user_data(state, uid, user_uuid, &mut archive).await?;
+    let (mut file, file_size) = archive.finish().await?;
+    let archive_key = format!("data_export/{}/{}.zip", uid, export_id);
+    state
+      .bucket_storage
+      .put_object_stream(&archive_key, &mut file)
+      .await?;
+
+    let expires_at =
+      Utc::now() + chrono::Duration::seconds(state.config.data_export.expires_secs as i64);
+    let is_completed = update_user_data_export_completed(
+      &state.pg_pool,
+      export_id,
+      &archive_key,
+      file_size as i64,
+      expires_at,
+    )
+    .await?;
+    if !is_completed {
+      // The export took too long and was marked as interrupted, so the archive is never served.
+      info!("data export:{} is no longer pending", export_id);
+      state.bucket_storage.delete_object(&archive_key).await?;
+    }
+    Ok::<_, AppError>(())
+  }
+  .await;
+
+  if let Err(err) = result {
+    error!("data export:{} failed: {}", export_id, err);
+    if let Err(err) = update_user_data_export_failed(&state.pg_pool, export_id, &err.message).await
+    {
+      error!(
+        "failed to record the failure of data export:{}: {}",
+        export_id, err
+      );
+    }
+  }
+}
+
+/// Writes the files of the archive:
+/// - `profile.json`: the profile and the metadata of the user.
+/// - `workspaces.json`: the workspaces the user is a member of, with the role of the user.
+/// - `collabs/{workspace_id}/{collab_type}/{object_id}.json`: the collabs owned by the user. The
+///   collabs that can't be decoded, e.g. the encrypted ones, are exported as `.bin` files with
+///   their encoded data.
+/// - `blobs/{workspace_id}/...`: the blobs of the workspaces owned by the user, along with their
+///   metadata in `metadata.json`. The blobs don't record who uploaded them, so the blobs of the
+///   workspaces the user doesn't own are left out.
+async fn write_user_data(
+  state: &AppState,
+  uid: i64,
+  user_uuid: &Uuid,
+  archive: &mut ArchiveWriter,
+) -> Result<(), AppError> {
+  let pg_pool = &state.pg_pool;
+
+  let profile = select_user_profile(pg_pool, user_uuid)
+    .await?
+    .context("the profile of the user is not found")?;
+  let mut profile = AFUserProfile::try_from(profile)?;
+  profile.password = None;
+  archive
+    .add(
+      "profile.json".to_string(),
+      serde_json::to_vec_pretty(&profile)?,
+    )
+    .await?;
+
+  let mut workspaces = vec![];
+  for row in select_user_workspace(pg_pool, user_uuid).await? {
+    let role = select_user_role(pg_pool, &uid, &row.workspace_id).await?;
+    workspaces.push(json!({
+      "workspace": AFWorkspace::try_from(row)?,
+      "role": role,
+    }));
+  }
+  archive
+    .add(
+      "workspaces.json".to_string(),
+      serde_json::to_vec_pretty(&workspaces)?,
+    )
+    .await?;
+
+  let mut collabs = select_user_collabs(pg_pool, &uid);
+  while let Some(collab) = collabs.next().await {
+    let collab = collab?;
+    let path = format!(
+      "collabs/{}/{}/{}",
+      collab.workspace_id,
+      collab_type_name(collab.partition_key),
+      collab.oid
+    );
+    match collab_to_json(&collab.oid, &collab.blob) {
+      Some(value) => {
+        archive
+          .add(format!("{}.json", path), serde_json::to_vec_pretty(&value)?)
+          .await?
+      },
+      None => archive.add(format!("{}.bin", path), collab.blob).await?,
+    }
+  }
+  drop(collabs);
+
+  for workspace in select_all_user_workspaces(pg_pool, user_uuid).await? {
+    let workspace_id = workspace.workspace_id;
+    let metadata = get_all_workspace_blob_metadata(pg_pool, &workspace_id).await?;
+    if metadata.is_empty() {
+      continue;
+    }
+    for blob_metadata in &metadata {
+      let blob = state
+        .bucket_storage
+        .get_blob(&blob_metadata.file_id)
+        .await?;
+      archive
+        .add(
+          format!("blobs/{}/{}", workspace_id, blob_metadata.file_id),
+          blob,
+        )
+        .await?;
+    }
+    archive
+      .add(
+        format!("blobs/{}/metadata.json", workspace_id),
+        serde_json::to_vec_pretty(&metadata)?,
+      )
+      .await?;
+  }
+  Ok(())
+}
+
+fn collab_to_json(object_id: &str, blob: &[u8]) -> Option<serde_json::Value> {
+  Collab::new_with_raw_data(CollabOrigin::Empty, object_id, vec![blob.to_vec()], vec![])
+    .ok()
+    .map(|collab| collab.to_json_value())
+}
+
+/// The name of the partition of `af_collab` that stores the collab.
+fn collab_type_name(partition_key: i32) -> &'static str {
+  match CollabType::from(partition_key) {
+    CollabType::Document => "document",
+    CollabType::Database => "database",
+    CollabType::WorkspaceDatabase => "w_database",
+    CollabType::Folder => "folder",
+    CollabType::DatabaseRow => "database_row",
+    CollabType::UserAwareness => "user_awareness",
+  }
+}
+
+/// Writes the archive to a temporary file, one file at a time, so the data of the user is never
+/// held in memory at once. The file is removed once it's dropped.
+struct ArchiveWriter {
+  zip: Option<ZipWriter<File>>,
+}
+
+impl ArchiveWriter {
+  fn new() -> Result<Self, AppError> {
+    let file = tempfile::tempfile().context("failed to create the archive file")?;
+    Ok(Self {
+      zip: Some(ZipWriter::new(file)),
+    })
+  }
+
+  async fn add(&mut self, path: String, data: Vec<u8>) -> Result<(), AppError> {
+    let mut zip = self.zip.take().context("the archive is broken")?;
+    let zip = tokio::task::spawn_blocking(move || {
+      let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+      zip
+        .start_file(path, options)
+        .context("failed to add a file to the archive")?;
+      zip
+        .write_all(&data)
+        .context("failed to write a file to the archive")?;
+      Ok::<_, AppError>(zip)
+    })
+    .await
+    .context("failed to write the archive")??;
+    self.zip = Some(zip);
+    Ok(())
+  }
+
+  /// Returns the archive, positioned at its start, and its size in bytes.
+  async fn finish(mut self) -> Result<(tokio::fs::File, u64), AppError> {
+    let zip = self.zip.take().context("the archive is broken")?;
+    let (file, file_size) = tokio::task::spawn_blocking(move || {
+      let mut file = zip.finish().context("failed to finish the archive")?;
+      let file_size = file
+        .seek(SeekFrom::End(0))
+        .context("failed to read the size of the archive")?;
+      file
+        .seek(SeekFrom::Start(0))
+        .context("failed to rewind the archive")?;
+      Ok::<_, AppError>((file, file_size))
+    })
+    .await
+    .context("failed to finish the archive")??;
+    Ok((tokio::fs::File::from_std(file), file_size))
+  }
+}
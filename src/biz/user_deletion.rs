@@ -0,0 +1,280 @@
+use std::ops::DerefMut;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use database::audit::insert_audit_log;
+use database::file::bucket_s3_impl::S3BucketStorage;
+use database::job_lock::JobLock;
+use database::resource_usage::get_all_workspace_blob_metadata;
+use database::user::{delete_user, select_uid_from_uuid, select_user_avatar_id};
+use database::user_deletion::{
+  delete_user_deletion, insert_user_deletion, select_due_user_deletions, select_user_deletion,
+};
+use database::workspace::{
+  delete_workspace, select_all_user_workspaces, select_workspace_successor,
+  transfer_workspace_ownership,
+};
+use database_entity::dto::{AFAuditAction, AFUserDeletion, AuditActor};
+use database_entity::pg_row::AFUserDeletionRow;
+use gotrue::params::AdminDeleteUserParams;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::biz::user::gotrue_admin_token;
+use crate::config::config::{GoTrueSetting, UserDeletionSetting};
+use crate::state::AppState;
+
+/// The maximum number of accounts deleted in a single run.
+const USER_DELETION_BATCH_SIZE: i64 = 100;
+
+pub const USER_DELETION_JOB: &str = "user_deletion";
+
+/// Spawns a task that periodically deletes the accounts whose grace period is over.
+pub fn spawn_user_deletion(state: AppState) {
+  let setting = state.config.user_deletion.clone();
+  if !setting.enable {
+    return;
+  }
+
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(setting.interval_secs));
+    // The first tick completes immediately, skip it so that the server finishes starting up first.
+    interval.tick().await;
+    loop {
+      interval.tick().await;
+      // Only one server deletes the accounts at a time, so an account isn't deleted twice.
+      let lock = match JobLock::try_acquire(&state.pg_pool, USER_DELETION_JOB).await {
+        Ok(Some(lock)) => lock,
+        Ok(None) => continue,
+        Err(err) => {
+          error!("failed to acquire the user deletion lock: {}", err);
+          continue;
+        },
+      };
+      match delete_due_users(&state).await {
+        Ok(deleted) if deleted > 0 => info!("deleted {} user accounts", deleted),
+        Ok(_) => {},
+        Err(err) => error!("user deletion failed: {}", err),
+      }
+      if let Err(err) = lock.release().await {
+        error!("failed to release the user deletion lock: {}", err);
+      }
+    }
+  });
+}
+
+/// Schedules the deletion of the account after the grace period. Requesting the deletion again
+/// keeps the original schedule.
+#[instrument(skip(pg_pool, setting), err)]
+pub async fn request_user_deletion(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+  setting: &UserDeletionSetting,
+) -> Result<AFUserDeletion, AppError> {
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  let scheduled_at = Utc::now() + chrono::Duration::seconds(setting.grace_period_secs as i64);
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to schedule the user deletion")?;
+  let row = insert_user_deletion(txn.deref_mut(), &uid, user_uuid, scheduled_at).await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::RequestUserDeletion,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to schedule the user deletion")?;
+  Ok(row.into())
+}
+
+pub async fn get_user_deletion(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+) -> Result<AFUserDeletion, AppError> {
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  select_user_deletion(pg_pool, &uid)
+    .await?
+    .map(AFUserDeletion::from)
+    .ok_or_else(user_deletion_not_found)
+}
+
+#[instrument(skip(pg_pool), err)]
+pub async fn cancel_user_deletion(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+) -> Result<(), AppError> {
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to cancel the user deletion")?;
+  if !delete_user_deletion(txn.deref_mut(), &uid).await? {
+    return Err(user_deletion_not_found());
+  }
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::CancelUserDeletion,
+    &user_uuid.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to cancel the user deletion")?;
+  Ok(())
+}
+
+fn user_deletion_not_found() -> AppError {
+  AppError::new(
+    ErrorCode::RecordNotFound,
+    "the deletion of the account is not scheduled",
+  )
+}
+
+/// Deletes the accounts whose grace period is over and returns how many were deleted. An account
+/// that fails to be deleted is retried in the next run.
+pub async fn delete_due_users(state: &AppState) -> Result<usize, AppError> {
+  let mut deleted = 0;
+  for row in select_due_user_deletions(&state.pg_pool, USER_DELETION_BATCH_SIZE).await? {
+    match delete_user_account(state, &row).await {
+      Ok(_) => deleted += 1,
+      Err(err) => error!("failed to delete user:{}: {}", row.uuid, err),
+    }
+  }
+  Ok(deleted)
+}
+
+/// Deletes the account of the user:
+/// 1. Each workspace owned by the user is transferred to another member, see
+///    [select_workspace_successor], who gets the full access to the workspace's collab object.
+///    The workspaces without such a member are deleted along with their blobs.
+/// 2. The GoTrue user is deleted, so the user can't sign in anymore. The avatar of the user is
+///    deleted from the bucket.
+/// 3. The user is removed from the other workspaces and collabs, and the `af_user` row is deleted.
+///
+/// Each step can be run again, so an account that fails halfway is completed by the next run.
+#[instrument(skip(state, row), fields(user_uuid = %row.uuid), err)]
+async fn delete_user_account(state: &AppState, row: &AFUserDeletionRow) -> Result<(), AppError> {
+  let pg_pool = &state.pg_pool;
+  let actor = AuditActor::system();
+  for workspace in select_all_user_workspaces(pg_pool, &row.uuid).await? {
+    let workspace_id = workspace.workspace_id;
+    let txn = match select_workspace_successor(pg_pool, &workspace_id, &row.uid).await? {
+      Some(new_owner_uid) => {
+        let mut txn = pg_pool
+          .begin()
+          .await
+          .context("failed to acquire the transaction to transfer the workspace")?;
+        transfer_workspace_ownership(&mut txn, &workspace_id, &new_owner_uid).await?;
+        insert_audit_log(
+          txn.deref_mut(),
+          &actor,
+          Some(&workspace_id),
+          AFAuditAction::TransferWorkspaceOwnership,
+          &new_owner_uid.to_string(),
+        )
+        .await?;
+        txn
+      },
+      None => {
+        purge_workspace_blobs(pg_pool, &state.bucket_storage, &workspace_id).await?;
+        let mut txn = pg_pool
+          .begin()
+          .await
+          .context("failed to acquire the transaction to delete the workspace")?;
+        delete_workspace(txn.deref_mut(), &workspace_id).await?;
+        insert_audit_log(
+          txn.deref_mut(),
+          &actor,
+          Some(&workspace_id),
+          AFAuditAction::DeleteWorkspace,
+          &workspace_id.to_string(),
+        )
+        .await?;
+        txn
+      },
+    };
+    txn
+      .commit()
+      .await
+      .context("failed to commit the change of the workspace")?;
+  }
+
+  delete_gotrue_user(&state.gotrue_client, &state.config.gotrue, &row.uuid).await?;
+
+  // The avatar isn't stored in a workspace, so it isn't removed along with the blobs.
+  if let Some(avatar_id) = select_user_avatar_id(pg_pool, &row.uuid).await? {
+    state
+      .bucket_storage
+      .delete_user_avatar(&row.uuid, &avatar_id)
+      .await?;
+  }
+
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to delete the user")?;
+  delete_user(&mut txn, &row.uid).await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    &actor,
+    None,
+    AFAuditAction::DeleteUser,
+    &row.uuid.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to delete the user")?;
+  Ok(())
+}
+
+/// Removes the blobs of the workspace. The blobs are content addressed, so the objects that
+/// another workspace also stores are kept in the bucket by [S3BucketStorage::delete_blob]. Each
+/// deletion is recorded in the audit log.
+async fn purge_workspace_blobs(
+  pg_pool: &PgPool,
+  bucket_storage: &S3BucketStorage,
+  workspace_id: &Uuid,
+) -> Result<(), AppError> {
+  for metadata in get_all_workspace_blob_metadata(pg_pool, workspace_id).await? {
+    bucket_storage
+      .delete_blob(&AuditActor::system(), workspace_id, &metadata.file_id)
+      .await?;
+  }
+  Ok(())
+}
+
+async fn delete_gotrue_user(
+  gotrue_client: &gotrue::api::Client,
+  setting: &GoTrueSetting,
+  user_uuid: &Uuid,
+) -> Result<(), AppError> {
+  let admin_token = gotrue_admin_token(gotrue_client, setting).await?;
+  let params = AdminDeleteUserParams {
+    should_soft_delete: false,
+  };
+  match gotrue_client
+    .admin_delete_user(&admin_token, &user_uuid.to_string(), &params)
+    .await
+  {
+    // Deleted by an earlier run that failed afterwards.
+    Err(err) if err.code == 404 => Ok(()),
+    result => Ok(result?),
+  }
+}
@@ -0,0 +1,325 @@
+use crate::biz::pg_listener::PostgresDBListener;
+use crate::state::AppState;
+use actix::Recipient;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use database::audit::insert_audit_log;
+use database::user::select_uid_from_uuid;
+use database::user_session::{
+  select_active_user_sessions, select_signed_out_user_sessions, sign_out_user_session,
+  upsert_user_session,
+};
+use database_entity::dto::{AFAuditAction, AFUserSession, AFUserSessions, AuditActor};
+use database_entity::pg_row::AFUserSessionRow;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use realtime::entities::CloseSession;
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::sync::{Arc, PoisonError};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{instrument, trace};
+use uuid::Uuid;
+
+/// Notified when a device is signed out.
+pub type UserSessionListener = PostgresDBListener<AFUserSessionRow>;
+
+type SessionByDevice = HashMap<(i64, String), Recipient<CloseSession>>;
+
+/// The websocket connections that are opened on this server, by the uid of the user and the id
+/// of the device. The connection of a device is closed when the device is signed out, on any of
+/// the servers.
+pub struct RealtimeSessions {
+  session_by_device: Arc<RwLock<SessionByDevice>>,
+}
+
+impl RealtimeSessions {
+  pub fn new(listener: broadcast::Receiver<AFUserSessionRow>) -> Self {
+    let session_by_device = Arc::new(RwLock::new(SessionByDevice::new()));
+    spawn_listen_on_user_session_sign_out(listener, session_by_device.clone());
+    Self { session_by_device }
+  }
+
+  pub async fn insert(&self, uid: i64, device_id: String, session: Recipient<CloseSession>) {
+    let mut session_by_device = self.session_by_device.write().await;
+    session_by_device.retain(|_, session| session.connected());
+    session_by_device.insert((uid, device_id), session);
+  }
+
+  /// Closes the connection of the device without waiting for the PostgreSQL notification.
+  pub async fn close(&self, uid: i64, device_id: &str) {
+    close_device_session(uid, device_id, &self.session_by_device).await;
+  }
+
+  /// Closes the connections of all the devices of the user on this server.
+  pub async fn close_user(&self, uid: i64, reason: &str) {
+    self
+      .session_by_device
+      .write()
+      .await
+      .retain(|(session_uid, device_id), session| {
+        if *session_uid != uid {
+          return true;
+        }
+        trace!("close the connection of user:{} device:{}", uid, device_id);
+        session.do_send(CloseSession {
+          reason: reason.to_string(),
+        });
+        false
+      });
+  }
+}
+
+fn spawn_listen_on_user_session_sign_out(
+  mut listener: broadcast::Receiver<AFUserSessionRow>,
+  session_by_device: Arc<RwLock<SessionByDevice>>,
+) {
+  tokio::spawn(async move {
+    while let Ok(session) = listener.recv().await {
+      close_device_session(session.uid, &session.device_id, &session_by_device).await;
+    }
+  });
+}
+
+async fn close_device_session(
+  uid: i64,
+  device_id: &str,
+  session_by_device: &Arc<RwLock<SessionByDevice>>,
+) {
+  let session = session_by_device
+    .write()
+    .await
+    .remove(&(uid, device_id.to_string()));
+  if let Some(session) = session {
+    trace!("close the connection of user:{} device:{}", uid, device_id);
+    session.do_send(CloseSession {
+      reason: "the device is signed out".to_string(),
+    });
+  }
+}
+
+/// How long a signed out session is remembered. The access tokens of the session are rejected
+/// until then, so it must be longer than the lifetime of the GoTrue access tokens, which is an
+/// hour by default.
+const SIGNED_OUT_SESSION_RETENTION_HOURS: i64 = 24;
+
+type SignedOutAtBySession = HashMap<Uuid, DateTime<Utc>>;
+
+/// The GoTrue sessions that a device was signed out of. Revoking the refresh tokens of a session
+/// doesn't revoke the access tokens that were already issued, so they are rejected by the servers
+/// until they expire, whatever device they are used on. Like the [UserBanList], the sessions are
+/// kept in memory and updated when a device is signed out on any of the servers.
+///
+/// [UserBanList]: crate::biz::user_ban::UserBanList
+pub struct SignedOutSessions {
+  signed_out_at_by_session: Arc<std::sync::RwLock<SignedOutAtBySession>>,
+}
+
+impl SignedOutSessions {
+  pub async fn new(
+    pg_pool: &PgPool,
+    listener: broadcast::Receiver<AFUserSessionRow>,
+  ) -> Result<Self, anyhow::Error> {
+    let since = Utc::now() - Duration::hours(SIGNED_OUT_SESSION_RETENTION_HOURS);
+    let signed_out_at_by_session = select_signed_out_user_sessions(pg_pool, since)
+      .await?
+      .into_iter()
+      .filter_map(|row| Some((row.session_id?, row.signed_out_at?)))
+      .collect::<SignedOutAtBySession>();
+    let signed_out_at_by_session = Arc::new(std::sync::RwLock::new(signed_out_at_by_session));
+    spawn_listen_on_session_sign_out(listener, signed_out_at_by_session.clone());
+    Ok(Self {
+      signed_out_at_by_session,
+    })
+  }
+
+  /// Returns [ErrorCode::NotLoggedIn] if the access token belongs to a signed out session.
+  pub fn check(&self, session_id: Option<&str>) -> Result<(), AppError> {
+    let session_id = match session_id.and_then(|id| Uuid::parse_str(id).ok()) {
+      None => return Ok(()),
+      Some(session_id) => session_id,
+    };
+    let is_signed_out = self
+      .signed_out_at_by_session
+      .read()
+      .unwrap_or_else(PoisonError::into_inner)
+      .contains_key(&session_id);
+    if is_signed_out {
+      return Err(AppError::new(
+        ErrorCode::NotLoggedIn,
+        "the session is signed out, please sign in again",
+      ));
+    }
+    Ok(())
+  }
+
+  /// Updates the signed out sessions without waiting for the PostgreSQL notification.
+  fn insert(&self, session_id: Uuid, signed_out_at: DateTime<Utc>) {
+    insert_signed_out_session(&self.signed_out_at_by_session, session_id, signed_out_at);
+  }
+}
+
+fn spawn_listen_on_session_sign_out(
+  mut listener: broadcast::Receiver<AFUserSessionRow>,
+  signed_out_at_by_session: Arc<std::sync::RwLock<SignedOutAtBySession>>,
+) {
+  tokio::spawn(async move {
+    while let Ok(row) = listener.recv().await {
+      if let (Some(session_id), Some(signed_out_at)) = (row.session_id, row.signed_out_at) {
+        insert_signed_out_session(&signed_out_at_by_session, session_id, signed_out_at);
+      }
+    }
+  });
+}
+
+fn insert_signed_out_session(
+  signed_out_at_by_session: &std::sync::RwLock<SignedOutAtBySession>,
+  session_id: Uuid,
+  signed_out_at: DateTime<Utc>,
+) {
+  let mut signed_out_at_by_session = signed_out_at_by_session
+    .write()
+    .unwrap_or_else(PoisonError::into_inner);
+  let since = Utc::now() - Duration::hours(SIGNED_OUT_SESSION_RETENTION_HOURS);
+  signed_out_at_by_session.retain(|_, signed_out_at| *signed_out_at > since);
+  signed_out_at_by_session.insert(session_id, signed_out_at);
+}
+
+/// Records the device that opens a websocket connection. Fails if the device was signed out
+/// after the access token was issued.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(pg_pool), err)]
+pub async fn record_user_session(
+  pg_pool: &PgPool,
+  uid: &i64,
+  device_id: &str,
+  session_id: Option<&Uuid>,
+  issued_at: Option<DateTime<Utc>>,
+  client_version: Option<&str>,
+  ip: Option<&str>,
+  user_agent: Option<&str>,
+) -> Result<(), AppError> {
+  let row = upsert_user_session(
+    pg_pool,
+    uid,
+    device_id,
+    session_id,
+    issued_at,
+    client_version,
+    ip,
+    user_agent,
+  )
+  .await?;
+  if row.is_none() {
+    return Err(AppError::new(
+      ErrorCode::NotLoggedIn,
+      format!("device:{} is signed out, please sign in again", device_id),
+    ));
+  }
+  Ok(())
+}
+
+pub async fn list_user_sessions(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+  current_session_id: Option<&Uuid>,
+) -> Result<AFUserSessions, AppError> {
+  let uid = select_uid_from_uuid(pg_pool, user_uuid).await?;
+  let sessions = select_active_user_sessions(pg_pool, &uid)
+    .await?
+    .into_iter()
+    .map(|row| AFUserSession::new(row, current_session_id))
+    .collect();
+  Ok(AFUserSessions(sessions))
+}
+
+/// Signs the device out. Its GoTrue session is logged out, so the device can't get a new access
+/// token, and the access tokens of the session are rejected by the [SignedOutSessions]. Returns
+/// the uid of the user, which is needed to close the websocket connection of the device.
+#[instrument(skip(state), err)]
+pub async fn sign_out_device(
+  state: &AppState,
+  actor: &AuditActor,
+  user_uuid: &Uuid,
+  device_id: &str,
+) -> Result<i64, AppError> {
+  let uid = select_uid_from_uuid(&state.pg_pool, user_uuid).await?;
+  let mut txn = state
+    .pg_pool
+    .begin()
+    .await
+    .context("failed to acquire the transaction to sign out the device")?;
+  let session = sign_out_user_session(txn.deref_mut(), &uid, device_id)
+    .await?
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("device:{} is not signed in", device_id),
+      )
+    })?;
+
+  // The session is logged out before the sign out is committed, so a failed logout can be
+  // retried.
+  if let Some(session_id) = &session.session_id {
+    logout_gotrue_session(state, user_uuid, session_id).await?;
+  }
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    None,
+    AFAuditAction::SignOutDevice,
+    device_id,
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("failed to commit the transaction to sign out the device")?;
+  if let (Some(session_id), Some(signed_out_at)) = (session.session_id, session.signed_out_at) {
+    state.signed_out_sessions.insert(session_id, signed_out_at);
+  }
+  Ok(uid)
+}
+
+/// The claims GoTrue needs to log out a session. GoTrue logs out the session of the token that
+/// the logout is requested with.
+#[derive(Serialize)]
+struct LogoutSessionClaims {
+  sub: String,
+  session_id: String,
+  role: &'static str,
+  aud: &'static str,
+  iat: i64,
+  exp: i64,
+}
+
+/// Logs out the GoTrue session of another device of the user. The logout API only logs out the
+/// session of its access token, so a short lived token of that session is signed with the GoTrue
+/// secret. GoTrue revokes the refresh tokens of the session.
+async fn logout_gotrue_session(
+  state: &AppState,
+  user_uuid: &Uuid,
+  session_id: &Uuid,
+) -> Result<(), AppError> {
+  let now = Utc::now().timestamp();
+  let claims = LogoutSessionClaims {
+    sub: user_uuid.to_string(),
+    session_id: session_id.to_string(),
+    role: "authenticated",
+    aud: "authenticated",
+    iat: now,
+    exp: now + 60,
+  };
+  let token = encode(
+    &Header::default(),
+    &claims,
+    &EncodingKey::from_secret(state.config.gotrue.jwt_secret.expose_secret().as_bytes()),
+  )
+  .context("failed to sign the token to log out the session")?;
+  state.gotrue_client.logout_session(&token).await?;
+  Ok(())
+}
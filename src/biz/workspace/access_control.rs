@@ -1,7 +1,8 @@
 use crate::biz::workspace::member_listener::{WorkspaceMemberAction, WorkspaceMemberChange};
+use crate::biz::workspace::role_listener::RolePermissionChange;
 use crate::component::auth::jwt::UserUuid;
 use crate::middleware::access_control_mw::{AccessResource, HttpAccessControlService};
-use actix_http::Method;
+use crate::middleware::access_policy::{AccessDeniedReason, AccessRequirement};
 use async_trait::async_trait;
 use database::user::select_uid_from_uuid;
 use shared_entity::app_error::AppError;
@@ -10,7 +11,8 @@ use sqlx::PgPool;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
-use database_entity::dto::AFRole;
+use database::role::select_role_access_level;
+use database_entity::dto::{AFAccessLevel, AFRole};
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 use tracing::{instrument, trace, warn};
@@ -24,6 +26,20 @@ pub trait WorkspaceAccessControl: Send + Sync + 'static {
     workspace_id: &Uuid,
   ) -> Result<AFRole, AppError>;
   async fn get_role_from_uid(&self, uid: &i64, workspace_id: &Uuid) -> Result<AFRole, AppError>;
+
+  async fn get_access_level_from_uuid(
+    &self,
+    user_uuid: &Uuid,
+    workspace_id: &Uuid,
+  ) -> Result<AFAccessLevel, AppError>;
+
+  /// Returns the access level that the user's role grants in the workspace. It's resolved from
+  /// the permissions of the role in `af_role_permissions`, and cached until they change.
+  async fn get_access_level_from_uid(
+    &self,
+    uid: &i64,
+    workspace_id: &Uuid,
+  ) -> Result<AFAccessLevel, AppError>;
 }
 
 /// Represents the role of the user in the workspace by the workspace id.
@@ -46,26 +62,77 @@ enum MemberStatus {
   Valid(AFRole),
 }
 
+/// The access level that each role grants, resolved from the permissions of the role.
+/// - Key: the id of the role
+/// - Value: the highest access level of the permissions of the role, or None if the role doesn't
+///   have any permission
+///
+/// The `version` is bumped whenever a role is invalidated, so an access level that was read from
+/// the database before the invalidation isn't cached.
+#[derive(Default)]
+struct AccessLevelByRole {
+  version: u64,
+  levels: HashMap<i32, Option<AFAccessLevel>>,
+}
+
 pub struct WorkspaceAccessControlImpl {
   pg_pool: PgPool,
   member_status_by_uid: Arc<RwLock<MemberStatusByUid>>,
+  access_level_by_role: Arc<RwLock<AccessLevelByRole>>,
 }
 
 impl WorkspaceAccessControlImpl {
-  pub fn new(pg_pool: PgPool, listener: broadcast::Receiver<WorkspaceMemberChange>) -> Self {
+  pub fn new(
+    pg_pool: PgPool,
+    listener: broadcast::Receiver<WorkspaceMemberChange>,
+    role_permission_listener: broadcast::Receiver<RolePermissionChange>,
+  ) -> Self {
     let member_status_by_uid = Arc::new(RwLock::new(HashMap::new()));
     spawn_listen_on_workspace_member_change(
       listener,
       pg_pool.clone(),
       member_status_by_uid.clone(),
     );
+    let access_level_by_role = Arc::new(RwLock::new(AccessLevelByRole::default()));
+    spawn_listen_on_role_permission_change(role_permission_listener, access_level_by_role.clone());
 
     WorkspaceAccessControlImpl {
       pg_pool,
       member_status_by_uid,
+      access_level_by_role,
     }
   }
 
+  async fn get_role_access_level(&self, role: &AFRole) -> Result<AFAccessLevel, AppError> {
+    let role_id: i32 = role.clone().into();
+    let (version, cached) = {
+      let access_level_by_role = self.access_level_by_role.read().await;
+      (
+        access_level_by_role.version,
+        access_level_by_role.levels.get(&role_id).cloned(),
+      )
+    };
+    let access_level = match cached {
+      Some(access_level) => access_level,
+      None => {
+        let access_level = select_role_access_level(&self.pg_pool, role_id).await?;
+        let mut access_level_by_role = self.access_level_by_role.write().await;
+        if access_level_by_role.version == version {
+          access_level_by_role
+            .levels
+            .insert(role_id, access_level.clone());
+        }
+        access_level
+      },
+    };
+    access_level.ok_or_else(|| {
+      AppError::new(
+        ErrorCode::NotEnoughPermissions,
+        format!("role:{:?} doesn't have any permission", role),
+      )
+    })
+  }
+
   pub async fn update_member(&self, uid: &i64, workspace_id: &Uuid, role: AFRole) {
     update_workspace_member_status(uid, workspace_id, role, &self.member_status_by_uid).await;
   }
@@ -177,6 +244,30 @@ fn spawn_listen_on_workspace_member_change(
   });
 }
 
+fn spawn_listen_on_role_permission_change(
+  mut listener: broadcast::Receiver<RolePermissionChange>,
+  access_level_by_role: Arc<RwLock<AccessLevelByRole>>,
+) {
+  tokio::spawn(async move {
+    loop {
+      match listener.recv().await {
+        Ok(change) => {
+          let mut access_level_by_role = access_level_by_role.write().await;
+          access_level_by_role.version += 1;
+          access_level_by_role.levels.remove(&change.role_id);
+        },
+        // Some changes were missed, so none of the cached access levels can be trusted.
+        Err(broadcast::error::RecvError::Lagged(_)) => {
+          let mut access_level_by_role = access_level_by_role.write().await;
+          access_level_by_role.version += 1;
+          access_level_by_role.levels.clear();
+        },
+        Err(broadcast::error::RecvError::Closed) => break,
+      }
+    }
+  });
+}
+
 #[async_trait]
 impl WorkspaceAccessControl for WorkspaceAccessControlImpl {
   async fn get_role_from_uuid(
@@ -193,6 +284,24 @@ impl WorkspaceAccessControl for WorkspaceAccessControlImpl {
     let role = self.get_user_workspace_role(uid, workspace_id).await?;
     Ok(role)
   }
+
+  async fn get_access_level_from_uuid(
+    &self,
+    user_uuid: &Uuid,
+    workspace_id: &Uuid,
+  ) -> Result<AFAccessLevel, AppError> {
+    let uid = select_uid_from_uuid(&self.pg_pool, user_uuid).await?;
+    self.get_access_level_from_uid(&uid, workspace_id).await
+  }
+
+  async fn get_access_level_from_uid(
+    &self,
+    uid: &i64,
+    workspace_id: &Uuid,
+  ) -> Result<AFAccessLevel, AppError> {
+    let role = self.get_user_workspace_role(uid, workspace_id).await?;
+    self.get_role_access_level(&role).await
+  }
 }
 
 #[derive(Clone)]
@@ -211,36 +320,33 @@ where
     &self,
     workspace_id: &Uuid,
     user_uuid: &UserUuid,
-    method: Method,
-  ) -> Result<(), AppError> {
+    requirement: &AccessRequirement,
+  ) -> Result<(), AccessDeniedReason> {
     trace!(
-      "workspace_id: {:?}, user_uuid: {:?}",
+      "workspace_id: {:?}, user_uuid: {:?}, requirement: {:?}",
       workspace_id,
-      user_uuid
+      user_uuid,
+      requirement
     );
 
-    match self.0.get_role_from_uuid(user_uuid, workspace_id).await {
-      Ok(role) => {
-        if method == Method::DELETE || method == Method::POST || method == Method::PUT {
-          if matches!(role, AFRole::Owner) {
-            Ok(())
-          } else {
-            Err(AppError::new(
-              ErrorCode::NotEnoughPermissions,
-              format!(
-                "User:{:?} doesn't have the enough permission to access workspace:{}",
-                user_uuid, workspace_id
-              ),
-            ))
-          }
-        } else {
-          Ok(())
-        }
+    let not_member = |err: AppError| AccessDeniedReason::NotMember(err.message.to_string());
+    match requirement {
+      AccessRequirement::AccessLevel(_) => {
+        let access_level = self
+          .0
+          .get_access_level_from_uuid(user_uuid, workspace_id)
+          .await
+          .map_err(not_member)?;
+        requirement.check_access_level(&access_level)
+      },
+      AccessRequirement::Role(_) => {
+        let role = self
+          .0
+          .get_role_from_uuid(user_uuid, workspace_id)
+          .await
+          .map_err(not_member)?;
+        requirement.check_role(&role)
       },
-      Err(err) => Err(AppError::new(
-        ErrorCode::NotEnoughPermissions,
-        err.to_string(),
-      )),
     }
   }
 }
@@ -0,0 +1,107 @@
+use chrono::{Duration, Utc};
+use database_entity::dto::{AFRole, AFWorkspaceInvite, AuditActor};
+use serde::{Deserialize, Serialize};
+use shared_entity::app_error::AppError;
+use shared_entity::dto::workspace_dto::CreateWorkspaceMember;
+use shared_entity::error_code::ErrorCode;
+use token::{ParsedToken, TokenType};
+use uuid::Uuid;
+
+use crate::biz::workspace::ops::add_workspace_members;
+use crate::component::auth::signed_token::{
+  create_signed_token, parse_signed_token, revoke_signed_token, use_signed_token,
+};
+use crate::state::AppState;
+
+/// How long the invitation to a workspace can be accepted.
+const WORKSPACE_INVITE_EXPIRES_DAYS: i64 = 7;
+
+#[derive(Serialize, Deserialize)]
+struct WorkspaceInviteData {
+  email: String,
+  role: AFRole,
+}
+
+/// Creates the invitation of the email to the workspace. The token is sent to the email, e.g. in
+/// the link of the invitation email, so the user who accepts it proves to own the email.
+pub fn create_workspace_invite(
+  state: &AppState,
+  workspace_id: &Uuid,
+  member: CreateWorkspaceMember,
+) -> Result<AFWorkspaceInvite, AppError> {
+  let expire_duration = Duration::days(WORKSPACE_INVITE_EXPIRES_DAYS);
+  let token = create_signed_token(
+    state,
+    TokenType::WorkspaceInviteEmail,
+    &workspace_id.to_string(),
+    WorkspaceInviteData {
+      email: member.email,
+      role: member.role,
+    },
+    expire_duration,
+  )?;
+  Ok(AFWorkspaceInvite {
+    workspace_id: *workspace_id,
+    token,
+    expires_at: Utc::now() + expire_duration,
+  })
+}
+
+/// Adds the user to the workspace with the role of the invitation. The email of the user, which
+/// GoTrue verified, must be the email that the invitation is sent to. The invitation can only be
+/// accepted once.
+pub async fn accept_workspace_invite(
+  state: &AppState,
+  actor: &AuditActor,
+  email: &str,
+  workspace_id: &Uuid,
+  token: &str,
+) -> Result<(), AppError> {
+  let invite = parse_invite(state, workspace_id, token).await?;
+  if !invite.data.email.eq_ignore_ascii_case(email) {
+    return Err(AppError::new(
+      ErrorCode::NotEnoughPermissions,
+      "the invitation is sent to another email",
+    ));
+  }
+
+  let member = CreateWorkspaceMember {
+    email: email.to_string(),
+    role: invite.data.role.clone(),
+  };
+  let role_by_uid =
+    add_workspace_members(&state.pg_pool, actor, workspace_id, vec![member]).await?;
+  // The invitation can be accepted again if the user couldn't be added.
+  use_signed_token(state, &invite).await?;
+  for (uid, role) in role_by_uid {
+    state
+      .workspace_access_control
+      .update_member(&uid, workspace_id, role)
+      .await;
+  }
+  Ok(())
+}
+
+/// Revokes the invitation before it's accepted.
+pub async fn revoke_workspace_invite(
+  state: &AppState,
+  workspace_id: &Uuid,
+  token: &str,
+) -> Result<(), AppError> {
+  let invite = parse_invite(state, workspace_id, token).await?;
+  revoke_signed_token(state, &invite).await
+}
+
+async fn parse_invite(
+  state: &AppState,
+  workspace_id: &Uuid,
+  token: &str,
+) -> Result<ParsedToken<WorkspaceInviteData>, AppError> {
+  parse_signed_token(
+    state,
+    token,
+    TokenType::WorkspaceInviteEmail,
+    &workspace_id.to_string(),
+  )
+  .await
+}
@@ -1,3 +1,5 @@
 pub mod access_control;
+pub mod invite;
 pub mod member_listener;
 pub mod ops;
+pub mod role_listener;
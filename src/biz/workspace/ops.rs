@@ -1,20 +1,32 @@
 use crate::component::auth::jwt::UserUuid;
 use anyhow::Context;
+use database::audit::{insert_audit_log, select_audit_log_page};
 use database::collab::upsert_collab_member_with_txn;
+use database::role::{
+  insert_workspace_role, is_role_assigned, replace_role_permissions, select_permissions,
+  select_role_access_level, select_role_workspace_id, select_workspace_roles,
+  update_role_members_collab_permission, update_workspace_role_name,
+};
 use database::user::select_uid_from_email;
 use database::workspace::{
   delete_workspace_members, insert_workspace_member_with_txn, select_all_user_workspaces,
   select_workspace, select_workspace_member_list, update_updated_at_of_workspace,
   upsert_workspace_member,
 };
-use database_entity::dto::{AFAccessLevel, AFRole, AFWorkspace};
+use database_entity::dto::{
+  AFAccessLevel, AFAuditAction, AFAuditLogEntry, AFAuditLogPage, AFPermissions, AFRole,
+  AFWorkspace, AFWorkspaceRole, AFWorkspaceRoles, AuditActor, CreateWorkspaceRoleParams,
+  QueryAuditLogParams, UpdateWorkspaceRoleParams,
+};
 use database_entity::pg_row::{AFWorkspaceMemberRow, AFWorkspaceRow};
 use shared_entity::app_error::AppError;
 use shared_entity::dto::workspace_dto::{CreateWorkspaceMember, WorkspaceMemberChangeset};
-use sqlx::{types::uuid, PgPool};
+use shared_entity::error_code::ErrorCode;
+use sqlx::{types::uuid, Executor, PgConnection, PgPool, Postgres};
 use std::collections::HashMap;
 use std::ops::DerefMut;
 use uuid::Uuid;
+use validator::Validate;
 
 pub async fn get_all_user_workspaces(
   pg_pool: &PgPool,
@@ -54,7 +66,7 @@ pub async fn open_workspace(
 /// The function performs the following operations:
 /// 1. Begins a database transaction.
 /// 2. For each member:
-///    - Determines the access level based on the member's role.
+///    - Determines the access level based on the permissions of the member's role.
 ///    - If the member exists (based on their email), inserts them into the workspace and updates their collaboration access level.
 /// 3. Commits the database transaction.
 ///
@@ -64,7 +76,7 @@ pub async fn open_workspace(
 ///
 pub async fn add_workspace_members(
   pg_pool: &PgPool,
-  _user_uuid: &Uuid,
+  actor: &AuditActor,
   workspace_id: &Uuid,
   members: Vec<CreateWorkspaceMember>,
 ) -> Result<HashMap<i64, AFRole>, AppError> {
@@ -75,11 +87,7 @@ pub async fn add_workspace_members(
 
   let mut role_by_uid = HashMap::new();
   for member in members.into_iter() {
-    let access_level = match &member.role {
-      AFRole::Owner => AFAccessLevel::FullAccess,
-      AFRole::Member => AFAccessLevel::ReadAndWrite,
-      AFRole::Guest => AFAccessLevel::ReadOnly,
-    };
+    let access_level = role_access_level(txn.deref_mut(), workspace_id, &member.role).await?;
 
     let uid = select_uid_from_email(txn.deref_mut(), &member.email)
       .await
@@ -92,6 +100,14 @@ pub async fn add_workspace_members(
     insert_workspace_member_with_txn(&mut txn, workspace_id, &member.email, member.role.clone())
       .await?;
     upsert_collab_member_with_txn(uid, workspace_id.to_string(), &access_level, &mut txn).await?;
+    insert_audit_log(
+      txn.deref_mut(),
+      actor,
+      Some(workspace_id),
+      AFAuditAction::AddWorkspaceMember,
+      &member.email,
+    )
+    .await?;
     role_by_uid.insert(uid, member.role);
   }
 
@@ -104,6 +120,7 @@ pub async fn add_workspace_members(
 
 pub async fn remove_workspace_members(
   user_uuid: &UserUuid,
+  actor: &AuditActor,
   pg_pool: &PgPool,
   workspace_id: &Uuid,
   member_emails: &[String],
@@ -115,6 +132,14 @@ pub async fn remove_workspace_members(
 
   for email in member_emails {
     delete_workspace_members(user_uuid, &mut txn, workspace_id, email.as_str()).await?;
+    insert_audit_log(
+      txn.deref_mut(),
+      actor,
+      Some(workspace_id),
+      AFAuditAction::RemoveWorkspaceMember,
+      email,
+    )
+    .await?;
   }
 
   txn
@@ -134,15 +159,246 @@ pub async fn get_workspace_members(
 
 pub async fn update_workspace_member(
   pg_pool: &PgPool,
+  actor: &AuditActor,
   workspace_id: &Uuid,
   changeset: &WorkspaceMemberChangeset,
 ) -> Result<(), AppError> {
+  // Only the role of the member can be updated for now
+  let role = match &changeset.role {
+    None => return Ok(()),
+    Some(role) => role,
+  };
+
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("Begin transaction to update workspace member")?;
+  role_access_level(txn.deref_mut(), workspace_id, role).await?;
   upsert_workspace_member(
-    pg_pool,
+    txn.deref_mut(),
     workspace_id,
     &changeset.email,
-    changeset.role.clone(),
+    Some(role.clone()),
+  )
+  .await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(workspace_id),
+    AFAuditAction::UpdateWorkspaceMember,
+    &changeset.email,
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("Commit transaction to update workspace member")?;
+  Ok(())
+}
+
+pub async fn get_permissions(pg_pool: &PgPool) -> Result<AFPermissions, AppError> {
+  Ok(AFPermissions(select_permissions(pg_pool).await?))
+}
+
+pub async fn get_workspace_roles(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<AFWorkspaceRoles, AppError> {
+  Ok(AFWorkspaceRoles(
+    select_workspace_roles(pg_pool, workspace_id).await?,
+  ))
+}
+
+pub async fn create_workspace_role(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  workspace_id: &Uuid,
+  params: CreateWorkspaceRoleParams,
+) -> Result<AFWorkspaceRole, AppError> {
+  params.validate()?;
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("Begin transaction to create workspace role")?;
+  let role_id = insert_workspace_role(&mut txn, workspace_id, &params.name).await?;
+  replace_role_permissions(&mut txn, role_id, &params.permission_ids).await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(workspace_id),
+    AFAuditAction::CreateWorkspaceRole,
+    &role_id.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("Commit transaction to create workspace role")?;
+
+  find_workspace_role(pg_pool, workspace_id, role_id).await
+}
+
+/// Updates the name or the permissions of a custom role. The built-in roles can't be updated.
+pub async fn update_workspace_role(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  workspace_id: &Uuid,
+  role_id: i32,
+  params: UpdateWorkspaceRoleParams,
+) -> Result<AFWorkspaceRole, AppError> {
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("Begin transaction to update workspace role")?;
+  check_custom_role(txn.deref_mut(), workspace_id, role_id).await?;
+
+  if let Some(name) = &params.name {
+    if name.is_empty() {
+      return Err(AppError::new(
+        ErrorCode::InvalidRequestParams,
+        "role name should not be empty",
+      ));
+    }
+    update_workspace_role_name(&mut txn, workspace_id, role_id, name).await?;
+  }
+
+  if let Some(permission_ids) = &params.permission_ids {
+    replace_role_permissions(&mut txn, role_id, permission_ids).await?;
+    if let Some(access_level) = select_role_access_level(txn.deref_mut(), role_id).await? {
+      update_role_members_collab_permission(&mut txn, workspace_id, role_id, access_level).await?;
+    }
+  }
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(workspace_id),
+    AFAuditAction::UpdateWorkspaceRole,
+    &role_id.to_string(),
   )
   .await?;
+
+  txn
+    .commit()
+    .await
+    .context("Commit transaction to update workspace role")?;
+  find_workspace_role(pg_pool, workspace_id, role_id).await
+}
+
+/// Deletes a custom role. The role can't be deleted while it's assigned to any member.
+pub async fn delete_workspace_role(
+  pg_pool: &PgPool,
+  actor: &AuditActor,
+  workspace_id: &Uuid,
+  role_id: i32,
+) -> Result<(), AppError> {
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("Begin transaction to delete workspace role")?;
+  check_custom_role(txn.deref_mut(), workspace_id, role_id).await?;
+  if is_role_assigned(txn.deref_mut(), role_id).await? {
+    return Err(AppError::new(
+      ErrorCode::InvalidRequestParams,
+      format!("role:{} is still assigned to workspace members", role_id),
+    ));
+  }
+  database::role::delete_workspace_role(txn.deref_mut(), workspace_id, role_id).await?;
+  insert_audit_log(
+    txn.deref_mut(),
+    actor,
+    Some(workspace_id),
+    AFAuditAction::DeleteWorkspaceRole,
+    &role_id.to_string(),
+  )
+  .await?;
+  txn
+    .commit()
+    .await
+    .context("Commit transaction to delete workspace role")?;
   Ok(())
 }
+
+pub async fn get_workspace_audit_log(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  params: &QueryAuditLogParams,
+) -> Result<AFAuditLogPage, AppError> {
+  let (rows, next_cursor) = select_audit_log_page(pg_pool, Some(workspace_id), params).await?;
+  let items = rows
+    .into_iter()
+    .map(AFAuditLogEntry::try_from)
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(AFAuditLogPage { items, next_cursor })
+}
+
+async fn find_workspace_role(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  role_id: i32,
+) -> Result<AFWorkspaceRole, AppError> {
+  select_workspace_roles(pg_pool, workspace_id)
+    .await?
+    .into_iter()
+    .find(|role| role.role == AFRole::from(role_id))
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("role:{} not found", role_id),
+      )
+    })
+}
+
+async fn check_custom_role<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  role_id: i32,
+) -> Result<(), AppError> {
+  match select_role_workspace_id(executor, role_id).await? {
+    Some(Some(role_workspace_id)) if &role_workspace_id == workspace_id => Ok(()),
+    Some(None) => Err(AppError::new(
+      ErrorCode::NotEnoughPermissions,
+      "built-in roles can't be modified",
+    )),
+    _ => Err(AppError::new(
+      ErrorCode::RecordNotFound,
+      format!("role:{} not found in workspace:{}", role_id, workspace_id),
+    )),
+  }
+}
+
+/// Returns the highest access level granted by the permissions of the role. Custom roles can only
+/// be used in the workspace that defines them.
+async fn role_access_level(
+  conn: &mut PgConnection,
+  workspace_id: &Uuid,
+  role: &AFRole,
+) -> Result<AFAccessLevel, AppError> {
+  let role_id = i32::from(role.clone());
+  match select_role_workspace_id(&mut *conn, role_id).await? {
+    None => {
+      return Err(AppError::new(
+        ErrorCode::RecordNotFound,
+        format!("role:{:?} not found", role),
+      ))
+    },
+    Some(Some(role_workspace_id)) if &role_workspace_id != workspace_id => {
+      return Err(AppError::new(
+        ErrorCode::InvalidRequestParams,
+        format!(
+          "role:{:?} doesn't belong to workspace:{}",
+          role, workspace_id
+        ),
+      ))
+    },
+    Some(_) => {},
+  }
+
+  select_role_access_level(conn, role_id)
+    .await?
+    .ok_or_else(|| {
+      AppError::new(
+        ErrorCode::InvalidRequestParams,
+        format!("role:{:?} doesn't have any permission", role),
+      )
+    })
+}
@@ -0,0 +1,10 @@
+use crate::biz::pg_listener::PostgresDBListener;
+use serde::Deserialize;
+
+/// Notified when a permission is granted to or removed from a role.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RolePermissionChange {
+  pub role_id: i32,
+}
+
+pub type RolePermissionListener = PostgresDBListener<RolePermissionChange>;
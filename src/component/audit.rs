@@ -0,0 +1,59 @@
+use actix_http::Payload;
+use actix_web::http::header::USER_AGENT;
+use actix_web::{FromRequest, HttpRequest};
+use database_entity::dto::AuditActor;
+use std::ops::Deref;
+
+use crate::component::auth::jwt::UserUuid;
+use crate::component::client_ip::client_ip;
+
+/// The [AuditActor] of the request: the user that made the request, if any, together with the
+/// address and the user agent of the client. Extracting it never fails, so it can be used by
+/// the handlers that don't require a user.
+#[derive(Debug, Clone)]
+pub struct RequestActor(AuditActor);
+
+impl RequestActor {
+  fn new(req: &HttpRequest, user_uuid: Option<uuid::Uuid>) -> Self {
+    let ip = client_ip(req);
+    let user_agent = req
+      .headers()
+      .get(USER_AGENT)
+      .and_then(|value| value.to_str().ok())
+      .map(str::to_string);
+    Self(AuditActor {
+      user_uuid,
+      uid: None,
+      ip,
+      user_agent,
+    })
+  }
+
+  /// Identifies the user by uid, for the requests that don't carry the uuid of the user.
+  pub fn with_uid(mut self, uid: i64) -> Self {
+    self.0.uid = Some(uid);
+    self
+  }
+}
+
+impl Deref for RequestActor {
+  type Target = AuditActor;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl FromRequest for RequestActor {
+  type Error = actix_web::Error;
+
+  type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+    let user_uuid = UserUuid::from_request(req, payload)
+      .into_inner()
+      .ok()
+      .map(|uuid| *uuid);
+    std::future::ready(Ok(Self::new(req, user_uuid)))
+  }
+}
@@ -0,0 +1,178 @@
+use actix_http::Method;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use database::api_token::{select_active_api_token_by_hash, update_api_token_last_used};
+use database_entity::dto::AFApiTokenScope;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Personal access tokens start with this prefix, which is how they are told apart from the
+/// gotrue JWTs in the `Authorization` header.
+pub const API_TOKEN_PREFIX: &str = "afp_";
+
+pub fn is_api_token(token: &str) -> bool {
+  token.starts_with(API_TOKEN_PREFIX)
+}
+
+/// Generates a new token. Returns the plaintext token and the hash to store.
+pub fn generate_api_token() -> (String, String) {
+  generate_secret_token(API_TOKEN_PREFIX)
+}
+
+pub fn hash_api_token(token: &str) -> String {
+  hash_secret_token(token)
+}
+
+/// Generates a random token that starts with `prefix`. Returns the plaintext token and its
+/// sha256 hash. Only the hash is stored, so a leaked database doesn't leak usable tokens.
+pub fn generate_secret_token(prefix: &str) -> (String, String) {
+  let mut bytes = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  let token = format!("{}{}", prefix, URL_SAFE_NO_PAD.encode(bytes));
+  let hash = hash_secret_token(&token);
+  (token, hash)
+}
+
+pub fn hash_secret_token(token: &str) -> String {
+  hex_encode(&Sha256::digest(token.as_bytes()))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The authenticated personal access token of the request. It's inserted into the request
+/// extensions by the [WorkspaceAccessControlMiddleware](crate::middleware::access_control_mw::WorkspaceAccessControlMiddleware).
+#[derive(Debug, Clone)]
+pub struct ApiTokenAuth {
+  pub token_id: Uuid,
+  pub uid: i64,
+  pub user_uuid: Uuid,
+  pub workspace_id: Option<Uuid>,
+  pub scope: AFApiTokenScope,
+}
+
+impl ApiTokenAuth {
+  /// Checks that the token's scope allows the request.
+  ///
+  /// * `workspace_id` - the workspace of the request, None if the route isn't in a workspace.
+  /// * `is_collab` - whether the request reads or writes a collab object.
+  pub fn check_scope(
+    &self,
+    method: &Method,
+    workspace_id: Option<&Uuid>,
+    is_collab: bool,
+  ) -> Result<(), AppError> {
+    // A token that is limited to a workspace only works on the routes of that workspace, not on
+    // the routes of the user, e.g. the profile or the list of workspaces.
+    if let Some(allowed) = &self.workspace_id {
+      if workspace_id != Some(allowed) {
+        return Err(AppError::new(
+          ErrorCode::NotEnoughPermissions,
+          format!("api token is limited to workspace:{}", allowed),
+        ));
+      }
+    }
+
+    let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+    let allowed = match self.scope {
+      AFApiTokenScope::ReadOnly => is_read,
+      AFApiTokenScope::CollabWrite => is_read || is_collab,
+      AFApiTokenScope::Admin => true,
+    };
+    if allowed {
+      Ok(())
+    } else {
+      Err(AppError::new(
+        ErrorCode::NotEnoughPermissions,
+        format!(
+          "api token with scope {:?} can't {} this resource",
+          self.scope, method
+        ),
+      ))
+    }
+  }
+}
+
+/// Looks up the token in the database. Returns [ErrorCode::NotLoggedIn] if the token doesn't
+/// exist, is revoked or is expired.
+pub async fn resolve_api_token(pg_pool: &PgPool, token: &str) -> Result<ApiTokenAuth, AppError> {
+  let (row, user_uuid) = select_active_api_token_by_hash(pg_pool, &hash_api_token(token))
+    .await?
+    .ok_or_else(|| AppError::new(ErrorCode::NotLoggedIn, "invalid or expired api token"))?;
+
+  let pg_pool = pg_pool.clone();
+  let token_id = row.id;
+  tokio::spawn(async move {
+    if let Err(err) = update_api_token_last_used(&pg_pool, &token_id).await {
+      warn!(
+        "failed to update the last used time of api token: {:?}",
+        err
+      );
+    }
+  });
+
+  Ok(ApiTokenAuth {
+    token_id: row.id,
+    uid: row.uid,
+    user_uuid,
+    workspace_id: row.workspace_id,
+    scope: row.scope.into(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn token_auth(workspace_id: Option<Uuid>, scope: AFApiTokenScope) -> ApiTokenAuth {
+    ApiTokenAuth {
+      token_id: Uuid::new_v4(),
+      uid: 1,
+      user_uuid: Uuid::new_v4(),
+      workspace_id,
+      scope,
+    }
+  }
+
+  #[test]
+  fn generated_token_matches_hash() {
+    let (token, hash) = generate_api_token();
+    assert!(is_api_token(&token));
+    assert_eq!(hash_api_token(&token), hash);
+    assert_ne!(generate_api_token().0, token);
+  }
+
+  #[test]
+  fn read_only_scope_rejects_writes() {
+    let auth = token_auth(None, AFApiTokenScope::ReadOnly);
+    assert!(auth.check_scope(&Method::GET, None, true).is_ok());
+    assert!(auth.check_scope(&Method::POST, None, true).is_err());
+    assert!(auth.check_scope(&Method::DELETE, None, false).is_err());
+  }
+
+  #[test]
+  fn collab_write_scope_only_writes_collabs() {
+    let auth = token_auth(None, AFApiTokenScope::CollabWrite);
+    assert!(auth.check_scope(&Method::POST, None, true).is_ok());
+    assert!(auth.check_scope(&Method::POST, None, false).is_err());
+  }
+
+  #[test]
+  fn workspace_bound_token_rejects_other_workspaces() {
+    let workspace_id = Uuid::new_v4();
+    let auth = token_auth(Some(workspace_id), AFApiTokenScope::Admin);
+    assert!(auth
+      .check_scope(&Method::POST, Some(&workspace_id), false)
+      .is_ok());
+    assert!(auth
+      .check_scope(&Method::GET, Some(&Uuid::new_v4()), false)
+      .is_err());
+    assert!(auth.check_scope(&Method::GET, None, false).is_err());
+  }
+}
@@ -1,15 +1,19 @@
 use actix_http::Payload;
-use actix_web::{web::Data, FromRequest, HttpRequest};
+use actix_web::{web::Data, FromRequest, HttpMessage, HttpRequest};
 
-use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
-use secrecy::ExposeSecret;
+use database::user::select_user_has_verified_factor;
+use gotrue_entity::gotrue_jwt::{AuthenticatorAssuranceLevel, GoTrueJWTClaims};
 use serde::{Deserialize, Serialize};
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
 use sqlx::types::{uuid, Uuid};
+use sqlx::PgPool;
 use std::fmt::{Display, Formatter};
 use std::ops::Deref;
 use std::str::FromStr;
 use tracing::instrument;
 
+use crate::component::auth::api_token::{is_api_token, ApiTokenAuth};
 use crate::state::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +53,17 @@ impl FromRequest for UserUuid {
   type Future = std::future::Ready<Result<Self, Self::Error>>;
 
   fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+    // The personal access token was already verified by the access control middleware.
+    if let Some(api_token) = req.extensions().get::<ApiTokenAuth>() {
+      let state = req.app_data::<Data<AppState>>().unwrap();
+      let result = state
+        .user_ban_list
+        .check(&api_token.user_uuid)
+        .map(|_| UserUuid(api_token.user_uuid))
+        .map_err(actix_web::Error::from);
+      return std::future::ready(result);
+    }
+
     let auth = get_auth_from_request(req);
     match auth {
       Ok(auth) => match UserUuid::from_auth(auth) {
@@ -66,7 +81,14 @@ pub struct Authorization {
   pub claims: GoTrueJWTClaims,
 }
 
+/// The role that gotrue assigns to the admin account, see `setup_admin_account`.
+pub const ADMIN_ROLE: &str = "supabase_admin";
+
 impl Authorization {
+  pub fn is_admin(&self) -> bool {
+    self.claims.role == ADMIN_ROLE
+  }
+
   pub fn uuid(&self) -> Result<uuid::Uuid, actix_web::Error> {
     self
       .claims
@@ -83,6 +105,27 @@ impl Authorization {
         ))
       })
   }
+
+  /// Sensitive operations, e.g. changing the password, require the user to verify the second
+  /// factor in the session of the token, if the user has enrolled one.
+  pub async fn require_aal2(&self, pg_pool: &PgPool) -> Result<(), actix_web::Error> {
+    if self.claims.assurance_level() == AuthenticatorAssuranceLevel::Aal2 {
+      return Ok(());
+    }
+    let has_verified_factor = select_user_has_verified_factor(pg_pool, &self.uuid()?)
+      .await
+      .map_err(AppError::from)?;
+    if has_verified_factor {
+      return Err(
+        AppError::new(
+          ErrorCode::MfaRequired,
+          "verify the second factor before this operation",
+        )
+        .into(),
+      );
+    }
+    Ok(())
+  }
 }
 
 impl FromRequest for Authorization {
@@ -107,6 +150,26 @@ impl FromRequest for Authorization {
 
 fn get_auth_from_request(req: &HttpRequest) -> Result<Authorization, actix_web::Error> {
   let state = req.app_data::<Data<AppState>>().unwrap();
+  let token = bearer_token_from_request(req)?;
+  if is_api_token(token) {
+    return Err(actix_web::error::ErrorUnauthorized(
+      "Api tokens can't be used for this request",
+    ));
+  }
+  let auth = authorization_from_token(token, state)?;
+  // The access tokens issued before the ban, or before the sign out of their session, stay valid
+  // until they expire.
+  if let Ok(user_uuid) = auth.uuid() {
+    state.user_ban_list.check(&user_uuid)?;
+  }
+  state
+    .signed_out_sessions
+    .check(auth.claims.session_id.as_deref())?;
+  Ok(auth)
+}
+
+/// Returns the token of the `Authorization: Bearer <token>` header.
+pub fn bearer_token_from_request(req: &HttpRequest) -> Result<&str, actix_web::Error> {
   let bearer = req
     .headers()
     .get("Authorization")
@@ -123,8 +186,7 @@ fn get_auth_from_request(req: &HttpRequest) -> Result<Authorization, actix_web::
     .ok_or(actix_web::error::ErrorUnauthorized(
       "Invalid Authorization header, missing Bearer",
     ))?;
-
-  authorization_from_token(token, state)
+  Ok(token)
 }
 
 #[instrument(skip_all, err)]
@@ -139,14 +201,15 @@ pub fn authorization_from_token(
   })
 }
 
+/// The token is verified by the [JwtVerifier](crate::component::auth::jwt_verifier::JwtVerifier),
+/// which accepts the tokens of GoTrue and of the configured issuers.
 #[instrument(skip_all, err)]
 fn gotrue_jwt_claims_from_token(
   token: &str,
   state: &Data<AppState>,
 ) -> Result<GoTrueJWTClaims, actix_web::Error> {
-  GoTrueJWTClaims::verify(
-    token,
-    state.config.gotrue.jwt_secret.expose_secret().as_bytes(),
-  )
-  .map_err(actix_web::error::ErrorUnauthorized)
+  state
+    .jwt_verifier
+    .verify(token)
+    .map_err(actix_web::error::ErrorUnauthorized)
 }
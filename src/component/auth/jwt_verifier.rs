@@ -0,0 +1,427 @@
+use crate::config::config::{GoTrueSetting, JwtIssuerSetting, JwtSubject};
+use anyhow::{anyhow, Context, Error};
+use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// The keys are not fetched more often than this, even if the tokens keep using unknown keys.
+const MIN_JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Verifies the tokens that authenticate the http requests and the websocket connections.
+///
+/// HS256 tokens are verified with the shared `jwt_secret` of GoTrue. RS256 and ES256 tokens are
+/// verified with the public keys of the configured issuers, and their `iss` and `aud` claims must
+/// match the issuer of the key. The `sub` of an issuer other than GoTrue is mapped to a uuid of
+/// its own, see [JwtSubject].
+pub struct JwtVerifier {
+  jwt_secret: Secret<String>,
+  issuers: Vec<Arc<IssuerKeys>>,
+}
+
+impl JwtVerifier {
+  pub async fn new(setting: &GoTrueSetting) -> Result<Self, Error> {
+    let http_client = reqwest::Client::new();
+    let mut issuers = vec![];
+    for issuer_setting in &setting.jwt_issuers {
+      let issuer = Arc::new(IssuerKeys::new(issuer_setting.clone()));
+      match (&issuer_setting.jwks_url, &issuer_setting.key_file) {
+        (Some(_), None) => {
+          // The server starts even if the issuer is unreachable, the keys are fetched again later.
+          if let Err(err) = issuer.fetch_keys(&http_client).await {
+            warn!(
+              "failed to fetch the keys of {}: {:?}",
+              issuer_setting.issuer, err
+            );
+          }
+          spawn_refresh_keys(issuer.clone(), http_client.clone());
+        },
+        (None, Some(key_file)) => {
+          let content = tokio::fs::read_to_string(key_file)
+            .await
+            .with_context(|| format!("failed to read the key file: {}", key_file))?;
+          issuer.set_keys(parse_keys(&content)?);
+        },
+        _ => {
+          return Err(anyhow!(
+            "exactly one of jwks_url and key_file must be set for issuer: {}",
+            issuer_setting.issuer
+          ))
+        },
+      }
+      issuers.push(issuer);
+    }
+
+    Ok(Self {
+      jwt_secret: setting.jwt_secret.clone(),
+      issuers,
+    })
+  }
+
+  pub fn verify(&self, token: &str) -> Result<GoTrueJWTClaims, Error> {
+    self.verify_with_subject(token).map(|(claims, _)| claims)
+  }
+
+  /// Verifies the token, and returns its claims if it's issued by an issuer whose users GoTrue
+  /// doesn't know, see [JwtSubject::Namespaced]. Returns None for the tokens of GoTrue.
+  pub fn verify_external(&self, token: &str) -> Result<Option<GoTrueJWTClaims>, Error> {
+    let (claims, subject) = self.verify_with_subject(token)?;
+    Ok((subject == JwtSubject::Namespaced).then_some(claims))
+  }
+
+  fn verify_with_subject(&self, token: &str) -> Result<(GoTrueJWTClaims, JwtSubject), Error> {
+    let header = decode_header(token)?;
+    match header.alg {
+      Algorithm::HS256 => Ok((
+        GoTrueJWTClaims::verify(token, self.jwt_secret.expose_secret().as_bytes())?,
+        JwtSubject::Gotrue,
+      )),
+      Algorithm::RS256 | Algorithm::ES256 => {
+        self.verify_with_issuer_keys(token, header.alg, header.kid.as_deref())
+      },
+      alg => Err(anyhow!("unsupported token algorithm: {:?}", alg)),
+    }
+  }
+
+  fn verify_with_issuer_keys(
+    &self,
+    token: &str,
+    alg: Algorithm,
+    kid: Option<&str>,
+  ) -> Result<(GoTrueJWTClaims, JwtSubject), Error> {
+    let mut last_error = None;
+    for issuer in &self.issuers {
+      let mut validation = Validation::new(alg);
+      validation.set_issuer(&[&issuer.setting.issuer]);
+      validation.set_audience(&[&issuer.setting.audience]);
+
+      for key in issuer.matching_keys(alg, kid) {
+        match decode::<GoTrueJWTClaims>(token, &key, &validation) {
+          Ok(data) => {
+            let claims = issuer.map_subject(data.claims)?;
+            return Ok((claims, issuer.setting.subject));
+          },
+          Err(err) => {
+            // The key id may be used by more than one issuer, so an error of another issuer
+            // doesn't hide the error of the issuer of the token.
+            if last_error.is_none()
+              || !matches!(
+                err.kind(),
+                ErrorKind::InvalidIssuer | ErrorKind::InvalidAudience
+              )
+            {
+              last_error = Some(err);
+            }
+          },
+        }
+      }
+    }
+
+    match last_error {
+      Some(err) => Err(err.into()),
+      None => {
+        // The issuer may have rotated its keys.
+        for issuer in &self.issuers {
+          issuer.refresh.notify_one();
+        }
+        Err(anyhow!(
+          "the key that signed the token is unknown: {:?}",
+          kid
+        ))
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyType {
+  Rsa,
+  Ec,
+}
+
+impl KeyType {
+  fn from_algorithm(alg: Algorithm) -> Option<Self> {
+    match alg {
+      Algorithm::RS256 => Some(KeyType::Rsa),
+      Algorithm::ES256 => Some(KeyType::Ec),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone)]
+struct VerifyingKey {
+  kid: Option<String>,
+  key_type: KeyType,
+  key: DecodingKey,
+}
+
+struct IssuerKeys {
+  setting: JwtIssuerSetting,
+  keys: RwLock<Vec<VerifyingKey>>,
+  refresh: Notify,
+}
+
+impl IssuerKeys {
+  fn new(setting: JwtIssuerSetting) -> Self {
+    Self {
+      setting,
+      keys: RwLock::new(vec![]),
+      refresh: Notify::new(),
+    }
+  }
+
+  /// Returns the keys that can verify the token. A token without a key id is tried with every
+  /// key of the type, and a key without a key id, e.g. the key of a PEM file, with every token.
+  fn matching_keys(&self, alg: Algorithm, kid: Option<&str>) -> Vec<DecodingKey> {
+    let key_type = KeyType::from_algorithm(alg);
+    self
+      .keys
+      .read()
+      .unwrap()
+      .iter()
+      .filter(|key| Some(key.key_type) == key_type)
+      .filter(|key| kid.is_none() || key.kid.is_none() || key.kid.as_deref() == kid)
+      .map(|key| key.key.clone())
+      .collect()
+  }
+
+  fn map_subject(&self, mut claims: GoTrueJWTClaims) -> Result<GoTrueJWTClaims, Error> {
+    if self.setting.subject == JwtSubject::Namespaced {
+      let sub = claims
+        .sub
+        .as_deref()
+        .ok_or_else(|| anyhow!("the token of {} has no sub", self.setting.issuer))?;
+      claims.sub = Some(namespaced_subject(&self.setting.issuer, sub).to_string());
+    }
+    Ok(claims)
+  }
+
+  fn set_keys(&self, keys: Vec<VerifyingKey>) {
+    *self.keys.write().unwrap() = keys;
+  }
+
+  async fn fetch_keys(&self, http_client: &reqwest::Client) -> Result<(), Error> {
+    let jwks_url = self
+      .setting
+      .jwks_url
+      .as_ref()
+      .ok_or_else(|| anyhow!("the jwks_url of {} is not set", self.setting.issuer))?;
+    let content = http_client
+      .get(jwks_url)
+      .send()
+      .await?
+      .error_for_status()?
+      .text()
+      .await?;
+    let keys = parse_keys(&content)?;
+    info!(
+      "fetched {} keys of issuer: {}",
+      keys.len(),
+      self.setting.issuer
+    );
+    self.set_keys(keys);
+    Ok(())
+  }
+}
+
+/// Derives the uuid of the user from the issuer and the `sub`. The uuid is marked as a version 8
+/// uuid, which GoTrue never generates, so it can't be the id of a GoTrue user.
+fn namespaced_subject(issuer: &str, sub: &str) -> Uuid {
+  let digest = Sha256::new()
+    .chain_update(issuer.as_bytes())
+    .chain_update([0])
+    .chain_update(sub.as_bytes())
+    .finalize();
+  let mut bytes = [0u8; 16];
+  bytes.copy_from_slice(&digest[..16]);
+  bytes[6] = (bytes[6] & 0x0f) | 0x80;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  Uuid::from_bytes(bytes)
+}
+
+fn spawn_refresh_keys(issuer: Arc<IssuerKeys>, http_client: reqwest::Client) {
+  let interval =
+    Duration::from_secs(issuer.setting.refresh_interval_secs).max(MIN_JWKS_REFRESH_INTERVAL);
+  tokio::spawn(async move {
+    loop {
+      tokio::select! {
+        _ = tokio::time::sleep(interval) => {},
+        _ = issuer.refresh.notified() => {},
+      }
+      if let Err(err) = issuer.fetch_keys(&http_client).await {
+        warn!(
+          "failed to fetch the keys of {}: {:?}",
+          issuer.setting.issuer, err
+        );
+      }
+      tokio::time::sleep(MIN_JWKS_REFRESH_INTERVAL).await;
+    }
+  });
+}
+
+/// Parses a JWKS document or a PEM encoded public key. The keys that are not RSA or EC keys are
+/// skipped.
+fn parse_keys(content: &str) -> Result<Vec<VerifyingKey>, Error> {
+  if content.trim_start().starts_with('{') {
+    let jwks: JwkSet = serde_json::from_str(content).context("invalid JWKS")?;
+    let keys = jwks
+      .keys
+      .iter()
+      .filter_map(|jwk| {
+        let key_type = match jwk.algorithm {
+          AlgorithmParameters::RSA(_) => KeyType::Rsa,
+          AlgorithmParameters::EllipticCurve(_) => KeyType::Ec,
+          _ => return None,
+        };
+        match DecodingKey::from_jwk(jwk) {
+          Ok(key) => Some(VerifyingKey {
+            kid: jwk.common.key_id.clone(),
+            key_type,
+            key,
+          }),
+          Err(err) => {
+            warn!("skip the invalid key {:?}: {}", jwk.common.key_id, err);
+            None
+          },
+        }
+      })
+      .collect();
+    return Ok(keys);
+  }
+
+  let pem = content.as_bytes();
+  let (key_type, key) = match DecodingKey::from_rsa_pem(pem) {
+    Ok(key) => (KeyType::Rsa, key),
+    Err(_) => (
+      KeyType::Ec,
+      DecodingKey::from_ec_pem(pem).context("the key file is neither a JWKS nor a PEM key")?,
+    ),
+  };
+  Ok(vec![VerifyingKey {
+    kid: None,
+    key_type,
+    key,
+  }])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use jsonwebtoken::{encode, EncodingKey, Header};
+  use openssl::rsa::Rsa;
+  use serde_json::json;
+
+  const SUB: &str = "4b0e2f0a-0c2f-4a8e-9a84-9e4c1a1b7d3a";
+
+  fn verifier_with_issuer(issuer: &str, subject: JwtSubject, public_pem: &[u8]) -> JwtVerifier {
+    let keys = IssuerKeys::new(JwtIssuerSetting {
+      issuer: issuer.to_string(),
+      audience: "authenticated".to_string(),
+      subject,
+      jwks_url: None,
+      key_file: Some("unused".to_string()),
+      refresh_interval_secs: 600,
+    });
+    keys.set_keys(parse_keys(std::str::from_utf8(public_pem).unwrap()).unwrap());
+    JwtVerifier {
+      jwt_secret: Secret::new("secret".to_string()),
+      issuers: vec![Arc::new(keys)],
+    }
+  }
+
+  fn rs256_token(issuer: &str, audience: &str, private_pem: &[u8]) -> String {
+    let claims = json!({
+      "iss": issuer,
+      "aud": audience,
+      "sub": SUB,
+      "exp": chrono::Utc::now().timestamp() + 3600,
+      "email": "user@appflowy.io",
+      "phone": "",
+      "app_metadata": {},
+      "user_metadata": {},
+      "role": "authenticated",
+    });
+    encode(
+      &Header::new(Algorithm::RS256),
+      &claims,
+      &EncodingKey::from_rsa_pem(private_pem).unwrap(),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn verify_rs256_token_of_configured_issuer() {
+    let rsa = Rsa::generate(2048).unwrap();
+    let private_pem = rsa.private_key_to_pem().unwrap();
+    let public_pem = rsa.public_key_to_pem().unwrap();
+    let verifier = verifier_with_issuer(
+      "https://issuer.appflowy.io",
+      JwtSubject::Gotrue,
+      &public_pem,
+    );
+
+    let token = rs256_token("https://issuer.appflowy.io", "authenticated", &private_pem);
+    assert!(verifier.verify_external(&token).unwrap().is_none());
+    let claims = verifier.verify(&token).unwrap();
+    assert_eq!(claims.email, "user@appflowy.io");
+    assert_eq!(claims.sub.as_deref(), Some(SUB));
+
+    // The issuer of the token must be the issuer of the key.
+    let token = rs256_token("https://other.appflowy.io", "authenticated", &private_pem);
+    assert!(verifier.verify(&token).is_err());
+
+    // The token must be issued for the server.
+    let token = rs256_token("https://issuer.appflowy.io", "other", &private_pem);
+    assert!(verifier.verify(&token).is_err());
+  }
+
+  #[test]
+  fn namespace_sub_of_external_issuer() {
+    let rsa = Rsa::generate(2048).unwrap();
+    let private_pem = rsa.private_key_to_pem().unwrap();
+    let verifier = verifier_with_issuer(
+      "https://issuer.appflowy.io",
+      JwtSubject::Namespaced,
+      &rsa.public_key_to_pem().unwrap(),
+    );
+
+    let token = rs256_token("https://issuer.appflowy.io", "authenticated", &private_pem);
+    assert!(verifier.verify_external(&token).unwrap().is_some());
+    let sub = verifier.verify(&token).unwrap().sub.unwrap();
+    assert_ne!(sub, SUB);
+    assert_eq!(
+      sub,
+      namespaced_subject("https://issuer.appflowy.io", SUB).to_string()
+    );
+    assert_ne!(
+      namespaced_subject("https://issuer.appflowy.io", SUB),
+      namespaced_subject("https://other.appflowy.io", SUB)
+    );
+  }
+
+  #[test]
+  fn reject_rs256_token_signed_with_unknown_key() {
+    let rsa = Rsa::generate(2048).unwrap();
+    let verifier = verifier_with_issuer(
+      "https://issuer.appflowy.io",
+      JwtSubject::Gotrue,
+      &rsa.public_key_to_pem().unwrap(),
+    );
+
+    let other = Rsa::generate(2048).unwrap();
+    let token = rs256_token(
+      "https://issuer.appflowy.io",
+      "authenticated",
+      &other.private_key_to_pem().unwrap(),
+    );
+    assert!(verifier.verify(&token).is_err());
+  }
+}
@@ -1,6 +1,10 @@
+pub mod api_token;
 mod error;
 pub mod jwt;
+pub mod jwt_verifier;
 mod password;
+pub mod share_link;
+pub mod signed_token;
 mod user;
 
 pub use error::*;
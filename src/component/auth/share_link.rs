@@ -0,0 +1,152 @@
+use actix_http::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use collab_entity::CollabType;
+use database::share_link::select_active_share_link_by_hash;
+use database_entity::dto::{shareable_collab_type_from_value, AFAccessLevel};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use secrecy::Secret;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::component::auth::api_token::{generate_secret_token, hash_secret_token};
+use crate::component::auth::verify_password_hash;
+use crate::telemetry::spawn_blocking_with_tracing;
+
+/// Share link tokens start with this prefix.
+pub const SHARE_TOKEN_PREFIX: &str = "afs_";
+
+/// The header that carries the password of a password protected share link.
+pub const SHARE_PASSWORD_HEADER: &str = "X-Share-Password";
+
+/// The number of wrong passwords after which the link stops accepting passwords. The count is
+/// dropped once no wrong password is sent for the window.
+const MAX_SHARE_PASSWORD_ATTEMPTS: i64 = 10;
+const SHARE_PASSWORD_ATTEMPT_WINDOW_SECS: usize = 15 * 60;
+const SHARE_PASSWORD_ATTEMPT_KEY_PREFIX: &str = "af_share_password_attempts";
+
+pub fn is_share_token(token: &str) -> bool {
+  token.starts_with(SHARE_TOKEN_PREFIX)
+}
+
+/// Generates a new share link token. Returns the plaintext token and the hash to store.
+pub fn generate_share_token() -> (String, String) {
+  generate_secret_token(SHARE_TOKEN_PREFIX)
+}
+
+/// The share link that the anonymous request was made with. It's inserted into the request
+/// extensions by the [WorkspaceAccessControlMiddleware](crate::middleware::access_control_mw::WorkspaceAccessControlMiddleware).
+#[derive(Debug, Clone)]
+pub struct ShareLinkAuth {
+  pub link_id: Uuid,
+  pub oid: String,
+  pub workspace_id: Uuid,
+  pub collab_type: CollabType,
+  pub access_level: AFAccessLevel,
+  /// The uid of the user that created the link. The object is read on behalf of this user, so
+  /// the link stops working once the user loses access to the object.
+  pub created_by: i64,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl FromRequest for ShareLinkAuth {
+  type Error = actix_web::Error;
+
+  type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+    let result = req
+      .extensions()
+      .get::<ShareLinkAuth>()
+      .cloned()
+      .ok_or_else(|| AppError::new(ErrorCode::NotLoggedIn, "invalid share link").into());
+    std::future::ready(result)
+  }
+}
+
+/// Looks up the share link in the database and checks its password. Returns
+/// [ErrorCode::RecordNotFound] if the link doesn't exist or is expired, so a guessed token
+/// doesn't reveal anything, and [ErrorCode::InvalidPassword] if the password is missing or wrong.
+///
+/// The wrong passwords are counted per link, and [ErrorCode::TooManyRequests] is returned once
+/// there are too many of them, so the password can't be guessed.
+pub async fn resolve_share_link(
+  pg_pool: &PgPool,
+  redis_client: &ConnectionManager,
+  token: &str,
+  password: Option<String>,
+) -> Result<ShareLinkAuth, AppError> {
+  if !is_share_token(token) {
+    return Err(AppError::new(
+      ErrorCode::RecordNotFound,
+      "share link not found",
+    ));
+  }
+
+  let row = select_active_share_link_by_hash(pg_pool, &hash_secret_token(token))
+    .await?
+    .ok_or_else(|| AppError::new(ErrorCode::RecordNotFound, "share link not found"))?;
+
+  if let Some(password_hash) = row.password_hash {
+    let password = password.ok_or_else(|| {
+      AppError::new(
+        ErrorCode::InvalidPassword,
+        "the share link requires a password",
+      )
+    })?;
+    let attempt_key = format!("{}:{}", SHARE_PASSWORD_ATTEMPT_KEY_PREFIX, row.id);
+    let mut conn = redis_client.clone();
+    // Like the rate limiter, the check fails open if redis is unavailable.
+    let attempts = conn
+      .get::<_, Option<i64>>(&attempt_key)
+      .await
+      .unwrap_or_else(|err| {
+        warn!("share link password attempts are unavailable: {:?}", err);
+        None
+      })
+      .unwrap_or(0);
+    if attempts >= MAX_SHARE_PASSWORD_ATTEMPTS {
+      return Err(AppError::new(
+        ErrorCode::TooManyRequests,
+        "too many wrong share link passwords, retry later",
+      ));
+    }
+
+    let is_valid = spawn_blocking_with_tracing(move || {
+      verify_password_hash(Secret::new(password_hash), Secret::new(password))
+    })
+    .await
+    .context("Failed to spawn blocking task.")?
+    .is_ok();
+    if !is_valid {
+      let result = redis::pipe()
+        .atomic()
+        .incr(&attempt_key, 1)
+        .expire(&attempt_key, SHARE_PASSWORD_ATTEMPT_WINDOW_SECS)
+        .query_async::<_, ()>(&mut conn)
+        .await;
+      if let Err(err) = result {
+        warn!("failed to count the share link password attempt: {:?}", err);
+      }
+      return Err(AppError::new(
+        ErrorCode::InvalidPassword,
+        "invalid share link password",
+      ));
+    }
+  }
+
+  Ok(ShareLinkAuth {
+    link_id: row.id,
+    oid: row.oid,
+    workspace_id: row.workspace_id,
+    collab_type: shareable_collab_type_from_value(row.collab_type)?,
+    access_level: row.access_level.into(),
+    created_by: row.created_by,
+    expires_at: row.expires_at,
+  })
+}
@@ -0,0 +1,113 @@
+use chrono::{Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use token::{ParsedToken, TokenError, TokenType};
+
+use crate::state::AppState;
+
+/// The redis key that marks the token as used or revoked. It expires along with the token.
+fn revoked_token_key(token_id: &str) -> String {
+  format!("af_revoked_token:{}", token_id)
+}
+
+/// Creates a token signed with the current `server_key`, see [token::TokenKeys::create_token].
+pub fn create_signed_token(
+  state: &AppState,
+  token_type: TokenType,
+  audience: &str,
+  data: impl Serialize,
+  expire_duration: Duration,
+) -> Result<String, AppError> {
+  state
+    .token_keys
+    .create_token(token_type, audience, data, expire_duration)
+    .map_err(token_error)
+}
+
+/// Parses the token and checks that it's neither used nor revoked. A single-use token must be
+/// marked as used with [use_signed_token] once the action it allows has succeeded.
+///
+/// Unlike the rate limiter, it fails closed: the token is rejected if redis is unavailable, since
+/// the revoked token could be used again otherwise.
+pub async fn parse_signed_token<T: DeserializeOwned>(
+  state: &AppState,
+  token: &str,
+  token_type: TokenType,
+  audience: &str,
+) -> Result<ParsedToken<T>, AppError> {
+  let token = state
+    .token_keys
+    .parse_token::<T>(token, token_type, audience)
+    .map_err(token_error)?;
+
+  let mut conn = state.redis_client.clone();
+  let is_revoked = redis::cmd("EXISTS")
+    .arg(revoked_token_key(&token.id))
+    .query_async::<_, bool>(&mut conn)
+    .await
+    .map_err(redis_error)?;
+  if is_revoked {
+    return Err(token_error(TokenError::Revoked));
+  }
+  Ok(token)
+}
+
+/// Marks the single-use token as used, so it's rejected with [ErrorCode::TokenRevoked] the next
+/// time. Fails with [ErrorCode::TokenRevoked] if the token was used in the meantime, e.g. by a
+/// concurrent request, in which case the result of the action must be discarded.
+pub async fn use_signed_token<T>(state: &AppState, token: &ParsedToken<T>) -> Result<(), AppError> {
+  debug_assert!(token.token_type.is_single_use());
+  // SET NX only succeeds for the first use of the token.
+  let mut conn = state.redis_client.clone();
+  let reply: Option<String> = redis::cmd("SET")
+    .arg(revoked_token_key(&token.id))
+    .arg(1)
+    .arg("NX")
+    .arg("PX")
+    .arg(remaining_millis(token))
+    .query_async(&mut conn)
+    .await
+    .map_err(redis_error)?;
+  if reply.is_none() {
+    return Err(token_error(TokenError::Revoked));
+  }
+  Ok(())
+}
+
+/// Revokes the token before it expires.
+pub async fn revoke_signed_token<T>(
+  state: &AppState,
+  token: &ParsedToken<T>,
+) -> Result<(), AppError> {
+  let mut conn = state.redis_client.clone();
+  redis::cmd("SET")
+    .arg(revoked_token_key(&token.id))
+    .arg(1)
+    .arg("PX")
+    .arg(remaining_millis(token))
+    .query_async::<_, ()>(&mut conn)
+    .await
+    .map_err(redis_error)?;
+  Ok(())
+}
+
+fn remaining_millis<T>(token: &ParsedToken<T>) -> i64 {
+  // At least one millisecond, PX doesn't accept zero.
+  (token.expire_at - Utc::now()).num_milliseconds().max(1)
+}
+
+fn token_error(err: TokenError) -> AppError {
+  let code = match &err {
+    TokenError::Expired => ErrorCode::TokenExpired,
+    TokenError::WrongPurpose { .. } => ErrorCode::TokenPurposeMismatch,
+    TokenError::Revoked => ErrorCode::TokenRevoked,
+    TokenError::UnknownKey | TokenError::Jwt(_) => ErrorCode::InvalidRequestParams,
+  };
+  AppError::new(code, err.to_string())
+}
+
+fn redis_error(err: redis::RedisError) -> AppError {
+  AppError::new(ErrorCode::Unhandled, format!("redis error: {}", err))
+}
@@ -1,184 +1,203 @@
-use crate::component::auth::{
-  compute_hash_password, internal_error, validate_credentials, AuthError, Credentials,
-};
-use crate::config::env::domain;
-use crate::state::{AppState, UserCache};
-use crate::telemetry::spawn_blocking_with_tracing;
-use actix_web::HttpRequest;
-use anyhow::Context;
-use chrono::Duration;
-use std::fmt::{Display, Formatter};
-use std::hash::{Hash, Hasher};
-use std::ops::DerefMut;
-
-use secrecy::zeroize::DefaultIsZeroes;
-use secrecy::{CloneableSecret, DebugSecret, ExposeSecret, Secret, Zeroize};
-use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Postgres, Transaction};
-use std::sync::Arc;
-use token::{create_token, parse_token, TokenError};
-use tokio::sync::RwLock;
-use tracing::instrument;
-
+use crate::biz::user::{gotrue_admin_token, verify_token, LEGACY_UID_KEY};
+use crate::component::auth::jwt::Authorization;
+use crate::component::auth::{validate_credentials, AuthError, Credentials};
+use crate::state::AppState;
+use database::user::select_legacy_password_uid;
+use gotrue::grant::{Grant, PasswordGrant};
+use gotrue::params::{AdminUserParams, MagicLinkParams};
+use gotrue_entity::dto::{SignUpResponse, UpdateGotrueUserParams};
+use secrecy::Secret;
+use serde::Deserialize;
+use serde_json::json;
+use shared_entity::app_error::AppError;
+use shared_entity::dto::auth_dto::SignInPasswordResponse;
+use shared_entity::error_code::ErrorCode;
+use std::collections::BTreeMap;
+use tracing::{instrument, warn};
+
+/// Signs the user in with GoTrue. The users who registered with the deprecated local password are
+/// moved to GoTrue on their first login if [LegacyAuthSetting::migrate_on_login] is enabled. The
+/// moved user signs in once the email is confirmed.
+///
+/// [LegacyAuthSetting::migrate_on_login]: crate::config::config::LegacyAuthSetting
+#[instrument(skip(password, state), err)]
 pub async fn login(
   email: String,
   password: String,
   state: &AppState,
-) -> Result<(LoginResponse, Secret<Token>), AuthError> {
-  let credentials = Credentials {
-    email,
-    password: Secret::new(password),
+) -> Result<SignInPasswordResponse, AppError> {
+  let grant = Grant::Password(PasswordGrant {
+    email: email.clone(),
+    password: password.clone(),
+  });
+  let access_token_resp = match state.gotrue_client.token(&grant).await {
+    Ok(access_token_resp) => access_token_resp,
+    // GoTrue responds with 400 to the invalid credentials, including the unknown emails.
+    Err(err) if err.code == 400 && state.config.legacy_auth.migrate_on_login => {
+      if !migrate_legacy_user(state, &email, password).await? {
+        return Err(err.into());
+      }
+      return Err(AppError::new(
+        ErrorCode::NotLoggedIn,
+        format!("confirm the email sent to {}, then sign in again", email),
+      ));
+    },
+    Err(err) => return Err(err.into()),
   };
-  let server_key = &state.config.application.server_key;
 
-  match validate_credentials(credentials, &state.pg_pool).await {
-    Ok(uid) => {
-      let token = Token::create_token(uid, server_key)?;
-      let logged_user = LoggedUser::new(uid);
-      state.user.write().await.authorized(logged_user);
-      Ok((
-        LoginResponse {
-          token: token.0.clone(),
-          uid: uid.to_string(),
-        },
-        Secret::new(token),
-      ))
-    },
-    Err(err) => Err(err),
-  }
+  let is_new = verify_token(
+    &state.pg_pool,
+    &state.id_gen,
+    &state.gotrue_client,
+    &access_token_resp.access_token,
+  )
+  .await?;
+  Ok(SignInPasswordResponse {
+    access_token_resp,
+    is_new,
+  })
 }
 
-pub async fn logout(logged_user: LoggedUser, cache: Arc<RwLock<UserCache>>) {
-  cache.write().await.unauthorized(logged_user);
+/// Signs the user out of the GoTrue session of the token.
+pub async fn logout(auth: &Authorization, state: &AppState) -> Result<(), AppError> {
+  state.gotrue_client.logout(&auth.token).await?;
+  Ok(())
 }
 
-#[instrument(skip_all, err)]
+/// Signs the user up with GoTrue. The `af_user` is created once the user is signed in, which
+/// requires the email to be confirmed first unless GoTrue confirms it automatically.
+#[instrument(skip(password, state), err)]
 pub async fn register(
   username: String,
   email: String,
   password: String,
   state: &AppState,
-) -> Result<RegisterResponse, AuthError> {
-  let pg_pool = state.pg_pool.clone();
-  let server_key = &state.config.application.server_key;
-  let mut transaction = pg_pool
-    .begin()
-    .await
-    .context("Failed to acquire a Postgres connection to register user")
-    .map_err(internal_error)?;
-
-  if is_email_exist(&mut transaction, email.as_ref())
-    .await
-    .map_err(internal_error)?
+) -> Result<SignUpResponse, AppError> {
+  if select_legacy_password_uid(&state.pg_pool, &email)
+    .await?
+    .is_some()
   {
-    return Err(AuthError::UserAlreadyExist { email });
+    return Err(AppError::new(
+      ErrorCode::RecordAlreadyExists,
+      format!("{} is already used", email),
+    ));
   }
 
-  let uid = state.id_gen.write().await.next_id();
-  let token = Token::create_token(uid, server_key)?;
-  let password = compute_hash_password(password.as_bytes()).map_err(internal_error)?;
-  let _ = sqlx::query!(
-    r#"
-       INSERT INTO af_user (uid, email, name, password)
-       VALUES ($1, $2, $3, $4)
-     "#,
-    uid,
-    email,
-    username,
-    password.expose_secret(),
-  )
-  .execute(transaction.deref_mut())
-  .await
-  .context("Save user to disk failed")
-  .map_err(internal_error)?;
-
-  transaction
-    .commit()
-    .await
-    .context("Failed to commit SQL transaction to register user.")
-    .map_err(internal_error)?;
-
-  let logged_user = LoggedUser::new(uid);
-  state.user.write().await.authorized(logged_user);
-
-  Ok(RegisterResponse {
-    token: token.0.clone(),
-  })
+  let user_metadata = json!({ "name": username });
+  let resp = state
+    .gotrue_client
+    .sign_up_with_metadata(&email, &password, &user_metadata)
+    .await?;
+  if let SignUpResponse::Authenticated(access_token_resp) = &resp {
+    verify_token(
+      &state.pg_pool,
+      &state.id_gen,
+      &state.gotrue_client,
+      &access_token_resp.access_token,
+    )
+    .await?;
+  }
+  Ok(resp)
 }
 
+/// Changes the password of the GoTrue user after checking the current password.
+#[instrument(skip_all, err)]
 pub async fn change_password(
-  pg_pool: PgPool,
-  logged_user: LoggedUser,
+  auth: &Authorization,
   current_password: String,
   new_password: String,
-) -> Result<(), AuthError> {
-  let mut transaction = pg_pool
-    .begin()
-    .await
-    .context("Failed to acquire a Postgres connection to change password")
-    .map_err(internal_error)?;
-
-  let email = get_user_email(*logged_user.expose_secret(), &mut transaction).await?;
-
-  // check password
-  let credentials = Credentials {
-    email,
-    password: Secret::new(current_password),
+  state: &AppState,
+) -> Result<(), AppError> {
+  // GoTrue doesn't check the current password, so it's checked by signing in with it.
+  let grant = Grant::Password(PasswordGrant {
+    email: auth.claims.email.clone(),
+    password: current_password,
+  });
+  let access_token_resp = match state.gotrue_client.token(&grant).await {
+    Ok(access_token_resp) => access_token_resp,
+    Err(err) if err.code == 400 => {
+      return Err(AppError::new(
+        ErrorCode::InvalidPassword,
+        "the current password is incorrect",
+      ))
+    },
+    Err(err) => return Err(err.into()),
   };
-  let _ = validate_credentials(credentials, &pg_pool).await?;
-
-  // Hash password
-  let new_hash_password =
-    spawn_blocking_with_tracing(move || compute_hash_password(new_password.as_bytes()))
-      .await
-      .context("Failed to hash password")??;
-
-  // Save password to disk
-  let sql = "UPDATE af_user SET password = $1 where uid = $2";
-  let _ = sqlx::query(sql)
-    .bind(new_hash_password.expose_secret())
-    .bind(logged_user.expose_secret())
-    .execute(transaction.deref_mut())
-    .await
-    .context("Failed to change user's password in the database.")?;
+  // The session was only created to check the password.
+  state
+    .gotrue_client
+    .logout(&access_token_resp.access_token)
+    .await?;
 
-  transaction
-    .commit()
-    .await
-    .context("Failed to commit SQL transaction to change user's password")
-    .map_err(internal_error)?;
+  let params = UpdateGotrueUserParams::new().with_opt_password(Some(new_password));
+  state
+    .gotrue_client
+    .update_user(&auth.token, &params)
+    .await?;
   Ok(())
 }
 
-pub async fn get_user_email(
-  uid: i64,
-  transaction: &mut Transaction<'_, Postgres>,
-) -> Result<String, anyhow::Error> {
-  let row = sqlx::query!(
-    r#"
-        SELECT email
-        FROM af_user
-        WHERE uid = $1
-        "#,
-    uid,
-  )
-  .fetch_one(transaction.deref_mut())
-  .await
-  .context("Failed to retrieve the user email`")?;
-  Ok(row.email)
-}
-
-/// TODO: cache this state in [AppState]
-async fn is_email_exist(
-  transaction: &mut Transaction<'_, Postgres>,
+/// Creates the GoTrue user of the user who registered with the deprecated local password, if the
+/// password matches the one stored in `af_user`. Returns false otherwise. The email of the GoTrue
+/// user isn't confirmed, so a link is sent to confirm it, and the `af_user` is linked to the
+/// GoTrue user once it signs in, see [verify_token].
+async fn migrate_legacy_user(
+  state: &AppState,
   email: &str,
-) -> Result<bool, anyhow::Error> {
-  let result = sqlx::query(r#"SELECT email FROM af_user WHERE email = $1"#)
-    .bind(email)
-    .fetch_optional(transaction.deref_mut())
-    .await?;
+  password: String,
+) -> Result<bool, AppError> {
+  let uid = match select_legacy_password_uid(&state.pg_pool, email).await? {
+    Some(uid) => uid,
+    None => return Ok(false),
+  };
+  let credentials = Credentials {
+    email: email.to_string(),
+    password: Secret::new(password.clone()),
+  };
+  match validate_credentials(credentials, &state.pg_pool).await {
+    Ok(_) => {},
+    Err(AuthError::InvalidPassword | AuthError::InvalidCredentials(_)) => return Ok(false),
+    Err(err) => return Err(anyhow::Error::from(err).into()),
+  }
 
-  Ok(result.is_some())
+  let admin_token = gotrue_admin_token(&state.gotrue_client, &state.config.gotrue).await?;
+  let params = AdminUserParams {
+    email: email.to_string(),
+    password: Some(password),
+    email_confirm: false,
+    app_metadata: BTreeMap::from([(LEGACY_UID_KEY.to_string(), json!(uid))]),
+    ..Default::default()
+  };
+  match state
+    .gotrue_client
+    .admin_add_user(&admin_token, &params)
+    .await
+  {
+    Ok(_) => {
+      let params = MagicLinkParams {
+        email: email.to_string(),
+        ..Default::default()
+      };
+      // The user can ask GoTrue for another link, e.g. by recovering the password.
+      if let Err(err) = state.gotrue_client.magic_link(&admin_token, &params).await {
+        warn!("failed to send the confirmation link to {}: {}", email, err);
+      }
+    },
+    // GoTrue already has a user with the email, e.g. the user signed up again after the local
+    // password was deprecated, or the user was moved but hasn't confirmed the email yet. The
+    // GoTrue user is never changed here, the user signs in with it or recovers its password.
+    Err(err) if err.code == 422 => {
+      return Err(AppError::new(
+        ErrorCode::RecordAlreadyExists,
+        format!(
+          "{} already has an account, confirm its email, sign in or recover its password",
+          email
+        ),
+      ))
+    },
+    Err(err) => return Err(err.into()),
+  }
+  Ok(true)
 }
 
 #[derive(Default, Deserialize, Debug)]
@@ -187,12 +206,6 @@ pub struct LoginRequest {
   pub password: String,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
-pub struct LoginResponse {
-  pub token: String,
-  pub uid: String,
-}
-
 #[derive(Default, Deserialize, Debug)]
 pub struct RegisterRequest {
   pub email: String,
@@ -200,133 +213,9 @@ pub struct RegisterRequest {
   pub name: String,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
-pub struct RegisterResponse {
-  pub token: String,
-}
-
 #[derive(Default, Deserialize, Debug)]
 pub struct ChangePasswordRequest {
   pub current_password: String,
   pub new_password: String,
   pub new_password_confirm: String,
 }
-
-#[derive(Clone, Default)]
-pub struct SecretI64(i64);
-impl Copy for SecretI64 {}
-impl DefaultIsZeroes for SecretI64 {}
-impl DebugSecret for SecretI64 {}
-impl CloneableSecret for SecretI64 {}
-
-impl std::ops::Deref for SecretI64 {
-  type Target = i64;
-
-  fn deref(&self) -> &Self::Target {
-    &self.0
-  }
-}
-
-#[derive(Debug, Clone)]
-pub struct LoggedUser(Secret<SecretI64>);
-
-impl From<Claim> for LoggedUser {
-  fn from(c: Claim) -> Self {
-    Self(Secret::new(SecretI64(c.uid)))
-  }
-}
-
-impl LoggedUser {
-  pub fn new(uid: i64) -> Self {
-    Self(Secret::new(SecretI64(uid)))
-  }
-
-  pub fn from_token(server_key: &Secret<String>, token: &str) -> Result<Self, AuthError> {
-    let user: LoggedUser = Token::decode_token(server_key, token)?.into();
-    Ok(user)
-  }
-
-  pub fn expose_secret(&self) -> &i64 {
-    self.0.expose_secret()
-  }
-}
-
-impl Hash for LoggedUser {
-  fn hash<H: Hasher>(&self, state: &mut H) {
-    self.expose_secret().hash(state);
-  }
-}
-
-impl PartialEq<Self> for LoggedUser {
-  fn eq(&self, other: &Self) -> bool {
-    let uid = self.expose_secret();
-    let other_uid = other.expose_secret();
-    uid == other_uid
-  }
-}
-
-impl Eq for LoggedUser {}
-
-impl Display for LoggedUser {
-  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    f.write_str("LoggedUser")
-  }
-}
-
-pub const HEADER_TOKEN: &str = "token";
-pub const EXPIRED_DURATION_DAYS: i64 = 30;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claim {
-  iss: String,
-  uid: i64,
-}
-
-impl Claim {
-  pub fn with_user_id(uid: i64) -> Self {
-    Self { iss: domain(), uid }
-  }
-}
-
-#[derive(Clone, Default, Serialize, Deserialize)]
-pub struct Token(pub String);
-
-impl Zeroize for Token {
-  fn zeroize(&mut self) {
-    self.0.zeroize()
-  }
-}
-
-impl Token {
-  pub fn create_token(uid: i64, server_key: &Secret<String>) -> Result<Self, AuthError> {
-    let claim = Claim::with_user_id(uid);
-    let token = create_token(
-      server_key.expose_secret().as_str(),
-      claim,
-      Duration::days(EXPIRED_DURATION_DAYS),
-    )
-    .map_err(|e| match e {
-      TokenError::Jwt(_) => AuthError::Unauthorized,
-      TokenError::Expired => AuthError::Unauthorized,
-    })?;
-    Ok(Self(token))
-  }
-
-  pub fn decode_token(server_key: &Secret<String>, token: &str) -> Result<Claim, AuthError> {
-    parse_token::<Claim>(server_key.expose_secret().as_str(), token)
-      .map_err(|_| AuthError::Unauthorized)
-  }
-}
-
-pub fn logged_user_from_request(
-  request: &HttpRequest,
-  server_key: &Secret<String>,
-) -> Result<LoggedUser, AuthError> {
-  match request.headers().get(HEADER_TOKEN) {
-    None => Err(AuthError::Unauthorized),
-    Some(header) => match header.to_str() {
-      Ok(token_str) => LoggedUser::from_token(server_key, token_str),
-      Err(_) => Err(AuthError::Unauthorized),
-    },
-  }
-}
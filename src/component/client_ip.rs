@@ -0,0 +1,105 @@
+use actix_web::web::Data;
+use actix_web::HttpRequest;
+use std::net::IpAddr;
+
+use crate::config::config::IpRange;
+use crate::state::AppState;
+
+/// Returns the address of the client that made the request, which is the address of the peer
+/// unless the peer is one of the `trusted_proxies` of the
+/// [ApplicationSetting](crate::config::config::ApplicationSetting).
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+  let trusted_proxies = req
+    .app_data::<Data<AppState>>()
+    .map(|state| state.config.application.trusted_proxies.as_slice())
+    .unwrap_or_default();
+  client_ip_with_proxies(req, trusted_proxies).map(|ip| ip.to_string())
+}
+
+/// Each proxy appends the address it received the request from to the `X-Forwarded-For` header,
+/// so the header is read from the right, and the first address that isn't a trusted proxy is the
+/// client. The addresses to the left of it are set by the client and can't be trusted.
+fn client_ip_with_proxies(req: &HttpRequest, trusted_proxies: &[IpRange]) -> Option<IpAddr> {
+  let peer = canonical(req.peer_addr()?.ip());
+  let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|range| range.contains(ip));
+  if !is_trusted(&peer) {
+    return Some(peer);
+  }
+
+  let forwarded = req
+    .headers()
+    .get_all("X-Forwarded-For")
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .map(|ip| ip.trim().parse::<IpAddr>().ok().map(canonical))
+    .collect::<Vec<_>>();
+
+  let mut client = peer;
+  for ip in forwarded.into_iter().rev() {
+    match ip {
+      // A malformed entry can't be attributed to anyone, so stop at the last known hop.
+      None => break,
+      Some(ip) => {
+        client = ip;
+        if !is_trusted(&ip) {
+          break;
+        }
+      },
+    }
+  }
+  Some(client)
+}
+
+fn canonical(ip: IpAddr) -> IpAddr {
+  match ip {
+    IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+    ip => ip,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use actix_web::test::TestRequest;
+
+  fn range(value: &str) -> IpRange {
+    IpRange::try_from(value.to_string()).unwrap()
+  }
+
+  #[test]
+  fn forwarded_for_is_ignored_from_untrusted_peer_test() {
+    let req = TestRequest::default()
+      .peer_addr("203.0.113.7:4000".parse().unwrap())
+      .insert_header(("X-Forwarded-For", "198.51.100.1"))
+      .to_http_request();
+    let ip = client_ip_with_proxies(&req, &[range("10.0.0.0/8")]);
+    assert_eq!(ip, Some("203.0.113.7".parse().unwrap()));
+  }
+
+  #[test]
+  fn forwarded_for_is_read_from_the_right_test() {
+    let req = TestRequest::default()
+      .peer_addr("10.0.0.2:4000".parse().unwrap())
+      .insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.7, 10.0.0.3"))
+      .to_http_request();
+    let ip = client_ip_with_proxies(&req, &[range("10.0.0.0/8")]);
+    assert_eq!(ip, Some("203.0.113.7".parse().unwrap()));
+
+    // A trusted proxy that didn't forward any address is the client.
+    let req = TestRequest::default()
+      .peer_addr("10.0.0.2:4000".parse().unwrap())
+      .to_http_request();
+    let ip = client_ip_with_proxies(&req, &[range("10.0.0.2")]);
+    assert_eq!(ip, Some("10.0.0.2".parse().unwrap()));
+  }
+
+  #[test]
+  fn ip_range_test() {
+    assert!(range("172.16.0.0/12").contains(&"172.20.1.1".parse().unwrap()));
+    assert!(!range("172.16.0.0/12").contains(&"172.32.0.1".parse().unwrap()));
+    assert!(range("0.0.0.0/0").contains(&"8.8.8.8".parse().unwrap()));
+    assert!(range("fd00::/8").contains(&"fd12::1".parse().unwrap()));
+    assert!(!range("10.0.0.1").contains(&"10.0.0.2".parse().unwrap()));
+    assert!(IpRange::try_from("10.0.0.0/33".to_string()).is_err());
+  }
+}
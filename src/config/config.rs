@@ -3,6 +3,7 @@ use secrecy::Secret;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use std::convert::{TryFrom, TryInto};
+use std::net::IpAddr;
 use std::path::PathBuf;
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -13,6 +14,187 @@ pub struct Config {
   pub websocket: WebsocketSetting,
   pub redis_uri: Secret<String>,
   pub s3: S3Setting,
+  #[serde(default)]
+  pub blob_gc: BlobGCSetting,
+  #[serde(default)]
+  pub rate_limit: RateLimitSetting,
+  #[serde(default)]
+  pub user_deletion: UserDeletionSetting,
+  #[serde(default)]
+  pub data_export: DataExportSetting,
+  #[serde(default)]
+  pub legacy_auth: LegacyAuthSetting,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct BlobGCSetting {
+  pub enable: bool,
+  /// How often the garbage collector runs.
+  pub interval_secs: u64,
+  /// How long a blob must stay unreferenced before it is deleted.
+  pub grace_period_secs: u64,
+}
+
+impl Default for BlobGCSetting {
+  fn default() -> Self {
+    Self {
+      // Deleting blobs is opt-in.
+      enable: false,
+      interval_secs: 60 * 60 * 6,
+      // The references are found on a best-effort basis, so the blobs are kept for a while.
+      grace_period_secs: 60 * 60 * 24 * 30,
+    }
+  }
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct UserDeletionSetting {
+  pub enable: bool,
+  /// How often the accounts whose grace period is over are deleted.
+  pub interval_secs: u64,
+  /// How long the user can cancel the deletion of the account.
+  pub grace_period_secs: u64,
+}
+
+impl Default for UserDeletionSetting {
+  fn default() -> Self {
+    Self {
+      enable: true,
+      interval_secs: 60 * 60,
+      grace_period_secs: 60 * 60 * 24 * 30,
+    }
+  }
+}
+
+/// The users who registered with the deprecated `/api/user/register` have their password stored
+/// in `af_user` instead of GoTrue.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct LegacyAuthSetting {
+  /// Moves such a user to GoTrue the first time the user signs in with `/api/user/login`, using
+  /// the password the user signed in with.
+  pub migrate_on_login: bool,
+}
+
+impl Default for LegacyAuthSetting {
+  fn default() -> Self {
+    Self {
+      migrate_on_login: true,
+    }
+  }
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct DataExportSetting {
+  /// How long the archive of an export can be downloaded.
+  pub expires_secs: u64,
+  /// How often the expired archives are deleted.
+  pub cleanup_interval_secs: u64,
+}
+
+impl Default for DataExportSetting {
+  fn default() -> Self {
+    Self {
+      expires_secs: 60 * 60 * 24 * 7,
+      cleanup_interval_secs: 60 * 60,
+    }
+  }
+}
+
+/// The token bucket limits of the requests. Each key, a user or an ip address, gets its own bucket
+/// for each rule. The buckets are stored in redis, so the limits are shared by all the servers.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RateLimitSetting {
+  pub enable: bool,
+  /// The limit of the requests that don't match any of the `routes`. None to not limit them.
+  pub default: Option<RateLimit>,
+  pub routes: Vec<RouteRateLimit>,
+  /// The limit of the messages sent by a single websocket connection. The messages over the limit
+  /// are dropped.
+  pub websocket: MessageRateLimit,
+}
+
+impl Default for RateLimitSetting {
+  fn default() -> Self {
+    Self {
+      enable: true,
+      default: Some(RateLimit::new(RateLimitKey::UserOrIp, 300, 20.0)),
+      routes: vec![
+        RouteRateLimit {
+          pattern: "/api/user/login".to_string(),
+          method: None,
+          limit: RateLimit::new(RateLimitKey::Ip, 10, 0.2),
+        },
+        RouteRateLimit {
+          pattern: "/api/user/register".to_string(),
+          method: None,
+          limit: RateLimit::new(RateLimitKey::Ip, 5, 0.05),
+        },
+        RouteRateLimit {
+          pattern: "/api/user/password/recover".to_string(),
+          method: None,
+          limit: RateLimit::new(RateLimitKey::Ip, 5, 0.05),
+        },
+        RouteRateLimit {
+          pattern: "/api/user/otp/verify".to_string(),
+          method: None,
+          limit: RateLimit::new(RateLimitKey::Ip, 10, 0.2),
+        },
+        RouteRateLimit {
+          pattern: "/api/file_storage/{workspace_id}/blob".to_string(),
+          method: Some("PUT".to_string()),
+          limit: RateLimit::new(RateLimitKey::UserOrIp, 30, 1.0),
+        },
+      ],
+      websocket: MessageRateLimit {
+        capacity: 200,
+        refill_per_sec: 50.0,
+      },
+    }
+  }
+}
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+  /// Each user gets a bucket. The requests without a user share the bucket of their ip address.
+  UserOrIp,
+  /// Each ip address gets a bucket, whether the request has a user or not.
+  Ip,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RateLimit {
+  pub key: RateLimitKey,
+  /// The size of the bucket, which is the largest burst of requests that is allowed.
+  pub capacity: u32,
+  /// The number of tokens added to the bucket every second.
+  pub refill_per_sec: f64,
+}
+
+impl RateLimit {
+  pub fn new(key: RateLimitKey, capacity: u32, refill_per_sec: f64) -> Self {
+    Self {
+      key,
+      capacity,
+      refill_per_sec,
+    }
+  }
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct MessageRateLimit {
+  pub capacity: u32,
+  pub refill_per_sec: f64,
+}
+
+/// The limit of the requests that match the route pattern, e.g. `/api/workspace/{workspace_id}/member`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RouteRateLimit {
+  pub pattern: String,
+  /// None to apply the limit to all the methods.
+  pub method: Option<String>,
+  #[serde(flatten)]
+  pub limit: RateLimit,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -23,17 +205,66 @@ pub struct S3Setting {
   pub secret_key: String,
   pub bucket: String,
   pub region: String,
+  /// The sizes, in pixels, of the thumbnails generated for uploaded images.
+  #[serde(default = "default_thumbnail_sizes")]
+  pub thumbnail_sizes: Vec<u32>,
+}
+
+fn default_thumbnail_sizes() -> Vec<u32> {
+  database::file::DEFAULT_THUMBNAIL_SIZES.to_vec()
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct GoTrueSetting {
   pub base_url: String,
   pub ext_url: String, // public url
+  /// Verifies the HS256 tokens, which are issued by the GoTrue server by default.
   pub jwt_secret: Secret<String>,
+  /// The issuers of the RS256 and ES256 tokens.
+  #[serde(default)]
+  pub jwt_issuers: Vec<JwtIssuerSetting>,
   pub admin_email: String,
   pub admin_password: String,
 }
 
+/// An issuer of the tokens that are signed with asymmetric keys. The keys are either fetched from
+/// the `jwks_url`, which allows the issuer to rotate them, or read from the `key_file`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct JwtIssuerSetting {
+  /// Must match the `iss` claim of the tokens.
+  pub issuer: String,
+  /// Must be one of the `aud` claims of the tokens, so the tokens the issuer signs for other
+  /// services are not accepted.
+  pub audience: String,
+  #[serde(default)]
+  pub subject: JwtSubject,
+  pub jwks_url: Option<String>,
+  /// A JWKS document or a PEM encoded public key.
+  pub key_file: Option<String>,
+  /// How often the keys are fetched from the `jwks_url`. The keys are fetched earlier when a
+  /// token is signed with a key that is not known yet.
+  #[serde(default = "default_jwks_refresh_interval_secs")]
+  pub refresh_interval_secs: u64,
+}
+
+/// How the `sub` claim of the tokens of an issuer identifies the user.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JwtSubject {
+  /// The issuer is the GoTrue server, which signs its tokens with asymmetric keys, so the `sub`
+  /// is the id of the GoTrue user.
+  Gotrue,
+  /// The `sub` is only unique within the issuer. The user is identified by a uuid derived from
+  /// the issuer and the `sub`, so an issuer can't sign a token for a user of another issuer.
+  #[default]
+  Namespaced,
+}
+
+fn default_jwks_refresh_interval_secs() -> u64 {
+  // 10 minutes
+  600
+}
+
 // We are using 127.0.0.1 as our host in address, we are instructing our
 // application to only accept connections coming from the same machine. However,
 // request from the hose machine which is not seen as local by our Docker image.
@@ -49,7 +280,68 @@ pub struct ApplicationSetting {
   pub host: String,
   pub data_dir: PathBuf,
   pub server_key: Secret<String>,
+  /// The keys that were replaced by the `server_key`. The tokens signed with these keys are still
+  /// accepted, so a key can be rotated without invalidating the links that were already sent.
+  #[serde(default)]
+  pub previous_server_keys: Vec<Secret<String>>,
   pub tls_config: Option<TlsConfig>,
+  /// The reverse proxies in front of the server. The address of the client is only taken from the
+  /// `X-Forwarded-For` header when the request comes from one of them, otherwise anyone could
+  /// pick the address the rate limits and the audit log see.
+  #[serde(default)]
+  pub trusted_proxies: Vec<IpRange>,
+}
+
+/// An address, e.g. `10.0.0.1`, or a range of addresses in CIDR notation, e.g. `172.16.0.0/12`.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+  addr: IpAddr,
+  prefix_len: u8,
+}
+
+impl IpRange {
+  pub fn contains(&self, ip: &IpAddr) -> bool {
+    match (self.addr, ip) {
+      (IpAddr::V4(range), IpAddr::V4(ip)) => {
+        let mask = u32::MAX
+          .checked_shl(32 - self.prefix_len as u32)
+          .unwrap_or(0);
+        u32::from(range) & mask == u32::from(*ip) & mask
+      },
+      (IpAddr::V6(range), IpAddr::V6(ip)) => {
+        let mask = u128::MAX
+          .checked_shl(128 - self.prefix_len as u32)
+          .unwrap_or(0);
+        u128::from(range) & mask == u128::from(*ip) & mask
+      },
+      _ => false,
+    }
+  }
+}
+
+impl TryFrom<String> for IpRange {
+  type Error = String;
+
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    let (addr, prefix_len) = match value.split_once('/') {
+      None => (value.as_str(), None),
+      Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+    };
+    let addr = addr
+      .parse::<IpAddr>()
+      .map_err(|err| format!("invalid address {}: {}", value, err))?;
+    let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix_len = match prefix_len {
+      None => max_prefix_len,
+      Some(prefix_len) => prefix_len
+        .parse::<u8>()
+        .ok()
+        .filter(|prefix_len| *prefix_len <= max_prefix_len)
+        .ok_or_else(|| format!("invalid prefix length of {}", value))?,
+    };
+    Ok(Self { addr, prefix_len })
+  }
 }
 
 impl ApplicationSetting {
@@ -1,23 +1,34 @@
+use crate::component::auth::api_token::{is_api_token, resolve_api_token};
 use crate::component::auth::jwt::UserUuid;
+use crate::component::auth::share_link::{resolve_share_link, SHARE_PASSWORD_HEADER};
 
+use crate::api::share::SHARE_TOKEN_PATH;
 use crate::api::workspace::{COLLAB_OBJECT_ID_PATH, WORKSPACE_ID_PATH};
+use crate::middleware::access_policy::{
+  AccessDenied, AccessDeniedReason, AccessPolicy, AccessRequirement,
+};
+use crate::state::AppState;
 use actix_router::{Path, Url};
 use actix_service::{forward_ready, Service, Transform};
 use actix_web::dev::{ResourceDef, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorMethodNotAllowed, ErrorUnauthorized};
 use actix_web::http::Method;
-use actix_web::Error;
+use actix_web::web::Data;
+use actix_web::{Error, HttpMessage};
 use async_trait::async_trait;
 use futures_util::future::LocalBoxFuture;
+use serde::Serialize;
 use shared_entity::app_error::AppError;
 use std::collections::HashMap;
 use std::future::{ready, Ready};
 use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 use std::sync::Arc;
 use tracing::error;
 
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
 pub enum AccessResource {
   Workspace,
   Collab,
@@ -29,6 +40,9 @@ pub enum AccessResource {
 ///
 /// The collab and workspace access control can be separated into different traits. Currently, they are
 /// combined into one trait.
+///
+/// The [AccessRequirement] of the request is looked up in the [AccessPolicy]. The implementation
+/// only decides whether the user meets it.
 #[async_trait]
 pub trait HttpAccessControlService: Send + Sync {
   fn resource(&self) -> AccessResource;
@@ -38,8 +52,8 @@ pub trait HttpAccessControlService: Send + Sync {
     &self,
     workspace_id: &Uuid,
     user_uuid: &UserUuid,
-    method: Method,
-  ) -> Result<(), AppError> {
+    requirement: &AccessRequirement,
+  ) -> Result<(), AccessDeniedReason> {
     Ok(())
   }
 
@@ -48,9 +62,9 @@ pub trait HttpAccessControlService: Send + Sync {
     &self,
     oid: &str,
     user_uuid: &Uuid,
-    method: Method,
+    requirement: &AccessRequirement,
     path: Path<Url>,
-  ) -> Result<(), AppError> {
+  ) -> Result<(), AccessDeniedReason> {
     Ok(())
   }
 }
@@ -68,11 +82,11 @@ where
     &self,
     workspace_id: &Uuid,
     user_uuid: &UserUuid,
-    method: Method,
-  ) -> Result<(), AppError> {
+    requirement: &AccessRequirement,
+  ) -> Result<(), AccessDeniedReason> {
     self
       .as_ref()
-      .check_workspace_permission(workspace_id, user_uuid, method)
+      .check_workspace_permission(workspace_id, user_uuid, requirement)
       .await
   }
 
@@ -80,12 +94,12 @@ where
     &self,
     oid: &str,
     user_uuid: &Uuid,
-    method: Method,
+    requirement: &AccessRequirement,
     path: Path<Url>,
-  ) -> Result<(), AppError> {
+  ) -> Result<(), AccessDeniedReason> {
     self
       .as_ref()
-      .check_collab_permission(oid, user_uuid, method, path)
+      .check_collab_permission(oid, user_uuid, requirement, path)
       .await
   }
 }
@@ -98,6 +112,7 @@ pub type HttpAccessControlServices =
 #[derive(Clone, Default)]
 pub struct WorkspaceAccessControl {
   access_control_services: HttpAccessControlServices,
+  policy: Arc<AccessPolicy>,
 }
 
 impl WorkspaceAccessControl {
@@ -105,6 +120,11 @@ impl WorkspaceAccessControl {
     Self::default()
   }
 
+  pub fn with_policy(mut self, policy: AccessPolicy) -> Self {
+    self.policy = Arc::new(policy);
+    self
+  }
+
   pub fn with_acs<T: HttpAccessControlService + 'static>(
     mut self,
     access_control_service: T,
@@ -132,7 +152,7 @@ impl DerefMut for WorkspaceAccessControl {
 
 impl<S, B> Transform<S, ServiceRequest> for WorkspaceAccessControl
 where
-  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
   S::Future: 'static,
   B: 'static,
 {
@@ -144,8 +164,9 @@ where
 
   fn new_transform(&self, service: S) -> Self::Future {
     ready(Ok(WorkspaceAccessControlMiddleware {
-      service,
+      service: Rc::new(service),
       access_control_service: self.access_control_services.clone(),
+      policy: self.policy.clone(),
     }))
   }
 }
@@ -157,15 +178,20 @@ where
 /// For example, if the request path is `/api/workspace/{workspace_id}/collab/{object_id}`, then the
 /// [WorkspaceAccessControlMiddleware] will check the permission of the workspace and collab.
 ///
+/// If the request is authorized with a personal access token, the token is resolved here and
+/// its scope is checked before the workspace and collab permissions of the token's owner.
 ///
+/// The anonymous requests made with a share link carry the [SHARE_TOKEN_PATH] instead. The link
+/// is resolved here and only allows reading.
 pub struct WorkspaceAccessControlMiddleware<S> {
-  service: S,
+  service: Rc<S>,
   access_control_service: HttpAccessControlServices,
+  policy: Arc<AccessPolicy>,
 }
 
 impl<S, B> Service<ServiceRequest> for WorkspaceAccessControlMiddleware<S>
 where
-  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
   S::Future: 'static,
   B: 'static,
 {
@@ -176,64 +202,144 @@ where
   forward_ready!(service);
 
   fn call(&self, mut req: ServiceRequest) -> Self::Future {
-    match req.match_pattern().map(|pattern| {
-      let resource_ref = ResourceDef::new(pattern);
-      let mut path = req.match_info().clone();
-      resource_ref.capture_match_info(&mut path);
-      path
-    }) {
-      None => {
-        let fut = self.service.call(req);
-        Box::pin(fut)
-      },
-      Some(path) => {
-        let user_uuid = req.extract::<UserUuid>();
+    let service = self.service.clone();
+    let services = self.access_control_service.clone();
+    let policy = self.policy.clone();
+
+    Box::pin(async move {
+      // Personal access tokens are resolved here so that the handlers can extract the
+      // [UserUuid] of the token's owner.
+      let api_token = match req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|token| is_api_token(token))
+        .map(|token| token.to_string())
+      {
+        None => None,
+        Some(token) => {
+          let state = req.app_data::<Data<AppState>>().unwrap();
+          let api_token = resolve_api_token(&state.pg_pool, &token)
+            .await
+            .map_err(|err| {
+              error!("api token: {:?}", err);
+              ErrorUnauthorized(err.message)
+            })?;
+          req.extensions_mut().insert(api_token.clone());
+          Some(api_token)
+        },
+      };
 
+      let path = req.match_pattern().map(|pattern| {
+        let resource_ref = ResourceDef::new(pattern.as_str());
+        let mut path = req.match_info().clone();
+        resource_ref.capture_match_info(&mut path);
+        (pattern, path)
+      });
+
+      if let Some((pattern, path)) = path {
         let workspace_id = path
           .get(WORKSPACE_ID_PATH)
           .and_then(|id| Uuid::parse_str(id).ok());
         let collab_object_id = path.get(COLLAB_OBJECT_ID_PATH).map(|id| id.to_string());
-
         let method = req.method().clone();
-        let fut = self.service.call(req);
-        let services = self.access_control_service.clone();
-
-        Box::pin(async move {
-          // If the workspace_id or collab_object_id is not present, skip the access control
-          if workspace_id.is_some() || collab_object_id.is_some() {
-            let user_uuid = user_uuid.await?;
-
-            // check workspace permission
-            if let Some(workspace_id) = workspace_id {
-              if let Some(acs) = services.get(&AccessResource::Workspace) {
-                if let Err(err) = acs
-                  .check_workspace_permission(&workspace_id, &user_uuid, method.clone())
-                  .await
-                {
-                  error!("workspace access control: {:?}", err);
-                  return Err(Error::from(err));
-                }
-              };
-            }
-
-            // check collab permission
-            if let Some(collab_object_id) = collab_object_id {
-              if let Some(acs) = services.get(&AccessResource::Collab) {
-                if let Err(err) = acs
-                  .check_collab_permission(&collab_object_id, &user_uuid, method, path)
-                  .await
-                {
-                  error!("collab access control: {:?}", err);
-                  return Err(Error::from(err));
-                }
-              };
-            }
+
+        if let Some(share_token) = path.get(SHARE_TOKEN_PATH) {
+          if method != Method::GET {
+            return Err(ErrorMethodNotAllowed("share links are read only"));
+          }
+          let password = share_link_password(&req);
+          let state = req.app_data::<Data<AppState>>().unwrap();
+          let share_link =
+            resolve_share_link(&state.pg_pool, &state.redis_client, share_token, password)
+              .await
+              .map_err(|err| {
+                error!("share link: {:?}", err);
+                Error::from(err)
+              })?;
+          req.extensions_mut().insert(share_link);
+        }
+
+        if let Some(api_token) = &api_token {
+          // Managing the members and the share links of a collab is not part of writing the
+          // collab.
+          let is_collab = collab_object_id.is_some()
+            && !pattern.contains("/member")
+            && !pattern.contains("/share");
+          if let Err(err) = api_token.check_scope(&method, workspace_id.as_ref(), is_collab) {
+            error!("api token access control: {:?}", err);
+            return Err(Error::from(err));
           }
+        }
 
-          // call next service
-          fut.await
-        })
-      },
-    }
+        // If the workspace_id or collab_object_id is not present, skip the access control
+        if workspace_id.is_some() || collab_object_id.is_some() {
+          let user_uuid = req.extract::<UserUuid>().await?;
+
+          // check workspace permission
+          if let Some(workspace_id) = workspace_id {
+            let resource = AccessResource::Workspace;
+            if let Some(acs) = services.get(&resource) {
+              let requirement = policy.requirement(&resource, &pattern, &method);
+              if let Err(reason) = acs
+                .check_workspace_permission(&workspace_id, &user_uuid, &requirement)
+                .await
+              {
+                return Err(access_denied(resource, &pattern, &method, reason));
+              }
+            };
+          }
+
+          // check collab permission
+          if let Some(collab_object_id) = collab_object_id {
+            let resource = AccessResource::Collab;
+            if let Some(acs) = services.get(&resource) {
+              let requirement = policy.requirement(&resource, &pattern, &method);
+              if let Err(reason) = acs
+                .check_collab_permission(&collab_object_id, &user_uuid, &requirement, path)
+                .await
+              {
+                return Err(access_denied(resource, &pattern, &method, reason));
+              }
+            };
+          }
+        }
+      } else if let Some(api_token) = &api_token {
+        if let Err(err) = api_token.check_scope(req.method(), None, false) {
+          error!("api token access control: {:?}", err);
+          return Err(Error::from(err));
+        }
+      }
+
+      // call next service
+      service.call(req).await
+    })
   }
 }
+
+fn access_denied(
+  resource: AccessResource,
+  pattern: &str,
+  method: &Method,
+  reason: AccessDeniedReason,
+) -> Error {
+  let denied = AccessDenied {
+    resource,
+    pattern: pattern.to_string(),
+    method: method.to_string(),
+    reason,
+  };
+  error!("access control: {:?}", denied);
+  Error::from(AppError::from(denied))
+}
+
+/// The password of a share link is only accepted from the [SHARE_PASSWORD_HEADER] header, so it
+/// doesn't end up in the logged urls.
+fn share_link_password(req: &ServiceRequest) -> Option<String> {
+  req
+    .headers()
+    .get(SHARE_PASSWORD_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.to_string())
+}
@@ -0,0 +1,477 @@
+use crate::middleware::access_control_mw::AccessResource;
+use actix_http::Method;
+use anyhow::anyhow;
+use database_entity::dto::{AFAccessLevel, AFRole};
+use serde::Serialize;
+use shared_entity::app_error::AppError;
+use shared_entity::error_code::ErrorCode;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// What a user needs in order to access a route.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum AccessRequirement {
+  /// The access level of the user must be at least the given level. For a workspace, it is the
+  /// level granted by the user's role. For a collab, it is the user's permission on the collab.
+  AccessLevel(AFAccessLevel),
+  /// The user's role in the workspace must be one of the given roles. Only applies to workspaces.
+  Role(Vec<AFRole>),
+}
+
+impl AccessRequirement {
+  pub fn check_access_level(&self, actual: &AFAccessLevel) -> Result<(), AccessDeniedReason> {
+    match self {
+      AccessRequirement::AccessLevel(required) if actual >= required => Ok(()),
+      AccessRequirement::AccessLevel(required) => {
+        Err(AccessDeniedReason::InsufficientAccessLevel {
+          required: required.clone(),
+          actual: actual.clone(),
+        })
+      },
+      AccessRequirement::Role(_) => Err(AccessDeniedReason::Unsupported),
+    }
+  }
+
+  pub fn check_role(&self, actual: &AFRole) -> Result<(), AccessDeniedReason> {
+    match self {
+      AccessRequirement::Role(allowed) if allowed.contains(actual) => Ok(()),
+      AccessRequirement::Role(allowed) => Err(AccessDeniedReason::RoleNotAllowed {
+        allowed: allowed.clone(),
+        actual: actual.clone(),
+      }),
+      AccessRequirement::AccessLevel(_) => Err(AccessDeniedReason::Unsupported),
+    }
+  }
+}
+
+/// Why a request was denied by the [AccessPolicy].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum AccessDeniedReason {
+  InsufficientAccessLevel {
+    required: AFAccessLevel,
+    actual: AFAccessLevel,
+  },
+  RoleNotAllowed {
+    allowed: Vec<AFRole>,
+    actual: AFRole,
+  },
+  /// The user is not a member of the workspace or collab.
+  NotMember(String),
+  /// The requirement can't be checked for this kind of resource.
+  Unsupported,
+}
+
+impl Display for AccessDeniedReason {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AccessDeniedReason::InsufficientAccessLevel { required, actual } => write!(
+        f,
+        "requires access level {:?}, but the user has {:?}",
+        required, actual
+      ),
+      AccessDeniedReason::RoleNotAllowed { allowed, actual } => write!(
+        f,
+        "requires one of the roles {:?}, but the user is {:?}",
+        allowed, actual
+      ),
+      AccessDeniedReason::NotMember(msg) => write!(f, "not a member: {}", msg),
+      AccessDeniedReason::Unsupported => write!(f, "the requirement doesn't apply to the resource"),
+    }
+  }
+}
+
+/// A denied request, with the policy entry that denied it.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessDenied {
+  pub resource: AccessResource,
+  pub pattern: String,
+  pub method: String,
+  pub reason: AccessDeniedReason,
+}
+
+impl Display for AccessDenied {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{:?} access denied for {} {}: {}",
+      self.resource, self.method, self.pattern, self.reason
+    )
+  }
+}
+
+impl From<AccessDenied> for AppError {
+  fn from(value: AccessDenied) -> Self {
+    AppError::new(ErrorCode::NotEnoughPermissions, value.to_string())
+  }
+}
+
+/// An entry of the [AccessPolicy]. The `pattern` is the route pattern that actix matched for the
+/// request, for example `/api/workspace/{workspace_id}/member`.
+#[derive(Debug, Clone)]
+pub struct AccessPolicyEntry {
+  pub resource: AccessResource,
+  pub pattern: &'static str,
+  pub method: Method,
+  pub requirement: AccessRequirement,
+}
+
+impl AccessPolicyEntry {
+  fn new(
+    resource: AccessResource,
+    pattern: &'static str,
+    method: Method,
+    requirement: AccessRequirement,
+  ) -> Self {
+    Self {
+      resource,
+      pattern,
+      method,
+      requirement,
+    }
+  }
+}
+
+type PolicyKey = (AccessResource, String, Method);
+
+/// Maps (resource, route pattern, method) to the [AccessRequirement] of the route. Routes that
+/// are not listed fall back to the default requirement of the resource: reading requires
+/// [AFAccessLevel::ReadOnly], writing to a workspace requires [AFAccessLevel::FullAccess] and
+/// writing to a collab requires [AFAccessLevel::ReadAndWrite].
+#[derive(Debug, Clone)]
+pub struct AccessPolicy {
+  entries: HashMap<PolicyKey, AccessRequirement>,
+}
+
+impl Default for AccessPolicy {
+  fn default() -> Self {
+    Self::from_entries(default_policy_entries()).expect("the default access policy is invalid")
+  }
+}
+
+impl AccessPolicy {
+  /// Builds the policy from the entries. Returns an error if an entry is listed twice or if a
+  /// collab entry requires a role.
+  pub fn from_entries(entries: Vec<AccessPolicyEntry>) -> Result<Self, anyhow::Error> {
+    let mut map = HashMap::with_capacity(entries.len());
+    for entry in entries {
+      if entry.resource == AccessResource::Collab
+        && matches!(entry.requirement, AccessRequirement::Role(_))
+      {
+        return Err(anyhow!(
+          "collab entry {} {} can't require a role",
+          entry.method,
+          entry.pattern
+        ));
+      }
+
+      let key = (entry.resource, entry.pattern.to_string(), entry.method);
+      if map.insert(key.clone(), entry.requirement).is_some() {
+        return Err(anyhow!("duplicate access policy entry: {:?}", key));
+      }
+    }
+    Ok(Self { entries: map })
+  }
+
+  pub fn requirement(
+    &self,
+    resource: &AccessResource,
+    pattern: &str,
+    method: &Method,
+  ) -> AccessRequirement {
+    self
+      .entries
+      .get(&(resource.clone(), pattern.to_string(), method.clone()))
+      .cloned()
+      .unwrap_or_else(|| default_requirement(resource, method))
+  }
+}
+
+fn is_write_method(method: &Method) -> bool {
+  matches!(
+    *method,
+    Method::POST | Method::PUT | Method::DELETE | Method::PATCH
+  )
+}
+
+fn default_requirement(resource: &AccessResource, method: &Method) -> AccessRequirement {
+  let level = match (resource, is_write_method(method)) {
+    (_, false) => AFAccessLevel::ReadOnly,
+    (AccessResource::Workspace, true) => AFAccessLevel::FullAccess,
+    (AccessResource::Collab, true) => AFAccessLevel::ReadAndWrite,
+  };
+  AccessRequirement::AccessLevel(level)
+}
+
+/// The routes whose requirement differs from, or is worth spelling out next to, the defaults.
+pub fn default_policy_entries() -> Vec<AccessPolicyEntry> {
+  use AccessRequirement::{AccessLevel, Role};
+  use AccessResource::{Collab, Workspace};
+
+  vec![
+    // workspace members
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/member",
+      Method::GET,
+      AccessLevel(AFAccessLevel::ReadOnly),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/member",
+      Method::POST,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/member",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/member",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    // workspace invitations
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/invite",
+      Method::POST,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/invite",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    // workspace roles
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/role",
+      Method::POST,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/role/{role_id}",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/role/{role_id}",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    // audit log
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/workspace/{workspace_id}/audit_log",
+      Method::GET,
+      Role(vec![AFRole::Owner]),
+    ),
+    // collab
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}",
+      Method::GET,
+      AccessLevel(AFAccessLevel::ReadOnly),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}",
+      Method::POST,
+      AccessLevel(AFAccessLevel::ReadAndWrite),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::ReadAndWrite),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::ReadAndWrite),
+    ),
+    // collab members
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/member",
+      Method::POST,
+      AccessLevel(AFAccessLevel::ReadAndWrite),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/member",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::ReadAndWrite),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/member",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::ReadAndWrite),
+    ),
+    // collab parent
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/parent",
+      Method::GET,
+      AccessLevel(AFAccessLevel::ReadOnly),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/parent",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/parent",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    // collab share links
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/share",
+      Method::GET,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/share",
+      Method::POST,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Collab,
+      "/api/workspace/{workspace_id}/collab/{object_id}/share/{share_id}",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    // file storage
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/file_storage/{workspace_id}/blob",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/file_storage/{workspace_id}/blob/{file_id:.*}",
+      Method::DELETE,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/file_storage/{workspace_id}/file_type_policy",
+      Method::PUT,
+      AccessLevel(AFAccessLevel::FullAccess),
+    ),
+    // The download link only reads the blob.
+    AccessPolicyEntry::new(
+      Workspace,
+      "/api/file_storage/{workspace_id}/download_link/{file_id:.*}",
+      Method::POST,
+      AccessLevel(AFAccessLevel::ReadOnly),
+    ),
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const LEVELS: [AFAccessLevel; 4] = [
+    AFAccessLevel::ReadOnly,
+    AFAccessLevel::ReadAndComment,
+    AFAccessLevel::ReadAndWrite,
+    AFAccessLevel::FullAccess,
+  ];
+
+  #[test]
+  fn default_policy_is_valid() {
+    let _ = AccessPolicy::default();
+  }
+
+  #[test]
+  fn each_entry_only_allows_levels_at_or_above_its_requirement() {
+    let policy = AccessPolicy::default();
+    for entry in default_policy_entries() {
+      let requirement = policy.requirement(&entry.resource, entry.pattern, &entry.method);
+      assert_eq!(requirement, entry.requirement);
+
+      if let AccessRequirement::AccessLevel(required) = &requirement {
+        for level in LEVELS.iter() {
+          let result = requirement.check_access_level(level);
+          assert_eq!(
+            result.is_ok(),
+            level >= required,
+            "{} {} with {:?}",
+            entry.method,
+            entry.pattern,
+            level
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn unlisted_routes_use_resource_defaults() {
+    let policy = AccessPolicy::default();
+    assert_eq!(
+      policy.requirement(&AccessResource::Workspace, "/unlisted", &Method::GET),
+      AccessRequirement::AccessLevel(AFAccessLevel::ReadOnly)
+    );
+    assert_eq!(
+      policy.requirement(&AccessResource::Workspace, "/unlisted", &Method::POST),
+      AccessRequirement::AccessLevel(AFAccessLevel::FullAccess)
+    );
+    assert_eq!(
+      policy.requirement(&AccessResource::Collab, "/unlisted", &Method::PUT),
+      AccessRequirement::AccessLevel(AFAccessLevel::ReadAndWrite)
+    );
+  }
+
+  #[test]
+  fn role_requirement() {
+    let requirement = AccessRequirement::Role(vec![AFRole::Owner]);
+    assert!(requirement.check_role(&AFRole::Owner).is_ok());
+    assert_eq!(
+      requirement.check_role(&AFRole::Member),
+      Err(AccessDeniedReason::RoleNotAllowed {
+        allowed: vec![AFRole::Owner],
+        actual: AFRole::Member,
+      })
+    );
+    assert_eq!(
+      requirement.check_access_level(&AFAccessLevel::FullAccess),
+      Err(AccessDeniedReason::Unsupported)
+    );
+  }
+
+  #[test]
+  fn invalid_entries_are_rejected() {
+    let collab_role = AccessPolicyEntry::new(
+      AccessResource::Collab,
+      "/collab",
+      Method::GET,
+      AccessRequirement::Role(vec![AFRole::Owner]),
+    );
+    assert!(AccessPolicy::from_entries(vec![collab_role]).is_err());
+
+    let entry = AccessPolicyEntry::new(
+      AccessResource::Workspace,
+      "/workspace",
+      Method::GET,
+      AccessRequirement::AccessLevel(AFAccessLevel::ReadOnly),
+    );
+    assert!(AccessPolicy::from_entries(vec![entry.clone(), entry]).is_err());
+  }
+}
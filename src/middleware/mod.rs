@@ -1,2 +1,4 @@
 pub mod access_control_mw;
+pub mod access_policy;
 pub mod cors_mw;
+pub mod rate_limit_mw;
@@ -0,0 +1,247 @@
+use crate::component::auth::api_token::{hash_api_token, is_api_token};
+use crate::component::auth::jwt::authorization_from_token;
+use crate::component::client_ip::client_ip;
+use crate::config::config::{RateLimit, RateLimitKey, RateLimitSetting};
+use crate::state::AppState;
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::body::EitherBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::web::Data;
+use actix_web::{Error, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+use once_cell::sync::Lazy;
+use redis::aio::ConnectionManager;
+use redis::Script;
+use shared_entity::data::AppResponse;
+use shared_entity::error_code::ErrorCode;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Takes a token from the bucket stored in `KEYS[1]`, after adding the tokens refilled since the
+/// last call. The time of the redis server is used, so the servers don't need synchronized clocks.
+///
+/// Returns `{1, 0}` if the token was taken, otherwise `{0, wait_ms}` where `wait_ms` is the time
+/// until the next token is available.
+static TOKEN_BUCKET_SCRIPT: Lazy<Script> = Lazy::new(|| {
+  Script::new(
+    r#"
+local capacity = tonumber(ARGV[1])
+local refill_per_ms = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1])
+local ts = tonumber(bucket[2])
+if tokens == nil or ts == nil then
+  tokens = capacity
+  ts = now
+end
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+
+local allowed = 0
+local wait_ms = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+  allowed = 1
+elseif refill_per_ms > 0 then
+  wait_ms = math.ceil((1 - tokens) / refill_per_ms)
+else
+  wait_ms = 60000
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+local ttl = 86400000
+if refill_per_ms > 0 then
+  ttl = math.ceil(capacity / refill_per_ms) + 1000
+end
+redis.call('PEXPIRE', KEYS[1], ttl)
+return {allowed, wait_ms}
+"#,
+  )
+});
+
+const RATE_LIMIT_KEY_PREFIX: &str = "af_rate_limit";
+
+/// Limits the rate of the requests with the token buckets configured in [RateLimitSetting].
+///
+/// The request that runs out of tokens is rejected with `429 Too Many Requests`, and the
+/// `Retry-After` header tells the client how many seconds to wait.
+#[derive(Clone)]
+pub struct RateLimiter {
+  setting: Arc<RateLimitSetting>,
+  redis_client: ConnectionManager,
+}
+
+impl RateLimiter {
+  pub fn new(setting: RateLimitSetting, redis_client: ConnectionManager) -> Self {
+    Self {
+      setting: Arc::new(setting),
+      redis_client,
+    }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Transform = RateLimiterMiddleware<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(RateLimiterMiddleware {
+      service: Rc::new(service),
+      setting: self.setting.clone(),
+      redis_client: self.redis_client.clone(),
+    }))
+  }
+}
+
+/// Each request takes a token from the bucket of its route, or from the default bucket if none
+/// of the routes in the [RateLimitSetting] matches. The routes are matched by their pattern, e.g.
+/// `/api/file_storage/{workspace_id}/blob`, and optionally by the method.
+///
+/// The limiter fails open: if redis is unavailable, the request is let through.
+pub struct RateLimiterMiddleware<S> {
+  service: Rc<S>,
+  setting: Arc<RateLimitSetting>,
+  redis_client: ConnectionManager,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let service = self.service.clone();
+    let setting = self.setting.clone();
+    let mut redis_client = self.redis_client.clone();
+
+    Box::pin(async move {
+      if setting.enable {
+        if let Some((bucket, limit)) = matched_limit(&setting, &req) {
+          let key = format!(
+            "{}:{}:{}",
+            RATE_LIMIT_KEY_PREFIX,
+            bucket,
+            rate_limit_subject(&req, &limit.key)
+          );
+
+          match take_token(&mut redis_client, &key, limit).await {
+            Ok(None) => {},
+            Ok(Some(retry_after_secs)) => {
+              let resp = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after_secs.to_string()))
+                .json(AppResponse::<()>::new(
+                  ErrorCode::TooManyRequests,
+                  format!(
+                    "too many requests, retry after {} seconds",
+                    retry_after_secs
+                  ),
+                ));
+              return Ok(req.into_response(resp).map_into_right_body());
+            },
+            Err(err) => warn!("rate limiter is unavailable: {:?}", err),
+          }
+        }
+      }
+
+      service.call(req).await.map(|res| res.map_into_left_body())
+    })
+  }
+}
+
+/// Returns the name of the bucket and the limit that applies to the request.
+fn matched_limit<'a>(
+  setting: &'a RateLimitSetting,
+  req: &ServiceRequest,
+) -> Option<(String, &'a RateLimit)> {
+  if let Some(pattern) = req.match_pattern() {
+    let route = setting.routes.iter().find(|route| {
+      route.pattern == pattern
+        && route
+          .method
+          .as_ref()
+          .is_none_or(|method| method.eq_ignore_ascii_case(req.method().as_str()))
+    });
+    if let Some(route) = route {
+      let bucket = match &route.method {
+        None => route.pattern.clone(),
+        Some(method) => format!("{}:{}", method.to_uppercase(), route.pattern),
+      };
+      return Some((bucket, &route.limit));
+    }
+  }
+
+  setting
+    .default
+    .as_ref()
+    .map(|limit| ("default".to_string(), limit))
+}
+
+/// Identifies whose bucket the request takes the token from. The bearer token is only decoded
+/// here, the authentication of the request is left to the handlers.
+fn rate_limit_subject(req: &ServiceRequest, key: &RateLimitKey) -> String {
+  if *key == RateLimitKey::UserOrIp {
+    let token = req
+      .headers()
+      .get("Authorization")
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.strip_prefix("Bearer "));
+
+    if let Some(token) = token {
+      if is_api_token(token) {
+        return format!("token:{}", hash_api_token(token));
+      }
+      let uuid = req.app_data::<Data<AppState>>().and_then(|state| {
+        authorization_from_token(token, state)
+          .and_then(|auth| auth.uuid())
+          .ok()
+      });
+      if let Some(uuid) = uuid {
+        return format!("user:{}", uuid);
+      }
+    }
+  }
+
+  let ip = client_ip(req.request()).unwrap_or_else(|| "unknown".to_string());
+  format!("ip:{}", ip)
+}
+
+/// Returns the number of seconds to wait if the bucket is empty.
+async fn take_token(
+  conn: &mut ConnectionManager,
+  key: &str,
+  limit: &RateLimit,
+) -> Result<Option<u64>, redis::RedisError> {
+  let (allowed, wait_ms): (i64, i64) = TOKEN_BUCKET_SCRIPT
+    .key(key)
+    .arg(limit.capacity)
+    .arg(limit.refill_per_sec / 1000.0)
+    .invoke_async(conn)
+    .await?;
+
+  if allowed == 1 {
+    Ok(None)
+  } else {
+    // Round up, so the client doesn't retry before the token is available.
+    Ok(Some(((wait_ms.max(0) as u64) + 999) / 1000))
+  }
+}
@@ -1,30 +1,38 @@
 use crate::biz::collab::access_control::CollabAccessControlImpl;
 use crate::biz::collab::storage::CollabPostgresDBStorage;
 use crate::biz::pg_listener::PgListeners;
+use crate::biz::snowflake::next_id;
+use crate::biz::user_ban::UserBanList;
+use crate::biz::user_session::{RealtimeSessions, SignedOutSessions};
 use crate::biz::workspace::access_control::WorkspaceAccessControlImpl;
-use crate::component::auth::LoggedUser;
+use crate::component::auth::jwt_verifier::JwtVerifier;
 use crate::config::config::Config;
-use chrono::{DateTime, Utc};
+use anyhow::Context;
 use database::file::bucket_s3_impl::S3BucketStorage;
+use shared_entity::app_error::AppError;
 use snowflake::Snowflake;
 use sqlx::PgPool;
-use std::collections::BTreeMap;
 use std::sync::Arc;
+use token::TokenKeys;
 use tokio::sync::RwLock;
 
 #[derive(Clone)]
 pub struct AppState {
   pub pg_pool: PgPool,
   pub config: Arc<Config>,
-  pub user: Arc<RwLock<UserCache>>,
   pub id_gen: Arc<RwLock<Snowflake>>,
   pub gotrue_client: gotrue::api::Client,
+  pub jwt_verifier: Arc<JwtVerifier>,
   pub redis_client: redis::aio::ConnectionManager,
+  pub token_keys: Arc<TokenKeys>,
   pub collab_storage: Arc<CollabPostgresDBStorage>,
   pub collab_access_control: Arc<CollabAccessControlImpl>,
   pub workspace_access_control: Arc<WorkspaceAccessControlImpl>,
   pub bucket_storage: Arc<S3BucketStorage>,
   pub pg_listeners: Arc<PgListeners>,
+  pub realtime_sessions: Arc<RealtimeSessions>,
+  pub user_ban_list: Arc<UserBanList>,
+  pub signed_out_sessions: Arc<SignedOutSessions>,
 }
 
 impl AppState {
@@ -32,60 +40,10 @@ impl AppState {
     todo!()
   }
 
-  pub async fn next_user_id(&self) -> i64 {
-    self.id_gen.write().await.next_id()
-  }
-}
-
-#[derive(Clone, Debug, Copy)]
-enum AuthStatus {
-  Authorized(DateTime<Utc>),
-  NotAuthorized,
-}
-
-pub const EXPIRED_DURATION_DAYS: i64 = 30;
-
-#[derive(Debug, Default)]
-pub struct UserCache {
-  // Keep track the user authentication state
-  user: BTreeMap<i64, AuthStatus>,
-}
-
-impl UserCache {
-  pub fn new() -> Self {
-    UserCache::default()
-  }
-
-  pub fn is_authorized(&self, user: &LoggedUser) -> bool {
-    match self.user.get(user.expose_secret()) {
-      None => {
-        tracing::debug!("user not login yet or server was reboot");
-        false
-      },
-      Some(status) => match *status {
-        AuthStatus::Authorized(last_time) => {
-          let current_time = Utc::now();
-          let days = (current_time - last_time).num_days();
-          days < EXPIRED_DURATION_DAYS
-        },
-        AuthStatus::NotAuthorized => {
-          tracing::debug!("user logout already");
-          false
-        },
-      },
-    }
-  }
-
-  pub fn authorized(&mut self, user: LoggedUser) {
-    self.user.insert(
-      user.expose_secret().to_owned(),
-      AuthStatus::Authorized(Utc::now()),
-    );
-  }
-
-  pub fn unauthorized(&mut self, user: LoggedUser) {
-    self
-      .user
-      .insert(user.expose_secret().to_owned(), AuthStatus::NotAuthorized);
+  pub async fn next_user_id(&self) -> Result<i64, AppError> {
+    let uid = next_id(&self.id_gen)
+      .await
+      .context("failed to generate the uid")?;
+    Ok(uid)
   }
 }
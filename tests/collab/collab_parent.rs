@@ -0,0 +1,176 @@
+use crate::util::test_client::TestClient;
+use collab_entity::CollabType;
+use database_entity::dto::{AFAccessLevel, AFRole, InsertCollabParams};
+use shared_entity::error_code::ErrorCode;
+use uuid::Uuid;
+
+async fn create_document(client: &TestClient, workspace_id: &str) -> String {
+  let object_id = Uuid::new_v4().to_string();
+  client
+    .api_client
+    .create_collab(InsertCollabParams::new(
+      &object_id,
+      CollabType::Document,
+      vec![0; 10],
+      workspace_id.to_string(),
+    ))
+    .await
+    .unwrap();
+  object_id
+}
+
+fn update_params(workspace_id: &str, object_id: &str) -> InsertCollabParams {
+  InsertCollabParams::new(
+    object_id,
+    CollabType::Document,
+    vec![1; 10],
+    workspace_id.to_string(),
+  )
+}
+
+#[tokio::test]
+async fn child_inherits_parent_access_level_test() {
+  let owner = TestClient::new_user_without_ws_conn().await;
+  let member = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .add_workspace_member(&workspace_id, &member, AFRole::Member)
+    .await;
+
+  let parent_id = create_document(&owner, &workspace_id).await;
+  let child_id = create_document(&owner, &workspace_id).await;
+  owner
+    .add_client_as_collab_member(&workspace_id, &parent_id, &member, AFAccessLevel::ReadOnly)
+    .await;
+
+  let parent = owner
+    .api_client
+    .set_collab_parent(&workspace_id, &child_id, &parent_id)
+    .await
+    .unwrap();
+  assert_eq!(parent.parent_id, Some(parent_id.clone()));
+
+  // The member can only read the parent, so it can only read the child.
+  let err = member
+    .api_client
+    .update_collab(update_params(&workspace_id, &child_id))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // Being a member of the child overrides the inherited access level.
+  owner
+    .add_client_as_collab_member(
+      &workspace_id,
+      &child_id,
+      &member,
+      AFAccessLevel::ReadAndWrite,
+    )
+    .await;
+  member
+    .api_client
+    .update_collab(update_params(&workspace_id, &child_id))
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn member_of_child_can_tighten_inherited_access_level_test() {
+  let owner = TestClient::new_user_without_ws_conn().await;
+  let member = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .add_workspace_member(&workspace_id, &member, AFRole::Member)
+    .await;
+
+  let parent_id = create_document(&owner, &workspace_id).await;
+  let child_id = create_document(&owner, &workspace_id).await;
+  let grandchild_id = create_document(&owner, &workspace_id).await;
+  owner
+    .add_client_as_collab_member(
+      &workspace_id,
+      &parent_id,
+      &member,
+      AFAccessLevel::FullAccess,
+    )
+    .await;
+  owner
+    .api_client
+    .set_collab_parent(&workspace_id, &child_id, &parent_id)
+    .await
+    .unwrap();
+
+  // The member has full access to the child through the parent, so it can move the grandchild
+  // under the child once it has full access to the grandchild as well.
+  owner
+    .add_client_as_collab_member(
+      &workspace_id,
+      &grandchild_id,
+      &member,
+      AFAccessLevel::FullAccess,
+    )
+    .await;
+  member
+    .api_client
+    .set_collab_parent(&workspace_id, &grandchild_id, &child_id)
+    .await
+    .unwrap();
+
+  // Tighten the access level of the member on the child.
+  owner
+    .add_client_as_collab_member(&workspace_id, &child_id, &member, AFAccessLevel::ReadOnly)
+    .await;
+  let err = member
+    .api_client
+    .update_collab(update_params(&workspace_id, &child_id))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // Removing the parent of the child.
+  owner
+    .api_client
+    .remove_collab_parent(&workspace_id, &child_id)
+    .await
+    .unwrap();
+  let parent = owner
+    .api_client
+    .get_collab_parent(&workspace_id, &child_id)
+    .await
+    .unwrap();
+  assert!(parent.parent_id.is_none());
+}
+
+#[tokio::test]
+async fn collab_parent_can_not_form_cycle_test() {
+  let owner = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = owner.workspace_id().await;
+  let a = create_document(&owner, &workspace_id).await;
+  let b = create_document(&owner, &workspace_id).await;
+  let c = create_document(&owner, &workspace_id).await;
+
+  owner
+    .api_client
+    .set_collab_parent(&workspace_id, &b, &a)
+    .await
+    .unwrap();
+  owner
+    .api_client
+    .set_collab_parent(&workspace_id, &c, &b)
+    .await
+    .unwrap();
+
+  let err = owner
+    .api_client
+    .set_collab_parent(&workspace_id, &a, &c)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+
+  let err = owner
+    .api_client
+    .set_collab_parent(&workspace_id, &a, &Uuid::new_v4().to_string())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
@@ -3,8 +3,10 @@ use client_api::Client;
 mod member_crud;
 mod storage_test;
 
+mod collab_parent;
 mod edit_permission;
 mod multi_devices_edit;
+mod share_link;
 mod single_device_edit;
 mod workspace_collab;
 
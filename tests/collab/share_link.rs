@@ -0,0 +1,173 @@
+use crate::collab::workspace_id_from_client;
+use crate::localhost_client;
+use crate::user::utils::generate_unique_registered_user_client;
+use chrono::{Duration, Utc};
+use collab_entity::CollabType;
+use database_entity::dto::{AFAccessLevel, CreateShareLinkParams, InsertCollabParams};
+use shared_entity::error_code::ErrorCode;
+use uuid::Uuid;
+
+fn share_params(access_level: AFAccessLevel) -> CreateShareLinkParams {
+  CreateShareLinkParams {
+    access_level,
+    collab_type: CollabType::Document,
+    expires_at: None,
+    password: None,
+  }
+}
+
+async fn create_document(c: &client_api::Client, workspace_id: &str, raw_data: Vec<u8>) -> String {
+  let object_id = Uuid::new_v4().to_string();
+  c.create_collab(InsertCollabParams::new(
+    &object_id,
+    CollabType::Document,
+    raw_data,
+    workspace_id.to_string(),
+  ))
+  .await
+  .unwrap();
+  object_id
+}
+
+#[tokio::test]
+async fn anonymous_viewer_get_shared_collab_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let raw_data = "hello world".as_bytes().to_vec();
+  let object_id = create_document(&c, &workspace_id, raw_data.clone()).await;
+
+  let created = c
+    .create_share_link(
+      &workspace_id,
+      &object_id,
+      share_params(AFAccessLevel::ReadOnly),
+    )
+    .await
+    .unwrap();
+  assert_eq!(created.info.object_id, object_id);
+  assert!(!created.info.has_password);
+
+  let shared = localhost_client()
+    .get_shared_collab(&created.token, None)
+    .await
+    .unwrap();
+  assert_eq!(shared.object_id, object_id);
+  assert_eq!(shared.access_level, AFAccessLevel::ReadOnly);
+  assert_eq!(shared.data, raw_data);
+
+  let links = c.get_share_links(&workspace_id, &object_id).await.unwrap();
+  assert_eq!(links.0.len(), 1);
+}
+
+#[tokio::test]
+async fn share_link_can_not_grant_write_access_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = create_document(&c, &workspace_id, "hello world".as_bytes().to_vec()).await;
+
+  let err = c
+    .create_share_link(
+      &workspace_id,
+      &object_id,
+      share_params(AFAccessLevel::ReadAndWrite),
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+}
+
+#[tokio::test]
+async fn password_protected_share_link_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = create_document(&c, &workspace_id, "hello world".as_bytes().to_vec()).await;
+
+  let mut params = share_params(AFAccessLevel::ReadAndComment);
+  params.password = Some("secret".to_string());
+  let created = c
+    .create_share_link(&workspace_id, &object_id, params)
+    .await
+    .unwrap();
+  assert!(created.info.has_password);
+
+  let viewer = localhost_client();
+  let err = viewer
+    .get_shared_collab(&created.token, None)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidPassword);
+  let err = viewer
+    .get_shared_collab(&created.token, Some("wrong"))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidPassword);
+
+  let shared = viewer
+    .get_shared_collab(&created.token, Some("secret"))
+    .await
+    .unwrap();
+  assert_eq!(shared.access_level, AFAccessLevel::ReadAndComment);
+}
+
+#[tokio::test]
+async fn revoked_or_expired_share_link_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = create_document(&c, &workspace_id, "hello world".as_bytes().to_vec()).await;
+
+  let created = c
+    .create_share_link(
+      &workspace_id,
+      &object_id,
+      share_params(AFAccessLevel::ReadOnly),
+    )
+    .await
+    .unwrap();
+  c.revoke_share_link(&workspace_id, &object_id, &created.info.id)
+    .await
+    .unwrap();
+  let err = localhost_client()
+    .get_shared_collab(&created.token, None)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+
+  // An expiry in the past is rejected.
+  let mut params = share_params(AFAccessLevel::ReadOnly);
+  params.expires_at = Some(Utc::now() - Duration::minutes(1));
+  let err = c
+    .create_share_link(&workspace_id, &object_id, params)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+}
+
+#[tokio::test]
+async fn share_link_password_attempts_are_limited_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = create_document(&c, &workspace_id, "hello world".as_bytes().to_vec()).await;
+
+  let mut params = share_params(AFAccessLevel::ReadOnly);
+  params.password = Some("secret".to_string());
+  let created = c
+    .create_share_link(&workspace_id, &object_id, params)
+    .await
+    .unwrap();
+
+  let viewer = localhost_client();
+  for _ in 0..10 {
+    let err = viewer
+      .get_shared_collab(&created.token, Some("wrong"))
+      .await
+      .unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidPassword);
+  }
+
+  // Even the right password is rejected until the window has passed.
+  let err = viewer
+    .get_shared_collab(&created.token, Some("secret"))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::TooManyRequests);
+}
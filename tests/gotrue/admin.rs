@@ -2,7 +2,7 @@ use client_api::extract_sign_in_url;
 use gotrue::{
   api::Client,
   grant::{Grant, PasswordGrant},
-  params::{AdminDeleteUserParams, AdminUserParams, GenerateLinkParams},
+  params::{AdminDeleteUserParams, AdminListUsersParams, AdminUserParams, GenerateLinkParams},
 };
 
 use crate::{
@@ -53,7 +53,13 @@ async fn admin_user_create_list_edit_delete() {
 
   // list users
   let users = gotrue_client
-    .admin_list_user(&admin_token.access_token)
+    .admin_list_user(
+      &admin_token.access_token,
+      &AdminListUsersParams {
+        filter: Some(user_email.clone()),
+        ..Default::default()
+      },
+    )
     .await
     .unwrap()
     .users;
@@ -100,7 +106,13 @@ async fn admin_user_create_list_edit_delete() {
     .unwrap();
 
   let users = gotrue_client
-    .admin_list_user(&admin_token.access_token)
+    .admin_list_user(
+      &admin_token.access_token,
+      &AdminListUsersParams {
+        filter: Some(user_email.clone()),
+        ..Default::default()
+      },
+    )
     .await
     .unwrap()
     .users;
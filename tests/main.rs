@@ -1,6 +1,7 @@
 use client_api::Client;
 mod collab;
 mod gotrue;
+mod rate_limit;
 mod user;
 mod util;
 mod websocket;
@@ -0,0 +1,92 @@
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App, HttpResponse};
+use appflowy_cloud::config::config::{
+  get_configuration, MessageRateLimit, RateLimit, RateLimitKey, RateLimitSetting, RouteRateLimit,
+};
+use appflowy_cloud::middleware::rate_limit_mw::RateLimiter;
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+
+/// Returns a service whose only route allows a burst of `capacity` requests per ip. The route is
+/// unique to the test, so it starts with a full bucket.
+async fn rate_limited_service(
+  capacity: u32,
+) -> (
+  String,
+  impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+) {
+  let config = get_configuration().unwrap();
+  let redis_client = redis::Client::open(config.redis_uri.expose_secret().as_str())
+    .unwrap()
+    .get_tokio_connection_manager()
+    .await
+    .unwrap();
+  let path = format!("/rate_limit_test/{}", Uuid::new_v4());
+  let setting = RateLimitSetting {
+    enable: true,
+    default: None,
+    routes: vec![RouteRateLimit {
+      pattern: path.clone(),
+      method: None,
+      limit: RateLimit::new(RateLimitKey::Ip, capacity, 0.01),
+    }],
+    websocket: MessageRateLimit {
+      capacity: 200,
+      refill_per_sec: 50.0,
+    },
+  };
+  let service = init_service(
+    App::new()
+      .wrap(RateLimiter::new(setting, redis_client))
+      .route(&path, web::get().to(HttpResponse::Ok)),
+  )
+  .await;
+  (path, service)
+}
+
+fn request(path: &str, peer: &str, forwarded_for: &str) -> actix_http::Request {
+  TestRequest::get()
+    .uri(path)
+    .peer_addr(format!("{}:4000", peer).parse().unwrap())
+    .insert_header(("X-Forwarded-For", forwarded_for))
+    .to_request()
+}
+
+#[actix_rt::test]
+async fn burst_over_capacity_is_rejected_with_retry_after() {
+  let (path, service) = rate_limited_service(2).await;
+  for _ in 0..2 {
+    let resp = call_service(&service, request(&path, "203.0.113.7", "198.51.100.1")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+  }
+
+  let resp = call_service(&service, request(&path, "203.0.113.7", "198.51.100.1")).await;
+  assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+  let retry_after = resp
+    .headers()
+    .get(RETRY_AFTER)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<u64>().ok())
+    .unwrap();
+  assert!(retry_after > 0);
+}
+
+#[actix_rt::test]
+async fn untrusted_forwarded_for_does_not_change_the_bucket() {
+  let (path, service) = rate_limited_service(2).await;
+  // The peer isn't a trusted proxy, so every request takes a token from the bucket of the peer,
+  // whatever address it claims to forward.
+  for forwarded_for in ["198.51.100.1", "198.51.100.2"] {
+    let resp = call_service(&service, request(&path, "203.0.113.7", forwarded_for)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+  }
+  let resp = call_service(&service, request(&path, "203.0.113.7", "198.51.100.3")).await;
+  assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+  // Another peer has its own bucket.
+  let resp = call_service(&service, request(&path, "203.0.113.8", "198.51.100.3")).await;
+  assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,103 @@
+use database_entity::dto::BanUserParams;
+use shared_entity::dto::auth_dto::AdminListUsersQuery;
+use shared_entity::error_code::ErrorCode;
+
+use crate::localhost_client;
+use crate::user::utils::{generate_unique_registered_user_client, ADMIN_USER};
+
+#[tokio::test]
+async fn non_admin_cannot_list_users() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let err = c
+    .admin_list_users(&AdminListUsersQuery::default())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+#[tokio::test]
+async fn admin_list_users_with_filter() {
+  let (c, user) = generate_unique_registered_user_client().await;
+  // The user is created in AppFlowy Cloud once signed in.
+  c.get_profile().await.unwrap();
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let page = admin_client
+    .admin_list_users(&AdminListUsersQuery {
+      filter: Some(user.email.clone()),
+      ..Default::default()
+    })
+    .await
+    .unwrap();
+  assert_eq!(page.total, 1);
+  assert_eq!(page.page, 1);
+  let item = &page.items[0];
+  assert_eq!(item.user.email, user.email);
+  assert!(item.uid.is_some());
+  assert_eq!(item.workspace_count, 1);
+  assert_eq!(item.storage_usage, 0);
+}
+
+#[tokio::test]
+async fn admin_list_users_page_size() {
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let page = admin_client
+    .admin_list_users(&AdminListUsersQuery {
+      per_page: Some(1),
+      ..Default::default()
+    })
+    .await
+    .unwrap();
+  assert_eq!(page.per_page, 1);
+  assert_eq!(page.items.len(), 1);
+}
+
+#[tokio::test]
+async fn non_admin_cannot_ban_user() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, _user2) = generate_unique_registered_user_client().await;
+  let user_uuid = c2.get_profile().await.unwrap().uuid;
+  let err = c1
+    .ban_user(&user_uuid, &BanUserParams::default())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+#[tokio::test]
+async fn banned_user_is_rejected_until_unbanned() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let user_uuid = c.get_profile().await.unwrap().uuid;
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let ban = admin_client
+    .ban_user(
+      &user_uuid,
+      &BanUserParams {
+        duration_secs: Some(3600),
+        reason: Some("spam".to_string()),
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(ban.user_uuid, user_uuid);
+
+  // The access token issued before the ban is rejected.
+  let err = c.get_profile().await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::UserBanned);
+
+  admin_client.unban_user(&user_uuid).await.unwrap();
+  c.get_profile().await.unwrap();
+}
@@ -0,0 +1,140 @@
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+use crate::LOCALHOST_URL;
+use database_entity::dto::{AFApiTokenScope, AFUserProfile, CreateApiTokenParams};
+use reqwest::{Method, StatusCode};
+use shared_entity::data::AppResponse;
+use shared_entity::error_code::ErrorCode;
+
+fn api_token_request(method: Method, path: &str, token: &str) -> reqwest::RequestBuilder {
+  reqwest::Client::new()
+    .request(method, format!("{}{}", LOCALHOST_URL, path))
+    .bearer_auth(token)
+}
+
+fn create_params(scope: AFApiTokenScope) -> CreateApiTokenParams {
+  CreateApiTokenParams {
+    name: "ci bot".to_string(),
+    workspace_id: None,
+    scope,
+    expires_at: None,
+  }
+}
+
+#[tokio::test]
+async fn create_list_and_revoke_api_token() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let created = c
+    .create_api_token(create_params(AFApiTokenScope::ReadOnly))
+    .await
+    .unwrap();
+  assert!(created.token.starts_with("afp_"));
+
+  let tokens = c.list_api_tokens().await.unwrap().0;
+  assert_eq!(tokens.len(), 1);
+  assert_eq!(tokens[0].id, created.info.id);
+
+  let resp = api_token_request(Method::GET, "/api/user/profile", &created.token)
+    .send()
+    .await
+    .unwrap();
+  let profile = AppResponse::<AFUserProfile>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+  assert_eq!(profile.uuid, c.get_profile().await.unwrap().uuid);
+
+  c.revoke_api_token(&created.info.id).await.unwrap();
+  assert!(c.list_api_tokens().await.unwrap().0.is_empty());
+
+  let resp = api_token_request(Method::GET, "/api/user/profile", &created.token)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn read_only_api_token_can_not_write() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let created = c
+    .create_api_token(create_params(AFApiTokenScope::ReadOnly))
+    .await
+    .unwrap();
+
+  let resp = api_token_request(
+    Method::GET,
+    &format!("/api/workspace/{}/member", workspace_id),
+    &created.token,
+  )
+  .send()
+  .await
+  .unwrap();
+  assert!(AppResponse::<serde_json::Value>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .is_ok());
+
+  let resp = api_token_request(
+    Method::PUT,
+    &format!("/api/workspace/{}/open", workspace_id),
+    &created.token,
+  )
+  .send()
+  .await
+  .unwrap();
+  let err = AppResponse::<()>::from_response(resp)
+    .await
+    .unwrap()
+    .into_error()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+#[tokio::test]
+async fn workspace_api_token_can_not_access_other_workspace() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, _user2) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let other_workspace_id = workspace_id_from_client(&c2).await;
+
+  let mut params = create_params(AFApiTokenScope::Admin);
+  params.workspace_id = Some(workspace_id.parse().unwrap());
+  let created = c1.create_api_token(params).await.unwrap();
+
+  let resp = api_token_request(
+    Method::GET,
+    &format!("/api/workspace/{}/member", other_workspace_id),
+    &created.token,
+  )
+  .send()
+  .await
+  .unwrap();
+  let err = AppResponse::<serde_json::Value>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // The routes that aren't in a workspace are rejected too.
+  let resp = api_token_request(Method::GET, "/api/user/profile", &created.token)
+    .send()
+    .await
+    .unwrap();
+  let err = AppResponse::<serde_json::Value>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // The token can't be created for a workspace that the user is not a member of.
+  let mut params = create_params(AFApiTokenScope::ReadOnly);
+  params.workspace_id = Some(other_workspace_id.parse().unwrap());
+  let err = c1.create_api_token(params).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
@@ -0,0 +1,63 @@
+use shared_entity::error_code::ErrorCode;
+
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+const PNG_PATH: &str = "tests/workspace/blob/asset/16kb_logo.png";
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G'];
+
+#[tokio::test]
+async fn upload_and_delete_avatar() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let image = std::fs::read(PNG_PATH).unwrap();
+
+  let profile = c.upload_user_avatar(image, mime::IMAGE_PNG).await.unwrap();
+  let avatar_url = profile.avatar_url.unwrap();
+  assert_eq!(
+    c.get_profile().await.unwrap().avatar_url.as_ref(),
+    Some(&avatar_url)
+  );
+  let avatar = c.get_user_avatar(&avatar_url).await.unwrap();
+  assert!(avatar.starts_with(PNG_SIGNATURE));
+
+  // The members of the workspace see the avatar
+  let members = c.get_workspace_members2(&workspace_id).await.unwrap();
+  assert_eq!(members[0].avatar_url.as_ref(), Some(&avatar_url));
+
+  c.delete_user_avatar().await.unwrap();
+  assert!(c.get_profile().await.unwrap().avatar_url.is_none());
+  assert!(c.get_user_avatar(&avatar_url).await.is_err());
+}
+
+#[tokio::test]
+async fn replaced_avatar_is_not_served() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let image = std::fs::read(PNG_PATH).unwrap();
+  let first_url = c
+    .upload_user_avatar(image.clone(), mime::IMAGE_PNG)
+    .await
+    .unwrap()
+    .avatar_url
+    .unwrap();
+  let second_url = c
+    .upload_user_avatar(image, mime::IMAGE_PNG)
+    .await
+    .unwrap()
+    .avatar_url
+    .unwrap();
+  assert_ne!(first_url, second_url);
+  assert!(c.get_user_avatar(&first_url).await.is_err());
+  assert!(c.get_user_avatar(&second_url).await.is_ok());
+}
+
+#[tokio::test]
+async fn upload_invalid_avatar() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let err = c
+    .upload_user_avatar("not an image", mime::IMAGE_PNG)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+  assert!(c.get_profile().await.unwrap().avatar_url.is_none());
+}
@@ -0,0 +1,41 @@
+use gotrue_entity::dto::OtpType;
+use shared_entity::error_code::ErrorCode;
+
+use crate::localhost_client;
+use crate::user::utils::{generate_unique_email, generate_unique_registered_user_client};
+
+#[tokio::test]
+async fn change_password_then_sign_in_with_new_password() {
+  let (c, user) = generate_unique_registered_user_client().await;
+  let new_password = "Hello456!";
+  c.change_password(new_password, None).await.unwrap();
+
+  let c = localhost_client();
+  c.sign_in_password(&user.email, &user.password)
+    .await
+    .unwrap_err();
+  c.sign_in_password(&user.email, new_password).await.unwrap();
+}
+
+#[tokio::test]
+async fn change_password_not_logged_in() {
+  let c = localhost_client();
+  let err = c.change_password("Hello456!", None).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotLoggedIn);
+}
+
+#[tokio::test]
+async fn send_password_recovery_with_invalid_email() {
+  let c = localhost_client();
+  let err = c.send_password_recovery("not an email").await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidEmail);
+}
+
+#[tokio::test]
+async fn verify_otp_with_wrong_code() {
+  let c = localhost_client();
+  c.verify_otp(OtpType::Recovery, &generate_unique_email(), "000000")
+    .await
+    .unwrap_err();
+  assert!(c.access_token().is_err());
+}
@@ -0,0 +1,144 @@
+use database_entity::dto::{AFAccessLevel, AFRole};
+use shared_entity::dto::workspace_dto::CreateWorkspaceMember;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::collab::workspace_id_from_client;
+use crate::localhost_client;
+use crate::user::utils::{generate_unique_registered_user_client, ADMIN_USER};
+use crate::util::test_pg_pool;
+
+#[tokio::test]
+async fn request_and_cancel_account_deletion_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let err = c.get_account_deletion().await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+
+  let deletion = c.request_account_deletion().await.unwrap();
+  assert!(deletion.scheduled_at > deletion.requested_at);
+
+  // Requesting the deletion again keeps the schedule.
+  let again = c.request_account_deletion().await.unwrap();
+  assert_eq!(again.scheduled_at, deletion.scheduled_at);
+  assert_eq!(
+    c.get_account_deletion().await.unwrap().scheduled_at,
+    deletion.scheduled_at
+  );
+
+  c.cancel_account_deletion().await.unwrap();
+  let err = c.get_account_deletion().await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+  let err = c.cancel_account_deletion().await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+/// Moves the deletion of the accounts to now, as if the grace period was zero.
+async fn expire_grace_period(pg_pool: &PgPool, user_uuids: &[Uuid]) {
+  sqlx::query("UPDATE af_user_deletion SET scheduled_at = NOW() WHERE uuid = ANY($1)")
+    .bind(user_uuids)
+    .execute(pg_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn due_account_is_deleted_and_its_workspaces_handed_over_or_removed() {
+  let pg_pool = test_pg_pool().await;
+  let (owner, owner_user) = generate_unique_registered_user_client().await;
+  let (member, member_user) = generate_unique_registered_user_client().await;
+  let (sole, sole_user) = generate_unique_registered_user_client().await;
+
+  let shared_workspace_id = workspace_id_from_client(&owner).await;
+  owner
+    .add_workspace_members(
+      &shared_workspace_id,
+      vec![CreateWorkspaceMember {
+        email: member_user.email.clone(),
+        role: AFRole::Member,
+      }],
+    )
+    .await
+    .unwrap();
+
+  let sole_workspace_id = workspace_id_from_client(&sole).await;
+  let url = sole
+    .put_blob(
+      &sole_workspace_id,
+      "blob of the sole workspace",
+      &mime::TEXT_PLAIN_UTF_8,
+    )
+    .await
+    .unwrap();
+  let file_id = url.rsplit('/').next().unwrap().to_string();
+
+  owner.request_account_deletion().await.unwrap();
+  sole.request_account_deletion().await.unwrap();
+  let user_uuids = vec![
+    owner.get_profile().await.unwrap().uuid,
+    sole.get_profile().await.unwrap().uuid,
+  ];
+  expire_grace_period(&pg_pool, &user_uuids).await;
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  admin_client.run_user_deletion().await.unwrap();
+
+  // The remaining member owns the shared workspace, with the full access to its collab object
+  let members = member
+    .get_workspace_members(&shared_workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(members.len(), 1);
+  assert_eq!(members[0].email, member_user.email);
+  assert_eq!(members[0].role, AFRole::Owner);
+  let member_uid = member.get_profile().await.unwrap().uid;
+  let access_level: i32 = sqlx::query_scalar(
+    r#"
+    SELECT af_permissions.access_level FROM af_collab_member
+    JOIN af_permissions ON af_permissions.id = af_collab_member.permission_id
+    WHERE af_collab_member.uid = $1 AND af_collab_member.oid = $2
+    "#,
+  )
+  .bind(member_uid)
+  .bind(shared_workspace_id)
+  .fetch_one(&pg_pool)
+  .await
+  .unwrap();
+  assert_eq!(AFAccessLevel::from(access_level), AFAccessLevel::FullAccess);
+
+  // The workspace without another member is deleted along with its blobs
+  let sole_workspace_id = Uuid::parse_str(&sole_workspace_id).unwrap();
+  let workspace_exists: bool =
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM af_workspace WHERE workspace_id = $1)")
+      .bind(sole_workspace_id)
+      .fetch_one(&pg_pool)
+      .await
+      .unwrap();
+  assert!(!workspace_exists);
+  let blob_deleted: bool = sqlx::query_scalar(
+    r#"
+    SELECT EXISTS(
+      SELECT 1 FROM af_audit_log
+      WHERE workspace_id = $1 AND action = 'delete_blob' AND target = $2
+    )
+    "#,
+  )
+  .bind(sole_workspace_id)
+  .bind(file_id)
+  .fetch_one(&pg_pool)
+  .await
+  .unwrap();
+  assert!(blob_deleted);
+
+  // The deleted users can't sign in anymore
+  for user in [owner_user, sole_user] {
+    assert!(localhost_client()
+      .sign_in_password(&user.email, &user.password)
+      .await
+      .is_err());
+  }
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use database_entity::dto::AFUserDataExportStatus;
+use shared_entity::error_code::ErrorCode;
+
+use crate::user::utils::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn export_user_data_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let export = c.request_data_export().await.unwrap();
+
+  let mut status = export.status;
+  for _ in 0..30 {
+    status = c.get_data_export(&export.export_id).await.unwrap().status;
+    if status != AFUserDataExportStatus::Pending {
+      break;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+  }
+  assert_eq!(status, AFUserDataExportStatus::Completed);
+
+  let archive = c.download_data_export(&export.export_id).await.unwrap();
+  assert!(archive.starts_with(b"PK"));
+}
+
+#[tokio::test]
+async fn download_unknown_data_export_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let err = c
+    .download_data_export(&uuid::Uuid::new_v4())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
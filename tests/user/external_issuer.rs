@@ -0,0 +1,108 @@
+use crate::user::utils::generate_unique_email;
+use crate::LOCALHOST_URL;
+use database_entity::dto::AFUserProfile;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use reqwest::StatusCode;
+use serde_json::json;
+use shared_entity::data::AppResponse;
+use shared_entity::dto::auth_dto::SignInTokenResponse;
+use shared_entity::error_code::ErrorCode;
+use uuid::Uuid;
+
+// The issuer configured in configuration/local.yaml, whose public key is in the asset folder.
+const TEST_ISSUER: &str = "http://localhost:8000/test_issuer";
+const TEST_AUDIENCE: &str = "appflowy_cloud_test";
+const TEST_ISSUER_PRIVATE_KEY: &[u8] = include_bytes!("asset/test_issuer_private.pem");
+
+fn external_token(sub: &str, email: &str) -> String {
+  let claims = json!({
+    "iss": TEST_ISSUER,
+    "aud": TEST_AUDIENCE,
+    "sub": sub,
+    "exp": chrono::Utc::now().timestamp() + 3600,
+    "email": email,
+    "user_metadata": { "name": "external user" },
+  });
+  encode(
+    &Header::new(Algorithm::RS256),
+    &claims,
+    &EncodingKey::from_rsa_pem(TEST_ISSUER_PRIVATE_KEY).unwrap(),
+  )
+  .unwrap()
+}
+
+async fn verify(token: &str) -> reqwest::Response {
+  reqwest::Client::new()
+    .get(format!("{}/api/user/verify/{}", LOCALHOST_URL, token))
+    .send()
+    .await
+    .unwrap()
+}
+
+async fn get_profile(token: &str) -> reqwest::Response {
+  reqwest::Client::new()
+    .get(format!("{}/api/user/profile", LOCALHOST_URL))
+    .bearer_auth(token)
+    .send()
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn external_issuer_token_reaches_authenticated_handler() {
+  let email = generate_unique_email();
+  let token = external_token(&Uuid::new_v4().to_string(), &email);
+
+  let is_new = AppResponse::<SignInTokenResponse>::from_response(verify(&token).await)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap()
+    .is_new;
+  assert!(is_new);
+
+  let resp = get_profile(&token).await;
+  assert_eq!(resp.status(), StatusCode::OK);
+  let profile = AppResponse::<AFUserProfile>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+  assert_eq!(profile.email, Some(email));
+  assert_eq!(profile.name.as_deref(), Some("external user"));
+
+  // The user is only created once
+  let is_new = AppResponse::<SignInTokenResponse>::from_response(verify(&token).await)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap()
+    .is_new;
+  assert!(!is_new);
+}
+
+#[tokio::test]
+async fn external_issuer_token_can_not_take_the_email_of_another_user() {
+  let email = generate_unique_email();
+  let first = external_token(&Uuid::new_v4().to_string(), &email);
+  AppResponse::<SignInTokenResponse>::from_response(verify(&first).await)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+
+  let second = external_token(&Uuid::new_v4().to_string(), &email);
+  let err = AppResponse::<SignInTokenResponse>::from_response(verify(&second).await)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordAlreadyExists);
+
+  // The second token has no user
+  let profile = AppResponse::<AFUserProfile>::from_response(get_profile(&second).await)
+    .await
+    .unwrap()
+    .into_data();
+  assert!(profile.is_err());
+}
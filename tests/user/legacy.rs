@@ -0,0 +1,211 @@
+use crate::localhost_client;
+use crate::user::utils::{generate_unique_email, generate_unique_registered_user, ADMIN_USER};
+use crate::util::test_pg_pool;
+use crate::LOCALHOST_URL;
+use appflowy_cloud::component::auth::compute_hash_password;
+use reqwest::StatusCode;
+use secrecy::ExposeSecret;
+use serde_json::json;
+use shared_entity::data::AppResponse;
+use shared_entity::dto::auth_dto::SignInPasswordResponse;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn legacy_login(email: &str, password: &str) -> reqwest::Response {
+  reqwest::Client::new()
+    .post(format!("{}/api/user/login", LOCALHOST_URL))
+    .json(&json!({ "email": email, "password": password }))
+    .send()
+    .await
+    .unwrap()
+}
+
+/// Inserts an `af_user` that registered with the deprecated local password, so it doesn't have a
+/// GoTrue user. Returns the uid.
+async fn insert_legacy_user(pg_pool: &PgPool, email: &str, password: &str) -> i64 {
+  let uid = (rand::random::<u64>() >> 1) as i64;
+  let password = compute_hash_password(password.as_bytes()).unwrap();
+  sqlx::query("INSERT INTO af_user (uid, uuid, email, name, password) VALUES ($1, $2, $3, $4, $5)")
+    .bind(uid)
+    .bind(Uuid::new_v4())
+    .bind(email)
+    .bind(email)
+    .bind(password.expose_secret())
+    .execute(pg_pool)
+    .await
+    .unwrap();
+  uid
+}
+
+/// Confirms the email of the GoTrue user, as the link sent to the user would.
+async fn confirm_gotrue_email(pg_pool: &PgPool, email: &str) {
+  sqlx::query("UPDATE auth.users SET email_confirmed_at = NOW() WHERE email = $1")
+    .bind(email)
+    .execute(pg_pool)
+    .await
+    .unwrap();
+}
+
+async fn select_uuid_and_password(pg_pool: &PgPool, uid: i64) -> (Uuid, String) {
+  sqlx::query_as("SELECT uuid, password FROM af_user WHERE uid = $1")
+    .bind(uid)
+    .fetch_one(pg_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn legacy_login_returns_gotrue_token() {
+  let user = generate_unique_registered_user().await;
+  let resp = legacy_login(&user.email, &user.password).await;
+  let sign_in = AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+
+  let resp = reqwest::Client::new()
+    .get(format!("{}/api/user/profile", LOCALHOST_URL))
+    .bearer_auth(&sign_in.access_token_resp.access_token)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn legacy_login_with_wrong_password() {
+  let user = generate_unique_registered_user().await;
+  let resp = legacy_login(&user.email, "Wrong123!").await;
+  AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+}
+
+#[tokio::test]
+async fn legacy_change_password_then_login() {
+  let user = generate_unique_registered_user().await;
+  let resp = legacy_login(&user.email, &user.password).await;
+  let sign_in = AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+
+  let new_password = "Hello456!";
+  let resp = reqwest::Client::new()
+    .post(format!("{}/api/user/password", LOCALHOST_URL))
+    .bearer_auth(&sign_in.access_token_resp.access_token)
+    .json(&json!({
+      "current_password": user.password,
+      "new_password": new_password,
+      "new_password_confirm": new_password,
+    }))
+    .send()
+    .await
+    .unwrap();
+  AppResponse::<()>::from_response(resp)
+    .await
+    .unwrap()
+    .into_error()
+    .unwrap();
+
+  let resp = legacy_login(&user.email, new_password).await;
+  AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+}
+
+#[tokio::test]
+async fn legacy_password_user_is_moved_to_gotrue_on_login() {
+  let pg_pool = test_pg_pool().await;
+  let email = generate_unique_email();
+  let password = "Hello123!";
+  let uid = insert_legacy_user(&pg_pool, &email, password).await;
+
+  // The GoTrue user is created, but it can't sign in until the email is confirmed.
+  let resp = legacy_login(&email, password).await;
+  let err = AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotLoggedIn);
+  let (_, stored_password) = select_uuid_and_password(&pg_pool, uid).await;
+  assert!(!stored_password.is_empty());
+
+  confirm_gotrue_email(&pg_pool, &email).await;
+  let resp = legacy_login(&email, password).await;
+  let sign_in = AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+  let user = sign_in.access_token_resp.user;
+  assert_eq!(user.email, email);
+  assert!(!sign_in.is_new);
+
+  let (uuid, stored_password) = select_uuid_and_password(&pg_pool, uid).await;
+  assert_eq!(uuid.to_string(), user.id);
+  assert!(stored_password.is_empty());
+
+  // The password is checked by GoTrue from now on.
+  let resp = legacy_login(&email, password).await;
+  AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+}
+
+#[tokio::test]
+async fn legacy_password_user_does_not_change_gotrue_user_with_same_email() {
+  let pg_pool = test_pg_pool().await;
+  let email = generate_unique_email();
+  let password = "Hello123!";
+  let uid = insert_legacy_user(&pg_pool, &email, password).await;
+  let (legacy_uuid, legacy_password) = select_uuid_and_password(&pg_pool, uid).await;
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let gotrue_password = "Other123!";
+  let gotrue_user = admin_client
+    .create_email_verified_user(&email, gotrue_password)
+    .await
+    .unwrap();
+
+  let resp = legacy_login(&email, password).await;
+  let err = AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordAlreadyExists);
+
+  // The GoTrue user keeps its password, and the legacy user isn't linked to it.
+  let resp = legacy_login(&email, gotrue_password).await;
+  let sign_in = AppResponse::<SignInPasswordResponse>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap();
+  assert_eq!(sign_in.access_token_resp.user.id, gotrue_user.id);
+  assert!(sign_in
+    .access_token_resp
+    .user
+    .app_metadata
+    .get("legacy_uid")
+    .is_none());
+  assert_eq!(
+    select_uuid_and_password(&pg_pool, uid).await,
+    (legacy_uuid, legacy_password)
+  );
+}
@@ -0,0 +1,32 @@
+use shared_entity::error_code::ErrorCode;
+
+use crate::user::utils::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn enroll_and_unenroll_totp_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let enrollment = c.enroll_totp(Some("phone")).await.unwrap();
+  assert!(!enrollment.totp.secret.is_empty());
+  assert!(enrollment.totp.uri.starts_with("otpauth://totp/"));
+
+  let factors = c.list_factors().await.unwrap();
+  assert_eq!(factors.len(), 1);
+  assert_eq!(factors[0].id, enrollment.id);
+  assert!(!factors[0].is_verified());
+
+  // An unverified factor can be removed without the second factor.
+  c.unenroll_factor(&enrollment.id).await.unwrap();
+  assert!(c.list_factors().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn verify_factor_with_invalid_code_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let enrollment = c.enroll_totp(None).await.unwrap();
+  let err = c
+    .challenge_and_verify_factor(&enrollment.id, "000000")
+    .await
+    .unwrap_err();
+  assert_ne!(err.code, ErrorCode::Ok);
+  assert!(!c.list_factors().await.unwrap()[0].is_verified());
+}
@@ -1,4 +1,14 @@
+mod admin;
+mod api_token;
+mod avatar;
+mod credential;
+mod deletion;
+mod export;
+mod external_issuer;
+mod legacy;
+mod mfa;
 mod refresh;
+mod session;
 mod sign_in;
 mod sign_out;
 mod sign_up;
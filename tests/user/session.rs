@@ -0,0 +1,89 @@
+use client_api::ws::{WSClient, WSClientConfig};
+use shared_entity::error_code::ErrorCode;
+
+use crate::localhost_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+fn ws_client() -> WSClient {
+  WSClient::new(WSClientConfig {
+    buffer_capacity: 100,
+    ping_per_secs: 6,
+    retry_connect_per_pings: 5,
+  })
+}
+
+#[tokio::test]
+async fn list_user_sessions_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let ws = ws_client();
+  ws.connect(c.ws_url("device_a").unwrap()).await.unwrap();
+
+  let sessions = c.list_user_sessions().await.unwrap().0;
+  assert_eq!(sessions.len(), 1);
+  assert_eq!(sessions[0].device_id, "device_a");
+  assert!(sessions[0].is_current);
+}
+
+#[tokio::test]
+async fn sign_out_device_test() {
+  let (c1, user) = generate_unique_registered_user_client().await;
+  let c2 = localhost_client().with_client_version("0.3.4");
+  c2.sign_in_password(&user.email, &user.password)
+    .await
+    .unwrap();
+
+  let ws_1 = ws_client();
+  ws_1.connect(c1.ws_url("device_a").unwrap()).await.unwrap();
+  let ws_2 = ws_client();
+  ws_2.connect(c2.ws_url("device_b").unwrap()).await.unwrap();
+
+  let sessions = c1.list_user_sessions().await.unwrap().0;
+  assert_eq!(sessions.len(), 2);
+  let device_b = sessions
+    .iter()
+    .find(|session| session.device_id == "device_b")
+    .unwrap();
+  assert!(!device_b.is_current);
+  assert_eq!(device_b.client_version.as_deref(), Some("0.3.4"));
+
+  c1.sign_out_device("device_b").await.unwrap();
+  let sessions = c1.list_user_sessions().await.unwrap().0;
+  assert_eq!(sessions.len(), 1);
+  assert_eq!(sessions[0].device_id, "device_a");
+
+  // The access token of the signed out session is rejected, on any device.
+  let err = c2.get_profile().await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotLoggedIn);
+  let ws_3 = ws_client();
+  assert!(ws_3.connect(c2.ws_url("device_c").unwrap()).await.is_err());
+
+  // The refresh token of the signed out device is revoked, and the other device stays signed in.
+  assert!(c2.refresh().await.is_err());
+  c1.get_profile().await.unwrap();
+
+  let err = c1.sign_out_device("device_b").await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+#[tokio::test]
+async fn signed_out_device_can_sign_in_again_test() {
+  let (c1, user) = generate_unique_registered_user_client().await;
+  let c2 = localhost_client();
+  c2.sign_in_password(&user.email, &user.password)
+    .await
+    .unwrap();
+  let ws_2 = ws_client();
+  ws_2.connect(c2.ws_url("device_b").unwrap()).await.unwrap();
+  c1.sign_out_device("device_b").await.unwrap();
+
+  // The tokens issued after the sign out are accepted on the same device.
+  tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+  c2.sign_in_password(&user.email, &user.password)
+    .await
+    .unwrap();
+  let ws_3 = ws_client();
+  ws_3.connect(c2.ws_url("device_b").unwrap()).await.unwrap();
+  let sessions = c1.list_user_sessions().await.unwrap().0;
+  assert_eq!(sessions.len(), 1);
+  assert_eq!(sessions[0].device_id, "device_b");
+}
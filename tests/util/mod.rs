@@ -1,3 +1,5 @@
+use dotenv::dotenv;
+use sqlx::PgPool;
 use std::sync::Once;
 use tracing_subscriber::fmt::Subscriber;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -20,3 +22,12 @@ pub fn setup_log() {
     subscriber.try_init().unwrap();
   });
 }
+
+/// Connects to the database of the server, for the tests that need to change the data the API
+/// doesn't expose, e.g. to move the time of a record back.
+pub async fn test_pg_pool() -> PgPool {
+  dotenv().ok();
+  PgPool::connect(&std::env::var("DATABASE_URL").unwrap())
+    .await
+    .unwrap()
+}
@@ -0,0 +1,144 @@
+use crate::localhost_client;
+use crate::user::utils::ADMIN_USER;
+use crate::util::test_client::TestClient;
+use database_entity::dto::{AFAuditAction, AFRole, QueryAuditLogParams};
+use shared_entity::dto::workspace_dto::{AuditLogExportFormat, ExportAuditLogParams};
+use shared_entity::error_code::ErrorCode;
+
+#[tokio::test]
+async fn owner_reads_member_changes_from_audit_log() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c1.workspace_id().await;
+
+  c1.add_workspace_member(&workspace_id, &c2, AFRole::Member)
+    .await;
+  c1.try_remove_workspace_member(&workspace_id, &c2)
+    .await
+    .unwrap();
+
+  let page = c1
+    .api_client
+    .get_workspace_audit_log(&workspace_id, &QueryAuditLogParams::default())
+    .await
+    .unwrap();
+  let actions = page
+    .items
+    .iter()
+    .map(|entry| entry.action)
+    .collect::<Vec<_>>();
+  // Newest first
+  assert_eq!(
+    actions,
+    vec![
+      AFAuditAction::RemoveWorkspaceMember,
+      AFAuditAction::AddWorkspaceMember
+    ]
+  );
+
+  let c1_uid = c1.uid().await;
+  let c2_email = c2.email().await;
+  for entry in &page.items {
+    assert_eq!(entry.actor_uid, Some(c1_uid));
+    assert_eq!(entry.target, c2_email);
+  }
+
+  // Filter by action
+  let page = c1
+    .api_client
+    .get_workspace_audit_log(
+      &workspace_id,
+      &QueryAuditLogParams {
+        action: Some(AFAuditAction::AddWorkspaceMember),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(page.items.len(), 1);
+  assert!(page.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn paginate_audit_log() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c1.workspace_id().await;
+  c1.add_workspace_member(&workspace_id, &c2, AFRole::Member)
+    .await;
+  for role in [AFRole::Guest, AFRole::Member] {
+    c1.try_update_workspace_member(&workspace_id, &c2, role)
+      .await
+      .unwrap();
+  }
+
+  let mut params = QueryAuditLogParams {
+    limit: Some(2),
+    ..Default::default()
+  };
+  let first_page = c1
+    .api_client
+    .get_workspace_audit_log(&workspace_id, &params)
+    .await
+    .unwrap();
+  assert_eq!(first_page.items.len(), 2);
+  params.cursor = first_page.next_cursor;
+  let second_page = c1
+    .api_client
+    .get_workspace_audit_log(&workspace_id, &params)
+    .await
+    .unwrap();
+  assert_eq!(second_page.items.len(), 1);
+  assert!(second_page.next_cursor.is_none());
+  assert!(second_page.items[0].id < first_page.items[1].id);
+}
+
+#[tokio::test]
+async fn member_cannot_read_audit_log() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c1.workspace_id().await;
+  c1.add_workspace_member(&workspace_id, &c2, AFRole::Member)
+    .await;
+
+  let err = c2
+    .api_client
+    .get_workspace_audit_log(&workspace_id, &QueryAuditLogParams::default())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+#[tokio::test]
+async fn admin_exports_audit_log() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c1.workspace_id().await;
+  c1.add_workspace_member(&workspace_id, &c2, AFRole::Member)
+    .await;
+
+  let mut params = ExportAuditLogParams {
+    workspace_id: Some(workspace_id.parse().unwrap()),
+    ..Default::default()
+  };
+  let err = c1.api_client.export_audit_log(&params).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let json = admin_client.export_audit_log(&params).await.unwrap();
+  let entries: Vec<serde_json::Value> = serde_json::from_slice(&json).unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0]["action"], "add_workspace_member");
+
+  params.format = AuditLogExportFormat::Csv;
+  let csv = admin_client.export_audit_log(&params).await.unwrap();
+  let csv = String::from_utf8(csv.to_vec()).unwrap();
+  let lines = csv.lines().collect::<Vec<_>>();
+  assert_eq!(lines.len(), 2);
+  assert!(lines[0].starts_with("id,created_at,actor_uid"));
+  assert!(lines[1].contains("add_workspace_member"));
+}
@@ -0,0 +1,78 @@
+use reqwest::Url;
+use shared_entity::data::AppResponse;
+use shared_entity::error_code::ErrorCode;
+
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn download_link_is_single_use() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = "downloaded with a link";
+  let file_url = c1.put_blob(&workspace_id, data, &mime).await.unwrap();
+  let url = Url::parse(&file_url).unwrap();
+  let file_id = url.path_segments().unwrap().last().unwrap();
+
+  let link = c1
+    .create_blob_download_link(&workspace_id, file_id)
+    .await
+    .unwrap();
+  let link_url = c1.get_blob_download_link_url(file_id, &link);
+
+  // The link doesn't require signing in
+  let resp = reqwest::get(&link_url).await.unwrap();
+  assert_eq!(resp.bytes().await.unwrap(), data.as_bytes());
+
+  // and only works once.
+  let resp = reqwest::get(&link_url).await.unwrap();
+  let err = AppResponse::<()>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::TokenRevoked);
+
+  c1.delete_blob(&file_url).await.unwrap();
+}
+
+#[tokio::test]
+async fn download_link_only_works_for_its_blob() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let url_1 = c1.put_blob(&workspace_id, "blob 1", &mime).await.unwrap();
+  let url_2 = c1.put_blob(&workspace_id, "blob 2", &mime).await.unwrap();
+  let file_id_1 = Url::parse(&url_1)
+    .unwrap()
+    .path_segments()
+    .unwrap()
+    .last()
+    .unwrap()
+    .to_string();
+  let file_id_2 = Url::parse(&url_2)
+    .unwrap()
+    .path_segments()
+    .unwrap()
+    .last()
+    .unwrap()
+    .to_string();
+
+  let link = c1
+    .create_blob_download_link(&workspace_id, &file_id_1)
+    .await
+    .unwrap();
+  let resp = reqwest::get(c1.get_blob_download_link_url(&file_id_2, &link))
+    .await
+    .unwrap();
+  let err = AppResponse::<()>::from_response(resp)
+    .await
+    .unwrap()
+    .into_data()
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::TokenPurposeMismatch);
+
+  c1.delete_blob(&url_1).await.unwrap();
+  c1.delete_blob(&url_2).await.unwrap();
+}
@@ -0,0 +1,66 @@
+use database_entity::dto::AFFileTypePolicy;
+use shared_entity::error_code::ErrorCode;
+
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+const PNG_PATH: &str = "tests/workspace/blob/asset/16kb_logo.png";
+
+/// The leading bytes of an ELF executable.
+const ELF_HEADER: [u8; 20] = [
+  0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0x3e, 0,
+];
+
+#[tokio::test]
+async fn put_mismatched_file_type() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let data = std::fs::read(PNG_PATH).unwrap();
+  let err = c1
+    .put_blob(&workspace_id, data, &mime::TEXT_PLAIN)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+}
+
+#[tokio::test]
+async fn put_executable_is_denied_by_default() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let err = c1
+    .put_blob(
+      &workspace_id,
+      ELF_HEADER.to_vec(),
+      &mime::APPLICATION_OCTET_STREAM,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::FileTypeNotAllowed);
+}
+
+#[tokio::test]
+async fn put_with_workspace_policy() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let policy = AFFileTypePolicy {
+    allowed: vec!["image/*".to_string()],
+    denied: vec![],
+  };
+  c1.update_file_type_policy(&workspace_id, &policy)
+    .await
+    .unwrap();
+  let got = c1.get_file_type_policy(&workspace_id).await.unwrap();
+  assert_eq!(got.allowed, policy.allowed);
+
+  let err = c1
+    .put_blob(&workspace_id, "hello world", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::FileTypeNotAllowed);
+
+  let url = c1
+    .put_blob_with_path(&workspace_id, PNG_PATH)
+    .await
+    .unwrap();
+  c1.delete_blob(&url).await.unwrap();
+}
@@ -0,0 +1,139 @@
+use collab_entity::CollabType;
+use database_entity::dto::InsertCollabParams;
+use shared_entity::error_code::ErrorCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::collab::workspace_id_from_client;
+use crate::localhost_client;
+use crate::user::utils::{generate_unique_registered_user_client, ADMIN_USER};
+use crate::util::test_pg_pool;
+
+#[tokio::test]
+async fn non_admin_cannot_get_gc_report() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let err = c1
+    .get_blob_gc_report(Some(&workspace_id))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+#[tokio::test]
+async fn recently_uploaded_blob_is_not_collected() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let url = c1
+    .put_blob(&workspace_id, "unreferenced blob", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap();
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let reports = admin_client
+    .get_blob_gc_report(Some(&workspace_id))
+    .await
+    .unwrap();
+  assert_eq!(reports.0.len(), 1);
+  let report = &reports.0[0];
+  assert!(report.marked.is_empty());
+  assert!(report.deleted.is_empty());
+
+  // The dry run doesn't delete anything
+  c1.get_blob(&url).await.unwrap();
+}
+
+/// Moves the upload time of the blobs and the time they were marked past the grace period.
+async fn expire_grace_period(pg_pool: &PgPool, workspace_id: &str) {
+  let workspace_id = Uuid::parse_str(workspace_id).unwrap();
+  sqlx::query(
+    "UPDATE af_blob_metadata SET modified_at = NOW() - INTERVAL '365 days' WHERE workspace_id = $1",
+  )
+  .bind(workspace_id)
+  .execute(pg_pool)
+  .await
+  .unwrap();
+  sqlx::query(
+    "UPDATE af_blob_gc_mark SET marked_at = NOW() - INTERVAL '365 days' WHERE workspace_id = $1",
+  )
+  .bind(workspace_id)
+  .execute(pg_pool)
+  .await
+  .unwrap();
+}
+
+fn file_id_from_url(url: &str) -> String {
+  url.rsplit('/').next().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn unreferenced_blob_is_collected_and_referenced_blobs_are_kept() {
+  let pg_pool = test_pg_pool().await;
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, _user2) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let other_workspace_id = workspace_id_from_client(&c2).await;
+
+  let referenced_url = c1
+    .put_blob(&workspace_id, "referenced blob", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap();
+  let embedded_url = c1
+    .put_blob(&workspace_id, "embedded blob", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap();
+  let unreferenced_url = c1
+    .put_blob(&workspace_id, "unreferenced blob", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap();
+
+  // Referenced by a collab of the workspace that owns it, and by a collab of another workspace.
+  c1.create_collab(InsertCollabParams::new(
+    Uuid::new_v4(),
+    CollabType::Document,
+    format!("{{\"url\":\"{}\"}}", referenced_url).into_bytes(),
+    workspace_id.clone(),
+  ))
+  .await
+  .unwrap();
+  c2.create_collab(InsertCollabParams::new(
+    Uuid::new_v4(),
+    CollabType::Document,
+    format!("{{\"url\":\"{}\"}}", embedded_url).into_bytes(),
+    other_workspace_id,
+  ))
+  .await
+  .unwrap();
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+
+  // The unreferenced blob is marked on the first run, and deleted once it stays unreferenced for
+  // longer than the grace period.
+  expire_grace_period(&pg_pool, &workspace_id).await;
+  let reports = admin_client.run_blob_gc(Some(&workspace_id)).await.unwrap();
+  assert_eq!(
+    reports.0[0].marked,
+    vec![file_id_from_url(&unreferenced_url)]
+  );
+  assert!(reports.0[0].deleted.is_empty());
+
+  expire_grace_period(&pg_pool, &workspace_id).await;
+  let reports = admin_client.run_blob_gc(Some(&workspace_id)).await.unwrap();
+  assert_eq!(
+    reports.0[0].deleted,
+    vec![file_id_from_url(&unreferenced_url)]
+  );
+
+  c1.get_blob(&referenced_url).await.unwrap();
+  c1.get_blob(&embedded_url).await.unwrap();
+  let err = c1.get_blob(&unreferenced_url).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
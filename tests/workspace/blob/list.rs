@@ -0,0 +1,85 @@
+use database_entity::dto::{BlobMetadataOrderBy, QueryBlobMetadataParams};
+
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn list_blob_metadata_by_page() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let mut urls = vec![];
+  for data in ["1", "22", "333"] {
+    urls.push(c1.put_blob(&workspace_id, data, &mime).await.unwrap());
+  }
+
+  let mut params = QueryBlobMetadataParams {
+    limit: Some(2),
+    order_by: BlobMetadataOrderBy::FileSize,
+    ..Default::default()
+  };
+  let page = c1
+    .get_workspace_blob_metadata(&workspace_id, &params)
+    .await
+    .unwrap();
+  let sizes = page.items.iter().map(|m| m.file_size).collect::<Vec<_>>();
+  assert_eq!(sizes, vec![3, 2]);
+
+  params.cursor = page.next_cursor;
+  assert!(params.cursor.is_some());
+  let page = c1
+    .get_workspace_blob_metadata(&workspace_id, &params)
+    .await
+    .unwrap();
+  let sizes = page.items.iter().map(|m| m.file_size).collect::<Vec<_>>();
+  assert_eq!(sizes, vec![1]);
+  assert!(page.next_cursor.is_none());
+
+  let all = c1
+    .get_workspace_all_blob_metadata(&workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(all.0.len(), 3);
+
+  for url in urls {
+    c1.delete_blob(&url).await.unwrap();
+  }
+}
+
+#[tokio::test]
+async fn filter_blob_metadata_by_file_type() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let text_url = c1
+    .put_blob(&workspace_id, "hello", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap();
+  let image_url = c1
+    .put_blob_with_path(&workspace_id, "tests/workspace/blob/asset/16kb_logo.png")
+    .await
+    .unwrap();
+
+  let params = QueryBlobMetadataParams {
+    file_type: Some("image/*".to_string()),
+    ..Default::default()
+  };
+  let page = c1
+    .get_workspace_blob_metadata(&workspace_id, &params)
+    .await
+    .unwrap();
+  assert_eq!(page.items.len(), 1);
+  assert_eq!(page.items[0].file_type, "image/png");
+
+  let usage = c1
+    .get_workspace_usage_by_file_type(&workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage.0.len(), 2);
+  assert_eq!(usage.0[0].file_type, "image/png");
+  assert_eq!(usage.0[0].total_size, 15694);
+  assert_eq!(usage.0[1].file_type, "text/plain");
+  assert_eq!(usage.0[1].file_count, 1);
+
+  c1.delete_blob(&text_url).await.unwrap();
+  c1.delete_blob(&image_url).await.unwrap();
+}
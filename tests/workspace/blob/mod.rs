@@ -1,2 +1,7 @@
+mod download_link;
+mod file_type;
+mod gc;
+mod list;
+mod presigned;
 mod put_and_get;
 mod usage;
@@ -0,0 +1,116 @@
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use database_entity::dto::{AFPresignedUpload, PresignedUploadParams};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use shared_entity::error_code::ErrorCode;
+
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn presigned_put_and_get() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = "uploaded with a pre-signed url";
+  let file_url = c1
+    .put_blob_presigned(&workspace_id, data, &mime)
+    .await
+    .unwrap();
+
+  // The blob can be downloaded through the server
+  let got_data = c1.get_blob(&file_url).await.unwrap();
+  assert_eq!(got_data, data.as_bytes());
+
+  // and directly from the bucket
+  let url = Url::parse(&file_url).unwrap();
+  let file_id = url.path_segments().unwrap().last().unwrap();
+  let got_data = c1.get_blob_presigned(&workspace_id, file_id).await.unwrap();
+  assert_eq!(got_data, data.as_bytes());
+
+  c1.delete_blob(&file_url).await.unwrap();
+}
+
+#[tokio::test]
+async fn presigned_put_existing_blob() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = "uploaded twice";
+  let url_1 = c1.put_blob(&workspace_id, data, &mime).await.unwrap();
+  let url_2 = c1
+    .put_blob_presigned(&workspace_id, data, &mime)
+    .await
+    .unwrap();
+  assert_eq!(url_1, url_2);
+
+  c1.delete_blob(&url_1).await.unwrap();
+}
+
+#[tokio::test]
+async fn presigned_put_with_wrong_content() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let declared = "declared content";
+  let uploaded = "uploaded content";
+  let params = PresignedUploadParams {
+    file_id: URL_SAFE.encode(Sha256::digest(declared)),
+    file_type: mime::TEXT_PLAIN_UTF_8.to_string(),
+    file_size: uploaded.len() as i64,
+  };
+  let (upload_id, url) = match c1
+    .presign_blob_upload(&workspace_id, &params)
+    .await
+    .unwrap()
+  {
+    AFPresignedUpload::Url { upload_id, url, .. } => (upload_id, url),
+    upload => panic!("unexpected upload: {:?}", upload),
+  };
+  c1.put_presigned_url(&url, uploaded).await.unwrap();
+
+  let err = c1
+    .complete_presigned_upload(&workspace_id, &upload_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+
+  // The declared file id isn't stored
+  let err = c1
+    .get_blob_presigned(&workspace_id, &params.file_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+
+  // The upload can only be completed once
+  let err = c1
+    .complete_presigned_upload(&workspace_id, &upload_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+#[tokio::test]
+async fn presigned_put_blob_of_other_workspace() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, _user2) = generate_unique_registered_user_client().await;
+  let workspace_id_1 = workspace_id_from_client(&c1).await;
+  let workspace_id_2 = workspace_id_from_client(&c2).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = "stored in another workspace";
+  let url_1 = c1.put_blob(&workspace_id_1, data, &mime).await.unwrap();
+
+  // Knowing the file id isn't enough to add the blob to another workspace, it must be uploaded.
+  let params = PresignedUploadParams {
+    file_id: URL_SAFE.encode(Sha256::digest(data)),
+    file_type: mime.to_string(),
+    file_size: data.len() as i64,
+  };
+  let upload = c2
+    .presign_blob_upload(&workspace_id_2, &params)
+    .await
+    .unwrap();
+  assert!(matches!(upload, AFPresignedUpload::Url { .. }));
+
+  c1.delete_blob(&url_1).await.unwrap();
+}
@@ -1,4 +1,5 @@
 use crate::collab::workspace_id_from_client;
+use image::GenericImageView;
 use reqwest::Url;
 use shared_entity::error_code::ErrorCode;
 
@@ -78,6 +79,25 @@ async fn put_delete_get() {
   assert_eq!(err.code, ErrorCode::RecordNotFound);
 }
 
+#[tokio::test]
+async fn delete_blob_shared_with_other_workspace() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, _user2) = generate_unique_registered_user_client().await;
+  let workspace_id_1 = workspace_id_from_client(&c1).await;
+  let workspace_id_2 = workspace_id_from_client(&c2).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = "shared contents";
+  let url_1 = c1.put_blob(&workspace_id_1, data, &mime).await.unwrap();
+  let url_2 = c2.put_blob(&workspace_id_2, data, &mime).await.unwrap();
+
+  // The other workspace stores the same content, so it keeps it.
+  c1.delete_blob(&url_1).await.unwrap();
+  let got_data = c2.get_blob(&url_2).await.unwrap();
+  assert_eq!(got_data, data.as_bytes());
+
+  c2.delete_blob(&url_2).await.unwrap();
+}
+
 #[tokio::test]
 async fn put_and_download_png() {
   let image_path = "tests/workspace/blob/asset/16kb_logo.png";
@@ -97,3 +117,27 @@ async fn put_and_download_png() {
 
   assert_image_equal(Path::new(image_path), &temp_file);
 }
+
+#[tokio::test]
+async fn put_png_and_download_thumbnail() {
+  let image_path = "tests/workspace/blob/asset/16kb_logo.png";
+  let client = TestClient::new_user_without_ws_conn().await;
+  let url = client.upload_file_with_path(image_path).await;
+
+  let thumbnail = client
+    .api_client
+    .get_blob_thumbnail(&url, 100)
+    .await
+    .unwrap();
+  let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+  assert_eq!(thumbnail.width(), 128);
+  assert_eq!(thumbnail.height(), 128);
+
+  // The image is smaller than the largest thumbnail size, so the original is returned.
+  let original = client
+    .api_client
+    .get_blob_thumbnail(&url, 1024)
+    .await
+    .unwrap();
+  assert_eq!(original.len(), 15694);
+}
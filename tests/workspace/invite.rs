@@ -0,0 +1,64 @@
+use database_entity::dto::AFRole;
+use shared_entity::dto::workspace_dto::CreateWorkspaceMember;
+use shared_entity::error_code::ErrorCode;
+
+use crate::collab::workspace_id_from_client;
+use crate::user::utils::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn invited_user_joins_workspace_once() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, user2) = generate_unique_registered_user_client().await;
+  let (c3, _user3) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let invite = c1
+    .create_workspace_invite(
+      &workspace_id,
+      CreateWorkspaceMember {
+        email: user2.email.clone(),
+        role: AFRole::Member,
+      },
+    )
+    .await
+    .unwrap();
+
+  // Only the user with the invited email can accept the invitation
+  let err = c3.accept_workspace_invite(&invite).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  c2.accept_workspace_invite(&invite).await.unwrap();
+  let members = c1.get_workspace_members(&workspace_id).await.unwrap();
+  let member = members
+    .iter()
+    .find(|member| member.email == user2.email)
+    .unwrap();
+  assert_eq!(member.role, AFRole::Member);
+
+  let err = c2.accept_workspace_invite(&invite).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::TokenRevoked);
+}
+
+#[tokio::test]
+async fn revoked_invite_can_not_be_accepted() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let (c2, user2) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let invite = c1
+    .create_workspace_invite(
+      &workspace_id,
+      CreateWorkspaceMember {
+        email: user2.email.clone(),
+        role: AFRole::Member,
+      },
+    )
+    .await
+    .unwrap();
+
+  c1.revoke_workspace_invite(&workspace_id, &invite.token)
+    .await
+    .unwrap();
+  let err = c2.accept_workspace_invite(&invite).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::TokenRevoked);
+  let members = c1.get_workspace_members(&workspace_id).await.unwrap();
+  assert!(members.iter().all(|member| member.email != user2.email));
+}
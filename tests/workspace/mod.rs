@@ -1,2 +1,6 @@
+mod audit_log;
 mod blob;
+mod invite;
 mod member_crud;
+mod role;
+mod sso;
@@ -0,0 +1,141 @@
+use crate::util::test_client::TestClient;
+use database_entity::dto::{
+  AFAccessLevel, AFRole, CreateWorkspaceRoleParams, UpdateWorkspaceRoleParams,
+};
+use shared_entity::error_code::ErrorCode;
+
+async fn permission_id(c: &TestClient, access_level: AFAccessLevel) -> i32 {
+  c.api_client
+    .get_permissions()
+    .await
+    .unwrap()
+    .0
+    .into_iter()
+    .find(|permission| permission.access_level == access_level)
+    .unwrap()
+    .id
+}
+
+#[tokio::test]
+async fn create_custom_role_and_assign_to_member() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c1.workspace_id().await;
+
+  let read_and_write = permission_id(&c1, AFAccessLevel::ReadAndWrite).await;
+  let role = c1
+    .api_client
+    .create_workspace_role(
+      &workspace_id,
+      CreateWorkspaceRoleParams {
+        name: "Editor".to_string(),
+        permission_ids: vec![read_and_write],
+      },
+    )
+    .await
+    .unwrap();
+  assert!(matches!(role.role, AFRole::Custom(_)));
+  assert_eq!(role.permissions.len(), 1);
+
+  let roles = c1
+    .api_client
+    .get_workspace_roles(&workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(roles.0.len(), 4);
+
+  c1.add_workspace_member(&workspace_id, &c2, role.role.clone())
+    .await;
+  let members = c1.get_workspace_members(&workspace_id).await;
+  assert_eq!(members[1].role, role.role);
+
+  // The custom role can't be deleted while it's assigned.
+  let role_id = i32::from(role.role.clone());
+  let err = c1
+    .api_client
+    .delete_workspace_role(&workspace_id, role_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+}
+
+#[tokio::test]
+async fn custom_role_permissions_decide_workspace_access() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let c3 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c1.workspace_id().await;
+
+  let read_only = permission_id(&c1, AFAccessLevel::ReadOnly).await;
+  let role = c1
+    .api_client
+    .create_workspace_role(
+      &workspace_id,
+      CreateWorkspaceRoleParams {
+        name: "Viewer".to_string(),
+        permission_ids: vec![read_only],
+      },
+    )
+    .await
+    .unwrap();
+  c1.add_workspace_member(&workspace_id, &c2, role.role.clone())
+    .await;
+
+  // A read only role can't add members.
+  let err = c2
+    .try_add_workspace_member(&workspace_id, &c3, AFRole::Member)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // Granting full access to the role allows it.
+  let full_access = permission_id(&c1, AFAccessLevel::FullAccess).await;
+  c1.api_client
+    .update_workspace_role(
+      &workspace_id,
+      i32::from(role.role.clone()),
+      UpdateWorkspaceRoleParams {
+        name: None,
+        permission_ids: Some(vec![full_access]),
+      },
+    )
+    .await
+    .unwrap();
+  c2.add_workspace_member(&workspace_id, &c3, AFRole::Member)
+    .await;
+}
+
+#[tokio::test]
+async fn custom_role_of_other_workspace_can_not_be_used() {
+  let c1 = TestClient::new_user_without_ws_conn().await;
+  let c2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id_1 = c1.workspace_id().await;
+  let workspace_id_2 = c2.workspace_id().await;
+
+  let read_only = permission_id(&c1, AFAccessLevel::ReadOnly).await;
+  let role = c1
+    .api_client
+    .create_workspace_role(
+      &workspace_id_1,
+      CreateWorkspaceRoleParams {
+        name: "Viewer".to_string(),
+        permission_ids: vec![read_only],
+      },
+    )
+    .await
+    .unwrap();
+
+  let err = c2
+    .try_add_workspace_member(&workspace_id_2, &c1, role.role)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequestParams);
+
+  // Built-in roles can't be modified.
+  let err = c1
+    .api_client
+    .delete_workspace_role(&workspace_id_1, i32::from(AFRole::Member))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
@@ -0,0 +1,54 @@
+use database_entity::dto::{CreateSsoProviderParams, SsoProviderConfig};
+use shared_entity::error_code::ErrorCode;
+use uuid::Uuid;
+
+use crate::collab::workspace_id_from_client;
+use crate::localhost_client;
+use crate::user::utils::{generate_unique_registered_user_client, ADMIN_USER};
+
+#[tokio::test]
+async fn non_admin_cannot_create_sso_provider() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let params = CreateSsoProviderParams {
+    domain: format!("{}.appflowy.io", Uuid::new_v4()),
+    workspace_id: Uuid::parse_str(&workspace_id).unwrap(),
+    config: SsoProviderConfig::Oidc {
+      name: "keycloak".to_string(),
+    },
+  };
+  let err = c1.create_sso_provider(&params).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let err = c1.list_sso_providers().await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+#[tokio::test]
+async fn create_sso_provider_with_disabled_provider() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+
+  let admin_client = localhost_client();
+  admin_client
+    .sign_in_password(&ADMIN_USER.email, &ADMIN_USER.password)
+    .await
+    .unwrap();
+  let params = CreateSsoProviderParams {
+    domain: format!("{}.appflowy.io", Uuid::new_v4()),
+    workspace_id: Uuid::parse_str(&workspace_id).unwrap(),
+    config: SsoProviderConfig::Oidc {
+      name: "not_a_provider".to_string(),
+    },
+  };
+  let err = admin_client.create_sso_provider(&params).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidOAuthProvider);
+}
+
+#[tokio::test]
+async fn discover_sso_provider_of_unknown_domain() {
+  let client = localhost_client();
+  let email = format!("someone@{}.appflowy.io", Uuid::new_v4());
+  let err = client.discover_sso_provider(&email).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}